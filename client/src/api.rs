@@ -1,22 +1,267 @@
 #![allow(dead_code)]
 
-use bytes::{Buf, Bytes};
+use bytes::{Buf, Bytes, BytesMut};
 use chrono::{DateTime, Utc};
-use http_body_util::{BodyExt, Full};
-use hyper::{body::Incoming, Method, Request, Response, Uri};
-use hyper_util::rt::TokioIo;
+use http_body_util::{combinators::UnsyncBoxBody, BodyExt, Full, StreamBody};
+use hyper::{
+    body::{Body, Frame, Incoming},
+    client::conn::{http1, http2},
+    http::request,
+    Method, Request, Response, StatusCode, Uri,
+};
+use hyper_util::rt::{TokioExecutor, TokioIo};
 use interface::{
-    routes, FetchLatestUpdateDateForm, FetchLatestUpdateDateResponse, FetchMessagesForm,
-    FetchMessagesResponse, HttpMethod, Message, SendMessageForm, SendMessageResponse,
+    routes, Announcement, Attachment, AttachmentId, ContentKind, DeleteAccountForm,
+    DeleteAccountResponse, DeleteMessagesForm, DeleteMessagesResponse, Envelope, ErrorResponse,
+    FetchAnnouncementForm, FetchAnnouncementResponse, FetchBlocksForm, FetchBlocksResponse,
+    FetchCapabilitiesForm, FetchCapabilitiesResponse, FetchLatestUpdateDateForm,
+    FetchLatestUpdateDateResponse, FetchMembersForm, FetchMembersResponse, FetchMessagesForm,
+    FetchMessagesResponse, FetchProfileResponse, FetchReactionsForm, FetchReactionsResponse,
+    FetchServerInfoForm, FetchServerInfoResponse, FetchStatsForm, FetchStatsResponse,
+    FetchTimeForm, FetchTimeResponse, FetchTopicForm, FetchTopicResponse, ForwardMessageForm,
+    HttpMethod, Limits, Message, MessageDeletion, MessageId, MessageReactions, Profile, ReactForm,
+    ReactResponse, RegisterForm, RegisterResponse, RotateTokenForm, RotateTokenResponse,
+    SearchMessagesForm, SearchMessagesResponse, SendMessageForm, SendMessageResponse,
+    SetArchivedForm, SetArchivedResponse, SetBlocksForm, SetBlocksResponse, SetProfileForm,
+    SetProfileResponse, SetReadOnlyForm, SetReadOnlyResponse, SetTopicForm, SetTopicResponse,
+    UpdateMembersForm, UpdateMembersResponse, UploadAttachmentResponse, VoteForm, VoteResponse,
 };
 use serde::{de::DeserializeOwned, Serialize};
-use tokio::net::TcpStream;
+use std::{
+    collections::VecDeque,
+    fmt::{self, Display},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::{io::AsyncReadExt, net::TcpStream, sync::Mutex};
+
+use crate::{
+    connector::{ConnectTiming, Connector},
+    error::{ClientError, ClientResult},
+    proxy::Proxy,
+    utils::{format_size, PrettyUnwrap},
+};
+
+/// Files are uploaded in chunks of this many bytes.
+const UPLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Requests kept in `Timings`.
+const TIMED_REQUESTS: usize = 64;
+
+/// Requests taking longer than this are logged as slow.
+const SLOW_REQUEST: Duration = Duration::from_secs(1);
+
+/// Body of a response, whichever HTTP version it came over.
+pub type ResponseBody = UnsyncBoxBody<Bytes, ClientError>;
+
+/// HTTP version used for talking to the server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HttpVersion {
+    /// A fresh HTTP/1.1 connection for every request.
+    /// Mostly for debugging.
+    Http1,
+    /// HTTP/2 over cleartext (h2c with prior knowledge).
+    /// All requests are multiplexed over one long-lived connection.
+    #[default]
+    Http2,
+    /// HTTP/3 over QUIC, experimental. Requires a HTTPS server URL and feature `http3`.
+    Http3,
+}
+
+/// Bytes exchanged with the server in this session, over every transport.
+/// Only bodies and websocket payloads are counted, not headers or framing.
+#[derive(Debug)]
+pub struct Bandwidth {
+    sent: AtomicU64,
+    received: AtomicU64,
+    since: Instant,
+}
+
+impl Default for Bandwidth {
+    fn default() -> Self {
+        Self {
+            sent: AtomicU64::new(0),
+            received: AtomicU64::new(0),
+            since: Instant::now(),
+        }
+    }
+}
+
+impl Bandwidth {
+    pub fn record_sent(&self, bytes: usize) {
+        self.sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_received(&self, bytes: usize) {
+        self.received.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    pub fn received(&self) -> u64 {
+        self.received.load(Ordering::Relaxed)
+    }
+}
+
+impl Display for Bandwidth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Per minute of the session so far, counting a partial minute as a whole one.
+        let minutes = self.since.elapsed().as_secs().div_ceil(60).max(1);
+        let (sent, received) = (self.sent(), self.received());
+        writeln!(
+            f,
+            "Data sent:       {} ({}/min)",
+            format_size(sent),
+            format_size(sent / minutes)
+        )?;
+        write!(
+            f,
+            "Data received:   {} ({}/min)",
+            format_size(received),
+            format_size(received / minutes)
+        )
+    }
+}
+
+/// How long the phases of a request took.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestTiming {
+    /// `None` if an open connection was reused.
+    pub connect: Option<ConnectTiming>,
+    /// From sending the request until the response's headers arrived, connecting excluded.
+    pub first_byte: Duration,
+    /// From the start until the end of the response's body, connecting included.
+    pub total: Duration,
+}
+
+impl Display for RequestTiming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(connect) = self.connect {
+            write!(
+                f,
+                "DNS {}ms, connect {}ms, ",
+                connect.dns.as_millis(),
+                connect.connect.as_millis()
+            )?;
+            if let Some(tls) = connect.tls {
+                write!(f, "TLS {}ms, ", tls.as_millis())?;
+            }
+        }
+        write!(
+            f,
+            "first byte {}ms, total {}ms",
+            self.first_byte.as_millis(),
+            self.total.as_millis()
+        )
+    }
+}
+
+/// Timing of the latest requests, for the stats screen. Requests are also logged, at `info`
+/// level if slower than `SLOW_REQUEST`.
+#[derive(Debug, Default)]
+pub struct Timings {
+    recent: std::sync::Mutex<VecDeque<RequestTiming>>,
+}
 
-use crate::utils::DynResult;
+impl Timings {
+    fn record(&self, path: &str, timing: RequestTiming) {
+        if timing.total >= SLOW_REQUEST {
+            log::info!("Slow request to {path}: {timing}");
+        } else {
+            log::debug!("Request to {path}: {timing}");
+        }
+        let mut recent = self.recent.lock().pretty_unwrap();
+        if recent.len() == TIMED_REQUESTS {
+            recent.pop_front();
+        }
+        recent.push_back(timing);
+    }
+}
+
+impl Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        /// Average and maximum, or a dash if there are no samples.
+        fn summary(durations: impl Iterator<Item = Duration>) -> String {
+            let (count, sum, max) = durations.fold(
+                (0, Duration::ZERO, Duration::ZERO),
+                |(count, sum, max), duration| (count + 1, sum + duration, max.max(duration)),
+            );
+            match count {
+                0 => String::from("-"),
+                _ => format!(
+                    "{}ms avg, {}ms max",
+                    (sum / count).as_millis(),
+                    max.as_millis()
+                ),
+            }
+        }
+        let recent = self.recent.lock().pretty_unwrap();
+        let connects = || recent.iter().filter_map(|timing| timing.connect);
+        writeln!(
+            f,
+            "Requests timed:  last {}, {} connecting",
+            recent.len(),
+            connects().count()
+        )?;
+        writeln!(f, "DNS:             {}", summary(connects().map(|c| c.dns)))?;
+        writeln!(
+            f,
+            "Connect:         {}",
+            summary(connects().map(|c| c.connect))
+        )?;
+        writeln!(
+            f,
+            "TLS:             {}",
+            summary(connects().filter_map(|c| c.tls))
+        )?;
+        writeln!(
+            f,
+            "First byte:      {}",
+            summary(recent.iter().map(|timing| timing.first_byte))
+        )?;
+        write!(
+            f,
+            "Total:           {}",
+            summary(recent.iter().map(|timing| timing.total))
+        )
+    }
+}
+
+/// Records the timing of a request once dropped along with the response's body, i.e. once the
+/// body is read to the end or abandoned.
+struct PendingTiming {
+    timings: Arc<Timings>,
+    path: Box<str>,
+    start: Instant,
+    timing: RequestTiming,
+}
+
+impl Drop for PendingTiming {
+    fn drop(&mut self) {
+        self.timing.total = self.start.elapsed();
+        self.timings.record(&self.path, self.timing);
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Client {
     server_url: String,
+    http_version: HttpVersion,
+    connector: Connector,
+    /// The shared HTTP/2 connection, established lazily on first request.
+    http2_sender: Arc<Mutex<Option<http2::SendRequest<Full<Bytes>>>>>,
+    /// Sent as `Authorization: Bearer <token>` if present.
+    session_token: Arc<std::sync::Mutex<Option<Box<str>>>>,
+    bandwidth: Arc<Bandwidth>,
+    timings: Arc<Timings>,
+    #[cfg(feature = "http3")]
+    http3: Arc<crate::http3::Http3Client>,
 }
 
 impl Default for Client {
@@ -30,21 +275,80 @@ impl Client {
         if server_url.chars().next_back().is_some_and(|c| c == '/') {
             server_url.pop().unwrap();
         }
-        Self { server_url }
+        Self {
+            server_url,
+            http_version: HttpVersion::default(),
+            connector: Connector::default(),
+            http2_sender: Default::default(),
+            session_token: Default::default(),
+            bandwidth: Default::default(),
+            timings: Default::default(),
+            #[cfg(feature = "http3")]
+            http3: Default::default(),
+        }
+    }
+
+    pub fn http_version(self, http_version: HttpVersion) -> Self {
+        Self {
+            http_version,
+            ..self
+        }
+    }
+
+    pub fn connector(self, connector: Connector) -> Self {
+        Self { connector, ..self }
+    }
+
+    /// Proxy the server is connected to through, if any.
+    pub fn proxy(&self) -> Option<&Proxy> {
+        self.connector.proxy()
+    }
+
+    /// TCP connection with the host of `url`, for transports that don't go through `Client`.
+    pub async fn connect_tcp(&self, url: &Uri) -> std::io::Result<TcpStream> {
+        let (stream, _) = self.connector.connect_tcp(url).await?;
+        Ok(stream)
+    }
+
+    fn session_token(&self) -> Option<Box<str>> {
+        self.session_token.lock().pretty_unwrap().clone()
+    }
+
+    pub fn set_session_token(&self, token: Option<Box<str>>) {
+        *self.session_token.lock().pretty_unwrap() = token;
+    }
+
+    pub fn bandwidth(&self) -> &Bandwidth {
+        &self.bandwidth
+    }
+
+    pub fn timings(&self) -> &Timings {
+        &self.timings
     }
 
     pub fn server_url(&self) -> &str {
         &self.server_url
     }
 
+    /// URL of the websocket endpoint, i.e. server URL with `http` replaced by `ws`.
+    /// Events are sent in `Envelope`s if `is_enveloped`.
+    pub fn websocket_url(&self, is_enveloped: bool) -> String {
+        let (_, path) = routes::WS;
+        let url = match self.server_url.strip_prefix("http") {
+            Some(rest) => format!("ws{rest}"),
+            None => self.server_url.clone(),
+        };
+        format!("{url}{path}{}", envelope_query(is_enveloped))
+    }
+
     async fn request<T: Serialize, U: DeserializeOwned>(
         &self,
         (method, path): (HttpMethod, &str),
         body: T,
-    ) -> DynResult<U> {
+    ) -> ClientResult<U> {
         let uri: Uri = format!("{}{path}", &self.server_url).parse().unwrap();
         let method: hyper::Method = method.try_into()?;
-        let response: U = request(uri, method, Some(body)).await?;
+        let response: U = self.request_json(uri, method, Some(body)).await?;
         Ok(response)
     }
 
@@ -55,40 +359,333 @@ impl Client {
     }
 
     /// Helper function for `test_connection` until rust stablizes try blocks.
-    async fn test_connection_(&self) -> DynResult<bool> {
+    async fn test_connection_(&self) -> ClientResult<bool> {
         // Unfortunately this is much of a rewrite of `Self::request` due to response to GET /hello
         // not being JSON.
         let (method, path) = routes::HELLO;
         let method: hyper::Method = method.try_into()?;
         let uri: Uri = format!("{}{path}", &self.server_url).parse().unwrap();
-        let response = request_raw(uri, method, None::<()>, "text/plain").await?;
+        let response = self
+            .request_raw(uri, method, None::<()>, "text/plain")
+            .await?;
         let response_string = collect_response_to_string(response).await?;
         Ok(response_string.as_str() == interface::EXPECTED_RESPONSE_TO_HELLO)
     }
 
-    pub async fn send_message(&self, content: Box<str>) -> DynResult<()> {
-        let response: SendMessageResponse = self
-            .request(routes::SEND_MESSAGE, SendMessageForm { content })
+    /// Round-trip time of a `GET /hello`.
+    pub async fn ping(&self) -> ClientResult<Duration> {
+        let start = Instant::now();
+        if self.test_connection_().await? {
+            Ok(start.elapsed())
+        } else {
+            Err(ClientError::NotABoard)
+        }
+    }
+
+    /// Time of the server, and the round-trip time of the request.
+    pub async fn fetch_time(&self) -> ClientResult<(DateTime<Utc>, Duration)> {
+        let start = Instant::now();
+        let response: FetchTimeResponse =
+            self.request(routes::FETCH_TIME, FetchTimeForm {}).await?;
+        Ok((response.now, start.elapsed()))
+    }
+
+    pub async fn fetch_server_info(&self) -> ClientResult<FetchServerInfoResponse> {
+        self.request(routes::FETCH_SERVER_INFO, FetchServerInfoForm {})
+            .await
+    }
+
+    /// Needs a session of a role allowed to view stats.
+    pub async fn fetch_stats(&self) -> ClientResult<FetchStatsResponse> {
+        self.request(routes::FETCH_STATS, FetchStatsForm {}).await
+    }
+
+    /// Names from `interface::capabilities` supported by the server, and its limits.
+    pub async fn fetch_capabilities(&self) -> ClientResult<FetchCapabilitiesResponse> {
+        let result = self
+            .request::<_, FetchCapabilitiesResponse>(
+                routes::FETCH_CAPABILITIES,
+                FetchCapabilitiesForm {},
+            )
+            .await;
+        match result {
+            // Servers predating the route support none of the capabilities.
+            Err(ClientError::Status { status, .. }) if status == StatusCode::NOT_FOUND => {
+                Ok(FetchCapabilitiesResponse {
+                    capabilities: Box::default(),
+                    limits: Limits::default(),
+                })
+            }
+            result => result,
+        }
+    }
+
+    /// Redeem an invite code, returning the session token.
+    pub async fn register(&self, invite_code: Box<str>) -> ClientResult<Box<str>> {
+        let response: RegisterResponse = self
+            .request(routes::REGISTER, RegisterForm { invite_code })
+            .await?;
+        Ok(response.token)
+    }
+
+    /// Replace the session token with a new one, which is used from then on.
+    /// Returns the new token.
+    pub async fn rotate_token(&self) -> ClientResult<Box<str>> {
+        self.session_token().ok_or(ClientError::NoSession)?;
+        let response: RotateTokenResponse = self
+            .request(routes::ROTATE_TOKEN, RotateTokenForm {})
+            .await?;
+        self.set_session_token(Some(response.token.clone()));
+        Ok(response.token)
+    }
+
+    /// Delete the session, forgetting its token.
+    /// Returns the number of its messages deleted or made anonymous.
+    pub async fn delete_account(&self) -> ClientResult<usize> {
+        self.session_token().ok_or(ClientError::NoSession)?;
+        let response: DeleteAccountResponse = self
+            .request(routes::DELETE_ACCOUNT, DeleteAccountForm {})
+            .await?;
+        self.set_session_token(None);
+        Ok(response.deleted.len() + response.anonymized.len())
+    }
+
+    /// `client_tag` is echoed back in `Event::Delivered` once the message is added.
+    pub async fn send_message(
+        &self,
+        content: Box<str>,
+        content_kind: ContentKind,
+        expires_in: Option<Duration>,
+        client_tag: Option<Box<str>>,
+        attachments: Box<[Attachment]>,
+    ) -> ClientResult<SendMessageResponse> {
+        let mut form = SendMessageForm::new(content)
+            .content_kind(content_kind)
+            .attachments(attachments);
+        if let Some(expires_in) = expires_in {
+            form = form.expires_in(expires_in);
+        }
+        if let Some(client_tag) = client_tag {
+            form = form.client_tag(client_tag);
+        }
+        self.send_message_form(form).await
+    }
+
+    pub async fn send_poll(
+        &self,
+        question: Box<str>,
+        options: Box<[Box<str>]>,
+    ) -> ClientResult<()> {
+        self.send_message_form(SendMessageForm::new(question).poll(options))
             .await?;
-        assert!(response.ok);
         Ok(())
     }
 
+    async fn send_message_form(&self, form: SendMessageForm) -> ClientResult<SendMessageResponse> {
+        form.validate()?;
+        let response: SendMessageResponse = self.request(routes::SEND_MESSAGE, form).await?;
+        if response.ok {
+            Ok(response)
+        } else {
+            Err(ClientError::Rejected {
+                reason: "no reason given".into(),
+            })
+        }
+    }
+
+    /// Members of the board, which only matter if it's private. Requires an admin session.
+    pub async fn fetch_members(&self) -> ClientResult<Box<[u64]>> {
+        let response: FetchMembersResponse = self
+            .request(routes::FETCH_MEMBERS, FetchMembersForm {})
+            .await?;
+        Ok(response.members)
+    }
+
+    /// Returns the members after the change. Requires an admin session.
+    pub async fn update_members(&self, form: UpdateMembersForm) -> ClientResult<Box<[u64]>> {
+        form.validate()?;
+        let response: UpdateMembersResponse = self.request(routes::UPDATE_MEMBERS, form).await?;
+        Ok(response.members)
+    }
+
+    /// Repost a message of this server, attributed by the server to where it was first posted.
+    pub async fn forward_message(
+        &self,
+        form: ForwardMessageForm,
+    ) -> ClientResult<SendMessageResponse> {
+        self.request(routes::FORWARD_MESSAGE, form).await
+    }
+
+    /// Returns the IDs of the messages deleted.
+    pub async fn delete_messages(
+        &self,
+        messages: Box<[MessageDeletion]>,
+    ) -> ClientResult<Box<[MessageId]>> {
+        let response: DeleteMessagesResponse = self
+            .request(routes::DELETE_MESSAGES, DeleteMessagesForm { messages })
+            .await?;
+        Ok(response.deleted)
+    }
+
+    /// Upload the file at `path` as `name`, streaming it in chunks and adding the number of bytes
+    /// sent so far to `progress`.
+    /// Always over a fresh HTTP/1.1 connection, as the shared connections only take whole bodies.
+    pub async fn upload_attachment(
+        &self,
+        path: &Path,
+        name: &str,
+        progress: Arc<AtomicU64>,
+    ) -> ClientResult<Attachment> {
+        let file = tokio::fs::File::open(path).await?;
+        let bandwidth = Arc::clone(&self.bandwidth);
+        let chunks = futures_util::stream::try_unfold(file, move |mut file| {
+            let progress = Arc::clone(&progress);
+            let bandwidth = Arc::clone(&bandwidth);
+            async move {
+                let mut chunk = BytesMut::with_capacity(UPLOAD_CHUNK_SIZE);
+                if file.read_buf(&mut chunk).await? == 0 {
+                    return Ok(None);
+                }
+                progress.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+                bandwidth.record_sent(chunk.len());
+                Ok::<_, std::io::Error>(Some((Frame::data(chunk.freeze()), file)))
+            }
+        });
+        let (_, route) = routes::UPLOAD_ATTACHMENT;
+        let path_and_query = format!("{route}?name={}", encode_query_component(name));
+        let url: Uri = format!("{}{path_and_query}", &self.server_url).parse()?;
+        let authority = url.authority().unwrap().clone();
+        let mut request = Request::builder()
+            .method(Method::POST)
+            .header(hyper::header::HOST, authority.as_str())
+            .header(hyper::header::CONTENT_TYPE, content_type_of(path));
+        request = match self.connector.forwarding_proxy(&url) {
+            Some(proxy) => forwarded(request, &url, proxy),
+            None => request.uri(path_and_query),
+        };
+        if let Some(token) = self.session_token.lock().pretty_unwrap().as_deref() {
+            request = request.header(hyper::header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+        let request = request.body(StreamBody::new(Box::pin(chunks)))?;
+        let (mut sender, _) = connect_http1(&url, &self.connector).await?;
+        let response = sender.send_request(request).await?.map(box_incoming);
+        let response = self.count_received(response);
+        let response: UploadAttachmentResponse = parse_json_response(response).await?;
+        Ok(response.attachment)
+    }
+
+    /// Content of the attachment.
+    pub async fn fetch_attachment(&self, id: AttachmentId) -> ClientResult<Bytes> {
+        let (_, route) = routes::FETCH_ATTACHMENT;
+        let path = route.replace(":id", &id.to_string());
+        let url: Uri = format!("{}{path}", &self.server_url).parse()?;
+        let response = self
+            .request_raw(url, Method::GET, None::<()>, "application/octet-stream")
+            .await?;
+        let status = response.status();
+        let response_body = response.collect().await?.to_bytes();
+        if !status.is_success() {
+            return Err(error_of_response(status, response_body));
+        }
+        Ok(response_body)
+    }
+
+    pub async fn vote(&self, message_id: MessageId, option: u32) -> ClientResult<()> {
+        let response: VoteResponse = self
+            .request(routes::VOTE, VoteForm { message_id, option })
+            .await?;
+        if response.ok {
+            Ok(())
+        } else {
+            Err(ClientError::Rejected {
+                reason: "no reason given".into(),
+            })
+        }
+    }
+
+    /// Returns whether the reaction was added, `false` if it was taken back.
+    pub async fn react(&self, message_id: MessageId, reaction: Box<str>) -> ClientResult<bool> {
+        let form = ReactForm {
+            message_id,
+            reaction,
+        };
+        form.validate()?;
+        let response: ReactResponse = self.request(routes::REACT, form).await?;
+        Ok(response.added)
+    }
+
+    /// Tallies of reactions to the messages, leaving out messages without reactions.
+    pub async fn fetch_reactions(
+        &self,
+        message_ids: &[MessageId],
+    ) -> ClientResult<Box<[MessageReactions]>> {
+        let (_, route) = routes::FETCH_REACTIONS;
+        let form = FetchReactionsForm::new(message_ids);
+        let url: Uri = format!(
+            "{}{route}?message_ids={}",
+            &self.server_url,
+            encode_query_component(&form.message_ids)
+        )
+        .parse()?;
+        let response: FetchReactionsResponse =
+            self.request_json(url, Method::GET, None::<()>).await?;
+        Ok(response.reactions)
+    }
+
+    /// Profile of the session with ID `user`, as in `Message::author`.
+    pub async fn fetch_profile(&self, user: u64) -> ClientResult<Profile> {
+        let (_, route) = routes::FETCH_PROFILE;
+        let path = route.replace(":user", &user.to_string());
+        let url: Uri = format!("{}{path}", &self.server_url).parse()?;
+        let response: FetchProfileResponse =
+            self.request_json(url, Method::GET, None::<()>).await?;
+        Ok(response.profile)
+    }
+
+    /// Returns the profile after the changes. Requires a session.
+    pub async fn set_profile(&self, form: SetProfileForm) -> ClientResult<Profile> {
+        form.validate()?;
+        let response: SetProfileResponse = self.request(routes::SET_PROFILE, form).await?;
+        Ok(response.profile)
+    }
+
+    /// IDs of the sessions whose messages are hidden from this one. Requires a session.
+    pub async fn fetch_blocks(&self) -> ClientResult<Box<[u64]>> {
+        let response: FetchBlocksResponse = self
+            .request(routes::FETCH_BLOCKS, FetchBlocksForm {})
+            .await?;
+        Ok(response.blocked)
+    }
+
+    /// Replace the block list, returning it as the server keeps it. Requires a session.
+    pub async fn set_blocks(&self, blocked: Box<[u64]>) -> ClientResult<Box<[u64]>> {
+        let form = SetBlocksForm { blocked };
+        form.validate()?;
+        let response: SetBlocksResponse = self.request(routes::SET_BLOCKS, form).await?;
+        Ok(response.blocked)
+    }
+
     pub async fn fetch_messages(
         &self,
         max_count: u32,
         since: Option<DateTime<Utc>>,
-    ) -> DynResult<Box<[Message]>> {
-        let response: FetchMessagesResponse = self
-            .request(
-                routes::FETCH_MESSAGES,
-                FetchMessagesForm { max_count, since },
-            )
-            .await?;
+    ) -> ClientResult<Box<[Message]>> {
+        let mut form = FetchMessagesForm::new(max_count);
+        if let Some(since) = since {
+            form = form.since(since);
+        }
+        let response: FetchMessagesResponse = self.request(routes::FETCH_MESSAGES, form).await?;
         Ok(response.messages)
     }
 
-    pub async fn fetch_latest_update_date(&self) -> DynResult<Option<DateTime<Utc>>> {
+    pub async fn search_messages(
+        &self,
+        form: SearchMessagesForm,
+    ) -> ClientResult<SearchMessagesResponse> {
+        self.request(routes::SEARCH_MESSAGES, form).await
+    }
+
+    pub async fn fetch_latest_update_date(&self) -> ClientResult<Option<DateTime<Utc>>> {
         let response: FetchLatestUpdateDateResponse = self
             .request(
                 routes::FETCH_LATEST_UPDATE_DATE,
@@ -97,67 +694,343 @@ impl Client {
             .await?;
         Ok(response.latest_update_date)
     }
+
+    pub async fn fetch_topic(&self) -> ClientResult<Option<Box<str>>> {
+        let response: FetchTopicResponse =
+            self.request(routes::FETCH_TOPIC, FetchTopicForm {}).await?;
+        Ok(response.topic)
+    }
+
+    pub async fn fetch_announcement(&self) -> ClientResult<Option<Announcement>> {
+        let response: FetchAnnouncementResponse = self
+            .request(routes::FETCH_ANNOUNCEMENT, FetchAnnouncementForm {})
+            .await?;
+        Ok(response.announcement)
+    }
+
+    pub async fn set_topic(&self, topic: Box<str>) -> ClientResult<()> {
+        let form = SetTopicForm::new(topic);
+        form.validate()?;
+        let response: SetTopicResponse = self.request(routes::SET_TOPIC, form).await?;
+        if response.ok {
+            Ok(())
+        } else {
+            Err(ClientError::Rejected {
+                reason: "no reason given".into(),
+            })
+        }
+    }
+
+    /// Enter read-only mode with `reason`, or leave it if `None`. Requires a moderator session.
+    pub async fn set_read_only(&self, reason: Option<Box<str>>) -> ClientResult<()> {
+        let form = SetReadOnlyForm { reason };
+        let response: SetReadOnlyResponse = self.request(routes::SET_READ_ONLY, form).await?;
+        if response.ok {
+            Ok(())
+        } else {
+            Err(ClientError::Rejected {
+                reason: "no reason given".into(),
+            })
+        }
+    }
+
+    /// Requires an admin session.
+    pub async fn set_archived(&self, archived: bool) -> ClientResult<()> {
+        let form = SetArchivedForm { archived };
+        let response: SetArchivedResponse = self.request(routes::SET_ARCHIVED, form).await?;
+        if response.ok {
+            Ok(())
+        } else {
+            Err(ClientError::Rejected {
+                reason: "no reason given".into(),
+            })
+        }
+    }
+
+    /// Open the server-sent event stream, resuming after the event with ID `last_event_id`.
+    /// Events are sent in `Envelope`s if `is_enveloped`.
+    pub async fn open_event_stream(
+        &self,
+        last_event_id: Option<&str>,
+        is_enveloped: bool,
+    ) -> ClientResult<ResponseBody> {
+        let (method, path) = routes::EVENTS;
+        let query = envelope_query(is_enveloped);
+        let uri: Uri = format!("{}{path}{query}", &self.server_url)
+            .parse()
+            .unwrap();
+        let mut headers = vec![("Accept", "text/event-stream")];
+        if let Some(last_event_id) = last_event_id {
+            headers.push(("Last-Event-ID", last_event_id));
+        }
+        // Not timed, as the body lasts as long as the stream.
+        let (response, _) = self
+            .request_raw_with_headers(uri, method.try_into()?, None::<()>, "text/plain", &headers)
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let message = status.canonical_reason().unwrap_or_default().into();
+            return Err(ClientError::Status { status, message });
+        }
+        Ok(response.into_body())
+    }
+
+    async fn request_raw(
+        &self,
+        url: Uri,
+        method: Method,
+        body: Option<impl Serialize>,
+        content_type: &'static str,
+    ) -> ClientResult<Response<ResponseBody>> {
+        let start = Instant::now();
+        let path = Box::from(url.path());
+        let (response, timing) = self
+            .request_raw_with_headers(url, method, body, content_type, &[])
+            .await?;
+        let pending_timing = PendingTiming {
+            timings: Arc::clone(&self.timings),
+            path,
+            start,
+            timing,
+        };
+        Ok(response.map(|body| {
+            body.map_frame(move |frame| {
+                // Owned by the body, to be dropped along with it.
+                let _pending_timing = &pending_timing;
+                frame
+            })
+            .boxed_unsync()
+        }))
+    }
+
+    async fn request_raw_with_headers(
+        &self,
+        url: Uri,
+        method: Method,
+        body: Option<impl Serialize>,
+        content_type: &'static str,
+        headers: &[(&str, &str)],
+    ) -> ClientResult<(Response<ResponseBody>, RequestTiming)> {
+        let start = Instant::now();
+        let authority = url.authority().unwrap().clone();
+        let body_string = match body {
+            Some(ref body) => serde_json::to_string(body)?,
+            None => String::new(),
+        };
+        self.bandwidth.record_sent(body_string.len());
+        let mut request = Request::builder()
+            .method(method)
+            .header(hyper::header::HOST, authority.as_str())
+            .header(hyper::header::CONTENT_TYPE, content_type);
+        if let Some(token) = self.session_token.lock().pretty_unwrap().as_deref() {
+            request = request.header(hyper::header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+        for &(name, value) in headers {
+            request = request.header(name, value);
+        }
+        let (response, connect) = match self.http_version {
+            HttpVersion::Http1 => {
+                let request = match self.connector.forwarding_proxy(&url) {
+                    Some(proxy) => forwarded(request, &url, proxy),
+                    None => request.uri(url.path_and_query().map_or("/", |path| path.as_str())),
+                };
+                let request = request.body(Full::new(Bytes::from(body_string)))?;
+                let (mut sender, connect) = connect_http1(&url, &self.connector).await?;
+                let response = sender.send_request(request).await?.map(box_incoming);
+                (response, Some(connect))
+            }
+            HttpVersion::Http2 => {
+                // HTTP/2 requires absolute-form URIs for the `:scheme` and `:authority`
+                // pseudo-headers.
+                let request = request.uri(url).body(Full::new(Bytes::from(body_string)))?;
+                let (mut sender, connect) = self.http2_sender().await?;
+                sender.ready().await?;
+                let response = sender.send_request(request).await?.map(box_incoming);
+                (response, connect)
+            }
+            #[cfg(feature = "http3")]
+            HttpVersion::Http3 => {
+                let request = request.uri(url).body(Bytes::from(body_string))?;
+                self.http3.send(request, &self.connector).await?
+            }
+            #[cfg(not(feature = "http3"))]
+            HttpVersion::Http3 => unreachable!("`--http3` is rejected without feature `http3`"),
+        };
+        let elapsed = start.elapsed();
+        let timing = RequestTiming {
+            connect,
+            first_byte: elapsed - connect.map_or(Duration::ZERO, |connect| connect.total()),
+            total: elapsed,
+        };
+        Ok((self.count_received(response), timing))
+    }
+
+    /// Count the body of `response` in `bandwidth` as it is read.
+    fn count_received(&self, response: Response<ResponseBody>) -> Response<ResponseBody> {
+        let bandwidth = Arc::clone(&self.bandwidth);
+        response.map(|body| {
+            body.map_frame(move |frame| {
+                if let Some(data) = frame.data_ref() {
+                    bandwidth.record_received(data.len());
+                }
+                frame
+            })
+            .boxed_unsync()
+        })
+    }
+
+    /// Get the shared HTTP/2 connection, (re)connecting if there isn't a live one.
+    /// The connection's timing is returned if it was just established.
+    async fn http2_sender(
+        &self,
+    ) -> ClientResult<(http2::SendRequest<Full<Bytes>>, Option<ConnectTiming>)> {
+        let mut http2_sender = self.http2_sender.lock().await;
+        if let Some(sender) = http2_sender.as_ref().filter(|sender| !sender.is_closed()) {
+            return Ok((sender.clone(), None));
+        }
+        let url: Uri = self.server_url.parse()?;
+        let (stream, connect) = self.connector.connect_tcp(&url).await?;
+        let (sender, conn) = http2::handshake(TokioExecutor::new(), TokioIo::new(stream)).await?;
+        tokio::task::spawn(async move {
+            if let Err(err) = conn.await {
+                log::error!("HTTP/2 connection failed: {:?}", err);
+            }
+        });
+        *http2_sender = Some(sender.clone());
+        Ok((sender, Some(connect)))
+    }
+
+    async fn request_json<T: DeserializeOwned>(
+        &self,
+        url: Uri,
+        method: Method,
+        body: Option<impl Serialize>,
+    ) -> ClientResult<T> {
+        let response = self
+            .request_raw(url, method, body, "application/json")
+            .await?;
+        parse_json_response(response).await
+    }
+
+    /// Like `request_json`, but get the response as string as well as the serialized object.
+    async fn request_and_get_string<T: DeserializeOwned>(
+        &self,
+        url: Uri,
+        method: Method,
+        body: impl Serialize,
+    ) -> ClientResult<(T, String)> {
+        let response_body = self
+            .request_raw(url, method, Some(body), "application/json")
+            .await?;
+        let response_string = collect_response_to_string(response_body).await?;
+        let x = serde_json::from_str(&response_string)?;
+        Ok((x, response_string))
+    }
 }
 
-async fn request_raw(
-    url: Uri,
-    method: Method,
-    body: Option<impl Serialize>,
-    content_type: &'static str,
-) -> DynResult<Response<Incoming>> {
-    let host = url.host().expect("uri has no host");
-    let port = url.port_u16().unwrap_or(80);
-    let addr = format!("{}:{}", host, port);
-    let stream = TcpStream::connect(addr).await?;
+async fn connect_http1<B>(
+    url: &Uri,
+    connector: &Connector,
+) -> ClientResult<(http1::SendRequest<B>, ConnectTiming)>
+where
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let (stream, connect) = connector.connect_http1(url).await?;
     let io = TokioIo::new(stream);
-    let (mut sender, conn) = hyper::client::conn::http1::handshake(io).await?;
+    let (sender, conn) = http1::handshake(io).await?;
     tokio::task::spawn(async move {
         if let Err(err) = conn.await {
             println!("Connection failed: {:?}", err);
         }
     });
-    let authority = url.authority().unwrap().clone();
-    let body_string = match body {
-        Some(ref body) => serde_json::to_string(body)?,
-        None => String::new(),
+    Ok((sender, connect))
+}
+
+/// `request` to `url` as sent to a `Connector::forwarding_proxy`: in absolute form, with the
+/// proxy's credentials.
+fn forwarded(request: request::Builder, url: &Uri, proxy: &Proxy) -> request::Builder {
+    let request = request.uri(url.clone());
+    match proxy.authorization() {
+        Some(authorization) => request.header(hyper::header::PROXY_AUTHORIZATION, authorization),
+        None => request,
+    }
+}
+
+/// Deserialize a successful response, or turn the `ErrorResponse` of a failed one into an error.
+async fn parse_json_response<T: DeserializeOwned>(
+    response: Response<ResponseBody>,
+) -> ClientResult<T> {
+    let status = response.status();
+    let response_body = response.collect().await?.aggregate();
+    if !status.is_success() {
+        return Err(error_of_response(status, response_body));
+    }
+    Ok(serde_json::from_reader(response_body.reader())?)
+}
+
+/// Error of a failed response, from its `ErrorResponse` body if it has one.
+fn error_of_response(status: StatusCode, response_body: impl Buf) -> ClientError {
+    let message = match serde_json::from_reader::<_, ErrorResponse>(response_body.reader()) {
+        Ok(ErrorResponse {
+            read_only: Some(reason),
+            ..
+        }) => return ClientError::ReadOnly { reason },
+        Ok(ErrorResponse {
+            attachment_rejection: Some(rejection),
+            ..
+        }) => return ClientError::AttachmentRejected(rejection),
+        Ok(response) => response.error,
+        Err(_) => status.canonical_reason().unwrap_or_default().into(),
     };
-    let path = url.path();
-    let request = Request::builder()
-        .method(method)
-        .uri(path)
-        .header(hyper::header::HOST, authority.as_str())
-        .header(hyper::header::CONTENT_TYPE, content_type)
-        .body(Full::new(Bytes::from(body_string)))?;
-    let response = sender.send_request(request).await?;
-    Ok(response)
-}
-
-async fn request<T: DeserializeOwned>(
-    url: Uri,
-    method: Method,
-    body: Option<impl Serialize>,
-) -> DynResult<T> {
-    let response_body = request_raw(url, method, body, "application/json")
-        .await?
-        .collect()
-        .await?
-        .aggregate();
-    serde_json::from_reader(response_body.reader()).map_err(Into::into)
-}
-
-/// Like `request`, but get the response as string as well as the serialized object.
-async fn request_and_get_string<T: DeserializeOwned>(
-    url: Uri,
-    method: Method,
-    body: impl Serialize,
-) -> DynResult<(T, String)> {
-    let response_body = request_raw(url, method, Some(body), "application/json").await?;
-    let response_string = collect_response_to_string(response_body).await?;
-    let x = serde_json::from_str(&response_string)?;
-    Ok((x, response_string))
-}
-
-async fn collect_response_to_string(response: Response<Incoming>) -> DynResult<String> {
+    // What the server answers requests with content it won't take with.
+    if status == StatusCode::UNPROCESSABLE_ENTITY {
+        return ClientError::Rejected { reason: message };
+    }
+    ClientError::Status { status, message }
+}
+
+/// Query asking for events in `Envelope`s, see `EventStreamForm`.
+fn envelope_query(is_enveloped: bool) -> String {
+    match is_enveloped {
+        true => format!("?v={}", Envelope::VERSION),
+        false => String::new(),
+    }
+}
+
+/// Percent-encode everything but unreserved characters.
+fn encode_query_component(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+/// Claimed type of an uploaded file, from its extension.
+/// Only matters for text, the server tells apart other types from the content.
+fn content_type_of(path: &Path) -> &'static str {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    match extension.map(str::to_ascii_lowercase).as_deref() {
+        Some("md" | "markdown") => "text/markdown",
+        Some("csv") => "text/csv",
+        Some("html" | "htm") => "text/html",
+        Some("txt") => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+fn box_incoming(body: Incoming) -> ResponseBody {
+    body.map_err(ClientError::from).boxed_unsync()
+}
+
+async fn collect_response_to_string(response: Response<ResponseBody>) -> ClientResult<String> {
     let response_body = response.collect().await?.to_bytes();
     let response_string = String::from_utf8(response_body.to_vec())?;
     Ok(response_string)
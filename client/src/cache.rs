@@ -0,0 +1,189 @@
+//! Messages loaded in the previous session, so that start up doesn't wait on fetching them all.
+//! One file per server in the client's cache directory, encoded with bincode behind a header:
+//!
+//! ```txt
+//! MAGIC (8 bytes) | VERSION (u32 LE) | CRC-32 of the payload (u32 LE) | payload
+//! ```
+//!
+//! Files that are corrupted, or from another version, are ignored and the messages refetched.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use interface::{Attachment, ContentKind, ForwardedFrom, LinkPreview, Message, MessageId, Poll};
+use serde::{Deserialize, Serialize};
+
+const MAGIC: &[u8; 8] = b"MBCACHE\0";
+
+/// Bumped whenever the payload changes shape, files from other versions are ignored.
+const VERSION: u32 = 4;
+
+const HEADER_LEN: usize = MAGIC.len() + 4 + 4;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CacheError {
+    #[error("not a message cache")]
+    BadMagic,
+    #[error("cache version {0}, expected {VERSION}")]
+    IncompatibleVersion(u32),
+    #[error("checksum mismatch")]
+    ChecksumMismatch,
+    #[error("cache of another server ({0})")]
+    OtherServer(String),
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    Bincode(#[from] bincode::Error),
+}
+
+/// `interface::Message` as cached. `MessageId` only deserializes from self-describing formats,
+/// which bincode isn't, so IDs are kept as numbers.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedMessage {
+    id: u64,
+    content: Box<str>,
+    date: DateTime<Utc>,
+    link_preview: Option<LinkPreview>,
+    expires_at: Option<DateTime<Utc>>,
+    poll: Option<Poll>,
+    webhook_name: Option<Box<str>>,
+    content_kind: ContentKind,
+    attachments: Box<[Attachment]>,
+    author: Option<u64>,
+    /// `ForwardedFrom` holds a `MessageId` too, so it's kept as `(id, date, board)`.
+    forwarded_from: Option<(u64, DateTime<Utc>, Option<Box<str>>)>,
+}
+
+impl From<&Message> for CachedMessage {
+    fn from(message: &Message) -> Self {
+        Self {
+            id: message.id.0,
+            content: message.content.clone(),
+            date: message.date,
+            link_preview: message.link_preview.clone(),
+            expires_at: message.expires_at,
+            poll: message.poll.clone(),
+            webhook_name: message.webhook_name.clone(),
+            content_kind: message.content_kind,
+            attachments: message.attachments.clone(),
+            author: message.author,
+            forwarded_from: message.forwarded_from.as_ref().map(|forwarded_from| {
+                (
+                    forwarded_from.id.0,
+                    forwarded_from.date,
+                    forwarded_from.board.clone(),
+                )
+            }),
+        }
+    }
+}
+
+impl From<CachedMessage> for Message {
+    fn from(message: CachedMessage) -> Self {
+        Self {
+            id: MessageId(message.id),
+            content: message.content,
+            date: message.date,
+            link_preview: message.link_preview,
+            expires_at: message.expires_at,
+            poll: message.poll,
+            webhook_name: message.webhook_name,
+            content_kind: message.content_kind,
+            attachments: message.attachments,
+            author: message.author,
+            forwarded_from: message
+                .forwarded_from
+                .map(|(id, date, board)| ForwardedFrom {
+                    id: MessageId(id),
+                    date,
+                    board,
+                }),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Payload {
+    /// Checked on load, in case two URLs map to the same file name.
+    server_url: String,
+    messages: Vec<CachedMessage>,
+}
+
+/// Path of the cache of the server at `server_url`, `None` if the platform has no cache
+/// directory.
+pub fn default_path(server_url: &str) -> Option<PathBuf> {
+    let file_name: String = server_url
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    Some(
+        dirs::cache_dir()?
+            .join("message_board")
+            .join(format!("{file_name}.bin")),
+    )
+}
+
+/// Messages cached for `server_url`, oldest first.
+pub fn load(path: &Path, server_url: &str) -> Result<Vec<Message>, CacheError> {
+    let bytes = fs::read(path)?;
+    let (header, payload) = bytes
+        .split_at_checked(HEADER_LEN)
+        .ok_or(CacheError::BadMagic)?;
+    let (magic, header) = header.split_at(MAGIC.len());
+    if magic != MAGIC {
+        return Err(CacheError::BadMagic);
+    }
+    let (version, checksum) = header.split_at(4);
+    let version = u32::from_le_bytes(version.try_into().unwrap());
+    if version != VERSION {
+        return Err(CacheError::IncompatibleVersion(version));
+    }
+    let checksum = u32::from_le_bytes(checksum.try_into().unwrap());
+    if crc32fast::hash(payload) != checksum {
+        return Err(CacheError::ChecksumMismatch);
+    }
+    let payload: Payload = bincode::deserialize(payload)?;
+    if payload.server_url != server_url {
+        return Err(CacheError::OtherServer(payload.server_url));
+    }
+    Ok(payload.messages.into_iter().map(Message::from).collect())
+}
+
+pub fn save<'a>(
+    path: &Path,
+    server_url: &str,
+    messages: impl IntoIterator<Item = &'a Message>,
+) -> Result<(), CacheError> {
+    let payload = bincode::serialize(&Payload {
+        server_url: server_url.to_owned(),
+        messages: messages.into_iter().map(CachedMessage::from).collect(),
+    })?;
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // Written aside then renamed, so that a crash midway doesn't leave a truncated cache.
+    // Only readable by the user, like the session tokens, as messages of private boards are
+    // cached too.
+    let temp_path = path.with_extension("bin.tmp");
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(&temp_path)?;
+    // The mode only applies to new files, not to a temporary file left over by a crash.
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    file.write_all(&bytes)?;
+    drop(file);
+    fs::rename(temp_path, path)?;
+    Ok(())
+}
@@ -0,0 +1,734 @@
+//! Slash commands typed into the input field.
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::Utc;
+use interface::{
+    capabilities, AttachmentKind, ContentKind, ForwardMessageForm, Message, SetProfileForm,
+    UpdateMembersForm,
+};
+
+use crate::{
+    export,
+    filter::MessageFilter,
+    newtui::Density,
+    session,
+    state::AppState,
+    utils::PrettyUnwrap,
+    voice::{MAX_RECORDING_LENGTH, RECORDING_EXTENSION},
+};
+
+const POLL_USAGE: &str = "/poll QUESTION | OPTION 1 | OPTION 2 | ...";
+const MARKDOWN_USAGE: &str = "/md TEXT";
+const CODE_USAGE: &str = "/code CODE";
+const EXPORT_USAGE: &str = "/export PATH";
+const BATCH_USAGE: &str = "/batch [COUNT|max]";
+const INTERVAL_USAGE: &str = "/interval [MILLISECONDS]";
+const FILTER_USAGE: &str = "/filter [TEXT] [from:WEBHOOK] [has:poll] [has:link]";
+const ATTACH_USAGE: &str = "/attach PATH";
+const RECORD_USAGE: &str = "/record [SECONDS]";
+const DENSITY_USAGE: &str = "/density [compact|cozy]";
+const REACT_USAGE: &str = "/react EMOJI";
+const PROFILE_USAGE: &str = "/profile name|pronouns|avatar|bio [VALUE]";
+/// Asked for, as the account can't be brought back.
+const DELETE_ACCOUNT_USAGE: &str = "/deleteaccount yes";
+const BLOCK_USAGE: &str = "/block [USER]";
+const UNBLOCK_USAGE: &str = "/unblock USER";
+const MEMBERS_USAGE: &str = "/members [add|remove USER]";
+
+/// Shown to users if `/freeze` is given no reason.
+const DEFAULT_FREEZE_REASON: &str = "posting is frozen by an admin";
+
+/// Length of voice notes recorded with `/record` alone.
+const DEFAULT_RECORDING_LENGTH: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command<'a> {
+    Poll {
+        question: &'a str,
+        options: Vec<&'a str>,
+    },
+    /// Send a message rendered as something other than plain text.
+    Send {
+        content: &'a str,
+        content_kind: ContentKind,
+    },
+    /// Show local usage stats. Handled by the frontends.
+    Stats,
+    /// Only show messages matching the filter, or all messages if `None`. Handled by the
+    /// frontends.
+    Filter(Option<MessageFilter>),
+    /// Switch the density of the message list, toggling it if `None`. Handled by the frontends.
+    Density(Option<Density>),
+    /// React to the selected message, or take the reaction back. Handled by the frontends.
+    React { reaction: &'a str },
+    /// Repost the selected message on the server whose URL contains `server`, or on the current
+    /// server if `None`. Handled by the frontends.
+    Forward { server: Option<&'a str> },
+    /// Show or stop showing masked words as asterisks.
+    Mask,
+    /// Measure round-trip time to the server.
+    Ping,
+    /// Show the number of messages fetched per request, or change it if `count` is given.
+    Batch { count: Option<u32> },
+    /// Show how often new messages are checked for, or change it if `millis` is given.
+    Interval { millis: Option<u64> },
+    /// Write the loaded messages to a file, formatted by its extension.
+    Export { path: &'a str },
+    /// Forget the stored session token of the server.
+    Logout,
+    /// Replace the session token with a new one, invalidating the old one.
+    NewToken,
+    /// Delete the session on the server, and its messages or the attribution of them.
+    DeleteAccount,
+    /// Upload a file, to be sent along with the next message.
+    Attach { path: &'a str },
+    /// Record a voice note and attach it like `Attach`.
+    Record { length: Duration },
+    /// Play the latest audio attachment.
+    Play,
+    /// Put the board into read-only mode. Needs an admin session.
+    Freeze { reason: &'a str },
+    /// Take the board out of read-only mode. Needs an admin session.
+    Unfreeze,
+    /// Archive the board, or bring it back if `false`. Needs an admin session.
+    Archive { archived: bool },
+    /// Set a field of the session's profile, clearing it if `value` is empty.
+    Profile { field: ProfileField, value: &'a str },
+    /// Hide messages by session `user`, or show the blocked sessions if `None`.
+    Block { user: Option<u64> },
+    /// Show messages by session `user` again.
+    Unblock { user: u64 },
+    /// Show the members of the board, or add or remove one. Needs an admin session.
+    Members { change: Option<MemberChange> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemberChange {
+    Add(u64),
+    Remove(u64),
+}
+
+impl MemberChange {
+    fn parse(args: &str) -> Option<Self> {
+        let (action, user) = args.split_once(char::is_whitespace)?;
+        let user = user.trim().parse().ok()?;
+        match action {
+            "add" => Some(Self::Add(user)),
+            "remove" => Some(Self::Remove(user)),
+            _ => None,
+        }
+    }
+
+    fn form(self) -> UpdateMembersForm {
+        match self {
+            Self::Add(user) => UpdateMembersForm {
+                add: Box::new([user]),
+                ..Default::default()
+            },
+            Self::Remove(user) => UpdateMembersForm {
+                remove: Box::new([user]),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileField {
+    DisplayName,
+    Pronouns,
+    Avatar,
+    Bio,
+}
+
+impl ProfileField {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "name" => Some(Self::DisplayName),
+            "pronouns" => Some(Self::Pronouns),
+            "avatar" => Some(Self::Avatar),
+            "bio" => Some(Self::Bio),
+            _ => None,
+        }
+    }
+
+    /// Form changing only this field.
+    fn form(self, value: &str) -> SetProfileForm {
+        let value = Some(value.into());
+        match self {
+            Self::DisplayName => SetProfileForm {
+                display_name: value,
+                ..Default::default()
+            },
+            Self::Pronouns => SetProfileForm {
+                pronouns: value,
+                ..Default::default()
+            },
+            Self::Avatar => SetProfileForm {
+                avatar: value,
+                ..Default::default()
+            },
+            Self::Bio => SetProfileForm {
+                bio: value,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CommandError {
+    #[error("unknown command /{0}")]
+    Unknown(String),
+    #[error("usage: {0}")]
+    Usage(&'static str),
+}
+
+/// Parse input of the input field.
+/// Returns `None` if the input is not a command, i.e. doesn't start with `/`.
+/// Input starting with `//` is not a command, and should be sent as message with the first `/`
+/// removed.
+pub fn parse(input: &str) -> Option<Result<Command, CommandError>> {
+    let input = input.strip_prefix('/')?;
+    if input.starts_with('/') {
+        return None;
+    }
+    let (name, args) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+    let args = args.trim();
+    Some(match name {
+        "poll" => {
+            let mut parts = args.split('|').map(str::trim);
+            let question = parts.next().unwrap_or_default();
+            let options: Vec<&str> = parts.collect();
+            if question.is_empty() || options.len() < 2 {
+                Err(CommandError::Usage(POLL_USAGE))
+            } else {
+                Ok(Command::Poll { question, options })
+            }
+        }
+        "md" if args.is_empty() => Err(CommandError::Usage(MARKDOWN_USAGE)),
+        "md" => Ok(Command::Send {
+            content: args,
+            content_kind: ContentKind::Markdown,
+        }),
+        "code" if args.is_empty() => Err(CommandError::Usage(CODE_USAGE)),
+        "code" => Ok(Command::Send {
+            content: args,
+            content_kind: ContentKind::Code,
+        }),
+        "stats" => Ok(Command::Stats),
+        "filter" if args.is_empty() => Ok(Command::Filter(None)),
+        "filter" => MessageFilter::parse(args)
+            .map(|filter| Command::Filter(Some(filter)))
+            .ok_or(CommandError::Usage(FILTER_USAGE)),
+        "density" if args.is_empty() => Ok(Command::Density(None)),
+        "density" => args
+            .parse()
+            .map(|density| Command::Density(Some(density)))
+            .map_err(|_| CommandError::Usage(DENSITY_USAGE)),
+        "react" if args.is_empty() => Err(CommandError::Usage(REACT_USAGE)),
+        "react" => Ok(Command::React { reaction: args }),
+        "mask" => Ok(Command::Mask),
+        "ping" => Ok(Command::Ping),
+        "batch" => match args {
+            "" => Ok(Command::Batch { count: None }),
+            // Clamped to the server's maximum.
+            "max" => Ok(Command::Batch {
+                count: Some(u32::MAX),
+            }),
+            count => count
+                .parse()
+                .map(|count| Command::Batch { count: Some(count) })
+                .map_err(|_| CommandError::Usage(BATCH_USAGE)),
+        },
+        "interval" => match args {
+            "" => Ok(Command::Interval { millis: None }),
+            millis => millis
+                .parse()
+                .map(|millis| Command::Interval {
+                    millis: Some(millis),
+                })
+                .map_err(|_| CommandError::Usage(INTERVAL_USAGE)),
+        },
+        "export" if args.is_empty() => Err(CommandError::Usage(EXPORT_USAGE)),
+        "export" => Ok(Command::Export { path: args }),
+        "logout" => Ok(Command::Logout),
+        "newtoken" => Ok(Command::NewToken),
+        "deleteaccount" if args == "yes" => Ok(Command::DeleteAccount),
+        "deleteaccount" => Err(CommandError::Usage(DELETE_ACCOUNT_USAGE)),
+        "attach" if args.is_empty() => Err(CommandError::Usage(ATTACH_USAGE)),
+        "attach" => Ok(Command::Attach { path: args }),
+        "record" if args.is_empty() => Ok(Command::Record {
+            length: DEFAULT_RECORDING_LENGTH,
+        }),
+        "record" => args
+            .parse()
+            .ok()
+            .map(Duration::from_secs)
+            .filter(|length| !length.is_zero() && *length <= MAX_RECORDING_LENGTH)
+            .map(|length| Command::Record { length })
+            .ok_or(CommandError::Usage(RECORD_USAGE)),
+        "play" => Ok(Command::Play),
+        "freeze" if args.is_empty() => Ok(Command::Freeze {
+            reason: DEFAULT_FREEZE_REASON,
+        }),
+        "freeze" => Ok(Command::Freeze { reason: args }),
+        "unfreeze" => Ok(Command::Unfreeze),
+        "archive" => Ok(Command::Archive { archived: true }),
+        "unarchive" => Ok(Command::Archive { archived: false }),
+        "profile" => {
+            let (field, value) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+            ProfileField::parse(field)
+                .map(|field| Command::Profile {
+                    field,
+                    value: value.trim(),
+                })
+                .ok_or(CommandError::Usage(PROFILE_USAGE))
+        }
+        "forward" if args.is_empty() => Ok(Command::Forward { server: None }),
+        "forward" => Ok(Command::Forward { server: Some(args) }),
+        "block" if args.is_empty() => Ok(Command::Block { user: None }),
+        "block" => args
+            .parse()
+            .map(|user| Command::Block { user: Some(user) })
+            .map_err(|_| CommandError::Usage(BLOCK_USAGE)),
+        "unblock" => args
+            .parse()
+            .map(|user| Command::Unblock { user })
+            .map_err(|_| CommandError::Usage(UNBLOCK_USAGE)),
+        "members" if args.is_empty() => Ok(Command::Members { change: None }),
+        "members" => MemberChange::parse(args)
+            .map(|change| Command::Members {
+                change: Some(change),
+            })
+            .ok_or(CommandError::Usage(MEMBERS_USAGE)),
+        name => Err(CommandError::Unknown(name.to_owned())),
+    })
+}
+
+/// Run the command in the background.
+/// Commands that show something are left for the frontends to handle.
+pub fn execute(command: Command, app_state: Arc<AppState>) {
+    match command {
+        Command::Stats
+        | Command::Filter(_)
+        | Command::Density(_)
+        | Command::React { .. }
+        | Command::Forward { .. } => (),
+        Command::Mask => match app_state.toggle_masking() {
+            Some(true) => app_state.toasts().info("Masked words are hidden"),
+            Some(false) => app_state.toasts().info("Masked words are shown"),
+            None => app_state
+                .toasts()
+                .info("No words to mask, configure them with --mask-words or --mask-words-file"),
+        },
+        Command::Ping => {
+            tokio::spawn(async move {
+                match app_state.ping().await {
+                    Ok(latency) => app_state
+                        .toasts()
+                        .info(format!("Pong! Round-trip time: {}ms", latency.as_millis())),
+                    Err(e) => {
+                        log::error!("Error pinging server: {e}");
+                        app_state.toast_error("Ping failed", &e);
+                    }
+                }
+            });
+        }
+        Command::Batch { count } => {
+            let count = match count {
+                Some(count) => app_state.set_fetch_batch_size(count),
+                None => app_state.fetch_batch_size(),
+            };
+            app_state
+                .toasts()
+                .info(format!("Fetching up to {count} messages per request"));
+        }
+        Command::Interval { millis } => {
+            let interval = match millis {
+                Some(millis) => app_state.set_poll_interval(Duration::from_millis(millis)),
+                None => app_state.poll_interval(),
+            };
+            app_state.toasts().info(format!(
+                "Checking for new messages every {}ms",
+                interval.as_millis()
+            ));
+        }
+        Command::Export { path } => {
+            let path = PathBuf::from(path);
+            // Only the marked messages, if any are.
+            let mut messages = app_state.marked_messages();
+            if messages.is_empty() {
+                messages = app_state.lock_messages().iter().cloned().collect();
+            }
+            tokio::task::spawn_blocking(move || {
+                match export::export(&path, &messages, app_state.time_formatter()) {
+                    Ok(()) => app_state.toasts().info(format!(
+                        "Exported {} messages to {}",
+                        messages.len(),
+                        path.display()
+                    )),
+                    Err(e) => {
+                        log::error!("Error exporting messages to {}: {e}", path.display());
+                        app_state
+                            .toasts()
+                            .error(format!("Failed to export messages: {e}"));
+                    }
+                }
+            });
+        }
+        Command::Logout => match session::log_out(&app_state) {
+            Ok(()) => app_state
+                .toasts()
+                .info("Logged out, the session token is no longer stored"),
+            Err(e) => {
+                log::error!("Error logging out: {e}");
+                app_state.toasts().error(format!("Failed to log out: {e}"));
+            }
+        },
+        Command::NewToken | Command::DeleteAccount
+            if !app_state.supports(capabilities::ACCOUNTS) =>
+        {
+            app_state.toast_unsupported("Managing sessions");
+        }
+        Command::NewToken => {
+            tokio::spawn(async move {
+                match session::rotate_token(&app_state).await {
+                    Ok(()) => app_state
+                        .toasts()
+                        .info("Session token replaced, the old one is no longer valid"),
+                    Err(e) => {
+                        log::error!("Error rotating session token: {e}");
+                        app_state
+                            .toasts()
+                            .error(format!("Failed to replace session token: {e}"));
+                    }
+                }
+            });
+        }
+        Command::DeleteAccount => {
+            tokio::spawn(async move {
+                match session::delete_account(&app_state).await {
+                    Ok(message_count) => app_state.toasts().info(format!(
+                        "Account deleted, {message_count} of its messages deleted or made anonymous"
+                    )),
+                    Err(e) => {
+                        log::error!("Error deleting account: {e}");
+                        app_state
+                            .toasts()
+                            .error(format!("Failed to delete account: {e}"));
+                    }
+                }
+            });
+        }
+        Command::Attach { .. } if !app_state.supports(capabilities::ATTACHMENTS) => {
+            app_state.toast_unsupported("Attachments");
+        }
+        Command::Attach { path } => {
+            let path = PathBuf::from(path);
+            tokio::spawn(async move {
+                match app_state.attach(&path).await {
+                    Ok(attachment) => app_state.toasts().info(format!(
+                        "Attached {}, it will be sent with your next message",
+                        attachment.name
+                    )),
+                    Err(e) => {
+                        log::error!("Error uploading {}: {e}", path.display());
+                        app_state.toast_error("Failed to upload attachment", &e);
+                    }
+                }
+            });
+        }
+        Command::Record { .. } if !app_state.supports(capabilities::ATTACHMENTS) => {
+            app_state.toast_unsupported("Voice notes");
+        }
+        Command::Record { length } => {
+            app_state
+                .toasts()
+                .info(format!("Recording a {}s voice note", length.as_secs()));
+            tokio::spawn(record_voice_note(app_state, length));
+        }
+        Command::Play => {
+            tokio::spawn(play_voice_note(app_state));
+        }
+        Command::Freeze { reason } => {
+            tokio::spawn(set_read_only(app_state, Some(reason.into())));
+        }
+        Command::Unfreeze => {
+            tokio::spawn(set_read_only(app_state, None));
+        }
+        Command::Archive { .. } if !app_state.supports(capabilities::ARCHIVING) => {
+            app_state.toast_unsupported("Archiving boards");
+        }
+        Command::Archive { archived } => {
+            tokio::spawn(set_archived(app_state, archived));
+        }
+        Command::Profile { .. } if !app_state.supports(capabilities::PROFILES) => {
+            app_state.toast_unsupported("Profiles");
+        }
+        Command::Profile { field, value } => {
+            let form = field.form(value);
+            tokio::spawn(async move {
+                match app_state.api().set_profile(form).await {
+                    Ok(_) => app_state.toasts().info("Profile updated"),
+                    Err(e) => {
+                        log::error!("Error setting profile: {e}");
+                        app_state.toast_error("Failed to update profile", &e);
+                    }
+                }
+            });
+        }
+        Command::Block { .. } | Command::Unblock { .. }
+            if !app_state.supports(capabilities::BLOCKS) =>
+        {
+            app_state.toast_unsupported("Blocking");
+        }
+        Command::Block { user: None } => {
+            tokio::spawn(async move {
+                match app_state.api().fetch_blocks().await {
+                    Ok(blocked) if blocked.is_empty() => {
+                        app_state.toasts().info("You haven't blocked anyone")
+                    }
+                    Ok(blocked) => {
+                        let blocked: Vec<String> = blocked.iter().map(u64::to_string).collect();
+                        app_state
+                            .toasts()
+                            .info(format!("Blocked: {}", blocked.join(", ")));
+                    }
+                    Err(e) => {
+                        log::error!("Error fetching blocks: {e}");
+                        app_state.toast_error("Failed to fetch blocked users", &e);
+                    }
+                }
+            });
+        }
+        Command::Block { user: Some(user) } => {
+            tokio::spawn(update_blocks(app_state, user, true));
+        }
+        Command::Unblock { user } => {
+            tokio::spawn(update_blocks(app_state, user, false));
+        }
+        Command::Members { .. } if !app_state.supports(capabilities::MEMBERS) => {
+            app_state.toast_unsupported("Private boards");
+        }
+        Command::Members { change } => {
+            tokio::spawn(async move {
+                let result = match change {
+                    Some(change) => app_state.api().update_members(change.form()).await,
+                    None => app_state.api().fetch_members().await,
+                };
+                match result {
+                    Ok(members) if members.is_empty() => {
+                        app_state.toasts().info("The board has no members")
+                    }
+                    Ok(members) => {
+                        let members: Vec<String> = members.iter().map(u64::to_string).collect();
+                        app_state
+                            .toasts()
+                            .info(format!("Members: {}", members.join(", ")));
+                    }
+                    Err(e) => {
+                        log::error!("Error managing members: {e}");
+                        app_state.toast_error("Failed to manage members", &e);
+                    }
+                }
+            });
+        }
+        Command::Send { .. } if !app_state.supports(capabilities::CONTENT_KINDS) => {
+            app_state.toast_unsupported("Markdown and code messages");
+        }
+        Command::Send {
+            content,
+            content_kind,
+        } => {
+            let content: Box<str> = content.into();
+            tokio::spawn(async move {
+                let send_result = app_state.send_message(content, content_kind, None).await;
+                if let Err(e) = send_result {
+                    log::error!("Error sending message: {e}");
+                    app_state.toast_error("Failed to send message", &e);
+                }
+            });
+        }
+        Command::Poll { .. } if !app_state.supports(capabilities::POLLS) => {
+            app_state.toast_unsupported("Polls");
+        }
+        Command::Poll { question, options } => {
+            let question: Box<str> = question.into();
+            let options: Box<[Box<str>]> = options.into_iter().map(Into::into).collect();
+            tokio::spawn(async move {
+                match app_state.api().send_poll(question, options).await {
+                    Ok(()) => app_state.record_message_sent(),
+                    Err(e) => {
+                        log::error!("Error sending poll: {e}");
+                        app_state.toast_error("Failed to send poll", &e);
+                    }
+                }
+            });
+        }
+    }
+}
+
+/// The server announces the change with an `Event::ReadOnly`, only failures are shown here.
+async fn set_read_only(app_state: Arc<AppState>, reason: Option<Box<str>>) {
+    let is_freezing = reason.is_some();
+    if let Err(e) = app_state.api().set_read_only(reason).await {
+        log::error!("Error setting read-only mode: {e}");
+        match is_freezing {
+            true => app_state.toast_error("Failed to freeze the board", &e),
+            false => app_state.toast_error("Failed to unfreeze the board", &e),
+        }
+    }
+}
+
+/// The server announces the change with an `Event::Archived`, only failures are shown here.
+async fn set_archived(app_state: Arc<AppState>, archived: bool) {
+    if let Err(e) = app_state.api().set_archived(archived).await {
+        log::error!("Error setting archived: {e}");
+        match archived {
+            true => app_state.toast_error("Failed to archive the board", &e),
+            false => app_state.toast_error("Failed to unarchive the board", &e),
+        }
+    }
+}
+
+/// Repost `message` on the server of `app_state`, attributed to it by the server.
+/// `board` is the URL of the server the message is on, `None` if it's the same server.
+pub async fn forward_message(app_state: Arc<AppState>, message: Message, board: Option<Box<str>>) {
+    if !app_state.supports(capabilities::FORWARDING) {
+        app_state.toast_unsupported("Forwarding");
+        return;
+    }
+    if board.is_some() && !app_state.supports(capabilities::CROSS_BOARD_FORWARDING) {
+        app_state.toast_unsupported("Forwarding from other servers");
+        return;
+    }
+    let form = ForwardMessageForm {
+        message_id: message.id,
+        board,
+    };
+    match app_state.api().forward_message(form).await {
+        Ok(_) => app_state.toasts().info("Forwarded"),
+        Err(e) => {
+            log::error!("Error forwarding message: {e}");
+            app_state.toast_error("Failed to forward message", &e);
+        }
+    }
+}
+
+/// Add `user` to the block list or remove it, on top of the list the server has.
+/// Messages already loaded stay until they are fetched again.
+async fn update_blocks(app_state: Arc<AppState>, user: u64, is_blocking: bool) {
+    let result = async {
+        let mut blocked = app_state.api().fetch_blocks().await?.into_vec();
+        blocked.retain(|&id| id != user);
+        if is_blocking {
+            blocked.push(user);
+        }
+        app_state.api().set_blocks(blocked.into()).await
+    };
+    match (result.await, is_blocking) {
+        (Ok(_), true) => app_state.toasts().info(format!("Blocked {user}")),
+        (Ok(_), false) => app_state.toasts().info(format!("Unblocked {user}")),
+        (Err(e), _) => {
+            log::error!("Error updating blocks: {e}");
+            app_state.toast_error("Failed to update blocked users", &e);
+        }
+    }
+}
+
+/// Record into a temporary file, then upload it like `/attach`.
+async fn record_voice_note(app_state: Arc<AppState>, length: Duration) {
+    let file_name = format!(
+        "voice-note-{}.{RECORDING_EXTENSION}",
+        Utc::now().format("%Y%m%d-%H%M%S")
+    );
+    let path = env::temp_dir().join(file_name);
+    let audio = Arc::clone(app_state.audio());
+    let recording_path = path.clone();
+    let recorded = tokio::task::spawn_blocking(move || audio.record(&recording_path, length))
+        .await
+        .pretty_unwrap();
+    if let Err(e) = recorded {
+        log::error!("Error recording voice note: {e}");
+        app_state
+            .toasts()
+            .error(format!("Failed to record voice note: {e}"));
+        return;
+    }
+    let result = app_state.attach(&path).await;
+    if let Err(e) = tokio::fs::remove_file(&path).await {
+        log::warn!("Can't remove recorded voice note {}: {e}", path.display());
+    }
+    match result {
+        Ok(_) => app_state
+            .toasts()
+            .info("Voice note recorded, it will be sent with your next message"),
+        Err(e) => {
+            log::error!("Error uploading voice note: {e}");
+            app_state.toast_error("Failed to upload voice note", &e);
+        }
+    }
+}
+
+/// Download the latest audio attachment into a temporary file, and play it.
+async fn play_voice_note(app_state: Arc<AppState>) {
+    let attachment = app_state
+        .lock_messages()
+        .iter()
+        .rev()
+        .flat_map(|message| message.attachments.iter())
+        .find(|attachment| attachment.kind() == AttachmentKind::Audio)
+        .cloned();
+    let Some(attachment) = attachment else {
+        app_state.toasts().info("There are no voice notes to play");
+        return;
+    };
+    let content = match app_state.api().fetch_attachment(attachment.id).await {
+        Ok(content) => content,
+        Err(e) => {
+            log::error!("Error fetching attachment {}: {e}", attachment.id);
+            app_state.toast_error("Failed to fetch voice note", &e);
+            return;
+        }
+    };
+    // Keeps the extension of the original, which some players go by. The rest of the name is
+    // up to the sender, so it's left out lest it contain path separators or `..`.
+    let extension = Path::new(&*attachment.name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .filter(|extension| extension.chars().all(|c| c.is_ascii_alphanumeric()))
+        .map(|extension| format!(".{extension}"))
+        .unwrap_or_default();
+    let path = env::temp_dir().join(format!("voice-note-{}{extension}", attachment.id));
+    if let Err(e) = tokio::fs::write(&path, content).await {
+        log::error!("Error writing {}: {e}", path.display());
+        app_state
+            .toasts()
+            .error(format!("Failed to play voice note: {e}"));
+        return;
+    }
+    app_state
+        .toasts()
+        .info(format!("Playing {}", attachment.name));
+    let audio = Arc::clone(app_state.audio());
+    let playing_path = path.clone();
+    let played = tokio::task::spawn_blocking(move || audio.play(&playing_path))
+        .await
+        .pretty_unwrap();
+    if let Err(e) = played {
+        log::error!("Error playing voice note: {e}");
+        app_state
+            .toasts()
+            .error(format!("Failed to play voice note: {e}"));
+    }
+    if let Err(e) = tokio::fs::remove_file(&path).await {
+        log::warn!("Can't remove played voice note {}: {e}", path.display());
+    }
+}
@@ -0,0 +1,277 @@
+use std::{env, fs, time::Duration};
+
+use crate::{
+    api::HttpVersion,
+    connector::IpFamily,
+    mask::WordMask,
+    newtui::{Density, Grouping},
+    proxy::{InvalidProxy, Proxy, ProxyConfig},
+    theme::Theme,
+    time_format::{self, TimeFormatter, Timezone},
+    voice::ExternalCommands,
+};
+
+const DEFAULT_SERVER_URL: &str = if cfg!(debug_assertions) {
+    "http://127.0.0.1:3000"
+} else {
+    "http://64.176.51.97:3000"
+};
+
+/// Enough for hours of a busy board, while keeping memory use in the megabytes.
+const DEFAULT_MAX_MESSAGES: usize = 5000;
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum ConfigError {
+    #[error("invalid timezone {0:?}, expected `local`, `utc`, or an offset like `+08:00`")]
+    InvalidTimezone(String),
+    #[error("invalid date format {0:?}")]
+    InvalidDateFormat(String),
+    #[error("invalid latency warning threshold {0:?}, expected milliseconds")]
+    InvalidLatencyWarning(String),
+    #[error("invalid fetch batch size {0:?}, expected a number of messages")]
+    InvalidFetchBatchSize(String),
+    #[error("invalid maximum of messages {0:?}, expected a positive number")]
+    InvalidMaxMessages(String),
+    #[error("invalid poll interval {0:?}, expected milliseconds")]
+    InvalidPollInterval(String),
+    #[error("invalid group threshold {0:?}, expected seconds")]
+    InvalidGroupThreshold(String),
+    #[error("invalid density {0:?}, expected `compact` or `cozy`")]
+    InvalidDensity(String),
+    #[error("can't read words to mask from {path:?}: {error}")]
+    UnreadableMaskWords { path: String, error: String },
+    #[error("too many words to mask or mention: {0}")]
+    InvalidWords(String),
+    #[error("`--http3` requires building with feature `http3`")]
+    Http3Unsupported,
+    #[error(transparent)]
+    InvalidProxy(#[from] InvalidProxy),
+    #[error("unknown flag {0:?}")]
+    UnknownFlag(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Servers to connect to, switched between with `<CTRL + O>`. Never empty.
+    pub server_urls: Vec<String>,
+    pub http_version: HttpVersion,
+    /// Address families to connect over, both by default.
+    pub ip_family: IpFamily,
+    pub proxy: ProxyConfig,
+    pub time_formatter: TimeFormatter,
+    /// Use the linear, screen reader friendly frontend instead of the TUI.
+    pub plain: bool,
+    pub theme: Theme,
+    /// Keep local usage stats, opt-in.
+    pub stats: bool,
+    /// Round-trip time to the server above which the user is warned.
+    pub latency_warning: Duration,
+    /// Session token to use instead of a stored one, for the first server.
+    pub session_token: Option<Box<str>>,
+    /// Messages fetched per request, the server's `max_fetch_count` if `None`.
+    /// Clamped by `AppState`, as is `poll_interval`.
+    pub fetch_batch_size: Option<u32>,
+    /// Messages kept loaded, the oldest are dropped beyond that.
+    pub max_messages: usize,
+    /// How often to check for new messages.
+    pub poll_interval: Duration,
+    /// Commands recording and playing voice notes.
+    pub audio: ExternalCommands,
+    /// Initial density of the message list, switchable at runtime.
+    pub density: Density,
+    pub grouping: Grouping,
+    /// Restore the server shown, the selected message and new message markers from the previous
+    /// session, and save them for the next one. See `resume::Resume`.
+    pub resume: bool,
+    /// Load the messages cached in the previous session on start, and cache them on exit.
+    /// See `cache`.
+    pub cache: bool,
+    /// Words shown as asterisks. Masking can be toggled with `/mask`.
+    pub mask_words: WordMask,
+    /// Words that mention the user, e.g. `alice` or `@alice`, counted apart in unread messages of
+    /// servers not shown. Matched like `mask_words`, words starting with `@` match after spaces
+    /// and punctuation.
+    pub mention_words: WordMask,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            server_urls: vec![String::from(DEFAULT_SERVER_URL)],
+            http_version: HttpVersion::default(),
+            ip_family: IpFamily::default(),
+            proxy: ProxyConfig::default(),
+            time_formatter: TimeFormatter::default(),
+            plain: false,
+            theme: Theme::default(),
+            stats: false,
+            latency_warning: Duration::from_millis(500),
+            session_token: None,
+            fetch_batch_size: None,
+            max_messages: DEFAULT_MAX_MESSAGES,
+            poll_interval: Duration::from_secs(1),
+            audio: ExternalCommands::default(),
+            density: Density::default(),
+            grouping: Grouping::default(),
+            resume: true,
+            cache: true,
+            mask_words: WordMask::default(),
+            mention_words: WordMask::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Whether `--doctor` is among the arguments, to diagnose the terminal, config and servers,
+    /// then exit. Looked for on its own, so that the doctor can report invalid arguments.
+    pub fn doctor_requested() -> bool {
+        env::args().skip(1).any(|arg| arg == "--doctor")
+    }
+
+    /// Read config from command line arguments.
+    /// ```txt
+    /// client [--doctor] [--http1|--http3] [--ipv4|--ipv6] [--proxy=URL|--no-proxy] [--plain]
+    ///        [--stats] [--no-resume] [--no-cache] [--color|--no-color]
+    ///        [--timezone=local|utc|+HH:MM] [--date-format=FMT] [--precise-date-format=FMT]
+    ///        [--latency-warning=MILLISECONDS] [--token=SESSION_TOKEN]
+    ///        [--fetch-batch-size=COUNT] [--poll-interval=MILLISECONDS] [--max-messages=COUNT]
+    ///        [--recorder=COMMAND] [--player=COMMAND] [--density=compact|cozy]
+    ///        [--group-threshold=SECONDS] [--group-seconds] [--group-authors]
+    ///        [--mask-words=WORD,...] [--mask-words-file=PATH] [--mention-words=WORD,...]
+    ///        [SERVER_URL...]
+    /// ```
+    /// The first server is the one shown on start up.
+    /// Proxies are as in `proxy::Proxy::from_url`, taken from the env vars unless given, see
+    /// `proxy::ProxyConfig`.
+    /// Recorder and player commands are as in `ExternalCommands`, quoted as one argument.
+    /// `--group-seconds` shows seconds in dates between messages, `--group-authors` shows the
+    /// author only once for consecutive messages by them.
+    /// Words to mask are case-insensitive, files list one per line, skipping lines starting with
+    /// `#`. Both options can be given more than once.
+    /// Date formats are strftime-style, as in `chrono::format::strftime`.
+    /// Colors are disabled if env var `NO_COLOR` is set and not empty, unless `--color` is given.
+    pub fn from_args() -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+        if Theme::no_color_requested() {
+            config.theme = Theme::monochrome();
+        }
+        let mut server_urls = Vec::new();
+        let mut mask_words = Vec::new();
+        let mut mention_words = Vec::new();
+        for arg in env::args().skip(1) {
+            if let Some(timezone) = arg.strip_prefix("--timezone=") {
+                config.time_formatter.timezone = timezone
+                    .parse::<Timezone>()
+                    .map_err(|_| ConfigError::InvalidTimezone(timezone.to_owned()))?;
+            } else if let Some(format) = arg.strip_prefix("--date-format=") {
+                config.time_formatter.date_format = parse_date_format(format)?;
+            } else if let Some(format) = arg.strip_prefix("--precise-date-format=") {
+                config.time_formatter.precise_date_format = parse_date_format(format)?;
+            } else if let Some(millis) = arg.strip_prefix("--latency-warning=") {
+                config.latency_warning = millis
+                    .parse()
+                    .map(Duration::from_millis)
+                    .map_err(|_| ConfigError::InvalidLatencyWarning(millis.to_owned()))?;
+            } else if let Some(count) = arg.strip_prefix("--fetch-batch-size=") {
+                config.fetch_batch_size = Some(
+                    count
+                        .parse()
+                        .map_err(|_| ConfigError::InvalidFetchBatchSize(count.to_owned()))?,
+                );
+            } else if let Some(count) = arg.strip_prefix("--max-messages=") {
+                config.max_messages = count
+                    .parse()
+                    .ok()
+                    .filter(|&count| count != 0)
+                    .ok_or_else(|| ConfigError::InvalidMaxMessages(count.to_owned()))?;
+            } else if let Some(millis) = arg.strip_prefix("--poll-interval=") {
+                config.poll_interval = millis
+                    .parse()
+                    .map(Duration::from_millis)
+                    .map_err(|_| ConfigError::InvalidPollInterval(millis.to_owned()))?;
+            } else if let Some(density) = arg.strip_prefix("--density=") {
+                config.density = density
+                    .parse()
+                    .map_err(|_| ConfigError::InvalidDensity(density.to_owned()))?;
+            } else if let Some(seconds) = arg.strip_prefix("--group-threshold=") {
+                config.grouping.threshold = seconds
+                    .parse()
+                    .map(Duration::from_secs)
+                    .map_err(|_| ConfigError::InvalidGroupThreshold(seconds.to_owned()))?;
+            } else if let Some(recorder) = arg.strip_prefix("--recorder=") {
+                config.audio.recorder = Some(recorder.into());
+            } else if let Some(player) = arg.strip_prefix("--player=") {
+                config.audio.player = Some(player.into());
+            } else if let Some(words) = arg.strip_prefix("--mask-words=") {
+                mask_words.extend(words.split(',').map(String::from));
+            } else if let Some(path) = arg.strip_prefix("--mask-words-file=") {
+                let text =
+                    fs::read_to_string(path).map_err(|error| ConfigError::UnreadableMaskWords {
+                        path: path.to_owned(),
+                        error: error.to_string(),
+                    })?;
+                mask_words.extend(
+                    text.lines()
+                        .filter(|line| !line.trim_start().starts_with('#'))
+                        .map(String::from),
+                );
+            } else if let Some(words) = arg.strip_prefix("--mention-words=") {
+                mention_words.extend(words.split(',').map(String::from));
+            } else if let Some(token) = arg.strip_prefix("--token=") {
+                config.session_token = Some(token.into());
+            } else if arg == "--http1" {
+                config.http_version = HttpVersion::Http1;
+            } else if arg == "--http3" {
+                if !cfg!(feature = "http3") {
+                    return Err(ConfigError::Http3Unsupported);
+                }
+                config.http_version = HttpVersion::Http3;
+            } else if let Some(url) = arg.strip_prefix("--proxy=") {
+                config.proxy = ProxyConfig::Proxy(Proxy::from_url(url)?);
+            } else if arg == "--no-proxy" {
+                config.proxy = ProxyConfig::Direct;
+            } else if arg == "--ipv4" {
+                config.ip_family = IpFamily::V4;
+            } else if arg == "--ipv6" {
+                config.ip_family = IpFamily::V6;
+            } else if arg == "--doctor" {
+                // Looked for before parsing, see `doctor_requested`.
+            } else if arg == "--plain" {
+                config.plain = true;
+            } else if arg == "--group-seconds" {
+                config.grouping.show_seconds = true;
+            } else if arg == "--group-authors" {
+                config.grouping.group_authors = true;
+            } else if arg == "--no-resume" {
+                config.resume = false;
+            } else if arg == "--no-cache" {
+                config.cache = false;
+            } else if arg == "--stats" {
+                config.stats = true;
+            } else if arg == "--color" {
+                config.theme = Theme::colored();
+            } else if arg == "--no-color" {
+                config.theme = Theme::monochrome();
+            } else if arg.starts_with('-') {
+                return Err(ConfigError::UnknownFlag(arg));
+            } else {
+                server_urls.push(arg);
+            }
+        }
+        if !server_urls.is_empty() {
+            config.server_urls = server_urls;
+        }
+        let invalid_words = |error: regex::Error| ConfigError::InvalidWords(error.to_string());
+        config.mask_words = WordMask::new(&mask_words).map_err(invalid_words)?;
+        config.mention_words = WordMask::new(&mention_words).map_err(invalid_words)?;
+        Ok(config)
+    }
+}
+
+fn parse_date_format(format: &str) -> Result<Box<str>, ConfigError> {
+    if time_format::is_valid_format(format) {
+        Ok(format.into())
+    } else {
+        Err(ConfigError::InvalidDateFormat(format.to_owned()))
+    }
+}
@@ -0,0 +1,230 @@
+//! Connecting to the server by host name. Every address the host resolves to is tried, IPv6 and
+//! IPv4 alternating, with the next attempt started if the previous one hasn't connected in
+//! `ATTEMPT_DELAY`, as in Happy Eyeballs (RFC 8305). A host that is unreachable over one family,
+//! or has one dead address, is then only slower to connect to instead of failing.
+//! Connections may go through a proxy, see `proxy`.
+
+use std::{
+    io,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use futures_util::{stream::FuturesUnordered, StreamExt};
+use hyper::Uri;
+use tokio::net::TcpStream;
+
+use crate::proxy::{http_connect, socks5_connect, Proxy, ProxyKind};
+
+/// Between the starts of two connection attempts, per RFC 8305.
+const ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+/// For a proxy to answer its handshake, so that a proxy that accepts connections but never
+/// answers doesn't hang connecting forever.
+const PROXY_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Address families to connect over.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IpFamily {
+    #[default]
+    Any,
+    V4,
+    V6,
+}
+
+impl IpFamily {
+    fn allows(self, address: &SocketAddr) -> bool {
+        match self {
+            Self::Any => true,
+            Self::V4 => address.is_ipv4(),
+            Self::V6 => address.is_ipv6(),
+        }
+    }
+}
+
+/// Host and port of `url`, without the brackets around IPv6 literals that `Uri::host` keeps.
+fn host_and_port(url: &Uri, default_port: u16) -> io::Result<(&str, u16)> {
+    let host = url
+        .host()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "URL has no host"))?;
+    let host = host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host);
+    Ok((host, url.port_u16().unwrap_or(default_port)))
+}
+
+/// Addresses of the host of `url` in `family`, see `lookup`.
+#[cfg(feature = "http3")]
+pub async fn resolve(
+    url: &Uri,
+    default_port: u16,
+    family: IpFamily,
+) -> io::Result<Vec<SocketAddr>> {
+    let (host, port) = host_and_port(url, default_port)?;
+    lookup(host, port, family).await
+}
+
+/// Addresses of `host` in `family`, in the order to try them: the family of the resolver's
+/// first answer first, then alternating between families.
+async fn lookup(host: &str, port: u16, family: IpFamily) -> io::Result<Vec<SocketAddr>> {
+    let mut preferred: Vec<SocketAddr> = Vec::new();
+    let mut other = Vec::new();
+    for address in tokio::net::lookup_host((host, port)).await? {
+        if !family.allows(&address) || preferred.contains(&address) || other.contains(&address) {
+            continue;
+        }
+        match preferred.first() {
+            Some(first) if first.is_ipv4() != address.is_ipv4() => other.push(address),
+            _ => preferred.push(address),
+        }
+    }
+    if preferred.is_empty() {
+        let message = match family {
+            IpFamily::Any => format!("{host} has no address"),
+            IpFamily::V4 => format!("{host} has no IPv4 address"),
+            IpFamily::V6 => format!("{host} has no IPv6 address"),
+        };
+        return Err(io::Error::new(io::ErrorKind::NotFound, message));
+    }
+    let mut addresses = Vec::with_capacity(preferred.len() + other.len());
+    let (mut preferred, mut other) = (preferred.into_iter(), other.into_iter());
+    loop {
+        match (preferred.next(), other.next()) {
+            (None, None) => break,
+            (first, second) => addresses.extend(first.into_iter().chain(second)),
+        }
+    }
+    Ok(addresses)
+}
+
+/// How long the phases of establishing a connection took.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConnectTiming {
+    /// Resolving the host, or the proxy's host if there is one.
+    pub dns: Duration,
+    /// Until the connection is established, proxy handshakes included.
+    pub connect: Duration,
+    /// TLS handshake, `None` over cleartext. QUIC handshakes are counted here, `connect` being
+    /// zero, as they carry the TLS handshake.
+    pub tls: Option<Duration>,
+}
+
+impl ConnectTiming {
+    pub fn total(&self) -> Duration {
+        self.dns + self.connect + self.tls.unwrap_or_default()
+    }
+}
+
+/// How to reach servers: over which address families, and whether through a proxy.
+#[derive(Debug, Clone, Default)]
+pub struct Connector {
+    family: IpFamily,
+    proxy: Option<Proxy>,
+}
+
+impl Connector {
+    pub fn new(family: IpFamily, proxy: Option<Proxy>) -> Self {
+        Self { family, proxy }
+    }
+
+    #[cfg(feature = "http3")]
+    pub fn family(&self) -> IpFamily {
+        self.family
+    }
+
+    pub fn proxy(&self) -> Option<&Proxy> {
+        self.proxy.as_ref()
+    }
+
+    /// The HTTP proxy that HTTP/1.1 requests to `url` are sent to in absolute form, instead of
+    /// through a tunnel, if `url` is plain HTTP. Requests then carry the proxy's
+    /// `Proxy-Authorization` themselves, see `Proxy::authorization`.
+    pub fn forwarding_proxy(&self, url: &Uri) -> Option<&Proxy> {
+        self.proxy
+            .as_ref()
+            .filter(|proxy| proxy.kind == ProxyKind::Http && url.scheme_str() == Some("http"))
+    }
+
+    /// Connection to send HTTP/1.1 requests to `url` over: to the proxy itself if it's a
+    /// `forwarding_proxy`, as `connect_tcp` otherwise.
+    pub async fn connect_http1(&self, url: &Uri) -> io::Result<(TcpStream, ConnectTiming)> {
+        let Some(proxy) = self.forwarding_proxy(url) else {
+            return self.connect_tcp(url).await;
+        };
+        let start = Instant::now();
+        let addresses = lookup(&proxy.host, proxy.port, self.family).await?;
+        let dns = start.elapsed();
+        let stream = connect_any(addresses).await?;
+        let timing = ConnectTiming {
+            dns,
+            connect: start.elapsed() - dns,
+            tls: None,
+        };
+        Ok((stream, timing))
+    }
+
+    /// TCP connection with the host of `url`, port 80 unless given, tunneled through the proxy
+    /// if there is one. The proxy resolves the host then, `family` only applies to reaching it.
+    pub async fn connect_tcp(&self, url: &Uri) -> io::Result<(TcpStream, ConnectTiming)> {
+        let (host, port) = host_and_port(url, 80)?;
+        let start = Instant::now();
+        let addresses = match &self.proxy {
+            Some(proxy) => lookup(&proxy.host, proxy.port, self.family).await?,
+            None => lookup(host, port, self.family).await?,
+        };
+        let dns = start.elapsed();
+        let mut stream = connect_any(addresses).await?;
+        if let Some(proxy) = &self.proxy {
+            let handshake = async {
+                match proxy.kind {
+                    ProxyKind::Http => {
+                        let authorization = proxy.authorization();
+                        http_connect(&mut stream, host, port, authorization.as_deref()).await
+                    }
+                    ProxyKind::Socks5 => {
+                        let credentials = proxy.credentials.as_ref();
+                        socks5_connect(&mut stream, host, port, credentials).await
+                    }
+                }
+            };
+            tokio::time::timeout(PROXY_HANDSHAKE_TIMEOUT, handshake)
+                .await
+                .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "proxy didn't answer"))??;
+        }
+        let timing = ConnectTiming {
+            dns,
+            connect: start.elapsed() - dns,
+            tls: None,
+        };
+        Ok((stream, timing))
+    }
+}
+
+/// The first of `addresses` to connect, racing them as described in the module docs.
+/// Fails with the error of the last attempt if none could be connected to.
+async fn connect_any(addresses: Vec<SocketAddr>) -> io::Result<TcpStream> {
+    let mut addresses = addresses.into_iter();
+    let mut attempts = FuturesUnordered::new();
+    let mut last_error = None;
+    loop {
+        if let Some(address) = addresses.next() {
+            attempts.push(TcpStream::connect(address));
+        }
+        if attempts.is_empty() {
+            break;
+        }
+        let has_next = !addresses.as_slice().is_empty();
+        tokio::select! {
+            Some(result) = attempts.next() => match result {
+                Ok(stream) => return Ok(stream),
+                Err(e) => {
+                    log::debug!("Connection attempt failed: {e}");
+                    last_error = Some(e);
+                }
+            },
+            // Start the next attempt alongside the pending ones.
+            _ = tokio::time::sleep(ATTEMPT_DELAY), if has_next => (),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address")))
+}
@@ -0,0 +1,287 @@
+//! `--doctor`, which diagnoses what may keep the client from working well and suggests fixes.
+
+use std::{
+    env,
+    io::{self, IsTerminal},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use copypasta::{ClipboardContext, ClipboardProvider};
+use interface::capabilities;
+use ratatui::crossterm::{
+    event::{self, EnableMouseCapture, Event, MouseEventKind},
+    execute,
+    terminal::enable_raw_mode,
+};
+
+use crate::{
+    config::{Config, ConfigError},
+    state::AppState,
+    terminal,
+    voice::ExternalCommands,
+    websocket,
+};
+
+/// How long the user has to click for the mouse check.
+const MOUSE_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+/// Servers not answering within this are taken as unreachable.
+const SERVER_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Warning,
+    Failure,
+}
+
+/// Prints findings as they come, and remembers whether anything failed.
+#[derive(Debug, Default)]
+struct Report {
+    has_failures: bool,
+}
+
+impl Report {
+    /// `hint` says what to do about it, if anything.
+    fn print(&mut self, status: Status, finding: impl AsRef<str>, hint: Option<&str>) {
+        let label = match status {
+            Status::Ok => " ok ",
+            Status::Warning => "warn",
+            Status::Failure => "FAIL",
+        };
+        println!("[{label}] {}", finding.as_ref());
+        if let Some(hint) = hint {
+            println!("       {hint}");
+        }
+        self.has_failures |= status == Status::Failure;
+    }
+}
+
+/// Run every check, printing the results. Returns `false` if any check failed.
+/// With invalid arguments, only the terminal is checked, as the rest depends on them.
+pub async fn run(config: Result<Config, ConfigError>) -> bool {
+    let mut report = Report::default();
+    println!("Terminal");
+    let theme_config = config.as_ref().cloned().unwrap_or_default();
+    check_terminal(&mut report, &theme_config);
+    println!("Config");
+    let config = match config {
+        Ok(config) => {
+            report.print(Status::Ok, "Arguments are valid", None);
+            config
+        }
+        Err(e) => {
+            report.print(
+                Status::Failure,
+                format!("Arguments are invalid: {e}"),
+                Some("Fix them, servers are only checked with valid arguments"),
+            );
+            return false;
+        }
+    };
+    check_config(&mut report, &config);
+    for server_url in &config.server_urls {
+        println!("Server {server_url}");
+        let app_state = AppState::with_config(&config, server_url.clone(), None);
+        check_server(&mut report, &app_state).await;
+    }
+    !report.has_failures
+}
+
+fn check_terminal(report: &mut Report, config: &Config) {
+    let is_terminal = io::stdout().is_terminal();
+    match is_terminal {
+        true => report.print(Status::Ok, "Output is a terminal", None),
+        false => report.print(
+            Status::Warning,
+            "Output is not a terminal",
+            Some("The TUI needs one, run without redirecting output or use --plain"),
+        ),
+    }
+    let term = env::var("TERM").unwrap_or_default();
+    let colorterm = env::var("COLORTERM").unwrap_or_default();
+    if config.theme.is_monochrome() {
+        report.print(Status::Ok, "Colors are disabled", None);
+    } else if colorterm == "truecolor" || colorterm == "24bit" {
+        report.print(Status::Ok, "Terminal supports 24-bit color", None);
+    } else if term.contains("256color") {
+        report.print(Status::Ok, "Terminal supports 256 colors", None);
+    } else if term.is_empty() || term == "dumb" {
+        report.print(
+            Status::Warning,
+            format!("Terminal type is {term:?}, colors and mouse likely don't work"),
+            Some("Set TERM to match your terminal, or use --plain or --no-color"),
+        );
+    } else {
+        report.print(
+            Status::Warning,
+            format!("Terminal {term:?} may only support 16 colors"),
+            Some("Colors may look off, --no-color disables them"),
+        );
+    }
+    if is_terminal && io::stdin().is_terminal() {
+        check_mouse(report);
+    }
+    match ClipboardContext::new().and_then(|mut clipboard| clipboard.get_contents()) {
+        Ok(_) => report.print(Status::Ok, "Clipboard is accessible", None),
+        Err(e) => report.print(
+            Status::Warning,
+            format!("Clipboard is not accessible: {e}"),
+            Some("Copy and paste in the input field won't work, e.g. over SSH without X11"),
+        ),
+    }
+}
+
+/// Mouse support can only be told by trying, so the user is asked to click.
+fn check_mouse(report: &mut Report) {
+    println!(
+        "       Click in the terminal within {}s to check the mouse, or press a key to skip",
+        MOUSE_CHECK_TIMEOUT.as_secs()
+    );
+    match wait_for_click() {
+        Ok(Some(true)) => report.print(Status::Ok, "Mouse works", None),
+        Ok(Some(false)) => report.print(
+            Status::Warning,
+            "No click arrived, the mouse may not work",
+            Some("Enable mouse reporting in your terminal, e.g. `set -g mouse on` in tmux"),
+        ),
+        Ok(None) => report.print(Status::Ok, "Mouse check skipped", None),
+        Err(e) => report.print(Status::Warning, format!("Can't check the mouse: {e}"), None),
+    }
+}
+
+/// Whether a click arrives within `MOUSE_CHECK_TIMEOUT`, `None` if a key is pressed first.
+fn wait_for_click() -> io::Result<Option<bool>> {
+    enable_raw_mode()?;
+    let result = execute!(io::stdout(), EnableMouseCapture).and_then(|()| {
+        let deadline = Instant::now() + MOUSE_CHECK_TIMEOUT;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if timeout.is_zero() || !event::poll(timeout)? {
+                return Ok(Some(false));
+            }
+            match event::read()? {
+                Event::Mouse(mouse_event)
+                    if matches!(mouse_event.kind, MouseEventKind::Down(_)) =>
+                {
+                    return Ok(Some(true));
+                }
+                Event::Key(_) => return Ok(None),
+                _ => (),
+            }
+        }
+    });
+    terminal::restore_terminal();
+    result
+}
+
+fn check_config(report: &mut Report, config: &Config) {
+    let ExternalCommands { recorder, player } = &config.audio;
+    for (what, command, flag) in [
+        ("Recorder", recorder, "--recorder"),
+        ("Player", player, "--player"),
+    ] {
+        let Some(command) = command else {
+            report.print(
+                Status::Ok,
+                format!("{what} not configured, voice notes need {flag}"),
+                None,
+            );
+            continue;
+        };
+        let program = command.split_whitespace().next().unwrap_or_default();
+        match is_executable(program) {
+            true => report.print(Status::Ok, format!("{what} {program:?} found"), None),
+            false => report.print(
+                Status::Failure,
+                format!("{what} {program:?} not found"),
+                Some(&format!("Install it or fix the path in {flag}")),
+            ),
+        }
+    }
+}
+
+async fn check_server(report: &mut Report, app_state: &AppState) {
+    let proxy = app_state.api().proxy();
+    if let Some(proxy) = proxy {
+        report.print(
+            Status::Ok,
+            format!("Connecting through proxy {proxy}"),
+            None,
+        );
+    }
+    let start = Instant::now();
+    let is_reachable = tokio::time::timeout(SERVER_TIMEOUT, app_state.api().test_connection())
+        .await
+        .unwrap_or(false);
+    if !is_reachable {
+        let hint = match proxy {
+            Some(_) => "Check the proxy, or bypass it with --no-proxy",
+            None => "Check the URL, your network, and that the server is running",
+        };
+        let finding = match start.elapsed() >= SERVER_TIMEOUT {
+            true => format!(
+                "Server is unreachable, no answer in {}s",
+                SERVER_TIMEOUT.as_secs()
+            ),
+            false => "Server is unreachable".to_owned(),
+        };
+        report.print(Status::Failure, finding, Some(hint));
+        return;
+    }
+    report.print(
+        Status::Ok,
+        format!("Server is reachable ({}ms)", start.elapsed().as_millis()),
+        None,
+    );
+    if let Err(e) = app_state.fetch_capabilities().await {
+        report.print(
+            Status::Warning,
+            format!("Can't fetch capabilities: {e}"),
+            Some("The server may be outdated, newer features will be unavailable"),
+        );
+    }
+    let websocket_url = app_state.api().websocket_url(false);
+    let connected =
+        tokio::time::timeout(SERVER_TIMEOUT, websocket::connect(app_state, websocket_url))
+            .await
+            .map_err(|_| format!("no answer in {}s", SERVER_TIMEOUT.as_secs()))
+            .and_then(|result| result.map_err(|e| e.to_string()));
+    match connected {
+        Ok(_) => report.print(Status::Ok, "Websocket connects", None),
+        Err(e) if app_state.supports(capabilities::SERVER_SENT_EVENTS) => report.print(
+            Status::Warning,
+            format!("Websocket can't connect: {e}"),
+            Some("Server-sent events will be used instead, check proxies or firewalls"),
+        ),
+        Err(e) => report.print(
+            Status::Failure,
+            format!("Websocket can't connect: {e}"),
+            Some("New events won't arrive in real time, check proxies or firewalls"),
+        ),
+    }
+}
+
+/// Is `program` a path to an executable file, or the name of one in `PATH`?
+fn is_executable(program: &str) -> bool {
+    if program.contains(std::path::MAIN_SEPARATOR) {
+        return is_executable_file(Path::new(program));
+    }
+    env::var_os("PATH").is_some_and(|path| {
+        env::split_paths(&path).any(|dir| is_executable_file(&dir.join(program)))
+    })
+}
+
+/// On Unix, whether anyone may execute the file. Elsewhere, whether it's a file at all.
+fn is_executable_file(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata()
+            .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
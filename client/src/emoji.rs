@@ -0,0 +1,49 @@
+//! Telling apart messages consisting solely of emoji, which are shown larger.
+
+use std::ops::RangeInclusive;
+
+/// Messages with more emoji than this are shown as normal text.
+pub const MAX_LARGE_EMOJI: usize = 8;
+
+/// Code points shown as emoji by themselves.
+/// Not exhaustive, but covers what's commonly typed and sent.
+const EMOJI: &[RangeInclusive<char>] = &[
+    '\u{1F000}'..='\u{1FAFF}',
+    '\u{2600}'..='\u{27BF}',
+    '\u{2B00}'..='\u{2BFF}',
+    '\u{2300}'..='\u{23FF}',
+    '\u{3030}'..='\u{3030}',
+    '\u{303D}'..='\u{303D}',
+    '\u{3297}'..='\u{3299}',
+];
+
+/// Code points that modify or join the emoji before them, and don't count as emoji of their own.
+const MODIFIERS: &[RangeInclusive<char>] = &[
+    // Zero width joiner, for sequences like family emoji.
+    '\u{200D}'..='\u{200D}',
+    // Variation selectors, to pick emoji or text presentation.
+    '\u{FE00}'..='\u{FE0F}',
+    // Keycap.
+    '\u{20E3}'..='\u{20E3}',
+    // Tags, for subdivision flags.
+    '\u{E0020}'..='\u{E007F}',
+];
+
+/// Does `text` consist of at least one and at most `MAX_LARGE_EMOJI` emoji, ignoring whitespace?
+/// Skin tones and joined sequences count by their code points, so they are limited a bit more.
+pub fn is_emoji_only(text: &str) -> bool {
+    let mut count = 0;
+    for char in text.chars().filter(|char| !char.is_whitespace()) {
+        if MODIFIERS.iter().any(|range| range.contains(&char)) {
+            continue;
+        }
+        if !EMOJI.iter().any(|range| range.contains(&char)) {
+            return false;
+        }
+        count += 1;
+        if count > MAX_LARGE_EMOJI {
+            return false;
+        }
+    }
+    count != 0
+}
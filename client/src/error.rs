@@ -0,0 +1,105 @@
+use std::string::FromUtf8Error;
+
+use hyper::StatusCode;
+use interface::{AttachmentRejection, UnknownHttpMethod};
+
+pub type ClientResult<T> = Result<T, ClientError>;
+
+/// Errors from talking to the server.
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    // Network failures.
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("HTTP error: {0}")]
+    Hyper(#[from] hyper::Error),
+    #[error("websocket error: {0}")]
+    WebSocket(#[from] tokio_tungstenite::tungstenite::Error),
+    #[cfg(feature = "http3")]
+    #[error("HTTP/3 error: {0}")]
+    Http3(#[from] h3::Error),
+    #[cfg(feature = "http3")]
+    #[error("QUIC error: {0}")]
+    Quic(#[from] quinn::ConnectionError),
+    #[cfg(feature = "http3")]
+    #[error("QUIC error: {0}")]
+    QuicConnect(#[from] quinn::ConnectError),
+
+    // Protocol failures.
+    #[error("malformed request: {0}")]
+    Http(#[from] hyper::http::Error),
+    #[error("invalid server URL: {0}")]
+    InvalidUri(#[from] hyper::http::uri::InvalidUri),
+    #[error(transparent)]
+    UnknownHttpMethod(#[from] UnknownHttpMethod),
+    #[error("malformed JSON response: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("response is not valid UTF-8: {0}")]
+    Utf8(#[from] FromUtf8Error),
+    /// `routes::HELLO` got another answer than `interface::EXPECTED_RESPONSE_TO_HELLO`.
+    #[error("not a message board server")]
+    NotABoard,
+
+    // Validation failures.
+    /// Refused before sending, as the server would refuse it too.
+    #[error(transparent)]
+    Invalid(#[from] interface::ValidationError),
+    /// The server refused the content of the request, e.g. a message with a blocked word.
+    #[error("server rejected the request: {reason}")]
+    Rejected { reason: Box<str> },
+    /// Server is in read-only mode, e.g. for maintenance.
+    #[error("board is read-only: {reason}")]
+    ReadOnly { reason: Box<str> },
+    #[error("attachment refused: {0}")]
+    AttachmentRejected(AttachmentRejection),
+    /// Only one file is uploaded at a time.
+    #[error("another file is still being uploaded")]
+    UploadInProgress,
+    /// Changing the session needs one, see `session::log_in`.
+    #[error("not logged in with a session token")]
+    NoSession,
+    /// Server responded with a non-2xx status code.
+    #[error("server responded with {status}: {message}")]
+    Status {
+        status: StatusCode,
+        message: Box<str>,
+    },
+}
+
+impl ClientError {
+    /// Is the error caused by the network (and therefore possibly worth retrying)?
+    pub fn is_network(&self) -> bool {
+        match self {
+            Self::Io(_) | Self::Hyper(_) | Self::WebSocket(_) => true,
+            #[cfg(feature = "http3")]
+            Self::Http3(_) | Self::Quic(_) | Self::QuicConnect(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Is the error caused by the server not speaking the expected protocol?
+    pub fn is_protocol(&self) -> bool {
+        matches!(
+            self,
+            Self::Http(_)
+                | Self::InvalidUri(_)
+                | Self::UnknownHttpMethod(_)
+                | Self::Json(_)
+                | Self::Utf8(_)
+                | Self::NotABoard
+        )
+    }
+
+    /// Is the error caused by the server refusing the content of the request?
+    pub fn is_validation(&self) -> bool {
+        match self {
+            Self::Invalid(_)
+            | Self::Rejected { .. }
+            | Self::AttachmentRejected(_)
+            | Self::UploadInProgress
+            | Self::NoSession => true,
+            Self::Status { status, .. } => status.is_client_error(),
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! Writing loaded messages to a file, for `/export`.
+
+use std::{fmt::Write as _, fs, io, path::Path};
+
+use interface::{ContentKind, Message};
+
+use crate::time_format::TimeFormatter;
+
+/// Shown as the author of messages not posted by a webhook.
+const ANONYMOUS: &str = "anonymous";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Text,
+    Markdown,
+    /// The messages as returned by the server.
+    Json,
+}
+
+impl ExportFormat {
+    /// From the extension of `path`, text unless it's `.md` or `.json`.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("md" | "markdown") => Self::Markdown,
+            Some("json") => Self::Json,
+            _ => Self::Text,
+        }
+    }
+}
+
+/// Write `messages` to `path`, in the format of its extension.
+pub fn export(path: &Path, messages: &[Message], time_formatter: &TimeFormatter) -> io::Result<()> {
+    let output = match ExportFormat::from_path(path) {
+        ExportFormat::Text => to_text(messages, time_formatter),
+        ExportFormat::Markdown => to_markdown(messages, time_formatter),
+        ExportFormat::Json => serde_json::to_string_pretty(messages)?,
+    };
+    fs::write(path, output)
+}
+
+fn author(message: &Message) -> &str {
+    message.webhook_name.as_deref().unwrap_or(ANONYMOUS)
+}
+
+fn to_text(messages: &[Message], time_formatter: &TimeFormatter) -> String {
+    let mut output = String::new();
+    for message in messages {
+        let date = time_formatter.format_precise(message.date);
+        let mut lines = message.content.lines();
+        let first_line = lines.next().unwrap_or_default();
+        let _ = writeln!(output, "[{date}] {}: {first_line}", author(message));
+        // Continuation lines are indented, only the first line of a message starts with a date.
+        for line in lines {
+            let _ = writeln!(output, "    {line}");
+        }
+        if let Some(poll) = &message.poll {
+            for option in poll.options.iter() {
+                let _ = writeln!(output, "    - {} ({} votes)", option.text, option.votes);
+            }
+        }
+    }
+    output
+}
+
+fn to_markdown(messages: &[Message], time_formatter: &TimeFormatter) -> String {
+    let mut output = String::new();
+    for message in messages {
+        let date = time_formatter.format_precise(message.date);
+        let _ = writeln!(output, "**{}** _{date}_\n", author(message));
+        match message.content_kind {
+            ContentKind::Code => {
+                let _ = writeln!(output, "```\n{}\n```", message.content);
+            }
+            _ => {
+                let _ = writeln!(output, "{}", message.content);
+            }
+        }
+        if let Some(poll) = &message.poll {
+            output.push('\n');
+            for option in poll.options.iter() {
+                let _ = writeln!(output, "- {} ({} votes)", option.text, option.votes);
+            }
+        }
+        output.push('\n');
+    }
+    output
+}
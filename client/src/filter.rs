@@ -0,0 +1,199 @@
+//! Filters narrowing down the messages shown, without refetching them.
+
+use std::ops::Range;
+
+use interface::Message;
+use regex::{Regex, RegexBuilder};
+
+/// Size limit of compiled regexes, as for the server's.
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Term {
+    /// Lowercased text the content contains.
+    Contains(String),
+    /// Lowercased display name of the webhook the message is from.
+    From(String),
+    HasPoll,
+    HasLink,
+}
+
+impl Term {
+    fn matches(&self, message: &Message) -> bool {
+        match self {
+            Self::Contains(text) => message.content.to_lowercase().contains(text),
+            Self::From(name) => message
+                .webhook_name
+                .as_ref()
+                .is_some_and(|webhook_name| webhook_name.to_lowercase() == *name),
+            Self::HasPoll => message.poll.is_some(),
+            Self::HasLink => message.link_preview.is_some(),
+        }
+    }
+}
+
+/// Messages matching all of the terms, ignoring case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageFilter {
+    /// As typed by the user.
+    query: Box<str>,
+    terms: Vec<Term>,
+}
+
+impl MessageFilter {
+    /// Parse whitespace-separated terms, see `/filter` in the help page. Words that aren't
+    /// `from:` or `has:` terms are searched for in the content. Returns `None` if a term is
+    /// invalid.
+    pub fn parse(query: &str) -> Option<Self> {
+        let mut terms = Vec::new();
+        let mut words = Vec::new();
+        for word in query.split_whitespace() {
+            if let Some(name) = word.strip_prefix("from:") {
+                terms.push(Term::From(name.to_lowercase()));
+            } else if let Some(kind) = word.strip_prefix("has:") {
+                terms.push(match kind {
+                    "poll" => Term::HasPoll,
+                    "link" => Term::HasLink,
+                    _ => return None,
+                });
+            } else {
+                words.push(word);
+            }
+        }
+        if !words.is_empty() {
+            terms.push(Term::Contains(words.join(" ").to_lowercase()));
+        }
+        Some(Self {
+            query: query.trim().into(),
+            terms,
+        })
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn matches(&self, message: &Message) -> bool {
+        self.terms.iter().all(|term| term.matches(message))
+    }
+}
+
+/// Search of the message list, typed after `/`, with options toggled in its options bar.
+#[derive(Debug, Clone, Default)]
+pub struct Search {
+    text: String,
+    is_regex: bool,
+    is_case_sensitive: bool,
+    /// Display name of the webhook the messages are from, compared ignoring case.
+    author: Option<Box<str>>,
+    /// `text` compiled, if `is_regex` and it's a valid regex.
+    regex: Option<Regex>,
+}
+
+impl Search {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn push(&mut self, char: char) {
+        self.text.push(char);
+        self.compile();
+    }
+
+    pub fn pop(&mut self) {
+        self.text.pop();
+        self.compile();
+    }
+
+    pub fn is_regex(&self) -> bool {
+        self.is_regex
+    }
+
+    pub fn toggle_regex(&mut self) {
+        self.is_regex = !self.is_regex;
+        self.compile();
+    }
+
+    pub fn is_case_sensitive(&self) -> bool {
+        self.is_case_sensitive
+    }
+
+    pub fn toggle_case_sensitive(&mut self) {
+        self.is_case_sensitive = !self.is_case_sensitive;
+        self.compile();
+    }
+
+    pub fn author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+
+    pub fn set_author(&mut self, author: Option<Box<str>>) {
+        self.author = author;
+    }
+
+    /// Whether `text` is a regex that doesn't compile, in which case nothing is highlighted and
+    /// only the author is filtered by.
+    pub fn is_invalid(&self) -> bool {
+        self.is_regex && self.regex.is_none() && !self.text.is_empty()
+    }
+
+    fn compile(&mut self) {
+        self.regex = match self.is_regex && !self.text.is_empty() {
+            true => RegexBuilder::new(&self.text)
+                .case_insensitive(!self.is_case_sensitive)
+                .size_limit(REGEX_SIZE_LIMIT)
+                .dfa_size_limit(REGEX_SIZE_LIMIT)
+                .build()
+                .ok(),
+            false => None,
+        };
+    }
+
+    /// Byte ranges of the matches in `text`.
+    pub fn find(&self, text: &str) -> Vec<Range<usize>> {
+        if self.is_regex {
+            return self.regex.as_ref().map_or_else(Vec::new, |regex| {
+                regex.find_iter(text).map(|found| found.range()).collect()
+            });
+        }
+        if self.text.is_empty() {
+            return Vec::new();
+        }
+        let (text, search) = match self.is_case_sensitive {
+            true => (text.to_owned(), self.text.clone()),
+            false => (search_lowercase(text), search_lowercase(&self.text)),
+        };
+        text.match_indices(&search)
+            .map(|(index, found)| index..index + found.len())
+            .collect()
+    }
+
+    pub fn matches(&self, message: &Message) -> bool {
+        let is_author = self.author.as_ref().map_or(true, |author| {
+            message
+                .webhook_name
+                .as_ref()
+                .is_some_and(|webhook_name| webhook_name.to_lowercase() == author.to_lowercase())
+        });
+        let is_found = match self.regex.as_ref() {
+            Some(regex) => regex.is_match(&message.content),
+            None if self.text.is_empty() || self.is_invalid() => true,
+            None => !self.find(&message.content).is_empty(),
+        };
+        is_author && is_found
+    }
+}
+
+/// Lowercase `text` for case-insensitive search, keeping the byte offsets of `text`.
+/// Characters whose lowercase takes up a different number of bytes are kept as they are.
+fn search_lowercase(text: &str) -> String {
+    text.chars()
+        .map(|char| {
+            let mut lowercase = char.to_lowercase();
+            match (lowercase.next(), lowercase.next()) {
+                (Some(lower), None) if lower.len_utf8() == char.len_utf8() => lower,
+                _ => char,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,36 @@
+//! Abstraction over user interfaces of the client.
+
+use std::{io, time::Duration};
+
+use ratatui::crossterm::event::{self, Event};
+
+use crate::{servers::Servers, utils::DynResult};
+
+/// Where a frontend gets its input events from.
+pub trait EventSource {
+    /// Returns `true` if an event is available within `timeout`.
+    fn poll(&mut self, timeout: Duration) -> io::Result<bool>;
+
+    /// Blockingly read the next event.
+    fn read(&mut self) -> io::Result<Event>;
+}
+
+/// Events from the terminal, via crossterm.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TerminalEvents;
+
+impl EventSource for TerminalEvents {
+    fn poll(&mut self, timeout: Duration) -> io::Result<bool> {
+        event::poll(timeout)
+    }
+
+    fn read(&mut self) -> io::Result<Event> {
+        event::read()
+    }
+}
+
+/// A user interface for the client.
+pub trait Frontend {
+    /// Run the UI until the user quits, starting with the current server of `servers`.
+    fn run(&mut self, event_source: &mut impl EventSource, servers: Servers) -> DynResult<()>;
+}
@@ -0,0 +1,49 @@
+//! Syntax highlighting of fenced code blocks in messages.
+//! Only does anything with the `syntax-highlighting` feature.
+
+use ratatui::{style::Style, text::Line};
+
+/// Highlight lines of code, on top of `style`.
+/// `language` is the info string of the code fence.
+/// Returns `None` if the language is unknown, or if highlighting is disabled.
+#[cfg(feature = "syntax-highlighting")]
+pub fn highlight_code(lines: &[&str], language: &str, style: Style) -> Option<Vec<Line<'static>>> {
+    use std::sync::LazyLock;
+
+    use ratatui::{style::Color, text::Span};
+    use syntect::{easy::HighlightLines, highlighting::ThemeSet, parsing::SyntaxSet};
+
+    static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_nonewlines);
+    static THEME_SET: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+
+    let syntax = SYNTAX_SET.find_syntax_by_token(language)?;
+    let theme = THEME_SET.themes.get("base16-ocean.dark")?;
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    lines
+        .iter()
+        .map(|line| {
+            let ranges = highlighter.highlight_line(line, &SYNTAX_SET).ok()?;
+            let spans: Vec<Span> = ranges
+                .into_iter()
+                .map(|(highlight, text)| {
+                    let color = highlight.foreground;
+                    let fg = Color::Rgb(color.r, color.g, color.b);
+                    Span::styled(text.to_owned(), style.fg(fg))
+                })
+                .collect();
+            Some(Line::from(spans))
+        })
+        .collect()
+}
+
+/// Highlight lines of code, on top of `style`.
+/// `language` is the info string of the code fence.
+/// Returns `None` if the language is unknown, or if highlighting is disabled.
+#[cfg(not(feature = "syntax-highlighting"))]
+pub fn highlight_code(
+    _lines: &[&str],
+    _language: &str,
+    _style: Style,
+) -> Option<Vec<Line<'static>>> {
+    None
+}
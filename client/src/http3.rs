@@ -0,0 +1,136 @@
+//! HTTP/3 transport, experimental. Meant for lossy mobile networks, where QUIC recovers from
+//! packet loss better than TCP.
+
+use std::{
+    fmt::{self, Debug},
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use bytes::{Buf, Bytes};
+use futures_util::stream;
+use h3::client::SendRequest;
+use h3_quinn::OpenStreams;
+use http_body_util::{BodyExt, StreamBody};
+use hyper::{body::Frame, Request, Response, Uri};
+use quinn::crypto::rustls::QuicClientConfig;
+use tokio::sync::Mutex;
+
+use crate::{
+    api::ResponseBody,
+    connector::{self, ConnectTiming, Connector},
+    error::{ClientError, ClientResult},
+};
+
+/// The shared HTTP/3 connection, established lazily on first request.
+#[derive(Default)]
+pub struct Http3Client {
+    sender: Mutex<Option<SendRequest<OpenStreams, Bytes>>>,
+}
+
+impl Debug for Http3Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Http3Client").finish_non_exhaustive()
+    }
+}
+
+impl Http3Client {
+    /// `request` must have an absolute HTTPS URI.
+    pub async fn send(
+        &self,
+        request: Request<Bytes>,
+        connector: &Connector,
+    ) -> ClientResult<(Response<ResponseBody>, Option<ConnectTiming>)> {
+        let (mut sender, connect) = self.sender(request.uri(), connector).await?;
+        let result = send(&mut sender, request).await;
+        if result.is_err() {
+            // The connection may be dead, reconnect on next request.
+            *self.sender.lock().await = None;
+        }
+        Ok((result?, connect))
+    }
+
+    /// The connection's timing is returned if it was just established.
+    async fn sender(
+        &self,
+        url: &Uri,
+        connector: &Connector,
+    ) -> ClientResult<(SendRequest<OpenStreams, Bytes>, Option<ConnectTiming>)> {
+        let mut sender = self.sender.lock().await;
+        if let Some(sender) = sender.as_ref() {
+            return Ok((sender.clone(), None));
+        }
+        let (new_sender, connect) = connect(url, connector).await?;
+        *sender = Some(new_sender.clone());
+        Ok((new_sender, Some(connect)))
+    }
+}
+
+/// Over the first address of the host only, QUIC has no cheap way to race attempts.
+/// Always direct, as proxies only tunnel TCP.
+async fn connect(
+    url: &Uri,
+    connector: &Connector,
+) -> ClientResult<(SendRequest<OpenStreams, Bytes>, ConnectTiming)> {
+    if let Some(proxy) = connector.proxy() {
+        log::warn!("HTTP/3 can't go through proxy {proxy}, connecting directly");
+    }
+    let host = url.host().unwrap_or_default();
+    let start = Instant::now();
+    let address = connector::resolve(url, 443, connector.family()).await?[0];
+    let dns = start.elapsed();
+    let mut roots = rustls::RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let mut tls_config = rustls::ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    tls_config.alpn_protocols = vec![b"h3".to_vec()];
+    let quic_config =
+        QuicClientConfig::try_from(tls_config).expect("default TLS config supports QUIC");
+    let local_address: SocketAddr = if address.is_ipv6() {
+        "[::]:0".parse().unwrap()
+    } else {
+        "0.0.0.0:0".parse().unwrap()
+    };
+    let mut endpoint = quinn::Endpoint::client(local_address)?;
+    endpoint.set_default_client_config(quinn::ClientConfig::new(Arc::new(quic_config)));
+    let handshake_start = Instant::now();
+    let connection = endpoint.connect(address, host)?.await?;
+    let timing = ConnectTiming {
+        dns,
+        connect: Duration::ZERO,
+        tls: Some(handshake_start.elapsed()),
+    };
+    let (mut driver, sender) = h3::client::new(h3_quinn::Connection::new(connection)).await?;
+    tokio::spawn(async move {
+        if let Err(error) = std::future::poll_fn(|cx| driver.poll_close(cx)).await {
+            log::error!("HTTP/3 connection failed: {error}");
+        }
+    });
+    Ok((sender, timing))
+}
+
+async fn send(
+    sender: &mut SendRequest<OpenStreams, Bytes>,
+    request: Request<Bytes>,
+) -> ClientResult<Response<ResponseBody>> {
+    let (parts, body) = request.into_parts();
+    let mut stream = sender.send_request(Request::from_parts(parts, ())).await?;
+    stream.send_data(body).await?;
+    stream.finish().await?;
+    let response = stream.recv_response().await?;
+    // Streamed instead of collected, for server-sent events.
+    let frames = stream::unfold(Some(stream), |stream| async move {
+        let mut stream = stream?;
+        match stream.recv_data().await {
+            Ok(Some(mut chunk)) => {
+                let data = chunk.copy_to_bytes(chunk.remaining());
+                Some((Ok(Frame::data(data)), Some(stream)))
+            }
+            Ok(None) => None,
+            Err(error) => Some((Err(ClientError::from(error)), None)),
+        }
+    });
+    Ok(response.map(|()| StreamBody::new(frames).boxed_unsync()))
+}
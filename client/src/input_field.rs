@@ -1,8 +1,10 @@
 /// State of input fields.
 /// Manages cursor, selection, etc.
 use core::range::Range;
+use std::collections::VecDeque;
 
 use copypasta::{ClipboardContext, ClipboardProvider};
+use unicode_width::UnicodeWidthChar;
 
 use crate::utils::DynResult;
 
@@ -81,6 +83,26 @@ pub enum Cursor {
     Selection(Range<usize>),
 }
 
+/// Maximum number of undo steps kept.
+const MAX_UNDO_STEPS: usize = 100;
+
+/// Maximum number of kills kept for yanking.
+const KILL_RING_SIZE: usize = 8;
+
+/// Text and caret before or after an edit.
+#[derive(Debug, Clone)]
+struct Snapshot {
+    text: String,
+    caret: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EditKind {
+    /// Typing a character, consecutive ones are undone together.
+    Insert,
+    Other,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct InputFieldState {
     text: String,
@@ -88,6 +110,23 @@ pub struct InputFieldState {
     caret: usize,
     /// The end of selection.
     caret2: Option<usize>,
+    undo_stack: Vec<Snapshot>,
+    redo_stack: Vec<Snapshot>,
+    /// Caret after the last typed character, a character typed here is coalesced into the same
+    /// undo step.
+    coalesce_caret: Option<usize>,
+    /// Killed text, most recent first.
+    kill_ring: VecDeque<String>,
+    /// Text inserted by the last yank, if nothing has been edited since.
+    last_yank: Option<Yank>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Yank {
+    start: usize,
+    end: usize,
+    /// Index into the kill ring.
+    index: usize,
 }
 
 impl InputFieldState {
@@ -106,13 +145,76 @@ impl InputFieldState {
         self.take_text();
     }
 
+    /// Also clears the edit history.
     pub fn take_text(&mut self) -> String {
         let text = std::mem::take(&mut self.text);
         self.caret = 0;
         self.caret2 = None;
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.coalesce_caret = None;
+        self.last_yank = None;
         text
     }
 
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            text: self.text.clone(),
+            caret: self.caret_position(),
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.text = snapshot.text;
+        self.caret = snapshot.caret;
+        self.caret2 = None;
+        self.coalesce_caret = None;
+    }
+
+    /// Run `edit`, recording an undo step if it changed the text.
+    fn edit(&mut self, kind: EditKind, edit: impl FnOnce(&mut Self)) {
+        self.last_yank = None;
+        let before = self.snapshot();
+        let coalesce = kind == EditKind::Insert
+            && !self.is_in_selection_mode()
+            && self.coalesce_caret == Some(self.caret);
+        edit(self);
+        if self.text == before.text {
+            return;
+        }
+        if !coalesce {
+            if self.undo_stack.len() == MAX_UNDO_STEPS {
+                self.undo_stack.remove(0);
+            }
+            self.undo_stack.push(before);
+        }
+        self.redo_stack.clear();
+        self.coalesce_caret = (kind == EditKind::Insert).then_some(self.caret);
+    }
+
+    /// `<C-Z>` by convention.
+    pub fn undo(&mut self) {
+        let Some(snapshot) = self.undo_stack.pop() else {
+            return;
+        };
+        self.redo_stack.push(self.snapshot());
+        self.restore(snapshot);
+    }
+
+    /// `<C-S-Z>` by convention.
+    pub fn redo(&mut self) {
+        let Some(snapshot) = self.redo_stack.pop() else {
+            return;
+        };
+        self.undo_stack.push(self.snapshot());
+        self.restore(snapshot);
+    }
+
+    /// Where the caret is drawn, i.e. the moving end of the selection in selection mode.
+    pub fn caret_position(&self) -> usize {
+        self.caret2.unwrap_or(self.caret)
+    }
+
     pub fn is_in_selection_mode(&self) -> bool {
         self.caret2.is_some()
     }
@@ -122,24 +224,33 @@ impl InputFieldState {
     }
 
     pub fn insert(&mut self, char: char) {
-        if self.is_in_selection_mode() {
-            self.delete_backward();
-        }
-        debug_assert!(self.caret2.is_none());
-        self.text.insert(self.caret, char);
-        index_next(&self.text, &mut self.caret);
+        self.edit(EditKind::Insert, |this| {
+            if this.is_in_selection_mode() {
+                this.delete_backward_unrecorded();
+            }
+            debug_assert!(this.caret2.is_none());
+            this.text.insert(this.caret, char);
+            index_next(&this.text, &mut this.caret);
+        });
     }
 
     pub fn batch_insert(&mut self, input: &str) {
-        if self.is_in_selection_mode() {
-            self.delete_backward();
-        }
-        debug_assert!(self.caret2.is_none());
-        self.text.insert_str(self.caret, input);
-        self.caret += input.len();
+        self.edit(EditKind::Other, |this| {
+            if this.is_in_selection_mode() {
+                this.delete_backward_unrecorded();
+            }
+            debug_assert!(this.caret2.is_none());
+            this.text.insert_str(this.caret, input);
+            this.caret += input.len();
+        });
     }
 
     pub fn delete_backward(&mut self) {
+        self.edit(EditKind::Other, Self::delete_backward_unrecorded);
+    }
+
+    /// `delete_backward` without recording an undo step, for edits that record their own.
+    fn delete_backward_unrecorded(&mut self) {
         match self.caret2 {
             Some(caret2) => {
                 self.text.drain(range(self.caret, caret2));
@@ -147,7 +258,9 @@ impl InputFieldState {
                 self.caret = usize::min(self.caret, caret2);
             }
             None => {
-                index_prev(&self.text, &mut self.caret);
+                if !index_prev(&self.text, &mut self.caret) {
+                    return;
+                }
                 if self.caret_is_at_end() {
                     self.text.pop();
                 } else {
@@ -158,6 +271,11 @@ impl InputFieldState {
     }
 
     pub fn delete_forward(&mut self) {
+        self.edit(EditKind::Other, Self::delete_forward_unrecorded);
+    }
+
+    /// `delete_forward` without recording an undo step.
+    fn delete_forward_unrecorded(&mut self) {
         match self.caret2 {
             Some(caret2) => {
                 self.text.drain(range(self.caret, caret2));
@@ -250,6 +368,137 @@ impl InputFieldState {
         }
     }
 
+    /// `<C-A>` by convention.
+    pub fn select_all(&mut self) {
+        self.caret = 0;
+        self.caret2 = (!self.text.is_empty()).then_some(self.text.len());
+    }
+
+    /// Delete text between `start` and `end` into the kill ring.
+    fn kill(&mut self, start: usize, end: usize) {
+        if start == end {
+            return;
+        }
+        self.edit(EditKind::Other, |this| {
+            let killed: String = this.text.drain(start..end).collect();
+            this.caret = start;
+            this.caret2 = None;
+            if this.kill_ring.len() == KILL_RING_SIZE {
+                this.kill_ring.pop_back();
+            }
+            this.kill_ring.push_front(killed);
+        });
+    }
+
+    /// `<C-K>` by convention.
+    /// Kills from the caret to the end of the line, or the newline itself if the caret is at the
+    /// end of a line.
+    pub fn kill_to_end(&mut self) {
+        let caret = self.caret_position();
+        let end = match self.text[caret..].find('\n') {
+            Some(0) => caret + 1,
+            Some(i) => caret + i,
+            None => self.text.len(),
+        };
+        self.kill(caret, end);
+    }
+
+    /// `<C-U>` by convention.
+    pub fn kill_to_start(&mut self) {
+        let caret = self.caret_position();
+        let start = self.text[..caret].rfind('\n').map_or(0, |i| i + 1);
+        self.kill(start, caret);
+    }
+
+    /// `<C-Y>` by convention.
+    /// Insert the most recently killed text at the caret.
+    pub fn yank(&mut self) {
+        let Some(killed) = self.kill_ring.front().cloned() else {
+            return;
+        };
+        self.batch_insert(&killed);
+        self.last_yank = Some(Yank {
+            start: self.caret - killed.len(),
+            end: self.caret,
+            index: 0,
+        });
+    }
+
+    /// `<M-Y>` by convention.
+    /// Right after a yank, replace the yanked text with the previous kill in the kill ring.
+    pub fn yank_pop(&mut self) {
+        let Some(yank) = self.last_yank else {
+            return;
+        };
+        if self.is_in_selection_mode() || self.caret != yank.end {
+            return;
+        }
+        let index = (yank.index + 1) % self.kill_ring.len();
+        let replacement = self.kill_ring[index].clone();
+        self.edit(EditKind::Other, |this| {
+            this.text.replace_range(yank.start..yank.end, &replacement);
+            this.caret = yank.start + replacement.len();
+        });
+        self.last_yank = Some(Yank {
+            start: yank.start,
+            end: self.caret,
+            index,
+        });
+    }
+
+    /// Move the caret to `index`, e.g. by clicking, and leave selection mode.
+    /// `index` must be on a character boundary.
+    pub fn set_caret(&mut self, index: usize) {
+        self.caret = usize::min(index, self.text.len());
+        self.caret2 = None;
+    }
+
+    /// Select from the caret to `index`, e.g. by dragging.
+    /// `index` must be on a character boundary.
+    pub fn select_to(&mut self, index: usize) {
+        let index = usize::min(index, self.text.len());
+        self.caret2 = (index != self.caret).then_some(index);
+    }
+
+    /// Select the word around `index`, e.g. by double-clicking.
+    /// Selects the single character at `index` if it isn't part of a word.
+    pub fn select_word_at(&mut self, index: usize) {
+        let is_word_char = |char: char| char.is_alphanumeric() || char == '_';
+        let index = usize::min(index, self.text.len());
+        let start = self.text[..index]
+            .char_indices()
+            .rev()
+            .take_while(|&(_, char)| is_word_char(char))
+            .last()
+            .map_or(index, |(i, _)| i);
+        let mut end = self.text[index..]
+            .char_indices()
+            .find(|&(_, char)| !is_word_char(char))
+            .map_or(self.text.len(), |(i, _)| index + i);
+        if start == end {
+            index_next(&self.text, &mut end);
+        }
+        self.caret = start;
+        self.caret2 = (start != end).then_some(end);
+    }
+
+    /// Byte index of the character drawn at `column` (counting from the start of the text), or
+    /// the end of the text if `column` is past it.
+    /// Newlines are drawn as one column wide.
+    pub fn index_at_column(&self, column: usize) -> usize {
+        let mut width = 0;
+        for (i, char) in self.text.char_indices() {
+            width += match char {
+                '\n' => 1,
+                char => char.width().unwrap_or(0),
+            };
+            if width > column {
+                return i;
+            }
+        }
+        self.text.len()
+    }
+
     /// Copy the text to clipboard, if text is selected.
     /// No-op if input field is not in selection mode (returns `Ok`).
     /// Returns `Err` if error occured during copying using `copypasta`.
@@ -261,6 +510,19 @@ impl InputFieldState {
         clipboard.set_contents(selected_text)
     }
 
+    /// Copy the selected text to clipboard and delete it.
+    /// No-op if input field is not in selection mode (returns `Ok`).
+    /// Returns `Err` if error occured during copying using `copypasta`, in which case the text is
+    /// not deleted.
+    pub fn cut(&mut self, clipboard: &mut ClipboardContext) -> DynResult<()> {
+        if !self.is_in_selection_mode() {
+            return Ok(());
+        }
+        self.copy(clipboard)?;
+        self.delete_backward();
+        Ok(())
+    }
+
     /// Paste text in clipboard to the input field.
     /// No-op if nothing or non-string content is in the clipboard.
     pub fn paste(&mut self, clipboard: &mut ClipboardContext) {
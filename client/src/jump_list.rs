@@ -0,0 +1,50 @@
+//! Back/forward history of positions in the message list, like the jump list of Vim.
+
+use std::collections::VecDeque;
+
+use interface::MessageId;
+
+/// Number of positions kept in each direction.
+const JUMP_LIST_CAPACITY: usize = 100;
+
+/// Where the message list was scrolled to and what was selected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ListPosition {
+    pub scroll: i16,
+    pub selected: Option<MessageId>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct JumpList {
+    /// Positions before the current one, latest last.
+    back: VecDeque<ListPosition>,
+    /// Positions gone back from, latest last.
+    forward: Vec<ListPosition>,
+}
+
+impl JumpList {
+    /// Record `from`, the position before a jump. Forward history is dropped, as in a browser.
+    pub fn record(&mut self, from: ListPosition) {
+        self.forward.clear();
+        if self.back.back() != Some(&from) {
+            self.back.push_back(from);
+        }
+        if self.back.len() > JUMP_LIST_CAPACITY {
+            self.back.pop_front();
+        }
+    }
+
+    /// Position to go back to from `current`, if any.
+    pub fn back(&mut self, current: ListPosition) -> Option<ListPosition> {
+        let position = self.back.pop_back()?;
+        self.forward.push(current);
+        Some(position)
+    }
+
+    /// Position to go forward to from `current`, if any.
+    pub fn forward(&mut self, current: ListPosition) -> Option<ListPosition> {
+        let position = self.forward.pop()?;
+        self.back.push_back(current);
+        Some(position)
+    }
+}
@@ -1,22 +1,51 @@
 #![feature(iter_collect_into, new_range_api, decl_macro)]
 
 mod api;
+mod cache;
+mod commands;
+mod config;
+mod connector;
+mod doctor;
+mod emoji;
+mod error;
+mod export;
+mod filter;
+mod frontend;
+mod highlight;
+#[cfg(feature = "http3")]
+mod http3;
 mod input_field;
+mod jump_list;
+mod mask;
 mod newtui;
+mod plain;
+mod proxy;
+mod resume;
+mod servers;
+mod session;
+mod sse;
 mod state;
+mod stats;
+mod terminal;
+mod theme;
+mod time_format;
+mod toast;
 mod utils;
+mod voice;
+mod websocket;
 
+use cache::CacheError;
+use config::Config;
 use flexi_logger::{FileSpec, Logger, WriteMode};
+use frontend::{Frontend, TerminalEvents};
+use resume::Resume;
+use servers::Servers;
 use state::AppState;
-use std::{env, sync::Arc};
+use stats::Stats;
+use std::{io, sync::Arc};
+use terminal::TerminalGuard;
 use utils::DynResult;
 
-const DEFAULT_SERVER_URL: &str = if cfg!(debug_assertions) {
-    "http://127.0.0.1:3000"
-} else {
-    "http://64.176.51.97:3000"
-};
-
 #[tokio::main]
 async fn main() -> DynResult<()> {
     let _logger = Logger::try_with_str("info")?
@@ -24,24 +53,158 @@ async fn main() -> DynResult<()> {
         .write_mode(WriteMode::BufferAndFlush)
         .start()?;
 
-    let server_url = env::args().nth(1).unwrap_or(DEFAULT_SERVER_URL.into());
-    let app_state = AppState::with_server(server_url);
+    if Config::doctor_requested() {
+        let is_healthy = doctor::run(Config::from_args()).await;
+        std::process::exit(if is_healthy { 0 } else { 1 });
+    }
+    let config = Config::from_args()?;
+    let stats = config
+        .stats
+        .then(Stats::default_path)
+        .flatten()
+        .map(|path| Arc::new(Stats::load(path)));
+
+    let resume_path = config.resume.then(Resume::default_path).flatten();
+    let mut resume = resume_path.as_deref().map(Resume::load).unwrap_or_default();
 
-    println!("Saying hello with server");
-    log::info!("Saying hello with server");
-    if !app_state.api().test_connection().await {
-        println!("Can't connect with server {}", app_state.api().server_url());
-        log::error!("Can't connect with server {}", app_state.api().server_url());
+    let mut states = Vec::new();
+    for (i, server_url) in config.server_urls.iter().enumerate() {
+        let app_state = AppState::with_config(&config, server_url.clone(), stats.clone());
+        if config.cache {
+            restore_cache(&app_state);
+        }
+        // `--token` is for the first server only, tokens of other servers come from storage.
+        let session_token = if i == 0 {
+            config.session_token.clone()
+        } else {
+            None
+        };
+        if connect(&app_state, session_token).await {
+            // Messages loaded on start are read, unless the previous session didn't see them.
+            app_state.mark_read();
+            if let Some(&position) = resume.positions.get(server_url) {
+                app_state.resume(position);
+            }
+            states.push(app_state);
+        }
+    }
+    if states.is_empty() {
         std::process::exit(1);
     }
+    let mut servers = Servers::new(states);
+    let current_server = resume.current_server.as_deref();
+    if let Some(index) = servers
+        .iter()
+        .position(|app_state| Some(app_state.api().server_url()) == current_server)
+    {
+        servers.select(index);
+    }
 
-    app_state.fetch_new_messages_if_needed().await?;
+    if config.plain {
+        plain::Plain::new().run(&mut TerminalEvents, servers.clone())?;
+    } else {
+        terminal::install_panic_hook();
+        let mut terminal = TerminalGuard::new();
+        newtui::Tui::new(&mut *terminal).run(&mut TerminalEvents, servers.clone())?;
+        drop(terminal);
+    }
 
-    state::setup_background_update(Arc::clone(&app_state));
+    if let Some(resume_path) = resume_path {
+        resume.update(&servers);
+        if let Err(e) = resume.save(&resume_path) {
+            log::error!("Error saving where the session left off: {e}");
+            eprintln!("Can't save where the session left off: {e}");
+        }
+    }
 
-    let mut terminal = domtui::setup_terminal();
-    newtui::event_loop(&mut terminal, Arc::clone(&app_state))?;
-    domtui::restore_terminal(terminal);
+    if config.cache {
+        for app_state in servers.iter() {
+            save_cache(app_state);
+        }
+    }
+
+    if let Some(stats) = stats {
+        if let Err(e) = stats.save() {
+            log::error!("Error saving stats: {e}");
+            eprintln!("Can't save stats: {e}");
+        }
+    }
 
     Ok(())
 }
+
+/// Say hello with the server, log in, and start the background tasks.
+/// Returns `false` if the server can't be reached or fails to set up, which is printed, so that
+/// the other servers can be used anyway.
+async fn connect(app_state: &Arc<AppState>, session_token: Option<Box<str>>) -> bool {
+    let server_url = app_state.api().server_url();
+    println!("Saying hello with server {server_url}");
+    log::info!("Saying hello with server {server_url}");
+    if !app_state.api().test_connection().await {
+        println!("Can't connect with server {server_url}");
+        log::error!("Can't connect with server {server_url}");
+        return false;
+    }
+    match start(app_state, session_token).await {
+        Ok(()) => true,
+        Err(e) => {
+            println!("Can't set up server {server_url}: {e}");
+            log::error!("Error setting up server {server_url}: {e}");
+            false
+        }
+    }
+}
+
+/// Log in, load the state of the board, and start the background tasks.
+async fn start(app_state: &Arc<AppState>, session_token: Option<Box<str>>) -> DynResult<()> {
+    session::log_in(app_state, session_token).await?;
+    app_state.fetch_capabilities().await?;
+
+    if app_state.lock_messages().is_empty() {
+        app_state.fetch_new_messages_if_needed().await?;
+    } else {
+        // Messages restored from the cache may be far behind and may have been deleted since.
+        app_state.reconcile_cached_messages().await?;
+    }
+    app_state.fetch_topic().await?;
+    app_state.fetch_announcement().await?;
+    app_state.fetch_read_only().await?;
+
+    state::setup_background_update(Arc::clone(app_state));
+    websocket::setup_websocket(Arc::clone(app_state));
+    state::setup_latency_probe(Arc::clone(app_state));
+    state::setup_expiry(Arc::clone(app_state));
+
+    Ok(())
+}
+
+/// Missing caches are expected on first start, others failing to load are only logged, as the
+/// messages are fetched anew.
+fn restore_cache(app_state: &AppState) {
+    let server_url = app_state.api().server_url();
+    let Some(path) = cache::default_path(server_url) else {
+        return;
+    };
+    match cache::load(&path, server_url) {
+        Ok(messages) => {
+            log::info!(
+                "Restored {} cached messages of {server_url}",
+                messages.len()
+            );
+            app_state.restore_messages(messages);
+        }
+        Err(CacheError::Io(e)) if e.kind() == io::ErrorKind::NotFound => (),
+        Err(e) => log::warn!("Ignoring the message cache of {server_url}: {e}"),
+    }
+}
+
+fn save_cache(app_state: &AppState) {
+    let server_url = app_state.api().server_url();
+    let Some(path) = cache::default_path(server_url) else {
+        return;
+    };
+    if let Err(e) = cache::save(&path, server_url, app_state.lock_messages().iter()) {
+        log::error!("Error caching the messages of {server_url}: {e}");
+        eprintln!("Can't cache the messages of {server_url}: {e}");
+    }
+}
@@ -0,0 +1,69 @@
+//! Masking of words the user doesn't want to see, e.g. profanity.
+//! Only changes what is shown, messages are kept and sent as they are.
+
+use std::borrow::Cow;
+
+use regex::{Regex, RegexBuilder};
+
+/// Replaces whole words of a list with asterisks, ignoring case.
+#[derive(Debug, Clone, Default)]
+pub struct WordMask {
+    /// `None` if there are no words to mask.
+    regex: Option<Regex>,
+}
+
+impl WordMask {
+    /// Fails if the words are too many or too long to compile into one regex.
+    pub fn new<S: AsRef<str>>(words: &[S]) -> Result<Self, regex::Error> {
+        let words: Vec<String> = words
+            .iter()
+            .map(|word| word.as_ref().trim())
+            .filter(|word| !word.is_empty())
+            .map(whole_word)
+            .collect();
+        if words.is_empty() {
+            return Ok(Self::default());
+        }
+        let regex = RegexBuilder::new(&format!("(?:{})", words.join("|")))
+            .case_insensitive(true)
+            .build()?;
+        Ok(Self { regex: Some(regex) })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regex.is_none()
+    }
+
+    /// Whether `text` has any of the words.
+    pub fn is_match(&self, text: &str) -> bool {
+        self.regex
+            .as_ref()
+            .is_some_and(|regex| regex.is_match(text))
+    }
+
+    /// Every char of a masked word becomes one `*`, so that lines keep their length.
+    pub fn mask<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match &self.regex {
+            Some(regex) => regex.replace_all(text, |captures: &regex::Captures| {
+                "*".repeat(captures[0].chars().count())
+            }),
+            None => Cow::Borrowed(text),
+        }
+    }
+}
+
+/// `word` escaped, matching only as a whole word.
+/// Word boundaries are only required on ends that are word characters, as `\b` between two
+/// non-word characters never matches, e.g. before `@` in "hi @alice".
+fn whole_word(word: &str) -> String {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    let start = match word.starts_with(is_word_char) {
+        true => r"\b",
+        false => "",
+    };
+    let end = match word.ends_with(is_word_char) {
+        true => r"\b",
+        false => "",
+    };
+    format!("{start}{}{end}", regex::escape(word))
+}
@@ -1,154 +1,1742 @@
-use std::sync::{Arc, Weak};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt::{self, Display},
+    str::FromStr,
+    sync::{atomic::Ordering, Arc, Weak},
+    time::{Duration, Instant},
+};
 
-use chrono::{DateTime, Local};
-use domtui::views::{InputField, MutView, ScreenBuilder, Size, Stack, ViewCell};
+use chrono::{DateTime, Utc};
+use copypasta::{ClipboardContext, ClipboardProvider};
+use domtui::views::{MutView, ScreenBuilder, Size, Stack, ViewCell};
+use interface::{
+    capabilities, AttachmentKind, ContentKind, ForwardedFrom, Message, MessageId, Poll, Profile,
+    ReactionTally,
+};
 use ratatui::{
     backend::Backend,
-    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
-    prelude::Rect,
-    style::{
-        Color::{self, *},
-        Modifier, Style,
+    crossterm::event::{
+        Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent,
+        MouseEventKind,
     },
-    text::Line,
-    widgets::{Block, Borders, Paragraph},
+    layout::{Constraint, Layout, Position},
+    prelude::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Bar, BarChart, BarGroup, Block, Borders, Clear, Paragraph},
     Frame, Terminal,
 };
 
-use crate::{state::AppState, utils::DynResult};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::{
+    commands::{self, Command},
+    emoji,
+    filter::{MessageFilter, Search},
+    frontend::{EventSource, Frontend},
+    highlight::highlight_code,
+    input_field::{Cursor, InputFieldState},
+    jump_list::{JumpList, ListPosition},
+    servers::Servers,
+    state::{AppState, Unread, Upload},
+    theme::Theme,
+    toast::{ToastKind, Toasts},
+    utils::{format_size, DynResult},
+};
 
 const INPUT_FIELD_TAG: &str = "input_field";
 const MESSAGES_LIST_TAG: &str = "messages_list";
 
-#[derive(Debug, Clone)]
-pub struct UIState {
-    app_state: Weak<AppState>,
-    current_screen: Screen,
-    main_screen: domtui::views::Screen<'static, Stack<(ViewCell<'static>, ViewCell<'static>)>>,
-}
+/// Maximum number of toasts on screen at once.
+const MAX_VISIBLE_TOASTS: usize = 3;
+
+/// Quotes longer than this are collapsed.
+const MAX_QUOTE_LINES: usize = 3;
+
+/// Two clicks at the same position within this interval are a double-click.
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+
+/// Messages with more lines or characters than this are only sent after confirmation.
+const LARGE_MESSAGE_LINES: usize = 10;
+const LARGE_MESSAGE_CHARS: usize = 2000;
+
+/// Shown above messages that arrived since the previous session.
+const NEW_MESSAGES_DIVIDER: &str = "── New since last time ──";
+
+/// Shown above the oldest message if older ones were dropped to bound memory use.
+const OLDER_MESSAGES_DIVIDER: &str = "── Older messages unloaded, search the server to see them ──";
+
+/// Lines rendered above the top of the message list, on top of the ones in view.
+const RENDER_MARGIN: usize = 20;
+
+/// Added to the selected message with `<+>`, other reactions are added with `/react`.
+const QUICK_REACTION: &str = "👍";
+
+/// How much space messages take in the message list, switched with `/density` or `<D>`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Density {
+    /// One line per message, truncated to the width of the list.
+    Compact,
+    /// Authors on a line of their own, and a blank line between messages.
+    #[default]
+    Cozy,
+}
+
+impl Density {
+    pub fn toggled(self) -> Self {
+        match self {
+            Self::Compact => Self::Cozy,
+            Self::Cozy => Self::Compact,
+        }
+    }
+}
+
+impl FromStr for Density {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "compact" => Ok(Self::Compact),
+            "cozy" => Ok(Self::Cozy),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for Density {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Compact => write!(f, "compact"),
+            Self::Cozy => write!(f, "cozy"),
+        }
+    }
+}
+
+/// How messages are grouped under dates in the message list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Grouping {
+    /// A date is shown above messages sent this long after the previous one.
+    pub threshold: Duration,
+    /// Show dates in the precise format, with seconds.
+    pub show_seconds: bool,
+    /// Show the author only on the first of consecutive messages by them under a date, instead
+    /// of on every message.
+    pub group_authors: bool,
+}
+
+impl Default for Grouping {
+    fn default() -> Self {
+        Self {
+            threshold: Duration::from_secs(120),
+            show_seconds: false,
+            group_authors: false,
+        }
+    }
+}
+
+/// Choices of lifetime for disappearing messages, cycled through with `<CTRL + T>`.
+const EXPIRY_CHOICES: [Option<Duration>; 4] = [
+    None,
+    Some(Duration::from_secs(60)),
+    Some(Duration::from_secs(10 * 60)),
+    Some(Duration::from_secs(60 * 60)),
+];
+
+#[derive(Debug, Clone)]
+pub struct UIState {
+    app_state: Weak<AppState>,
+    current_screen: Screen,
+    main_screen: domtui::views::Screen<'static, Stack<(ViewCell<'static>, ViewCell<'static>)>>,
+    toasts: Toasts,
+}
+
+impl Default for UIState {
+    fn default() -> Self {
+        let mut main_screen = {
+            let mut builder = ScreenBuilder::new();
+            let root_view = Stack::vertical((
+                builder.tagged_view_cell(MESSAGES_LIST_TAG, MessagesList::new(Weak::new())),
+                builder.tagged_view_cell(INPUT_FIELD_TAG, MessageInputField::new(Weak::new())),
+            ));
+            builder.finish(root_view)
+        };
+        main_screen.focus_next();
+        Self {
+            app_state: Weak::default(),
+            current_screen: Screen::default(),
+            main_screen,
+            toasts: Toasts::default(),
+        }
+    }
+}
+
+impl UIState {
+    /// This function may only be called by `AppState`.
+    pub fn messages_updated(&mut self) {
+        log::info!("todo");
+    }
+
+    pub fn toasts(&self) -> &Toasts {
+        &self.toasts
+    }
+
+    /// Is there anything typed or attached that hasn't been sent?
+    /// Each server has its own `UIState`, so drafts are kept while switching between them.
+    pub fn has_draft(&mut self) -> bool {
+        let has_text = unsafe {
+            self.main_screen
+                .inspect_view_with_tag_unchecked::<bool, MessageInputField>(INPUT_FIELD_TAG, |v| {
+                    !v.state.text().is_empty() || v.pending_confirmation.is_some()
+                })
+                .unwrap()
+        };
+        has_text
+            || self
+                .app_state
+                .upgrade()
+                .is_some_and(|app_state| !app_state.draft_attachments().is_empty())
+    }
+
+    /// Move a quote requested by the message list into the input field, and focus on the input
+    /// field.
+    fn forward_pending_quote(&mut self) {
+        let quote = unsafe {
+            self.main_screen
+                .inspect_view_with_tag_unchecked::<Option<String>, MessagesList>(
+                    MESSAGES_LIST_TAG,
+                    |v| v.pending_quote.take(),
+                )
+                .unwrap()
+        };
+        let Some(quote) = quote else {
+            return;
+        };
+        unsafe {
+            self.main_screen
+                .inspect_view_with_tag_unchecked::<(), MessageInputField>(INPUT_FIELD_TAG, |v| {
+                    v.state.batch_insert(&quote);
+                })
+                .unwrap();
+        }
+        self.main_screen.focus_next();
+    }
+
+    fn forward_mouse_event(&mut self, mouse_event: MouseEvent) {
+        unsafe {
+            self.main_screen
+                .inspect_view_with_tag_unchecked::<(), MessageInputField>(INPUT_FIELD_TAG, |v| {
+                    v.on_mouse_event(mouse_event);
+                })
+                .unwrap();
+        }
+    }
+
+    /// Start a `/filter` command in the input field when the message list asks for it, prefilled
+    /// with the current filter. Drafts in the input field are left alone.
+    fn forward_filter_prompt(&mut self) {
+        let query = unsafe {
+            self.main_screen
+                .inspect_view_with_tag_unchecked::<Option<String>, MessagesList>(
+                    MESSAGES_LIST_TAG,
+                    |v| {
+                        std::mem::take(&mut v.pending_filter_prompt).then(|| {
+                            let query = v.filter.as_ref().map_or("", MessageFilter::query);
+                            format!("/filter {query}")
+                        })
+                    },
+                )
+                .unwrap()
+        };
+        let Some(query) = query else {
+            return;
+        };
+        let is_inserted = unsafe {
+            self.main_screen
+                .inspect_view_with_tag_unchecked::<bool, MessageInputField>(INPUT_FIELD_TAG, |v| {
+                    let is_empty = v.state.text().is_empty();
+                    if is_empty {
+                        v.state.batch_insert(&query);
+                    }
+                    is_empty
+                })
+                .unwrap()
+        };
+        if is_inserted {
+            self.main_screen.focus_next();
+        } else {
+            self.toasts
+                .info("Send or clear the message being composed to edit the filter");
+        }
+    }
+
+    /// Returns whether there was a search to close.
+    fn close_search(&mut self) -> bool {
+        unsafe {
+            self.main_screen
+                .inspect_view_with_tag_unchecked::<bool, MessagesList>(MESSAGES_LIST_TAG, |v| {
+                    v.close_search()
+                })
+                .unwrap()
+        }
+    }
+
+    fn close_profile(&mut self) -> bool {
+        unsafe {
+            self.main_screen
+                .inspect_view_with_tag_unchecked::<bool, MessagesList>(MESSAGES_LIST_TAG, |v| {
+                    v.close_profile()
+                })
+                .unwrap()
+        }
+    }
+
+    /// Select message `id` and scroll to it once it's loaded.
+    pub fn resume_at(&mut self, id: MessageId) {
+        unsafe {
+            self.main_screen
+                .inspect_view_with_tag_unchecked::<(), MessagesList>(MESSAGES_LIST_TAG, |v| {
+                    v.pending_anchor = Some(id);
+                })
+                .unwrap();
+        }
+    }
+
+    /// The selected message, or the one to resume at if it isn't loaded yet.
+    pub fn anchor(&mut self) -> Option<MessageId> {
+        unsafe {
+            self.main_screen
+                .inspect_view_with_tag_unchecked::<_, MessagesList>(MESSAGES_LIST_TAG, |v| {
+                    v.selected.or(v.pending_anchor)
+                })
+                .unwrap()
+        }
+    }
+
+    /// Returns whether the message list jumped, and needs to be rendered again.
+    fn forward_pending_anchor(&mut self) -> bool {
+        unsafe {
+            self.main_screen
+                .inspect_view_with_tag_unchecked::<bool, MessagesList>(MESSAGES_LIST_TAG, |v| {
+                    v.jump_to_pending_anchor()
+                })
+                .unwrap()
+        }
+    }
+
+    /// Apply a density requested by `/density` to the message list.
+    fn forward_pending_density(&mut self) {
+        let density: Option<Option<Density>> = unsafe {
+            self.main_screen
+                .inspect_view_with_tag_unchecked::<_, MessageInputField>(INPUT_FIELD_TAG, |v| {
+                    v.pending_density.take()
+                })
+                .unwrap()
+        };
+        let Some(density) = density else {
+            return;
+        };
+        unsafe {
+            self.main_screen
+                .inspect_view_with_tag_unchecked::<(), MessagesList>(MESSAGES_LIST_TAG, |v| {
+                    v.set_density(density);
+                })
+                .unwrap();
+        }
+    }
+
+    /// Apply a filter requested by `/filter` to the message list.
+    fn forward_pending_filter(&mut self) {
+        let filter: Option<Option<MessageFilter>> = unsafe {
+            self.main_screen
+                .inspect_view_with_tag_unchecked::<_, MessageInputField>(INPUT_FIELD_TAG, |v| {
+                    v.pending_filter.take()
+                })
+                .unwrap()
+        };
+        let Some(filter) = filter else {
+            return;
+        };
+        unsafe {
+            self.main_screen
+                .inspect_view_with_tag_unchecked::<(), MessagesList>(MESSAGES_LIST_TAG, |v| {
+                    v.set_filter(filter);
+                })
+                .unwrap();
+        }
+    }
+
+    /// React to the selected message as requested by `/react`.
+    fn forward_pending_reaction(&mut self) {
+        let reaction = unsafe {
+            self.main_screen
+                .inspect_view_with_tag_unchecked::<_, MessageInputField>(INPUT_FIELD_TAG, |v| {
+                    v.pending_reaction.take()
+                })
+                .unwrap()
+        };
+        let Some(reaction) = reaction else {
+            return;
+        };
+        unsafe {
+            self.main_screen
+                .inspect_view_with_tag_unchecked::<(), MessagesList>(MESSAGES_LIST_TAG, |v| {
+                    v.react_selected(&reaction);
+                })
+                .unwrap();
+        }
+    }
+
+    /// Repost the selected message as requested by `/forward`, on the current server or on another
+    /// one.
+    fn forward_pending_forward(&mut self, servers: &Servers) {
+        let pending_forward = unsafe {
+            self.main_screen
+                .inspect_view_with_tag_unchecked::<_, MessageInputField>(INPUT_FIELD_TAG, |v| {
+                    std::mem::take(&mut v.pending_forward)
+                })
+                .unwrap()
+        };
+        let Some(server) = pending_forward else {
+            return;
+        };
+        let app_state = Arc::clone(servers.current());
+        let target = match server {
+            None => Arc::clone(&app_state),
+            Some(server) => {
+                let mut matching = servers
+                    .iter()
+                    .filter(|state| state.api().server_url().contains(&*server));
+                match (matching.next(), matching.next()) {
+                    (Some(target), None) => Arc::clone(target),
+                    (None, _) => {
+                        app_state.toasts().error(format!(
+                            "No server given on the command line matches {server}"
+                        ));
+                        return;
+                    }
+                    (Some(_), Some(_)) => {
+                        app_state
+                            .toasts()
+                            .error(format!("More than one server matches {server}"));
+                        return;
+                    }
+                }
+            }
+        };
+        let message = unsafe {
+            self.main_screen
+                .inspect_view_with_tag_unchecked::<_, MessagesList>(MESSAGES_LIST_TAG, |v| {
+                    v.selected_message()
+                })
+                .unwrap()
+        };
+        let Some(message) = message else {
+            app_state
+                .toasts()
+                .info("Select a message to forward with <J> and <K>");
+            return;
+        };
+        let board =
+            (!Arc::ptr_eq(&target, &app_state)).then(|| app_state.api().server_url().into());
+        tokio::spawn(commands::forward_message(target, message, board));
+    }
+
+    /// Switch to a screen requested by the input field, e.g. by a slash command.
+    fn forward_pending_screen(&mut self) {
+        let screen = unsafe {
+            self.main_screen
+                .inspect_view_with_tag_unchecked::<Option<Screen>, MessageInputField>(
+                    INPUT_FIELD_TAG,
+                    |v| v.pending_screen.take(),
+                )
+                .unwrap()
+        };
+        if let Some(screen) = screen {
+            self.current_screen = screen;
+        }
+    }
+
+    pub fn set_app_state(&mut self, app_state: Weak<AppState>) {
+        self.app_state = app_state.clone();
+        unsafe {
+            self.main_screen
+                .inspect_view_with_tag_unchecked::<(), MessageInputField>(INPUT_FIELD_TAG, |v| {
+                    v.app_state = app_state.clone();
+                })
+                .unwrap();
+            self.main_screen
+                .inspect_view_with_tag_unchecked::<(), MessagesList>(MESSAGES_LIST_TAG, |v| {
+                    v.app_state = app_state.clone();
+                })
+                .unwrap();
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub enum Screen {
+    #[default]
+    MainScreen,
+    HelpScreen,
+    ErrorLogScreen,
+    StatsScreen,
+    /// Opened with `<CTRL + O>`.
+    ServerPicker {
+        /// Index into `Servers`.
+        selected: usize,
+        /// Whether archived servers are listed, toggled with `<A>`, see `Servers::listed`.
+        show_archived: bool,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct MessageInputField {
+    state: InputFieldState,
+    app_state: Weak<AppState>,
+    /// Index into `EXPIRY_CHOICES`.
+    expiry_choice: usize,
+    /// Screen requested by a slash command, to be switched to by `UIState`.
+    pending_screen: Option<Screen>,
+    /// Filter requested by `/filter`, to be applied to the message list by `UIState`.
+    pending_filter: Option<Option<MessageFilter>>,
+    /// Density requested by `/density`, `None` inside to toggle, to be applied to the message
+    /// list by `UIState`.
+    pending_density: Option<Option<Density>>,
+    /// Reaction requested by `/react`, to be added to the selected message by `UIState`.
+    pending_reaction: Option<Box<str>>,
+    /// Server requested by `/forward`, `None` inside for the current one, for `UIState` to repost
+    /// the selected message on.
+    pending_forward: Option<Option<Box<str>>>,
+    /// Large message waiting for the user to confirm sending it.
+    pending_confirmation: Option<String>,
+    /// Area inside the borders and horizontal scroll of the last render, for mapping mouse
+    /// positions to the text.
+    layout: Cell<Option<(Rect, u16)>>,
+    /// Time and position of the last click, for detecting double-clicks.
+    last_click: Option<(Instant, Position)>,
+    /// Is a drag selection started in the input field going on?
+    is_dragging: bool,
+    /// Is the preview of the message being composed shown, toggled with `<CTRL + P>`?
+    is_previewing: bool,
+}
+
+impl MessageInputField {
+    const PLACEHOLDER: &str = "Send a message ...";
+
+    pub fn new(app_state: Weak<AppState>) -> Self {
+        Self {
+            state: InputFieldState::default(),
+            app_state,
+            expiry_choice: 0,
+            pending_screen: None,
+            pending_filter: None,
+            pending_density: None,
+            pending_reaction: None,
+            pending_forward: None,
+            pending_confirmation: None,
+            layout: Cell::new(None),
+            last_click: None,
+            is_dragging: false,
+            is_previewing: false,
+        }
+    }
+
+    fn expires_in(&self) -> Option<Duration> {
+        EXPIRY_CHOICES[self.expiry_choice]
+    }
+
+    fn cycle_expiry(&mut self) {
+        let Some(app_state) = self.app_state.upgrade() else {
+            return;
+        };
+        if !app_state.supports(capabilities::DISAPPEARING_MESSAGES) {
+            app_state.toast_unsupported("Disappearing messages");
+            return;
+        }
+        self.expiry_choice = (self.expiry_choice + 1) % EXPIRY_CHOICES.len();
+    }
+
+    fn send_message(&mut self) {
+        let app_state = self.app_state.upgrade().unwrap();
+        let mut message = self.state.take_text();
+        match commands::parse(&message) {
+            Some(Ok(Command::Stats)) => {
+                self.pending_screen = Some(Screen::StatsScreen);
+                return;
+            }
+            Some(Ok(Command::Filter(filter))) => {
+                self.pending_filter = Some(filter);
+                return;
+            }
+            Some(Ok(Command::Density(density))) => {
+                self.pending_density = Some(density);
+                return;
+            }
+            Some(Ok(Command::React { reaction })) => {
+                self.pending_reaction = Some(reaction.into());
+                return;
+            }
+            Some(Ok(Command::Forward { server })) => {
+                self.pending_forward = Some(server.map(Box::from));
+                return;
+            }
+            Some(Ok(command)) => return commands::execute(command, app_state),
+            Some(Err(error)) => {
+                app_state.toasts().error(error.to_string());
+                // Give the user a chance to fix the command.
+                self.state.batch_insert(&message);
+                return;
+            }
+            None => (),
+        }
+        if message.starts_with("//") {
+            message.remove(0);
+        }
+        if is_large_message(&message) {
+            self.pending_confirmation = Some(message);
+            return;
+        }
+        self.post_message(message);
+    }
+
+    /// Answer to the confirmation of a large message, `y` to send it, `n` to go back to editing.
+    fn confirm(&mut self, key_code: KeyCode) {
+        match key_code {
+            KeyCode::Char('y') | KeyCode::Enter => {
+                if let Some(message) = self.pending_confirmation.take() {
+                    self.post_message(message);
+                }
+            }
+            KeyCode::Char('n') => {
+                if let Some(message) = self.pending_confirmation.take() {
+                    self.state.batch_insert(&message);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn post_message(&self, message: String) {
+        let app_state = self.app_state.upgrade().unwrap();
+        let expires_in = self.expires_in();
+        tokio::spawn(async move {
+            let send_result = app_state
+                .send_message(message.into(), ContentKind::PlainText, expires_in)
+                .await;
+            if let Err(e) = send_result {
+                log::error!("Error sending message: {e}");
+                app_state.toast_error("Failed to send message", &e);
+            }
+        });
+    }
+
+    /// Render the message being composed the way the message list would, in a popup above
+    /// `area`. Commands other than those sending a message aren't previewed.
+    fn render_preview(&self, frame: &mut Frame, area: Rect, theme: &Theme) {
+        let text = self.state.text();
+        let (content, content_kind) = match commands::parse(text) {
+            Some(Ok(Command::Send {
+                content,
+                content_kind,
+            })) => (content, content_kind),
+            Some(_) => return,
+            None if text.starts_with("//") => (&text[1..], ContentKind::PlainText),
+            None => (text, ContentKind::PlainText),
+        };
+        if content.trim().is_empty() {
+            return;
+        }
+        let lines = content_lines(content, content_kind, theme.text, theme);
+        let height = u16::min(lines.len() as u16 + 2, area.y);
+        if height <= 2 {
+            return;
+        }
+        let preview_area = Rect {
+            y: area.y - height,
+            height,
+            ..area
+        };
+        frame.render_widget(Clear, preview_area);
+        frame.render_widget(
+            Paragraph::new(lines)
+                .block(borders(theme, false).title("Preview (<CTRL + P> to close)")),
+            preview_area,
+        );
+    }
+
+    fn copy(&mut self) {
+        let result =
+            ClipboardContext::new().and_then(|mut clipboard| self.state.copy(&mut clipboard));
+        if let Err(e) = result {
+            log::error!("Error copying to clipboard: {e}");
+            if let Some(app_state) = self.app_state.upgrade() {
+                app_state
+                    .toasts()
+                    .error(format!("Can't copy to clipboard: {e}"));
+            }
+        }
+    }
+
+    /// Click to move the caret, double-click to select a word, drag to select.
+    fn on_mouse_event(&mut self, mouse_event: MouseEvent) {
+        let Some((area, scroll)) = self.layout.get() else {
+            return;
+        };
+        let position = Position::new(mouse_event.column, mouse_event.row);
+        let column = mouse_event
+            .column
+            .saturating_sub(area.x)
+            .saturating_add(scroll);
+        let index = self.state.index_at_column(usize::from(column));
+        match mouse_event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                if !area.contains(position) {
+                    self.last_click = None;
+                    return;
+                }
+                let now = Instant::now();
+                let is_double_click = self.last_click.is_some_and(|(time, last_position)| {
+                    last_position == position && now - time < DOUBLE_CLICK_INTERVAL
+                });
+                if is_double_click {
+                    self.state.select_word_at(index);
+                    self.last_click = None;
+                } else {
+                    self.state.set_caret(index);
+                    self.last_click = Some((now, position));
+                    self.is_dragging = true;
+                }
+            }
+            MouseEventKind::Drag(MouseButton::Left) if self.is_dragging => {
+                self.state.select_to(index);
+            }
+            MouseEventKind::Up(MouseButton::Left) => self.is_dragging = false,
+            _ => (),
+        }
+    }
+
+    fn cut(&mut self) {
+        let result =
+            ClipboardContext::new().and_then(|mut clipboard| self.state.cut(&mut clipboard));
+        if let Err(e) = result {
+            log::error!("Error cutting to clipboard: {e}");
+            if let Some(app_state) = self.app_state.upgrade() {
+                app_state
+                    .toasts()
+                    .error(format!("Can't cut to clipboard: {e}"));
+            }
+        }
+    }
+
+    fn paste(&mut self) {
+        match ClipboardContext::new() {
+            Ok(mut clipboard) => self.state.paste(&mut clipboard),
+            Err(e) => log::error!("Error accessing clipboard: {e}"),
+        }
+    }
+}
+
+impl MutView for MessageInputField {
+    fn render(&self, frame: &mut Frame, area: Rect, is_focused: bool) {
+        let area_inner = inner_area(area, 1);
+        let app_state = self.app_state.upgrade().unwrap();
+        let theme = app_state.theme();
+        let mut block = borders(theme, is_focused);
+        if let Some(expires_in) = self.expires_in() {
+            block = block.title(format!(
+                "Disappears after {}",
+                format_duration(expires_in.as_secs())
+            ));
+        }
+        let draft_attachments = app_state.draft_attachments();
+        if !draft_attachments.is_empty() {
+            let names: Vec<&str> = draft_attachments
+                .iter()
+                .map(|attachment| &*attachment.name)
+                .collect();
+            block = block
+                .title(Line::styled(format!("📎 {}", names.join(", ")), theme.dim).right_aligned());
+        }
+        if let Some(message) = &self.pending_confirmation {
+            self.layout.set(None);
+            let prompt = format!("Send {}? y/n", describe_size(message));
+            frame.render_widget(
+                Paragraph::new(Line::styled(prompt, theme.toast_info)).block(block),
+                area,
+            );
+            return;
+        }
+        let text = self.state.text();
+        let text_style = theme.text;
+        let line = match self.state.cursor() {
+            _ if text.is_empty() => Line::styled(Self::PLACEHOLDER, theme.dim),
+            Cursor::Caret(_) => Line::styled(single_line(text), text_style),
+            Cursor::Selection(selection) => Line::from(vec![
+                Span::styled(single_line(&text[..selection.start]), text_style),
+                Span::styled(
+                    single_line(&text[selection.start..selection.end]),
+                    text_style.patch(theme.selected),
+                ),
+                Span::styled(single_line(&text[selection.end..]), text_style),
+            ]),
+        };
+        // Scroll horizontally to keep the caret visible.
+        let caret_column = single_line(&text[..self.state.caret_position()]).width() as u16;
+        let scroll = caret_column.saturating_sub(area_inner.width.saturating_sub(1));
+        self.layout.set(Some((area_inner, scroll)));
+        frame.render_widget(Paragraph::new(line).scroll((0, scroll)).block(block), area);
+        if is_focused {
+            frame.set_cursor_position((area_inner.x + caret_column - scroll, area_inner.y));
+        }
+        if self.is_previewing {
+            self.render_preview(frame, area, theme);
+        }
+    }
+
+    fn is_focusable(&self) -> bool {
+        true
+    }
+
+    fn on_key_event(&mut self, key_event: KeyEvent) {
+        if key_event.kind != KeyEventKind::Press {
+            return;
+        }
+
+        if self.pending_confirmation.is_some() {
+            self.confirm(key_event.code);
+            return;
+        }
+
+        use KeyCode::*;
+        match (key_event.modifiers, key_event.code) {
+            (KeyModifiers::NONE, Enter) => self.send_message(),
+            (KeyModifiers::NONE | KeyModifiers::SHIFT, Char(char)) => self.state.insert(char),
+            (KeyModifiers::NONE, Backspace) => self.state.delete_backward(),
+            (KeyModifiers::NONE, Delete) => self.state.delete_forward(),
+            (KeyModifiers::NONE, Left) => self.state.caret_left(),
+            (KeyModifiers::NONE, Right) => self.state.caret_right(),
+            (KeyModifiers::NONE, Home) => self.state.caret_left_end(),
+            (KeyModifiers::NONE, End) => self.state.caret_right_end(),
+            (KeyModifiers::SHIFT, Left) => self.state.select_left(),
+            (KeyModifiers::SHIFT, Right) => self.state.select_right(),
+            (KeyModifiers::SHIFT, Home) => self.state.select_left_end(),
+            (KeyModifiers::SHIFT, End) => self.state.select_right_end(),
+            (KeyModifiers::CONTROL, Char('a')) => self.state.select_all(),
+            (KeyModifiers::CONTROL, Char('c')) => self.copy(),
+            (KeyModifiers::CONTROL, Char('x')) => self.cut(),
+            (KeyModifiers::CONTROL, Char('v')) => self.paste(),
+            (KeyModifiers::CONTROL, Char('t')) => self.cycle_expiry(),
+            (KeyModifiers::CONTROL, Char('p')) => self.is_previewing = !self.is_previewing,
+            (KeyModifiers::CONTROL, Char('z')) => self.state.undo(),
+            (KeyModifiers::CONTROL, Char('k')) => self.state.kill_to_end(),
+            (KeyModifiers::CONTROL, Char('u')) => self.state.kill_to_start(),
+            (KeyModifiers::CONTROL, Char('y')) => self.state.yank(),
+            (KeyModifiers::ALT, Char('y')) => self.state.yank_pop(),
+            (modifiers, Char('z' | 'Z'))
+                if modifiers == KeyModifiers::CONTROL | KeyModifiers::SHIFT =>
+            {
+                self.state.redo()
+            }
+            (_, _) => (),
+        }
+    }
+
+    fn preferred_size(&self) -> Option<Size> {
+        Some(Size::new(u16::MAX, 3))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MessagesList {
+    app_state: Weak<AppState>,
+    scroll: i16,
+    selected: Option<MessageId>,
+    /// Quote of the selected message, to be moved into the input field by `UIState`.
+    pending_quote: Option<String>,
+    /// Only messages matching this are shown.
+    filter: Option<MessageFilter>,
+    /// Has the user asked to edit the filter? The input field is prefilled by `UIState`.
+    pending_filter_prompt: bool,
+    /// Search typed after `/`, if searching. Only messages matching it are shown, with the
+    /// matches highlighted, on top of `filter`.
+    search: Option<Search>,
+    jump_list: JumpList,
+    /// Message to jump to once rendered, where the previous session left off.
+    pending_anchor: Option<MessageId>,
+    /// Index of the first line of each message, and the number of lines above the bottom of
+    /// the list, from the last render. For scrolling to a message.
+    message_offsets: RefCell<Vec<(MessageId, usize)>>,
+    extra_lines: Cell<i16>,
+    /// Lines of the messages in the last render, so that only messages that changed are laid
+    /// out again.
+    layouts: RefCell<HashMap<MessageId, CachedLayout>>,
+    /// Session ID of the author whose profile is shown in a popup, opened with `<P>`.
+    shown_profile: Option<u64>,
+}
+
+impl MessagesList {
+    pub fn new(app_state: Weak<AppState>) -> Self {
+        Self {
+            app_state,
+            scroll: Default::default(),
+            selected: None,
+            pending_quote: None,
+            filter: None,
+            pending_filter_prompt: false,
+            search: None,
+            jump_list: JumpList::default(),
+            pending_anchor: None,
+            message_offsets: RefCell::new(Vec::new()),
+            extra_lines: Cell::new(0),
+            layouts: RefCell::new(HashMap::new()),
+            shown_profile: None,
+        }
+    }
+
+    fn is_shown(&self, message: &Message) -> bool {
+        let is_found = self
+            .search
+            .as_ref()
+            .map_or(true, |search| search.matches(message));
+        is_found
+            && self
+                .filter
+                .as_ref()
+                .map_or(true, |filter| filter.matches(message))
+    }
+
+    /// Scrolls back to the bottom, as positions in the list change.
+    fn set_filter(&mut self, filter: Option<MessageFilter>) {
+        self.filter = filter;
+        self.scroll = 0;
+    }
+
+    /// Scrolls back to the bottom, as positions in the list change.
+    fn set_search(&mut self, search: Option<Search>) {
+        self.search = search;
+        self.scroll = 0;
+    }
+
+    /// Returns whether there was a search to close.
+    fn close_search(&mut self) -> bool {
+        let is_searching = self.search.is_some();
+        self.set_search(None);
+        is_searching
+    }
+
+    /// Characters typed while searching edit the search, and options are toggled with `ALT`.
+    /// Other keys such as arrows work as usual. Returns whether the key was handled.
+    fn on_search_key_event(&mut self, key_event: KeyEvent) -> bool {
+        let Some(mut search) = self.search.clone() else {
+            return false;
+        };
+        match (key_event.modifiers, key_event.code) {
+            (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(char)) => search.push(char),
+            (KeyModifiers::NONE, KeyCode::Backspace) => search.pop(),
+            (KeyModifiers::ALT, KeyCode::Char('r')) => search.toggle_regex(),
+            (KeyModifiers::ALT, KeyCode::Char('c')) => search.toggle_case_sensitive(),
+            (KeyModifiers::ALT, KeyCode::Char('a')) => {
+                let author = match search.author() {
+                    Some(_) => None,
+                    None => self.selected_author(),
+                };
+                search.set_author(author);
+            }
+            (KeyModifiers::NONE, KeyCode::Enter) => {
+                self.search_server();
+                return true;
+            }
+            (_, _) => return false,
+        }
+        self.set_search(Some(search));
+        true
+    }
+
+    /// Search the server for older messages matching the search, which show up in the list once
+    /// loaded.
+    fn search_server(&self) {
+        let Some(app_state) = self.app_state.upgrade() else {
+            return;
+        };
+        let Some(search) = self.search.clone() else {
+            return;
+        };
+        if search.text().trim().is_empty() && search.author().is_none() {
+            return;
+        }
+        if !app_state.supports(capabilities::SEARCH) {
+            app_state.toast_unsupported("Searching older messages");
+            return;
+        }
+        let has_options =
+            search.is_regex() || search.is_case_sensitive() || search.author().is_some();
+        if has_options && !app_state.supports(capabilities::REGEX_SEARCH) {
+            app_state.toast_unsupported("Searching with options");
+            return;
+        }
+        if search.is_invalid() {
+            app_state.toasts().error("Invalid regex");
+            return;
+        }
+        tokio::spawn(async move {
+            match app_state.search_older_messages(&search).await {
+                Ok((0, false)) => app_state.toasts().info("No older matches on the server"),
+                Ok((count, false)) => app_state
+                    .toasts()
+                    .info(format!("Loaded {count} older matches from the server")),
+                Ok((count, true)) => app_state.toasts().info(format!(
+                    "Loaded {count} older matches, the server stopped searching early"
+                )),
+                Err(e) => {
+                    log::error!("Error searching messages: {e}");
+                    app_state.toast_error("Failed to search older messages", &e);
+                }
+            }
+        });
+    }
+
+    fn selected_message(&self) -> Option<Message> {
+        let app_state = self.app_state.upgrade()?;
+        let messages = app_state.lock_messages();
+        messages
+            .iter()
+            .find(|message| Some(message.id) == self.selected)
+            .cloned()
+    }
+
+    fn selected_author(&self) -> Option<Box<str>> {
+        let app_state = self.app_state.upgrade()?;
+        let messages = app_state.lock_messages();
+        let message = messages
+            .iter()
+            .find(|message| Some(message.id) == self.selected)?;
+        message.webhook_name.clone()
+    }
+
+    /// Toggles between densities if `density` is `None`.
+    /// Scrolls back to the bottom, as positions in the list change.
+    fn set_density(&mut self, density: Option<Density>) {
+        let Some(app_state) = self.app_state.upgrade() else {
+            return;
+        };
+        let density = density.unwrap_or_else(|| app_state.density().toggled());
+        app_state.set_density(density);
+        self.scroll = 0;
+        app_state
+            .toasts()
+            .info(format!("Message list density: {density}"));
+    }
+
+    fn position(&self) -> ListPosition {
+        ListPosition {
+            scroll: self.scroll,
+            selected: self.selected,
+        }
+    }
+
+    fn restore(&mut self, position: ListPosition) {
+        self.scroll = position.scroll;
+        self.selected = position.selected;
+    }
+
+    /// Select message `id` and scroll it to the top, recording the current position in the jump
+    /// list. Messages that aren't rendered, e.g. as they are far above the ones in view, are
+    /// jumped to after the next render.
+    fn jump_to(&mut self, id: MessageId) {
+        let line = self
+            .message_offsets
+            .borrow()
+            .iter()
+            .find(|(message_id, _)| *message_id == id)
+            .map(|&(_, line)| line);
+        let Some(line) = line else {
+            self.pending_anchor = Some(id);
+            return;
+        };
+        self.jump_list.record(self.position());
+        self.selected = Some(id);
+        // `scroll` is relative to the bottom of the list, don't scroll past it.
+        let line = i16::try_from(line).unwrap_or(i16::MAX);
+        self.scroll = line.saturating_sub(self.extra_lines.get()).min(0);
+    }
+
+    /// Index of the first of the `shown` messages to render, so that lines aren't built for every
+    /// loaded message on every frame. As messages take at least one line, rendering as many
+    /// messages as there are lines from the top of the view to the bottom of the list is enough.
+    fn first_rendered(&self, shown: &[&Message], height: u16) -> usize {
+        let scrolled_up = usize::from(self.scroll.min(0).unsigned_abs());
+        let line_count = usize::from(height) + scrolled_up + RENDER_MARGIN;
+        let mut start = shown.len().saturating_sub(line_count);
+        if let Some(anchor) = self.pending_anchor {
+            if let Some(index) = shown.iter().position(|message| message.id == anchor) {
+                start = start.min(index);
+            }
+        }
+        // Runs of large emoji take one line for many messages, so they are rendered whole.
+        while start > 0 && is_same_emoji(shown[start - 1], shown[start]) {
+            start -= 1;
+        }
+        start
+    }
+
+    /// Jump to `pending_anchor` if it has been rendered. Returns whether it jumped.
+    fn jump_to_pending_anchor(&mut self) -> bool {
+        let Some(id) = self.pending_anchor else {
+            return false;
+        };
+        let is_rendered = self
+            .message_offsets
+            .borrow()
+            .iter()
+            .any(|&(message_id, _)| message_id == id);
+        if !is_rendered {
+            return false;
+        }
+        self.pending_anchor = None;
+        self.jump_to(id);
+        true
+    }
+
+    /// Jump to the oldest message if `oldest`, else to the latest one.
+    fn jump_to_end(&mut self, oldest: bool) {
+        let Some(app_state) = self.app_state.upgrade() else {
+            return;
+        };
+        let messages = app_state.lock_messages();
+        let mut shown = messages.iter().filter(|message| self.is_shown(message));
+        let message = match oldest {
+            true => shown.next(),
+            false => shown.last(),
+        };
+        let Some(id) = message.map(|message| message.id) else {
+            return;
+        };
+        drop(messages);
+        self.jump_to(id);
+    }
+
+    fn jump_back(&mut self) {
+        if let Some(position) = self.jump_list.back(self.position()) {
+            self.restore(position);
+        }
+    }
+
+    fn jump_forward(&mut self) {
+        if let Some(position) = self.jump_list.forward(self.position()) {
+            self.restore(position);
+        }
+    }
+
+    /// Move selection by `offset` messages, positive is towards newer messages.
+    /// Selects the latest message if nothing is selected.
+    fn move_selection(&mut self, offset: isize) {
+        let Some(app_state) = self.app_state.upgrade() else {
+            return;
+        };
+        let all_messages = app_state.lock_messages();
+        let messages: Vec<&Message> = all_messages
+            .iter()
+            .filter(|message| self.is_shown(message))
+            .collect();
+        let selected_index = self
+            .selected
+            .and_then(|id| messages.iter().position(|message| message.id == id));
+        let index = match selected_index {
+            Some(index) => index
+                .saturating_add_signed(offset)
+                .min(messages.len().saturating_sub(1)),
+            None => messages.len().saturating_sub(1),
+        };
+        self.selected = messages.get(index).map(|message| message.id);
+    }
+
+    /// Vote for an option of the selected message, if it's a poll.
+    fn vote_selected(&mut self, option: u32) {
+        let Some(app_state) = self.app_state.upgrade() else {
+            return;
+        };
+        let Some(message_id) = self.selected else {
+            return;
+        };
+        let is_poll = app_state
+            .lock_messages()
+            .iter()
+            .any(|message| message.id == message_id && message.poll.is_some());
+        if !is_poll {
+            return;
+        }
+        if !app_state.supports(capabilities::POLLS) {
+            app_state.toast_unsupported("Voting");
+            return;
+        }
+        tokio::spawn(async move {
+            if let Err(e) = app_state.api().vote(message_id, option).await {
+                log::error!("Error voting: {e}");
+                app_state.toast_error("Failed to vote", &e);
+            }
+        });
+    }
+
+    /// React to the selected message, or take the reaction back if already added.
+    fn react_selected(&mut self, reaction: &str) {
+        let Some(app_state) = self.app_state.upgrade() else {
+            return;
+        };
+        let Some(message_id) = self.selected else {
+            app_state
+                .toasts()
+                .info("Select a message to react to with <J> and <K>");
+            return;
+        };
+        if !app_state.supports(capabilities::REACTIONS) {
+            app_state.toast_unsupported("Reacting");
+            return;
+        }
+        let reaction = reaction.to_owned();
+        tokio::spawn(async move {
+            if let Err(e) = app_state.react(message_id, &reaction).await {
+                log::error!("Error reacting: {e}");
+                app_state.toast_error("Failed to react", &e);
+            }
+        });
+    }
+
+    /// Show the profile of the author of the selected message, or close it if shown.
+    fn toggle_profile(&mut self) {
+        if self.close_profile() {
+            return;
+        }
+        let Some(app_state) = self.app_state.upgrade() else {
+            return;
+        };
+        if !app_state.supports(capabilities::PROFILES) {
+            app_state.toast_unsupported("Profiles");
+            return;
+        }
+        let author = app_state
+            .lock_messages()
+            .iter()
+            .find(|message| Some(message.id) == self.selected)
+            .and_then(|message| message.author);
+        match (self.selected, author) {
+            (None, _) => app_state
+                .toasts()
+                .info("Select a message with <J> and <K> to see the profile of its author"),
+            (Some(_), None) => app_state
+                .toasts()
+                .info("The author of this message is anonymous"),
+            (Some(_), Some(author)) => {
+                app_state.load_profile(author);
+                self.shown_profile = Some(author);
+            }
+        }
+    }
+
+    /// Returns whether there was a profile to close.
+    fn close_profile(&mut self) -> bool {
+        self.shown_profile.take().is_some()
+    }
+
+    fn toggle_marked(&mut self) {
+        let Some(app_state) = self.app_state.upgrade() else {
+            return;
+        };
+        if let Some(message_id) = self.selected {
+            app_state.toggle_marked(message_id);
+        }
+    }
+
+    /// Copy the contents of the marked messages, separated by blank lines.
+    fn copy_marked(&mut self) {
+        let Some(app_state) = self.app_state.upgrade() else {
+            return;
+        };
+        let messages = app_state.marked_messages();
+        if messages.is_empty() {
+            app_state
+                .toasts()
+                .info("No messages marked, mark them with <SPACE>");
+            return;
+        }
+        let contents: Vec<&str> = messages.iter().map(|message| &*message.content).collect();
+        let result = ClipboardContext::new()
+            .and_then(|mut clipboard| clipboard.set_contents(contents.join("\n\n")));
+        match result {
+            Ok(()) => app_state
+                .toasts()
+                .info(format!("Copied {} messages", messages.len())),
+            Err(e) => {
+                log::error!("Error copying messages: {e}");
+                app_state
+                    .toasts()
+                    .error(format!("Failed to copy messages: {e}"));
+            }
+        }
+    }
+
+    /// Delete the marked messages that were sent in this session.
+    fn delete_marked(&mut self) {
+        let Some(app_state) = self.app_state.upgrade() else {
+            return;
+        };
+        if !app_state.supports(capabilities::DELETE_MESSAGES) {
+            app_state.toast_unsupported("Deleting messages");
+            return;
+        }
+        let ids: Vec<MessageId> = app_state
+            .marked()
+            .into_iter()
+            .filter(|&id| app_state.is_own_message(id))
+            .collect();
+        if ids.is_empty() {
+            app_state
+                .toasts()
+                .info("None of the marked messages were sent in this session");
+            return;
+        }
+        tokio::spawn(async move {
+            match app_state.delete_own_messages(&ids).await {
+                Ok(deleted) => app_state
+                    .toasts()
+                    .info(format!("Deleted {deleted} messages")),
+                Err(e) => {
+                    log::error!("Error deleting messages: {e}");
+                    app_state.toast_error("Failed to delete messages", &e);
+                }
+            }
+        });
+    }
 
-impl Default for UIState {
-    fn default() -> Self {
-        let mut main_screen = {
-            let mut builder = ScreenBuilder::new();
-            let root_view = Stack::vertical((
-                builder.tagged_view_cell(MESSAGES_LIST_TAG, MessagesList::new(Weak::new())),
-                builder.tagged_view_cell(INPUT_FIELD_TAG, MessageInputField::new(Weak::new())),
-            ));
-            builder.finish(root_view)
+    fn quote_selected(&mut self) {
+        let Some(app_state) = self.app_state.upgrade() else {
+            return;
         };
-        main_screen.focus_next();
-        Self {
-            app_state: Weak::default(),
-            current_screen: Screen::default(),
-            main_screen,
+        let messages = app_state.lock_messages();
+        let Some(message) = messages
+            .iter()
+            .find(|message| Some(message.id) == self.selected)
+        else {
+            return;
+        };
+        let mut quote = String::new();
+        for line in message.content.lines() {
+            quote.push_str("> ");
+            quote.push_str(line);
+            quote.push('\n');
         }
+        self.pending_quote = Some(quote);
     }
 }
 
-impl UIState {
-    /// This function may only be called by `AppState`.
-    pub fn messages_updated(&mut self) {
-        log::info!("todo");
-    }
+/// Tallies of reactions, e.g. `👍 3  🎉 1`.
+fn reactions_line(reactions: &[ReactionTally], theme: &Theme) -> Line<'static> {
+    let tallies: Vec<String> = reactions
+        .iter()
+        .map(|tally| format!("{} {}", tally.reaction, tally.count))
+        .collect();
+    Line::styled(format!("  {}", tallies.join("  ")), theme.dim)
+}
 
-    pub fn set_app_state(&mut self, app_state: Weak<AppState>) {
-        self.app_state = app_state.clone();
-        unsafe {
-            self.main_screen
-                .inspect_view_with_tag_unchecked::<(), MessageInputField>(INPUT_FIELD_TAG, |v| {
-                    v.app_state = app_state.clone();
-                })
-                .unwrap();
-            self.main_screen
-                .inspect_view_with_tag_unchecked::<(), MessagesList>(MESSAGES_LIST_TAG, |v| {
-                    v.app_state = app_state.clone();
-                })
-                .unwrap();
+/// Width of the bars of poll results, in cells.
+const POLL_BAR_WIDTH: usize = 20;
+
+fn poll_lines(poll: &Poll, style: Style, theme: &Theme) -> Vec<Line<'static>> {
+    let total_votes: u32 = poll.options.iter().map(|option| option.votes).sum();
+    poll.options
+        .iter()
+        .enumerate()
+        .map(|(i, option)| {
+            let filled = (option.votes as usize * POLL_BAR_WIDTH)
+                .checked_div(total_votes as usize)
+                .unwrap_or(0);
+            Line::from(vec![
+                Span::styled(format!("  {}. {} ", i + 1, option.text), style),
+                Span::styled("█".repeat(filled), style.patch(theme.poll_bar_filled)),
+                Span::styled(
+                    "░".repeat(POLL_BAR_WIDTH - filled),
+                    style.patch(theme.poll_bar_empty),
+                ),
+                Span::styled(format!(" {}", option.votes), style),
+            ])
+        })
+        .collect()
+}
+
+/// Cut `line` to `width` columns, ending it with an ellipsis if anything is cut, or if `is_cut`
+/// as something has been cut already.
+fn truncate_line(line: &mut Line, width: usize, is_cut: bool, theme: &Theme) {
+    if !is_cut && line.width() <= width {
+        return;
+    }
+    // Leaving a column for the ellipsis.
+    let max_width = width.saturating_sub(1);
+    let mut used_width = 0;
+    let mut spans = Vec::with_capacity(line.spans.len() + 1);
+    for span in line.spans.drain(..) {
+        let span_width = span.width();
+        if used_width + span_width <= max_width {
+            used_width += span_width;
+            spans.push(span);
+            continue;
         }
+        let mut content = String::new();
+        for char in span.content.chars() {
+            used_width += char.width().unwrap_or(0);
+            if used_width > max_width {
+                break;
+            }
+            content.push(char);
+        }
+        spans.push(Span::styled(content, span.style));
+        break;
     }
+    spans.push(Span::styled("…", theme.dim));
+    line.spans = spans;
 }
 
-#[derive(Debug, Default, Clone)]
-pub enum Screen {
-    #[default]
-    MainScreen,
-    HelpScreen,
+/// Is the message shown as large emoji, i.e. plain text of only a few emoji and nothing else?
+fn is_large_emoji(message: &Message) -> bool {
+    message.content_kind == ContentKind::PlainText
+        && message.poll.is_none()
+        && message.link_preview.is_none()
+        && message.attachments.is_empty()
+        && message.expires_at.is_none()
+        && message.forwarded_from.is_none()
+        && emoji::is_emoji_only(&message.content)
+}
+
+/// What the lines of a message depend on, besides the message itself, which only changes in
+/// the parts included here.
+#[derive(Debug, Clone, PartialEq)]
+struct LayoutKey {
+    /// `None` if the lines don't depend on the width of the list.
+    width: Option<u16>,
+    style: Style,
+    density: Density,
+    is_grouped: bool,
+    is_masking: bool,
+    poll: Option<Poll>,
+    has_link_preview: bool,
+    reactions: Box<[ReactionTally]>,
 }
 
+/// Lines of a message from a previous render, reused while its `LayoutKey` is the same.
 #[derive(Debug, Clone)]
-pub struct MessageInputField {
-    super_: InputField<'static>,
-    app_state: Weak<AppState>,
+struct CachedLayout {
+    key: LayoutKey,
+    lines: Vec<Line<'static>>,
 }
 
-impl MessageInputField {
-    pub fn new(app_state: Weak<AppState>) -> Self {
-        Self {
-            super_: InputField::default()
-                .placeholder("Send a message ...")
-                .block_unfocused(borders(White))
-                .block_focused(borders(LightYellow)),
-            app_state,
+/// Names the board of the original by its host, if it's another board than the message's.
+fn forwarded_label(forwarded_from: &ForwardedFrom) -> String {
+    let Some(board) = &forwarded_from.board else {
+        return "↪ forwarded".to_owned();
+    };
+    let host = board
+        .parse::<hyper::Uri>()
+        .ok()
+        .and_then(|uri| uri.host().map(str::to_owned))
+        .unwrap_or_else(|| board.to_string());
+    format!("↪ forwarded from #{host}")
+}
+
+/// Lines of a message that isn't shown as large emoji, with `content` being its masked content.
+fn message_lines<'a>(
+    message: &Message,
+    content: &'a str,
+    key: &LayoutKey,
+    search: Option<&Search>,
+    now: DateTime<Utc>,
+    theme: &Theme,
+) -> Vec<Line<'a>> {
+    let LayoutKey {
+        style,
+        density,
+        is_grouped,
+        ..
+    } = *key;
+    let mut message_lines = content_lines(content, message.content_kind, style, theme);
+    if let Some(search) = search {
+        for line in &mut message_lines {
+            highlight_matches(line, search, theme.search_match);
         }
     }
-
-    fn send_message(&mut self) {
-        let app_state = self.app_state.upgrade().unwrap();
-        let message = self.super_.content_mut().take_text();
-        tokio::spawn(async move {
-            let send_result = app_state.api().send_message(message.into()).await;
-            if let Err(e) = send_result {
-                log::error!("Error sending message: {e}")
+    // Only the first line is shown when compact, anything else is cut.
+    let is_cut = density == Density::Compact
+        && (message_lines.len() > 1
+            || message.poll.is_some()
+            || message.link_preview.is_some()
+            || !message.attachments.is_empty()
+            || !key.reactions.is_empty());
+    if density == Density::Compact {
+        message_lines.truncate(1);
+    }
+    if let Some(forwarded_from) = &message.forwarded_from {
+        match density {
+            Density::Compact => {
+                if let Some(first_line) = message_lines.first_mut() {
+                    first_line.spans.insert(0, Span::styled("↪ ", theme.dim));
+                }
             }
-        });
+            // Above the content, below the name of the author.
+            Density::Cozy => {
+                message_lines.insert(0, Line::styled(forwarded_label(forwarded_from), theme.dim));
+            }
+        }
+    }
+    if let Some(webhook_name) = &message.webhook_name {
+        let name_style = style
+            .patch(theme.author_style(webhook_name))
+            .add_modifier(Modifier::BOLD);
+        match density {
+            Density::Compact => {
+                // Blanked out if grouped, keeping the content aligned.
+                let prefix = match is_grouped {
+                    true => " ".repeat(webhook_name.width() + 2),
+                    false => format!("{webhook_name}: "),
+                };
+                if let Some(first_line) = message_lines.first_mut() {
+                    first_line.spans.insert(0, Span::styled(prefix, name_style));
+                }
+            }
+            Density::Cozy if is_grouped => (),
+            // On a line of its own.
+            Density::Cozy => {
+                message_lines.insert(0, Line::styled(webhook_name.to_string(), name_style))
+            }
+        }
+    }
+    if let (Some(expires_at), Some(last_line)) = (message.expires_at, message_lines.last_mut()) {
+        let seconds_left = (expires_at - now).num_seconds().max(0) as u64;
+        last_line.push_span(Span::styled(
+            format!(" (disappears in {})", format_duration(seconds_left)),
+            theme.dim,
+        ));
+    }
+    if let Some(width) = key.width {
+        if let Some(line) = message_lines.first_mut() {
+            truncate_line(line, usize::from(width), is_cut, theme);
+        }
+        return message_lines;
+    }
+    if let Some(poll) = &message.poll {
+        message_lines.extend(poll_lines(poll, style, theme));
     }
+    if let Some(link_preview) = &message.link_preview {
+        message_lines.push(Line::styled(
+            format!("  -> {}", link_preview.title),
+            theme.dim,
+        ));
+    }
+    for attachment in &message.attachments {
+        let icon = match attachment.kind() {
+            AttachmentKind::Audio => "🔊",
+            AttachmentKind::File => "📎",
+        };
+        message_lines.push(Line::styled(
+            format!(
+                "  {icon} {} ({})",
+                attachment.name,
+                format_size(attachment.size)
+            ),
+            theme.dim,
+        ));
+    }
+    if !key.reactions.is_empty() {
+        message_lines.push(reactions_line(&key.reactions, theme));
+    }
+    message_lines
 }
 
-impl MutView for MessageInputField {
-    fn render(&self, frame: &mut Frame, area: Rect, is_focused: bool) {
-        self.super_.render(frame, area, is_focused);
+/// For keeping lines borrowing from a message past the render.
+fn into_owned(line: Line) -> Line<'static> {
+    Line {
+        spans: line
+            .spans
+            .into_iter()
+            .map(|span| Span::styled(span.content.into_owned(), span.style))
+            .collect(),
+        style: line.style,
+        alignment: line.alignment,
     }
+}
 
-    fn on_focus(&mut self) {
-        self.super_.on_focus()
-    }
+/// Would the messages be collapsed into an `EmojiRun` if consecutive?
+fn is_same_emoji(a: &Message, b: &Message) -> bool {
+    a.content == b.content
+        && a.webhook_name == b.webhook_name
+        && is_large_emoji(a)
+        && is_large_emoji(b)
+}
+
+/// Consecutive identical large emoji messages, shown as one line with a counter.
+struct EmojiRun<'a> {
+    /// The first message of the run.
+    message: &'a Message,
+    line_index: usize,
+    count: usize,
+    /// Whether any message of the run is selected.
+    is_selected: bool,
+}
 
-    fn on_unfocus(&mut self) {
-        self.super_.on_unfocus()
+impl<'a> EmojiRun<'a> {
+    fn line(&self, theme: &Theme) -> Line<'a> {
+        let (text_style, emoji_style) = match self.is_selected {
+            true => (
+                theme.text.patch(theme.selected),
+                theme.large_emoji.patch(theme.selected),
+            ),
+            false => (theme.text, theme.large_emoji),
+        };
+        let mut spans = Vec::new();
+        if let Some(webhook_name) = &self.message.webhook_name {
+            let name_style = text_style
+                .patch(theme.author_style(webhook_name))
+                .add_modifier(Modifier::BOLD);
+            spans.push(Span::styled(format!("{webhook_name}: "), name_style));
+        }
+        let emoji: Vec<&str> = self.message.content.split_whitespace().collect();
+        spans.push(Span::styled(
+            format!("  {}  ", emoji.join(" ")),
+            emoji_style,
+        ));
+        if self.count > 1 {
+            spans.push(Span::styled(format!(" ×{}", self.count), theme.dim));
+        }
+        Line::from(spans)
     }
+}
 
-    fn is_focusable(&self) -> bool {
-        self.super_.is_focusable()
+/// Lines of a message's content, rendered according to its kind.
+fn content_lines<'a>(
+    content: &'a str,
+    content_kind: ContentKind,
+    style: Style,
+    theme: &Theme,
+) -> Vec<Line<'a>> {
+    match content_kind {
+        ContentKind::PlainText => text_lines(content, false, style, theme),
+        ContentKind::Markdown => text_lines(content, true, style, theme),
+        ContentKind::Code => {
+            let mut lines = Vec::new();
+            let code: Vec<&str> = content.lines().collect();
+            push_code_block(&mut lines, "", &code, style, theme);
+            lines
+        }
+        ContentKind::System => {
+            let style = style.patch(theme.dim).add_modifier(Modifier::ITALIC);
+            content
+                .lines()
+                .map(|line| Line::styled(line, style))
+                .collect()
+        }
     }
+}
 
-    fn on_key_event(&mut self, key_event: KeyEvent) {
-        if key_event.kind == KeyEventKind::Press
-            && key_event.modifiers == KeyModifiers::NONE
-            && key_event.code == KeyCode::Enter
-        {
-            self.send_message();
+/// Lines of text, with quoted lines dimmed and long quotes collapsed, and fenced code blocks
+/// highlighted. With `markdown`, headings are bold and list items get bullets.
+fn text_lines<'a>(content: &'a str, markdown: bool, style: Style, theme: &Theme) -> Vec<Line<'a>> {
+    let mut lines = Vec::new();
+    let mut quote_length = 0usize;
+    let mut hidden_quote_lines = 0usize;
+    // Language and lines of the code block we're in, if any.
+    let mut code_block: Option<(&str, Vec<&str>)> = None;
+    for line in content.lines() {
+        if let Some(info_string) = line.trim_start().strip_prefix("```") {
+            push_collapsed_quote(&mut lines, &mut hidden_quote_lines, style, theme);
+            quote_length = 0;
+            match code_block.take() {
+                Some((language, code)) => {
+                    push_code_block(&mut lines, language, &code, style, theme)
+                }
+                None => code_block = Some((info_string.trim(), Vec::new())),
+            }
+            lines.push(Line::styled(line, style.patch(theme.dim)));
+        } else if let Some((_, code)) = &mut code_block {
+            code.push(line);
+        } else if line.starts_with('>') {
+            quote_length += 1;
+            if quote_length > MAX_QUOTE_LINES {
+                hidden_quote_lines += 1;
+            } else {
+                lines.push(Line::styled(line, style.patch(theme.dim)));
+            }
+        } else {
+            push_collapsed_quote(&mut lines, &mut hidden_quote_lines, style, theme);
+            quote_length = 0;
+            lines.push(match markdown {
+                true => markdown_line(line, style),
+                false => Line::styled(line, style),
+            });
         }
-        self.super_.on_key_event(key_event);
     }
+    push_collapsed_quote(&mut lines, &mut hidden_quote_lines, style, theme);
+    // Unclosed code block.
+    if let Some((language, code)) = code_block {
+        push_code_block(&mut lines, language, &code, style, theme);
+    }
+    lines
+}
 
-    fn preferred_size(&self) -> Option<Size> {
-        Some(Size::new(u16::MAX, 3))
+fn markdown_line(line: &str, style: Style) -> Line {
+    let heading = line
+        .trim_start_matches('#')
+        .strip_prefix(' ')
+        .filter(|_| line.starts_with('#'));
+    if let Some(heading) = heading {
+        return Line::styled(heading, style.add_modifier(Modifier::BOLD));
+    }
+    let indent = &line[..line.len() - line.trim_start().len()];
+    let list_item = line
+        .trim_start()
+        .strip_prefix("- ")
+        .or_else(|| line.trim_start().strip_prefix("* "));
+    match list_item {
+        Some(item) => Line::from(vec![
+            Span::styled(indent, style),
+            Span::styled("• ", style),
+            Span::styled(item, style),
+        ]),
+        None => Line::styled(line, style),
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct MessagesList {
-    app_state: Weak<AppState>,
-    scroll: i16,
+fn push_code_block<'a>(
+    lines: &mut Vec<Line<'a>>,
+    language: &str,
+    code: &[&'a str],
+    style: Style,
+    theme: &Theme,
+) {
+    // The language is the first word of the info string.
+    let language = language.split_whitespace().next().unwrap_or_default();
+    let highlighted = match theme.syntax_highlighting {
+        true => highlight_code(code, language, style),
+        false => None,
+    };
+    match highlighted {
+        Some(highlighted) => lines.extend(highlighted),
+        None => lines.extend(
+            code.iter()
+                .map(|line| Line::styled(*line, style.patch(theme.code))),
+        ),
+    }
 }
 
-impl MessagesList {
-    pub fn new(app_state: Weak<AppState>) -> Self {
-        Self {
-            app_state,
-            scroll: Default::default(),
-        }
+/// Push the "more lines" placeholder for collapsed quote lines, if there are any.
+fn push_collapsed_quote(
+    lines: &mut Vec<Line>,
+    hidden_quote_lines: &mut usize,
+    style: Style,
+    theme: &Theme,
+) {
+    if *hidden_quote_lines != 0 {
+        lines.push(Line::styled(
+            format!("> ... ({hidden_quote_lines} more lines)"),
+            style.patch(theme.dim),
+        ));
+        *hidden_quote_lines = 0;
     }
 }
 
@@ -157,37 +1745,261 @@ impl MutView for MessagesList {
         // Area inside the borders.
         let area_inner = inner_area(area, 1);
         let mut lines = Vec::new();
+        let mut message_offsets = self.message_offsets.borrow_mut();
+        message_offsets.clear();
         let app_state = self.app_state.upgrade().unwrap();
         let messages = app_state.lock_messages();
-        let mut prev_date: DateTime<Local> = messages
-            .front()
-            .map(|m| m.date.into())
-            .unwrap_or(DateTime::UNIX_EPOCH.into());
-        for message in messages.iter() {
-            let message_date: DateTime<Local> = message.date.into();
-            if message_date.signed_duration_since(prev_date).num_seconds() >= 120 {
-                lines.push(Line::styled(
-                    message_date.format("[%Y-%m-%d %H:%M]").to_string(),
-                    Style::new().fg(DarkGray),
-                ));
+        let time_formatter = app_state.time_formatter();
+        let theme = app_state.theme();
+        let shown_messages: Vec<&Message> = messages
+            .iter()
+            .filter(|message| self.is_shown(message))
+            .collect();
+        let match_count = self.search.as_ref().map(|_| shown_messages.len());
+        let start = self.first_rendered(&shown_messages, area_inner.height);
+        // Messages before `start` are left out, but still decide dates and dividers.
+        let prev_message = start.checked_sub(1).map(|index| shown_messages[index]);
+        let shown_messages = &shown_messages[start..];
+        let mut prev_date: DateTime<Utc> = prev_message
+            .or(shown_messages.first().copied())
+            .map(|m| m.date)
+            .unwrap_or(DateTime::UNIX_EPOCH);
+        let density = app_state.density();
+        let grouping = app_state.grouping();
+        let marked = app_state.marked();
+        let mut emoji_run: Option<EmojiRun> = None;
+        let mut prev_author: Option<&str> = None;
+        let last_seen = app_state.last_seen();
+        let mut prev_id = prev_message.map(|message| message.id);
+        if start == 0 && app_state.has_older_messages() {
+            lines.push(Line::styled(OLDER_MESSAGES_DIVIDER, theme.dim));
+        }
+        let mut old_layouts = self.layouts.take();
+        let mut new_layouts = HashMap::new();
+        // Masked up front, as lines borrow from them.
+        let contents: Vec<_> = shown_messages
+            .iter()
+            .map(|message| app_state.mask(&message.content))
+            .collect();
+        for (&message, content) in shown_messages.iter().zip(&contents) {
+            let message_date = message.date;
+            let mut is_separated = lines.is_empty();
+            if last_seen.is_some() && prev_id == last_seen {
+                lines.push(Line::styled(NEW_MESSAGES_DIVIDER, theme.dim));
+                is_separated = true;
+            }
+            prev_id = Some(message.id);
+            let seconds_since_prev = message_date.signed_duration_since(prev_date).num_seconds();
+            if seconds_since_prev >= grouping.threshold.as_secs() as i64 {
+                let date = match grouping.show_seconds {
+                    true => time_formatter.format_precise(message_date),
+                    false => time_formatter.format(message_date),
+                };
+                lines.push(Line::styled(format!("[{date}]"), theme.dim));
+                is_separated = true;
             }
             prev_date = message_date;
-            lines.push(Line::styled(
-                message.content.as_ref(),
-                Style::new().fg(White),
-            ));
+            let author = message.webhook_name.as_deref();
+            let is_grouped = grouping.group_authors
+                && !is_separated
+                && author.is_some()
+                && author == prev_author;
+            prev_author = author;
+            // Messages are spaced out when cozy, unless a date is between them already or they
+            // are grouped with the previous one.
+            let is_spaced = density == Density::Cozy && !is_separated && !is_grouped;
+            let mut style = match marked.contains(&message.id) {
+                true => theme.text.patch(theme.marked),
+                false => theme.text,
+            };
+            if Some(message.id) == self.selected {
+                style = style.patch(theme.selected);
+            }
+            if is_large_emoji(message) {
+                let is_selected = Some(message.id) == self.selected;
+                // Consecutive identical ones are collapsed into one line with a counter.
+                let run = emoji_run.as_mut().filter(|run| {
+                    run.line_index + 1 == lines.len()
+                        && run.message.content == message.content
+                        && run.message.webhook_name == message.webhook_name
+                });
+                match run {
+                    Some(run) => {
+                        run.count += 1;
+                        run.is_selected |= is_selected;
+                        lines[run.line_index] = run.line(theme);
+                        message_offsets.push((message.id, run.line_index));
+                    }
+                    None => {
+                        if is_spaced {
+                            lines.push(Line::default());
+                        }
+                        let run = EmojiRun {
+                            message,
+                            line_index: lines.len(),
+                            count: 1,
+                            is_selected,
+                        };
+                        message_offsets.push((message.id, run.line_index));
+                        lines.push(run.line(theme));
+                        emoji_run = Some(run);
+                    }
+                }
+                continue;
+            }
+            emoji_run = None;
+            if is_spaced {
+                lines.push(Line::default());
+            }
+            let key = LayoutKey {
+                // Only compact messages are cut to the width of the list.
+                width: (density == Density::Compact).then_some(area_inner.width),
+                style,
+                density,
+                is_grouped,
+                is_masking: app_state.is_masking(),
+                poll: message.poll.clone(),
+                has_link_preview: message.link_preview.is_some(),
+                reactions: app_state.reactions(message.id),
+            };
+            message_offsets.push((message.id, lines.len()));
+            // Disappearing messages count down, and matches change as the search is typed.
+            let is_cacheable = message.expires_at.is_none() && self.search.is_none();
+            let cached = old_layouts
+                .remove(&message.id)
+                .filter(|layout| is_cacheable && layout.key == key);
+            if let Some(layout) = cached {
+                lines.extend(layout.lines.iter().cloned());
+                new_layouts.insert(message.id, layout);
+                continue;
+            }
+            let message_lines = message_lines(
+                message,
+                content,
+                &key,
+                self.search.as_ref(),
+                app_state.server_now(),
+                theme,
+            );
+            if !is_cacheable {
+                lines.extend(message_lines);
+                continue;
+            }
+            let message_lines: Vec<Line<'static>> =
+                message_lines.into_iter().map(into_owned).collect();
+            lines.extend(message_lines.iter().cloned());
+            new_layouts.insert(
+                message.id,
+                CachedLayout {
+                    key,
+                    lines: message_lines,
+                },
+            );
+        }
+        // Layouts of messages that weren't rendered are dropped.
+        *self.layouts.borrow_mut() = new_layouts;
+        let pending_messages = app_state.pending_messages();
+        for pending in &pending_messages {
+            let status = match pending.delivered_as {
+                None => " (sending...)",
+                Some(_) => " (sent)",
+            };
+            let mut pending_lines =
+                content_lines(&pending.content, pending.content_kind, theme.dim, theme);
+            if let Some(last_line) = pending_lines.last_mut() {
+                last_line.push_span(Span::styled(status, theme.dim));
+            }
+            lines.extend(pending_lines);
+        }
+        // Read-only banner and announcement are pinned on top, taking up at most half of the
+        // space.
+        let mut announcement_lines: Vec<Line> = match app_state.read_only() {
+            Some(reason) => vec![Line::styled(
+                format!(" Board is read-only: {reason} "),
+                theme.read_only,
+            )],
+            None => Vec::new(),
+        };
+        if let Some(announcement) = app_state.announcement() {
+            announcement_lines.extend(
+                announcement
+                    .content
+                    .lines()
+                    .map(|line| Line::styled(format!(" {line} "), theme.announcement)),
+            );
         }
-        let extra_lines = lines.len().saturating_sub(usize::from(area_inner.height)) as i16;
+        let announcement_height = u16::min(announcement_lines.len() as u16, area_inner.height / 2);
+        let announcement_area = Rect {
+            height: announcement_height,
+            ..area_inner
+        };
+        let messages_area = Rect {
+            y: area_inner.y + announcement_height,
+            height: area_inner.height - announcement_height,
+            ..area_inner
+        };
+        let extra_lines = lines
+            .len()
+            .saturating_sub(usize::from(messages_area.height)) as i16;
+        self.extra_lines.set(extra_lines);
         let scroll = u16::try_from(self.scroll.saturating_add(extra_lines)).unwrap_or(0);
-        let block = Block::new()
-            .borders(Borders::ALL)
-            .style(Style::new().fg(if is_focused { LightYellow } else { White }))
-            .title("Welcome to Message_Board")
-            .title_style(Style::new().add_modifier(Modifier::BOLD));
-        let pargraph = Paragraph::new(lines.to_vec())
-            .scroll((scroll, 0))
-            .block(block);
-        frame.render_widget(pargraph, area);
+        // Reactions are only fetched for messages in view, including one cut off at the top.
+        let top = usize::from(scroll);
+        let bottom = top + usize::from(messages_area.height);
+        let first_visible = message_offsets
+            .iter()
+            .rposition(|&(_, offset)| offset <= top)
+            .unwrap_or(0);
+        let visible_ids: Vec<MessageId> = message_offsets[first_visible..]
+            .iter()
+            .take_while(|&&(_, offset)| offset < bottom)
+            .map(|&(id, _)| id)
+            .collect();
+        app_state.load_reactions(&visible_ids);
+        let mut title = match app_state.topic() {
+            Some(topic) => format!("Message_Board: {topic}"),
+            None => String::from("Welcome to Message_Board"),
+        };
+        if let Some(filter) = &self.filter {
+            title.push_str(&format!(" (filtered: {})", filter.query()));
+        }
+        if !marked.is_empty() {
+            title.push_str(&format!(" ({} marked)", marked.len()));
+        }
+        let mut block = borders(theme, is_focused)
+            .title(title)
+            .title_style(Style::new().add_modifier(Modifier::BOLD))
+            .title(
+                Line::styled(app_state.api().server_url().to_owned(), theme.dim).right_aligned(),
+            );
+        if let Some(latency) = app_state.latency() {
+            let style = match app_state.is_latency_high(latency) {
+                true => theme.status_error,
+                false => theme.dim,
+            };
+            block = block.title_bottom(
+                Line::styled(format!("{}ms", latency.as_millis()), style).right_aligned(),
+            );
+        }
+        if let (Some(search), Some(match_count)) = (&self.search, match_count) {
+            block = block.title_bottom(search_bar(search, match_count, theme));
+        }
+        if let Some(upload) = app_state.upload() {
+            block = block.title_bottom(Line::styled(upload_progress(&upload), theme.dim));
+        }
+        if let Some(status_error) = app_state.status_error() {
+            block =
+                block.title_bottom(Line::styled(status_error.into_string(), theme.status_error));
+        }
+        frame.render_widget(block, area);
+        frame.render_widget(Paragraph::new(announcement_lines), announcement_area);
+        let pargraph = Paragraph::new(lines.to_vec()).scroll((scroll, 0));
+        frame.render_widget(pargraph, messages_area);
+        if let Some(user) = self.shown_profile {
+            let profile = app_state.profile(user);
+            render_profile(frame, messages_area, user, profile.as_ref(), theme);
+        }
+        render_toasts(frame, area_inner, app_state.toasts(), theme);
     }
 
     fn is_focusable(&self) -> bool {
@@ -199,6 +2011,10 @@ impl MutView for MessagesList {
             return;
         }
 
+        if self.on_search_key_event(key_event) {
+            return;
+        }
+
         // TODO: limit scrolling.
         use KeyCode::*;
         match (key_event.modifiers, key_event.code) {
@@ -208,11 +2024,246 @@ impl MutView for MessagesList {
             (KeyModifiers::NONE, Down) | (KeyModifiers::CONTROL, Char('n')) => {
                 self.scroll += 1;
             }
+            (KeyModifiers::NONE, Char('k')) => self.move_selection(-1),
+            (KeyModifiers::NONE, Char('j')) => self.move_selection(1),
+            (KeyModifiers::NONE, Char('r')) => self.quote_selected(),
+            (KeyModifiers::NONE, Char('f')) => self.pending_filter_prompt = true,
+            (KeyModifiers::NONE, Char(' ')) => self.toggle_marked(),
+            (KeyModifiers::NONE | KeyModifiers::SHIFT, Char('+')) => {
+                self.react_selected(QUICK_REACTION)
+            }
+            (KeyModifiers::NONE, Char('p')) => self.toggle_profile(),
+            (KeyModifiers::NONE, Char('c')) => self.copy_marked(),
+            (KeyModifiers::NONE, Char('x')) => self.delete_marked(),
+            (KeyModifiers::NONE, Char('/')) => self.set_search(Some(Search::default())),
+            (KeyModifiers::NONE, Char('d')) => self.set_density(None),
+            (KeyModifiers::NONE, Char('g')) => self.jump_to_end(true),
+            (KeyModifiers::SHIFT, Char('G')) => self.jump_to_end(false),
+            (KeyModifiers::ALT, Left) => self.jump_back(),
+            (KeyModifiers::ALT, Right) => self.jump_forward(),
+            (KeyModifiers::NONE, Char(char @ '1'..='9')) => {
+                self.vote_selected(char as u32 - '1' as u32);
+            }
             (_, _) => (),
         }
     }
 }
 
+/// Split the spans of `line` around matches of `search`, patching `style` onto the matches.
+/// Matches are found within each span, not across them.
+fn highlight_matches(line: &mut Line, search: &Search, style: Style) {
+    let mut spans = Vec::with_capacity(line.spans.len());
+    for span in line.spans.drain(..) {
+        let ranges: Vec<_> = search
+            .find(&span.content)
+            .into_iter()
+            .filter(|range| !range.is_empty())
+            .collect();
+        if ranges.is_empty() {
+            spans.push(span);
+            continue;
+        }
+        let mut start = 0;
+        for range in ranges {
+            if range.start > start {
+                spans.push(Span::styled(
+                    span.content[start..range.start].to_owned(),
+                    span.style,
+                ));
+            }
+            spans.push(Span::styled(
+                span.content[range.clone()].to_owned(),
+                span.style.patch(style),
+            ));
+            start = range.end;
+        }
+        if start < span.content.len() {
+            spans.push(Span::styled(span.content[start..].to_owned(), span.style));
+        }
+    }
+    line.spans = spans;
+}
+
+/// The search and its match count, followed by its options and the keys toggling them.
+fn search_bar<'a>(search: &'a Search, match_count: usize, theme: &Theme) -> Line<'a> {
+    let matches = match match_count {
+        1 => String::from("1 match"),
+        count => format!("{count} matches"),
+    };
+    let on_off = |is_on: bool| if is_on { "on" } else { "off" };
+    let mut spans = vec![
+        Span::styled(format!("/{}", search.text()), theme.text),
+        Span::styled(format!(" ({matches})"), theme.dim),
+    ];
+    if search.is_invalid() {
+        spans.push(Span::styled(" invalid regex", theme.status_error));
+    }
+    spans.push(Span::styled(
+        format!(
+            " regex: {} <ALT + R> case: {} <ALT + C> author: {} <ALT + A> older: <ENTER>",
+            on_off(search.is_regex()),
+            on_off(search.is_case_sensitive()),
+            search.author().unwrap_or("any"),
+        ),
+        theme.dim,
+    ));
+    Line::from(spans)
+}
+
+/// Format seconds like `1h 2m`, `3m 4s` or `5s`.
+fn format_duration(seconds: u64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    match (hours, minutes) {
+        (0, 0) => format!("{seconds}s"),
+        (0, _) => format!("{minutes}m {seconds}s"),
+        (_, _) => format!("{hours}h {minutes}m"),
+    }
+}
+
+/// Width of the progress bar of uploads, in cells.
+const UPLOAD_BAR_WIDTH: usize = 10;
+
+/// `Uploading NAME [█████░░░░░] 50%`.
+fn upload_progress(upload: &Upload) -> String {
+    let sent = upload.sent.load(Ordering::Relaxed).min(upload.size);
+    let fraction = match upload.size {
+        0 => 1.0,
+        size => sent as f64 / size as f64,
+    };
+    let filled = (fraction * UPLOAD_BAR_WIDTH as f64).round() as usize;
+    format!(
+        "Uploading {} [{}{}] {:.0}%",
+        upload.name,
+        "█".repeat(filled),
+        "░".repeat(UPLOAD_BAR_WIDTH - filled),
+        fraction * 100.0
+    )
+}
+
+/// Is the message long enough to ask before sending it, e.g. an accidental paste?
+fn is_large_message(message: &str) -> bool {
+    message.lines().count() > LARGE_MESSAGE_LINES || message.chars().count() > LARGE_MESSAGE_CHARS
+}
+
+/// `84-line message` or `2000-character message`.
+fn describe_size(message: &str) -> String {
+    match message.lines().count() {
+        lines if lines > LARGE_MESSAGE_LINES => format!("{lines}-line message"),
+        _ => format!("{}-character message", message.chars().count()),
+    }
+}
+
+/// Newlines shown as `⏎`, for displaying multi-line text in one line.
+fn single_line(text: &str) -> String {
+    text.replace('\n', "⏎")
+}
+
+/// Width of the profile popup, borders included.
+const PROFILE_POPUP_WIDTH: u16 = 40;
+
+/// Render a popup with `profile` in the middle of `area`, `None` while it's being fetched.
+fn render_profile(
+    frame: &mut Frame,
+    area: Rect,
+    user: u64,
+    profile: Option<&Profile>,
+    theme: &Theme,
+) {
+    let mut lines = Vec::new();
+    match profile {
+        None => lines.push(Line::styled("Loading ...", theme.dim)),
+        Some(profile) if profile.is_empty() => {
+            lines.push(Line::styled("No profile", theme.dim));
+        }
+        Some(profile) => {
+            let name = profile.display_name.as_deref().unwrap_or("Anonymous");
+            let mut title = vec![Span::styled(
+                name.to_owned(),
+                theme.text.add_modifier(Modifier::BOLD),
+            )];
+            if let Some(avatar) = &profile.avatar {
+                title.insert(0, Span::styled(format!("{avatar} "), theme.text));
+            }
+            if let Some(pronouns) = &profile.pronouns {
+                title.push(Span::styled(format!(" ({pronouns})"), theme.dim));
+            }
+            lines.push(Line::from(title));
+            if let Some(bio) = &profile.bio {
+                lines.push(Line::default());
+                let wrapped = word_wrap(bio, usize::from(PROFILE_POPUP_WIDTH - 2));
+                lines.extend(
+                    wrapped
+                        .into_iter()
+                        .map(|line| Line::styled(line, theme.text)),
+                );
+            }
+        }
+    }
+    let width = u16::min(PROFILE_POPUP_WIDTH, area.width);
+    let height = u16::min(lines.len() as u16 + 2, area.height);
+    let popup_area = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(borders(theme, false).title(format!("Profile of {user} (<P> to close)"))),
+        popup_area,
+    );
+}
+
+/// Break `text` into lines at most `width` cells wide, between words where possible.
+fn word_wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let separator = usize::from(!line.is_empty());
+            if line.width() + separator + word.width() > width && !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            } else if !line.is_empty() {
+                line.push(' ');
+            }
+            // Words too long for a line of their own are broken anywhere.
+            for char in word.chars() {
+                if line.width() + char.width().unwrap_or(0) > width {
+                    lines.push(std::mem::take(&mut line));
+                }
+                line.push(char);
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Render toasts over the top rows of `area`, newest on top.
+fn render_toasts(frame: &mut Frame, area: Rect, toasts: &Toasts, theme: &Theme) {
+    let visible = toasts.visible();
+    for (i, toast) in visible.iter().rev().take(MAX_VISIBLE_TOASTS).enumerate() {
+        if i as u16 >= area.height {
+            break;
+        }
+        let toast_area = Rect {
+            y: area.y + i as u16,
+            height: 1,
+            ..area
+        };
+        let style = match toast.kind {
+            ToastKind::Info => theme.toast_info,
+            ToastKind::Error => theme.toast_error,
+        };
+        frame.render_widget(Clear, toast_area);
+        frame.render_widget(
+            Paragraph::new(Line::styled(format!(" {} ", toast.message), style)),
+            toast_area,
+        );
+    }
+}
+
 const fn inner_area(outer_area: Rect, border_width: u16) -> Rect {
     Rect {
         x: outer_area.x + border_width,
@@ -222,32 +2273,154 @@ const fn inner_area(outer_area: Rect, border_width: u16) -> Rect {
     }
 }
 
-pub fn event_loop<B: Backend>(
+/// The domtui-based frontend.
+pub struct Tui<'a, B: Backend> {
+    terminal: &'a mut Terminal<B>,
+}
+
+impl<'a, B: Backend> Tui<'a, B> {
+    pub fn new(terminal: &'a mut Terminal<B>) -> Self {
+        Self { terminal }
+    }
+}
+
+impl<B: Backend> Frontend for Tui<'_, B> {
+    fn run(&mut self, event_source: &mut impl EventSource, mut servers: Servers) -> DynResult<()> {
+        loop {
+            let app_state = Arc::clone(servers.current());
+            match event_loop(self.terminal, event_source, &app_state, &servers)? {
+                Some(index) => servers.select(index),
+                None => return Ok(()),
+            }
+        }
+    }
+}
+
+/// Runs until the user quits, returning `None`, or switches to another server, returning its
+/// index.
+fn event_loop<B: Backend>(
     terminal: &mut Terminal<B>,
-    app_state: Arc<AppState>,
-) -> DynResult<()> {
+    event_source: &mut impl EventSource,
+    app_state: &AppState,
+    servers: &Servers,
+) -> DynResult<Option<usize>> {
     let mut ui_state = app_state.lock_ui_state();
 
     'event_loop: loop {
         match &ui_state.current_screen {
-            Screen::MainScreen => ui_state.main_screen.render(terminal)?,
+            Screen::MainScreen => {
+                app_state.mark_read();
+                ui_state.main_screen.render(terminal)?;
+                // Offsets of messages are only known after rendering.
+                if ui_state.forward_pending_anchor() {
+                    continue 'event_loop;
+                }
+            }
             Screen::HelpScreen => {
                 let paragraph = domtui::views::Paragraph::new(include_str!("help_page_text.txt"))
-                    .block(borders(White).title("HELP (<ESC> TO GO BACK)"));
+                    .block(borders(app_state.theme(), false).title("HELP (<ESC> TO GO BACK)"));
+                domtui::render(terminal, paragraph)?
+            }
+            Screen::ErrorLogScreen => {
+                let mut text = String::new();
+                for (date, error) in ui_state.toasts.error_log() {
+                    let date = app_state.time_formatter().format_precise(date);
+                    text.push_str(&format!("[{date}] {error}\n"));
+                }
+                if text.is_empty() {
+                    text.push_str("No errors so far");
+                }
+                let paragraph = domtui::views::Paragraph::new(text)
+                    .block(borders(app_state.theme(), false).title("ERROR LOG (<ESC> TO GO BACK)"));
+                domtui::render(terminal, paragraph)?
+            }
+            Screen::StatsScreen => {
+                if app_state.should_refresh_board_activity() {
+                    let app_state = Arc::clone(servers.current());
+                    tokio::spawn(async move { app_state.fetch_board_activity().await });
+                }
+                terminal.draw(|frame| render_stats(frame, app_state))?;
+            }
+            Screen::ServerPicker {
+                selected,
+                show_archived,
+            } => {
+                let selected = *selected;
+                let theme = app_state.theme();
+                let mut lines = Vec::new();
+                let listed = servers.listed(*show_archived);
+                for (i, server) in servers.iter().enumerate() {
+                    if !listed.contains(&i) {
+                        continue;
+                    }
+                    let marker = if i == selected { ">" } else { " " };
+                    let is_current = i == servers.current_index();
+                    let current = if is_current { " (current)" } else { "" };
+                    // The UI state of the current server is already locked.
+                    let has_draft = match is_current {
+                        true => ui_state.has_draft(),
+                        false => server.lock_ui_state().has_draft(),
+                    };
+                    let draft = if has_draft { " •" } else { "" };
+                    let unread = match server.unread() {
+                        Unread { messages: 0, .. } => String::new(),
+                        Unread {
+                            messages,
+                            mentions: 0,
+                        } => format!(" ({messages} unread)"),
+                        Unread { messages, mentions } => {
+                            format!(" ({messages} unread, @{mentions})")
+                        }
+                    };
+                    let server_url = server.api().server_url();
+                    // Greyed out, as they can't be posted to.
+                    let (status, style) = match (server.is_archived(), server.read_only()) {
+                        (true, _) => (" (archived)", theme.dim),
+                        (false, Some(_)) => (" (frozen)", theme.dim),
+                        (false, None) => ("", theme.text),
+                    };
+                    let line = format!("{marker} {server_url}{status}{unread}{draft}{current}");
+                    lines.push(Line::styled(line, style));
+                }
+                let title = match *show_archived {
+                    true => "SERVERS (<ENTER> TO SWITCH, <A> TO HIDE ARCHIVED, <ESC> TO GO BACK)",
+                    false => "SERVERS (<ENTER> TO SWITCH, <A> TO SHOW ARCHIVED, <ESC> TO GO BACK)",
+                };
+                let paragraph = domtui::views::Paragraph::new(lines)
+                    .block(borders(app_state.theme(), false).title(title));
                 domtui::render(terminal, paragraph)?
             }
         }
-        if !event::poll(std::time::Duration::from_millis(100))? {
+        if !event_source.poll(std::time::Duration::from_millis(100))? {
             continue 'event_loop;
         }
-        match event::read().unwrap() {
+        let event = event_source.read()?;
+        app_state.record_user_activity();
+        match event {
             Event::Key(KeyEvent {
                 code: KeyCode::Char('q'),
                 modifiers: KeyModifiers::CONTROL,
                 kind: KeyEventKind::Press,
                 state: _,
             }) => {
-                break 'event_loop Ok(());
+                break 'event_loop Ok(None);
+            }
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('o'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            }) => {
+                match &mut ui_state.current_screen {
+                    screen @ Screen::ServerPicker { .. } => *screen = Screen::MainScreen,
+                    screen => {
+                        *screen = Screen::ServerPicker {
+                            selected: servers.current_index(),
+                            show_archived: false,
+                        }
+                    }
+                }
+                continue 'event_loop;
             }
             Event::Key(KeyEvent {
                 code: KeyCode::Char('h'),
@@ -256,8 +2429,21 @@ pub fn event_loop<B: Backend>(
                 state: _,
             }) => {
                 match &mut ui_state.current_screen {
-                    screen @ Screen::MainScreen => *screen = Screen::HelpScreen,
                     screen @ Screen::HelpScreen => *screen = Screen::MainScreen,
+                    screen => *screen = Screen::HelpScreen,
+                }
+                continue 'event_loop;
+            }
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('l'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            }) => {
+                ui_state.toasts.dismiss_all();
+                match &mut ui_state.current_screen {
+                    screen @ Screen::ErrorLogScreen => *screen = Screen::MainScreen,
+                    screen => *screen = Screen::ErrorLogScreen,
                 }
                 continue 'event_loop;
             }
@@ -267,16 +2453,68 @@ pub fn event_loop<B: Backend>(
                 kind: KeyEventKind::Press,
                 state: _,
             }) => {
-                match &mut ui_state.current_screen {
-                    Screen::MainScreen => (),
-                    screen @ Screen::HelpScreen => *screen = Screen::MainScreen,
+                if !matches!(ui_state.current_screen, Screen::MainScreen) {
+                    ui_state.current_screen = Screen::MainScreen;
+                } else if !ui_state.close_profile()
+                    && !ui_state.close_search()
+                    && !app_state.clear_marked()
+                {
+                    ui_state.toasts.dismiss_all();
                 }
                 continue 'event_loop;
             }
             event => {
                 match &mut ui_state.current_screen {
-                    Screen::MainScreen => ui_state.main_screen.handle_event(event),
-                    Screen::HelpScreen => (),
+                    Screen::MainScreen => {
+                        if let Event::Mouse(mouse_event) = event {
+                            ui_state.forward_mouse_event(mouse_event);
+                        }
+                        ui_state.main_screen.handle_event(event);
+                        ui_state.forward_pending_quote();
+                        ui_state.forward_filter_prompt();
+                        ui_state.forward_pending_filter();
+                        ui_state.forward_pending_density();
+                        ui_state.forward_pending_reaction();
+                        ui_state.forward_pending_forward(servers);
+                        ui_state.forward_pending_screen();
+                    }
+                    Screen::ServerPicker {
+                        selected,
+                        show_archived,
+                    } => {
+                        let Event::Key(key_event) = event else {
+                            continue 'event_loop;
+                        };
+                        if key_event.kind != KeyEventKind::Press {
+                            continue 'event_loop;
+                        }
+                        let listed = servers.listed(*show_archived);
+                        match key_event.code {
+                            KeyCode::Up | KeyCode::Char('k') => {
+                                if let Some(&i) = listed.iter().rev().find(|&&i| i < *selected) {
+                                    *selected = i;
+                                }
+                            }
+                            KeyCode::Down | KeyCode::Char('j') => {
+                                if let Some(&i) = listed.iter().find(|&&i| i > *selected) {
+                                    *selected = i;
+                                }
+                            }
+                            KeyCode::Char('a') => {
+                                *show_archived = !*show_archived;
+                                if !servers.listed(*show_archived).contains(selected) {
+                                    *selected = servers.current_index();
+                                }
+                            }
+                            KeyCode::Enter => {
+                                let index = *selected;
+                                ui_state.current_screen = Screen::MainScreen;
+                                break 'event_loop Ok(Some(index));
+                            }
+                            _ => (),
+                        }
+                    }
+                    _ => (),
                 }
                 continue 'event_loop;
             }
@@ -284,8 +2522,73 @@ pub fn event_loop<B: Backend>(
     }
 }
 
-fn borders(fg: Color) -> Block<'static> {
-    Block::new()
-        .borders(Borders::ALL)
-        .style(Style::new().fg(fg))
+/// Local usage stats on top, and the activity of the board in the last day below.
+fn render_stats(frame: &mut Frame, app_state: &AppState) {
+    let theme = app_state.theme();
+    let block = borders(theme, false).title("STATS (<ESC> TO GO BACK)");
+    let area = block.inner(frame.area());
+    frame.render_widget(block, frame.area());
+    let bandwidth = app_state.api().bandwidth();
+    let timings = app_state.api().timings();
+    let local_stats = match app_state.stats() {
+        Some(stats) => format!("{stats}\n{bandwidth}\n{timings}"),
+        None => format!(
+            "{bandwidth}\n{timings}\nLocal stats are disabled, restart with --stats to enable them"
+        ),
+    };
+    let local_stats_height = local_stats.lines().count() as u16 + 1;
+    let [local_stats_area, activity_area] =
+        Layout::vertical([Constraint::Length(local_stats_height), Constraint::Min(0)]).areas(area);
+    frame.render_widget(Paragraph::new(local_stats), local_stats_area);
+    let activity_block = Block::new()
+        .borders(Borders::TOP)
+        .style(theme.border)
+        .title("Messages per hour, last 24 hours");
+    let board_activity = app_state.board_activity();
+    let Some(messages_per_hour) = board_activity.messages_per_hour else {
+        let text = match board_activity.error {
+            Some(error) => format!("Can't fetch the activity of the board: {error}"),
+            None => String::from("Fetching the activity of the board..."),
+        };
+        let paragraph = Paragraph::new(Line::styled(text, theme.dim)).block(activity_block);
+        frame.render_widget(paragraph, activity_area);
+        return;
+    };
+    let now = app_state.server_now();
+    let hours = messages_per_hour.len();
+    let bars: Vec<Bar> = messages_per_hour
+        .iter()
+        .enumerate()
+        .map(|(i, &count)| {
+            let hours_ago = (hours - 1 - i) as i64;
+            let hour = now - chrono::Duration::hours(hours_ago);
+            Bar::default()
+                .value(count)
+                .label(Line::from(app_state.time_formatter().format_hour(hour)))
+                .style(theme.chart_bar)
+        })
+        .collect();
+    // Labels take 2 columns, bars are 1 column apart.
+    let bar_width = (activity_area.width / hours.max(1) as u16)
+        .saturating_sub(1)
+        .max(2);
+    let chart = BarChart::default()
+        .block(activity_block)
+        .data(BarGroup::default().bars(&bars))
+        .bar_width(bar_width)
+        .bar_gap(1)
+        .value_style(theme.text.add_modifier(Modifier::REVERSED))
+        .label_style(theme.dim);
+    frame.render_widget(chart, activity_area);
+}
+
+fn borders(theme: &Theme, is_focused: bool) -> Block<'static> {
+    let block = Block::new().borders(Borders::ALL);
+    if is_focused {
+        block
+            .style(theme.focused_border)
+            .border_type(theme.focused_border_type)
+    } else {
+        block.style(theme.border)
+    }
 }
@@ -0,0 +1,315 @@
+//! Linear, plain text frontend for screen readers.
+//! Everything is printed as labeled lines in the normal screen buffer, with no box-drawing
+//! characters or cursor movement. The terminal is left in cooked mode, so typing and line
+//! editing are handled by the terminal itself and input arrives a line at a time.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Write},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use interface::{AttachmentKind, ContentKind, Message, MessageId, Poll};
+use ratatui::crossterm::event::{Event, KeyCode, KeyEventKind};
+
+use crate::{
+    commands::{self, Command},
+    frontend::{EventSource, Frontend},
+    servers::Servers,
+    state::AppState,
+    toast::ToastKind,
+    utils::{format_size, DynResult},
+};
+
+const GREETING: &str = "Connected. Type a message and press Enter to send it. \
+    Type /help for commands, /quit to exit.";
+
+const HELP: &str = "Commands: \
+    /quit to exit. \
+    /topic to read the topic. \
+    /stats to read data sent and received this session, and local usage stats. \
+    /ping to measure round-trip time to the server. \
+    /mask to show or hide masked words, see --mask-words. \
+    /batch [COUNT] and /interval [MILLISECONDS] to show or tune fetching. \
+    /logout to forget the stored session token of the server. \
+    /newtoken to replace it with a new one, /deleteaccount yes to delete the session. \
+    /export PATH to save the loaded messages to a text, Markdown or JSON file. \
+    /poll QUESTION | OPTION 1 | OPTION 2 to start a poll. \
+    /md TEXT and /code CODE to send Markdown or code. \
+    /attach PATH to upload a file to send with the next message. \
+    /record [SECONDS] to record a voice note the same way, /play to play the latest one. \
+    /freeze [REASON] and /unfreeze to make the board read-only or writable, for admins. \
+    /archive and /unarchive to archive the board or bring it back, for admins. \
+    /members [add|remove USER] to list or change the members of a private board, for admins. \
+    /profile name|pronouns|avatar|bio [VALUE] to set or clear a field of your profile. \
+    /block [USER] and /unblock USER to hide or show messages by a session, or list blocks. \
+    Start a message with // to send a literal slash.";
+
+#[derive(Debug, Default)]
+pub struct Plain {
+    /// Messages that have already been read out, as they were then.
+    printed_messages: HashMap<MessageId, PrintedMessage>,
+    topic: Option<Box<str>>,
+    announcement: Option<Box<str>>,
+    read_only: Option<Box<str>>,
+    /// When the last toast that has been read out was shown.
+    last_toast_at: Option<Instant>,
+    line: String,
+}
+
+/// What of a message is kept to tell how it changed since it was read out.
+#[derive(Debug)]
+struct PrintedMessage {
+    date: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+    poll: Option<Poll>,
+}
+
+impl PrintedMessage {
+    fn new(message: &Message) -> Self {
+        Self {
+            date: message.date,
+            expires_at: message.expires_at,
+            poll: message.poll.clone(),
+        }
+    }
+}
+
+impl Plain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read out everything that changed since the last call.
+    fn print_updates(&mut self, app_state: &AppState, out: &mut impl Write) -> io::Result<()> {
+        let time_formatter = app_state.time_formatter();
+        let topic = app_state.topic();
+        if topic != self.topic {
+            match &topic {
+                Some(topic) => writeln!(out, "Topic changed to: {topic}")?,
+                None => writeln!(out, "Topic cleared.")?,
+            }
+            self.topic = topic;
+        }
+        let announcement = app_state
+            .announcement()
+            .map(|announcement| announcement.content);
+        if announcement != self.announcement {
+            if let Some(announcement) = &announcement {
+                writeln!(out, "Announcement: {announcement}")?;
+            }
+            self.announcement = announcement;
+        }
+        let read_only = app_state.read_only();
+        if read_only != self.read_only {
+            match &read_only {
+                Some(reason) => writeln!(out, "Board is read-only for maintenance: {reason}")?,
+                None => writeln!(out, "Board is no longer read-only.")?,
+            }
+            self.read_only = read_only;
+        }
+        let messages = app_state.lock_messages();
+        for message in messages.iter() {
+            let date = time_formatter.format(message.date);
+            let content = app_state.mask(&message.content);
+            match self.printed_messages.get_mut(&message.id) {
+                None => {
+                    write_message(message, &content, &date, out)?;
+                    let printed = PrintedMessage::new(message);
+                    self.printed_messages.insert(message.id, printed);
+                }
+                Some(printed) if printed.poll != message.poll => {
+                    if let Some(poll) = &message.poll {
+                        writeln!(out, "Poll at {date} updated: {content}")?;
+                        write_poll_options(poll, out)?;
+                    }
+                    printed.poll = message.poll.clone();
+                }
+                Some(_) => (),
+            }
+        }
+        // Messages older than the oldest loaded one were dropped to stay under
+        // `Config::max_messages`, and are let go silently.
+        let oldest = messages.front().map(|message| message.date);
+        let loaded: HashSet<MessageId> = messages.iter().map(|message| message.id).collect();
+        drop(messages);
+        let now = Utc::now();
+        let mut removed = Vec::new();
+        self.printed_messages.retain(|id, printed| {
+            let is_removed = !loaded.contains(id);
+            if is_removed {
+                removed.push((printed.date, printed.expires_at));
+            }
+            !is_removed
+        });
+        removed.sort_unstable();
+        for (date, expires_at) in removed {
+            let formatted_date = time_formatter.format(date);
+            if expires_at.is_some_and(|expires_at| expires_at <= now) {
+                writeln!(out, "Message from {formatted_date} expired.")?;
+            } else if oldest.is_some_and(|oldest| date >= oldest) {
+                writeln!(out, "Message from {formatted_date} was deleted.")?;
+            }
+        }
+        for toast in app_state.toasts().visible() {
+            if self
+                .last_toast_at
+                .is_some_and(|last| toast.shown_at <= last)
+            {
+                continue;
+            }
+            self.last_toast_at = Some(toast.shown_at);
+            match toast.kind {
+                ToastKind::Info => writeln!(out, "Info: {}", toast.message)?,
+                ToastKind::Error => writeln!(out, "Error: {}", toast.message)?,
+            }
+        }
+        out.flush()
+    }
+
+    /// Returns `false` if the user wants to quit.
+    fn handle_line(&mut self, app_state: &Arc<AppState>, out: &mut impl Write) -> io::Result<bool> {
+        let mut line = std::mem::take(&mut self.line);
+        match line.trim() {
+            "" => return Ok(true),
+            "/quit" => return Ok(false),
+            "/help" => writeln!(out, "{HELP}")?,
+            "/topic" => match app_state.topic() {
+                Some(topic) => writeln!(out, "Topic: {topic}")?,
+                None => writeln!(out, "There is no topic.")?,
+            },
+            _ => match commands::parse(&line) {
+                Some(Ok(Command::Stats)) => {
+                    writeln!(out, "{}", app_state.api().bandwidth())?;
+                    writeln!(out, "{}", app_state.api().timings())?;
+                    match app_state.stats() {
+                        Some(stats) => writeln!(out, "{stats}")?,
+                        None => writeln!(
+                            out,
+                            "Stats are disabled, restart with --stats to enable them."
+                        )?,
+                    }
+                }
+                Some(Ok(Command::Filter(_))) => {
+                    writeln!(out, "Filtering is only supported by the TUI.")?
+                }
+                Some(Ok(Command::Density(_))) => {
+                    writeln!(out, "Display density is only supported by the TUI.")?
+                }
+                Some(Ok(Command::React { .. })) => {
+                    writeln!(out, "Reacting is only supported by the TUI.")?
+                }
+                Some(Ok(Command::Forward { .. })) => {
+                    writeln!(out, "Forwarding is only supported by the TUI.")?
+                }
+                Some(Ok(command)) => commands::execute(command, Arc::clone(app_state)),
+                Some(Err(error)) => writeln!(out, "Error: {error}")?,
+                None => {
+                    if line.starts_with("//") {
+                        line.remove(0);
+                    }
+                    let app_state = Arc::clone(app_state);
+                    tokio::spawn(async move {
+                        let send_result = app_state
+                            .send_message(line.into(), ContentKind::PlainText, None)
+                            .await;
+                        if let Err(e) = send_result {
+                            log::error!("Error sending message: {e}");
+                            app_state.toast_error("Failed to send message", &e);
+                        }
+                    });
+                }
+            },
+        }
+        out.flush()?;
+        Ok(true)
+    }
+}
+
+/// `content` is that of `message`, masked.
+fn write_message(
+    message: &Message,
+    content: &str,
+    date: &str,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    match &message.poll {
+        Some(poll) => {
+            writeln!(out, "New poll at {date}: {content}")?;
+            write_poll_options(poll, out)?;
+        }
+        None if message.content_kind == ContentKind::System => {
+            writeln!(out, "Notice at {date}: {content}")?;
+        }
+        None => match &message.webhook_name {
+            Some(webhook_name) => {
+                writeln!(out, "New message from {webhook_name} at {date}: {content}")?
+            }
+            None => writeln!(out, "New message at {date}: {content}")?,
+        },
+    }
+    if let Some(link_preview) = &message.link_preview {
+        writeln!(out, "Link: {}", link_preview.title)?;
+    }
+    for attachment in &message.attachments {
+        let kind = match attachment.kind() {
+            AttachmentKind::Audio => "Voice note",
+            AttachmentKind::File => "Attachment",
+        };
+        writeln!(
+            out,
+            "{kind}: {}, {}",
+            attachment.name,
+            format_size(attachment.size)
+        )?;
+    }
+    Ok(())
+}
+
+fn write_poll_options(poll: &Poll, out: &mut impl Write) -> io::Result<()> {
+    for (i, option) in poll.options.iter().enumerate() {
+        writeln!(
+            out,
+            "Option {}: {}, {} votes",
+            i + 1,
+            option.text,
+            option.votes
+        )?;
+    }
+    Ok(())
+}
+
+impl Frontend for Plain {
+    fn run(&mut self, event_source: &mut impl EventSource, servers: Servers) -> DynResult<()> {
+        // Switching servers isn't supported in plain mode, only the first server is read out.
+        let app_state = Arc::clone(servers.current());
+        let mut stdout = io::stdout().lock();
+        writeln!(stdout, "{GREETING}")?;
+        // Messages fetched before startup are read out as well, so the user has some context.
+        loop {
+            self.print_updates(&app_state, &mut stdout)?;
+            if !event_source.poll(Duration::from_millis(100))? {
+                continue;
+            }
+            let Event::Key(key) = event_source.read()? else {
+                continue;
+            };
+            app_state.record_user_activity();
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char(char) => self.line.push(char),
+                KeyCode::Enter => {
+                    if !self.handle_line(&app_state, &mut stdout)? {
+                        break;
+                    }
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,344 @@
+//! Proxies that connections to servers are tunneled through, for networks that don't let them
+//! out directly. HTTP proxies are asked to `CONNECT`, SOCKS5 proxies to connect (RFC 1928), so
+//! that whatever runs over the connection afterwards, including websocket upgrades and TLS, goes
+//! through untouched. Plain HTTP requests are an exception, as many HTTP proxies only tunnel to
+//! port 443: they are sent to the proxy in absolute form instead, see
+//! `Connector::forwarding_proxy`.
+
+use std::{
+    env,
+    fmt::{self, Display},
+    io,
+    net::IpAddr,
+};
+
+use base64::Engine as _;
+use hyper::Uri;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+/// Longest response to a `CONNECT` accepted, headers included.
+const MAX_CONNECT_RESPONSE_LEN: usize = 8 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid proxy {0:?}, expected `http://[USER:PASSWORD@]HOST[:PORT]` or `socks5://...`")]
+pub struct InvalidProxy(pub String);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyKind {
+    Http,
+    Socks5,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub user: Box<str>,
+    pub password: Box<str>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Proxy {
+    pub kind: ProxyKind,
+    pub host: Box<str>,
+    pub port: u16,
+    pub credentials: Option<Credentials>,
+}
+
+impl Proxy {
+    /// Parse a proxy URL as in the `*_PROXY` env vars: `http://` or `socks5://`, also
+    /// `socks5h://` as host names are always resolved by the proxy, with optional credentials
+    /// and port.
+    /// A URL without scheme is an HTTP proxy. Ports default to 80 for HTTP and 1080 for SOCKS5.
+    pub fn from_url(url: &str) -> Result<Self, InvalidProxy> {
+        let invalid = || InvalidProxy(url.to_owned());
+        let (kind, rest) = match url.split_once("://") {
+            None => (ProxyKind::Http, url),
+            Some(("http", rest)) => (ProxyKind::Http, rest),
+            Some(("socks5" | "socks5h", rest)) => (ProxyKind::Socks5, rest),
+            Some(_) => return Err(invalid()),
+        };
+        let authority = rest.split('/').next().unwrap_or_default();
+        let (credentials, host_and_port) = match authority.rsplit_once('@') {
+            Some((user_info, host_and_port)) => {
+                let (user, password) = user_info.split_once(':').unwrap_or((user_info, ""));
+                // Percent-encoded in URLs, to allow e.g. `@` and `:`.
+                let credentials = Credentials {
+                    user: percent_decode(user).ok_or_else(invalid)?.into(),
+                    password: percent_decode(password).ok_or_else(invalid)?.into(),
+                };
+                (Some(credentials), host_and_port)
+            }
+            None => (None, authority),
+        };
+        let default_port = match kind {
+            ProxyKind::Http => 80,
+            ProxyKind::Socks5 => 1080,
+        };
+        // Brackets around IPv6 literals are kept, so that the port is split after them.
+        let (host, port) = match host_and_port.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => {
+                (host, port.parse().map_err(|_| invalid())?)
+            }
+            _ => (host_and_port, default_port),
+        };
+        let host = host
+            .strip_prefix('[')
+            .and_then(|host| host.strip_suffix(']'))
+            .unwrap_or(host);
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            kind,
+            host: host.into(),
+            port,
+            credentials,
+        })
+    }
+
+    /// Value of a `Proxy-Authorization` header, if there are credentials.
+    pub fn authorization(&self) -> Option<String> {
+        let Credentials { user, password } = self.credentials.as_ref()?;
+        let encoded =
+            base64::engine::general_purpose::STANDARD.encode(format!("{user}:{password}"));
+        Some(format!("Basic {encoded}"))
+    }
+}
+
+/// `None` if an escape is malformed, or the result isn't UTF-8.
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(after.get(..2)?).ok()?;
+            if !hex.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+                return None;
+            }
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &after[2..];
+        } else {
+            bytes.push(byte);
+            rest = after;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Without the credentials, for logs and the doctor.
+impl Display for Proxy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scheme = match self.kind {
+            ProxyKind::Http => "http",
+            ProxyKind::Socks5 => "socks5",
+        };
+        match self.host.contains(':') {
+            true => write!(f, "{scheme}://[{}]:{}", self.host, self.port),
+            false => write!(f, "{scheme}://{}:{}", self.host, self.port),
+        }
+    }
+}
+
+/// Which proxy to use, if any.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ProxyConfig {
+    /// From the env vars, as most command line tools do: `https_proxy` or `http_proxy` as fits
+    /// the server URL, falling back to `all_proxy`, upper case names also accepted. Servers
+    /// listed in `no_proxy` are connected to directly.
+    #[default]
+    FromEnv,
+    Direct,
+    Proxy(Proxy),
+}
+
+impl ProxyConfig {
+    /// Proxy to connect to the server at `server_url` through.
+    pub fn proxy_for(&self, server_url: &str) -> Option<Proxy> {
+        match self {
+            Self::FromEnv => proxy_from_env(server_url),
+            Self::Direct => None,
+            Self::Proxy(proxy) => Some(proxy.clone()),
+        }
+    }
+}
+
+/// Value of env var `name`, in lower case or upper case, if set and not empty.
+fn env_var(name: &str) -> Option<String> {
+    [name.to_lowercase(), name.to_uppercase()]
+        .into_iter()
+        .find_map(|name| env::var(name).ok().filter(|value| !value.is_empty()))
+}
+
+fn proxy_from_env(server_url: &str) -> Option<Proxy> {
+    let url: Uri = server_url.parse().ok()?;
+    let host = url.host()?.trim_start_matches('[').trim_end_matches(']');
+    if env_var("no_proxy").is_some_and(|no_proxy| is_excluded(host, &no_proxy)) {
+        return None;
+    }
+    let var = match url.scheme_str() {
+        Some("https") => "https_proxy",
+        _ => "http_proxy",
+    };
+    let proxy_url = env_var(var).or_else(|| env_var("all_proxy"))?;
+    match Proxy::from_url(&proxy_url) {
+        Ok(proxy) => Some(proxy),
+        Err(e) => {
+            log::warn!("Ignoring proxy from env: {e}");
+            None
+        }
+    }
+}
+
+/// Whether `host` matches an entry of `no_proxy`, a comma-separated list of host names, which
+/// match their subdomains too, or `*` for every host.
+fn is_excluded(host: &str, no_proxy: &str) -> bool {
+    no_proxy.split(',').map(str::trim).any(|entry| {
+        let entry = entry.trim_start_matches('.');
+        entry == "*"
+            || host.eq_ignore_ascii_case(entry)
+            || host
+                .to_ascii_lowercase()
+                .ends_with(&format!(".{}", entry.to_ascii_lowercase()))
+    })
+}
+
+fn proxy_error(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionRefused, message.into())
+}
+
+/// Ask the HTTP proxy connected to with `stream` to tunnel it to `host` and `port`.
+/// `host` may be an IPv6 literal, with or without brackets.
+pub async fn http_connect(
+    stream: &mut TcpStream,
+    host: &str,
+    port: u16,
+    authorization: Option<&str>,
+) -> io::Result<()> {
+    let target = match host.contains(':') && !host.starts_with('[') {
+        true => format!("[{host}]:{port}"),
+        false => format!("{host}:{port}"),
+    };
+    let mut request = format!("CONNECT {target} HTTP/1.1\r\nHost: {target}\r\n");
+    if let Some(authorization) = authorization {
+        request.push_str(&format!("Proxy-Authorization: {authorization}\r\n"));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).await?;
+
+    // Read byte by byte, as nothing past the headers belongs to the proxy.
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= MAX_CONNECT_RESPONSE_LEN {
+            return Err(proxy_error("proxy response is too long"));
+        }
+        response.push(stream.read_u8().await?);
+    }
+    let response = String::from_utf8_lossy(&response);
+    let status_line = response.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some(status) if status.starts_with('2') => Ok(()),
+        _ => Err(proxy_error(format!(
+            "proxy refused to connect to {target}: {status_line}"
+        ))),
+    }
+}
+
+/// Ask the SOCKS5 proxy connected to with `stream` to connect it to `host` and `port`.
+/// Host names are sent as is, to be resolved by the proxy.
+pub async fn socks5_connect(
+    stream: &mut TcpStream,
+    host: &str,
+    port: u16,
+    credentials: Option<&Credentials>,
+) -> io::Result<()> {
+    const VERSION: u8 = 5;
+    const NO_AUTHENTICATION: u8 = 0;
+    const USER_PASSWORD: u8 = 2;
+    const NO_ACCEPTABLE_METHOD: u8 = 0xff;
+
+    let methods: &[u8] = match credentials {
+        Some(_) => &[NO_AUTHENTICATION, USER_PASSWORD],
+        None => &[NO_AUTHENTICATION],
+    };
+    stream.write_all(&[VERSION, methods.len() as u8]).await?;
+    stream.write_all(methods).await?;
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await?;
+    match (reply, credentials) {
+        ([VERSION, NO_AUTHENTICATION], _) => (),
+        ([VERSION, USER_PASSWORD], Some(Credentials { user, password })) => {
+            // RFC 1929.
+            let (user, password) = (user.as_bytes(), password.as_bytes());
+            if user.len() > 255 || password.len() > 255 {
+                return Err(proxy_error("proxy user or password is too long"));
+            }
+            let mut request = vec![1, user.len() as u8];
+            request.extend_from_slice(user);
+            request.push(password.len() as u8);
+            request.extend_from_slice(password);
+            stream.write_all(&request).await?;
+            stream.read_exact(&mut reply).await?;
+            if reply[1] != 0 {
+                return Err(proxy_error("proxy rejected the user or password"));
+            }
+        }
+        ([VERSION, NO_ACCEPTABLE_METHOD], _) => {
+            return Err(proxy_error("proxy requires an unsupported authentication"));
+        }
+        _ => return Err(proxy_error("not a SOCKS5 proxy")),
+    }
+
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let mut request = vec![VERSION, 1, 0];
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(1);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(4);
+            request.extend_from_slice(&ip.octets());
+        }
+        Err(_) => {
+            let Ok(len) = u8::try_from(host.len()) else {
+                return Err(proxy_error("host name is too long for SOCKS5"));
+            };
+            request.push(3);
+            request.push(len);
+            request.extend_from_slice(host.as_bytes());
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != VERSION {
+        return Err(proxy_error("not a SOCKS5 proxy"));
+    }
+    if reply[1] != 0 {
+        let reason = match reply[1] {
+            2 => "not allowed by the proxy's rules",
+            3 => "network unreachable",
+            4 => "host unreachable",
+            5 => "connection refused",
+            6 => "TTL expired",
+            _ => "proxy failure",
+        };
+        return Err(proxy_error(format!(
+            "proxy can't connect to {host}:{port}: {reason}"
+        )));
+    }
+    // The address the proxy bound, unused.
+    let address_len = match reply[3] {
+        1 => 4,
+        4 => 16,
+        3 => usize::from(stream.read_u8().await?),
+        _ => return Err(proxy_error("malformed SOCKS5 reply")),
+    };
+    let mut bound = vec![0u8; address_len + 2];
+    stream.read_exact(&mut bound).await?;
+    Ok(())
+}
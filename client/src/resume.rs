@@ -0,0 +1,61 @@
+//! Where the user left off, restored on the next start.
+//! Kept in the client's data directory, next to the stats.
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use interface::MessageId;
+use serde::{Deserialize, Serialize};
+
+use crate::servers::Servers;
+
+/// Position in the messages of one server.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct ServerPosition {
+    /// Selected message, scrolled to on restore.
+    pub anchor: Option<MessageId>,
+    /// Latest message loaded, messages after it are marked as new.
+    pub last_seen: Option<MessageId>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Resume {
+    /// URL of the server shown last.
+    pub current_server: Option<String>,
+    /// Keyed by server URL. Servers not connected to in a session keep their positions.
+    pub positions: HashMap<String, ServerPosition>,
+}
+
+impl Resume {
+    /// Path of the resume file, `None` if the platform has no data directory.
+    pub fn default_path() -> Option<PathBuf> {
+        Some(dirs::data_dir()?.join("message_board").join("resume.json"))
+    }
+
+    /// A missing or corrupted file resumes nothing.
+    pub fn load(path: &Path) -> Self {
+        fs::read(path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    /// Record the positions of `servers`, at the end of a session.
+    pub fn update(&mut self, servers: &Servers) {
+        self.current_server = Some(servers.current().api().server_url().to_owned());
+        for app_state in servers.iter() {
+            let server_url = app_state.api().server_url().to_owned();
+            self.positions.insert(server_url, app_state.position());
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_vec_pretty(self)?)
+    }
+}
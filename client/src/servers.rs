@@ -0,0 +1,60 @@
+//! The servers the client is connected to, each with its own `AppState`.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use crate::state::AppState;
+
+#[derive(Debug, Clone)]
+pub struct Servers {
+    states: Box<[Arc<AppState>]>,
+    /// Index of the server being shown, shared between clones so that the server shown last is
+    /// known after the frontend exits.
+    current: Arc<AtomicUsize>,
+}
+
+impl Servers {
+    /// # Panics
+    /// If `states` is empty.
+    pub fn new(states: Vec<Arc<AppState>>) -> Self {
+        assert!(!states.is_empty(), "no servers to connect to");
+        Self {
+            states: states.into_boxed_slice(),
+            current: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn current(&self) -> &Arc<AppState> {
+        &self.states[self.current_index()]
+    }
+
+    pub fn current_index(&self) -> usize {
+        self.current.load(Ordering::Relaxed)
+    }
+
+    /// Switch to the server at `index`, does nothing if out of range.
+    pub fn select(&mut self, index: usize) {
+        if index < self.states.len() {
+            self.current.store(index, Ordering::Relaxed);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<AppState>> {
+        self.states.iter()
+    }
+
+    /// Indices of the servers listed in the server picker, in order. Archived servers are left
+    /// out unless `show_archived`, or if one is the current server.
+    pub fn listed(&self, show_archived: bool) -> Vec<usize> {
+        let current = self.current_index();
+        (0..self.states.len())
+            .filter(|&i| show_archived || i == current || !self.states[i].is_archived())
+            .collect()
+    }
+}
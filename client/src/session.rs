@@ -0,0 +1,186 @@
+//! Session tokens, kept in the OS keyring, and registration with invite codes on invite-only
+//! servers.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufRead, Write},
+    path::PathBuf,
+};
+
+use hyper::StatusCode;
+
+use crate::{error::ClientError, state::AppState, utils::DynResult};
+
+/// Service name of the session tokens in the OS keyring, the server URL is used as user name.
+const KEYRING_SERVICE: &str = "message_board";
+
+/// Path of the file storing session tokens of each server, `None` if the platform has no data
+/// directory.
+/// Only used when the OS keyring is unavailable, and for tokens stored by older versions.
+fn tokens_path() -> Option<PathBuf> {
+    Some(
+        dirs::data_dir()?
+            .join("message_board")
+            .join("sessions.json"),
+    )
+}
+
+/// Server URL -> session token.
+fn load_tokens() -> HashMap<String, Box<str>> {
+    tokens_path()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default()
+}
+
+fn store_tokens(tokens: &HashMap<String, Box<str>>) -> io::Result<()> {
+    let Some(path) = tokens_path() else {
+        return Ok(());
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    // The mode only applies to new files, not to ones written by older versions.
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    file.write_all(&serde_json::to_vec_pretty(tokens)?)
+}
+
+fn keyring_entry(server_url: &str) -> keyring::Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, server_url)
+}
+
+/// Load the token of a server from the OS keyring, falling back to the tokens file.
+/// Tokens found in the file are moved into the keyring if possible.
+fn load_token(server_url: &str) -> Option<Box<str>> {
+    match keyring_entry(server_url).and_then(|entry| entry.get_password()) {
+        Ok(token) => return Some(token.into()),
+        Err(keyring::Error::NoEntry) => (),
+        Err(e) => log::warn!("Can't read session token from keyring: {e}"),
+    }
+    let mut tokens = load_tokens();
+    let token = tokens.get(server_url).cloned()?;
+    if keyring_entry(server_url)
+        .and_then(|entry| entry.set_password(&token))
+        .is_ok()
+    {
+        tokens.remove(server_url);
+        if let Err(e) = store_tokens(&tokens) {
+            log::error!("Error removing session token moved into keyring: {e}");
+        }
+    }
+    Some(token)
+}
+
+/// Store the token of a server in the OS keyring, falling back to the tokens file if the keyring
+/// is unavailable, e.g. on a headless machine.
+fn store_token(server_url: &str, token: &str) -> io::Result<()> {
+    match keyring_entry(server_url).and_then(|entry| entry.set_password(token)) {
+        Ok(()) => return Ok(()),
+        Err(e) => log::warn!("Can't store session token in keyring, using a file instead: {e}"),
+    }
+    let mut tokens = load_tokens();
+    tokens.insert(server_url.to_owned(), token.into());
+    store_tokens(&tokens)
+}
+
+/// Wipe the stored token of a server, from both the keyring and the tokens file.
+/// The tokens file is cleared first, so that a failing keyring doesn't leave the token there too.
+fn forget_token(server_url: &str) -> DynResult<()> {
+    let mut tokens = load_tokens();
+    if tokens.remove(server_url).is_some() {
+        store_tokens(&tokens)?;
+    }
+    match keyring_entry(server_url).and_then(|entry| entry.delete_credential()) {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Wipe the stored token of the server, so the next start of the client doesn't log in
+/// automatically.
+/// The token is forgotten by this client only, it stays valid on the server.
+pub fn log_out(app_state: &AppState) -> DynResult<()> {
+    let api = app_state.api();
+    api.set_session_token(None);
+    forget_token(api.server_url())
+}
+
+/// Replace the session token with a new one from the server, e.g. if the old one leaked, and
+/// store it in place of the old one.
+pub async fn rotate_token(app_state: &AppState) -> DynResult<()> {
+    let api = app_state.api();
+    let token = api.rotate_token().await?;
+    store_token(api.server_url(), &token)?;
+    Ok(())
+}
+
+/// Delete the session on the server and wipe its stored token.
+/// Returns the number of its messages deleted or made anonymous, as the server is configured to.
+pub async fn delete_account(app_state: &AppState) -> DynResult<usize> {
+    let api = app_state.api();
+    let message_count = api.delete_account().await?;
+    forget_token(api.server_url())?;
+    Ok(message_count)
+}
+
+/// Set up the session token of the API client, from `session_token` (given in the config) or from
+/// tokens stored by previous runs.
+/// Tokens the server doesn't know, e.g. as it was restarted, are dropped, a stored one is also
+/// forgotten.
+/// If the server is invite-only and we have no token, prompt for an invite code on stdin and
+/// register with it. This must be called before the TUI takes over the terminal.
+pub async fn log_in(app_state: &AppState, session_token: Option<Box<str>>) -> DynResult<()> {
+    let api = app_state.api();
+    let is_stored = session_token.is_none();
+    let token = session_token.or_else(|| load_token(api.server_url()));
+    if token.is_some() {
+        api.set_session_token(token);
+        match api.fetch_latest_update_date().await {
+            Err(ClientError::Status { status, .. }) if status == StatusCode::UNAUTHORIZED => {
+                api.set_session_token(None);
+                if is_stored {
+                    println!("The stored session token is no longer valid, forgetting it");
+                    if let Err(e) = forget_token(api.server_url()) {
+                        log::error!("Error forgetting invalid session token: {e}");
+                    }
+                } else {
+                    println!("The session token given is not valid on this server");
+                }
+            }
+            // Other errors are left to be reported by what fetches the board.
+            _ => return Ok(()),
+        }
+    }
+    if !api.fetch_server_info().await?.invite_only {
+        return Ok(());
+    }
+    println!("This server is invite-only.");
+    let stdin = io::stdin();
+    loop {
+        print!("Invite code: ");
+        io::stdout().flush()?;
+        let mut invite_code = String::new();
+        if stdin.lock().read_line(&mut invite_code)? == 0 {
+            return Err("no invite code given".into());
+        }
+        match api.register(invite_code.trim().into()).await {
+            Ok(token) => {
+                if let Err(e) = store_token(api.server_url(), &token) {
+                    log::error!("Error storing session token: {e}");
+                    println!("Can't store session token: {e}");
+                }
+                api.set_session_token(Some(token));
+                println!("Registered");
+                return Ok(());
+            }
+            Err(e) => println!("Can't register: {e}"),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//! Subscription to server events over server-sent events, for when websockets are blocked by
+//! proxies or firewalls.
+
+use http_body_util::BodyExt;
+use interface::{sse_events, Event, Message};
+
+use crate::{error::ClientResult, state::AppState, websocket::parse_event};
+
+/// Consume the event stream until it's closed.
+/// `last_event_id` is kept across reconnects, so that no message is missed.
+/// Events are sent in `Envelope`s if `is_enveloped`, messages included.
+pub async fn run(
+    app_state: &AppState,
+    last_event_id: &mut Option<Box<str>>,
+    is_enveloped: bool,
+) -> ClientResult<()> {
+    let mut body = app_state
+        .api()
+        .open_event_stream(last_event_id.as_deref(), is_enveloped)
+        .await?;
+    log::info!("Event stream connected");
+    // Events may have been missed while disconnected.
+    app_state.fetch_topic().await?;
+    app_state.fetch_announcement().await?;
+    app_state.fetch_read_only().await?;
+    let mut buffer: Vec<u8> = Vec::new();
+    while let Some(frame) = body.frame().await {
+        let Ok(data) = frame?.into_data() else {
+            continue;
+        };
+        buffer.extend_from_slice(&data);
+        // Events are separated by blank lines.
+        while let Some(end) = buffer.windows(2).position(|window| window == b"\n\n") {
+            let block: Vec<u8> = buffer.drain(..end + 2).collect();
+            match String::from_utf8(block) {
+                Ok(block) => handle_block(app_state, &block, last_event_id, is_enveloped),
+                Err(error) => log::warn!("Event stream sent invalid UTF-8: {error}"),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_block(
+    app_state: &AppState,
+    block: &str,
+    last_event_id: &mut Option<Box<str>>,
+    is_enveloped: bool,
+) {
+    let mut name = sse_events::MESSAGE;
+    let mut id = None;
+    let mut data = String::new();
+    for line in block.lines() {
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => name = value,
+            "id" => id = Some(value),
+            "data" => {
+                if !data.is_empty() {
+                    data.push('\n');
+                }
+                data.push_str(value);
+            }
+            // Comments, which the server sends as keep-alive, and unknown fields.
+            _ => (),
+        }
+    }
+    if data.is_empty() {
+        return;
+    }
+    match name {
+        sse_events::MESSAGE | sse_events::EVENT if is_enveloped => {
+            match parse_event(&data, is_enveloped) {
+                Ok(Some(event)) => app_state.handle_event(event),
+                Ok(None) => (),
+                Err(error) => log::warn!("Unrecognized event {data:?}: {error}"),
+            }
+        }
+        sse_events::MESSAGE => match serde_json::from_str::<Message>(&data) {
+            Ok(message) => app_state.receive_message(message),
+            Err(error) => log::warn!("Unrecognized message {data:?}: {error}"),
+        },
+        sse_events::EVENT => match serde_json::from_str::<Event>(&data) {
+            Ok(event) => app_state.handle_event(event),
+            Err(error) => log::warn!("Unrecognized event {data:?}: {error}"),
+        },
+        name => log::warn!("Unrecognized server-sent event type {name:?}"),
+    }
+    if let Some(id) = id {
+        *last_event_id = Some(id.into());
+    }
+}
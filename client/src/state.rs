@@ -1,21 +1,103 @@
 use std::{
-    collections::VecDeque,
+    borrow::Cow,
+    collections::{HashMap, HashSet, VecDeque},
+    path::Path,
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         Arc, Mutex, MutexGuard,
     },
+    time::{Duration, Instant},
 };
 
 use chrono::{DateTime, Utc};
-use interface::Message;
-use tokio::time;
+use interface::{
+    capabilities, Announcement, Attachment, ContentKind, Event, Limits, Message, MessageDeletion,
+    MessageId, Profile, ReactionTally, SearchMessagesForm, SendMessageResponse, ValidationError,
+};
+use tokio::{sync::Notify, time};
 
 use crate::{
     api,
-    newtui::UIState,
-    utils::{DynResult, PrettyUnwrap},
+    config::Config,
+    connector::Connector,
+    error::{ClientError, ClientResult},
+    filter::Search,
+    mask::WordMask,
+    newtui::{Density, Grouping, UIState},
+    resume::ServerPosition,
+    stats::Stats,
+    theme::Theme,
+    time_format::TimeFormatter,
+    toast::Toasts,
+    utils::PrettyUnwrap,
+    voice::AudioBackend,
 };
 
+const LATENCY_PROBE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often expired messages are removed, so that their countdown doesn't stay at 0s.
+const EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Changes of the offset from the server's clock by more milliseconds than this are logged.
+const CLOCK_OFFSET_LOG_THRESHOLD: i64 = 500;
+
+/// The system is taken as having slept if the wall clock moved on further than the monotonic
+/// clock by more than this between two background updates.
+const SLEEP_THRESHOLD: Duration = Duration::from_secs(10);
+
+/// Bounds of the poll interval, so that it neither floods the server nor looks disconnected.
+const MIN_POLL_INTERVAL: Duration = Duration::from_millis(200);
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often the stats screen refreshes the activity of the board while shown.
+const BOARD_ACTIVITY_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Poll intervals after being idle for a while, i.e. without user activity or new messages.
+/// Intervals are never shortened below `AppState::poll_interval`.
+const IDLE_BACKOFF: [(Duration, Duration); 3] = [
+    (Duration::from_secs(30), Duration::from_secs(2)),
+    (Duration::from_secs(2 * 60), Duration::from_secs(5)),
+    (Duration::from_secs(5 * 60), Duration::from_secs(15)),
+];
+
+/// Unread messages of a server, see `AppState::unread`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Unread {
+    pub messages: usize,
+    /// Of `messages`, the ones with any of `Config::mention_words`.
+    pub mentions: usize,
+}
+
+/// A message sent by this client that hasn't shown up in the messages yet.
+#[derive(Debug, Clone)]
+pub struct PendingMessage {
+    pub client_tag: Box<str>,
+    pub content: Box<str>,
+    pub content_kind: ContentKind,
+    /// `None` while sending, the ID of the message once the server reports it delivered.
+    pub delivered_as: Option<MessageId>,
+}
+
+/// A file being uploaded with `AppState::attach`.
+#[derive(Debug, Clone)]
+pub struct Upload {
+    pub name: Box<str>,
+    /// In bytes.
+    pub size: u64,
+    /// Bytes sent so far.
+    pub sent: Arc<AtomicU64>,
+}
+
+/// Activity of the whole board from the server's stats, for the stats screen.
+#[derive(Debug, Clone, Default)]
+pub struct BoardActivity {
+    /// See `FetchStatsResponse::messages_per_hour`. `None` until fetched.
+    pub messages_per_hour: Option<Box<[u64]>>,
+    /// Why the last fetch failed, e.g. for lacking the permission to view stats.
+    pub error: Option<Box<str>>,
+    requested_at: Option<Instant>,
+}
+
 #[derive(Debug)]
 pub struct AppState {
     api: api::Client,
@@ -23,6 +105,75 @@ pub struct AppState {
     start_date: DateTime<Utc>,
     ui_state: Mutex<UIState>,
     is_fetching_message: AtomicBool,
+    /// Error shown in the status bar, if any.
+    status_error: Mutex<Option<Box<str>>>,
+    /// Handle to the toast queue in `UIState`.
+    toasts: Toasts,
+    topic: Mutex<Option<Box<str>>>,
+    announcement: Mutex<Option<Announcement>>,
+    /// Reason the server is read-only, `None` if it isn't.
+    read_only: Mutex<Option<Box<str>>>,
+    /// Whether the board is archived, which leaves it out of the server picker by default.
+    archived: AtomicBool,
+    time_formatter: TimeFormatter,
+    theme: Theme,
+    /// `None` unless the user opted in. Shared by the states of all servers.
+    stats: Option<Arc<Stats>>,
+    /// Server's clock minus ours, zero until measured.
+    clock_offset: Mutex<chrono::Duration>,
+    /// Round-trip time of the last ping, `None` if no ping has succeeded yet.
+    latency: Mutex<Option<Duration>>,
+    latency_warning: Duration,
+    pending_messages: Mutex<Vec<PendingMessage>>,
+    next_client_tag: AtomicU64,
+    audio: Arc<dyn AudioBackend>,
+    /// Of the message list in the TUI.
+    density: Mutex<Density>,
+    grouping: Grouping,
+    word_mask: WordMask,
+    /// Whether `word_mask` is applied, toggled with `/mask`.
+    is_masking: AtomicBool,
+    /// Uploaded but not sent yet, they are sent along with the next message.
+    draft_attachments: Mutex<Vec<Attachment>>,
+    upload: Mutex<Option<Upload>>,
+    /// Names from `interface::capabilities` supported by the server, empty until fetched.
+    capabilities: Mutex<Box<[Box<str>]>>,
+    /// Defaults of `interface::limits` until fetched with the capabilities.
+    limits: Mutex<Limits>,
+    /// `None` to fetch as many as the server allows.
+    fetch_batch_size: Mutex<Option<u32>>,
+    poll_interval: Mutex<Duration>,
+    /// Last user activity or new message, for backing off polling when idle.
+    last_activity: Mutex<Instant>,
+    /// Wakes the background update when the user becomes active while polling is backed off.
+    activity: Notify,
+    /// Whether messages are pushed over an event stream, making polling a fallback only.
+    is_streaming_messages: AtomicBool,
+    /// Tells the websocket task to drop its connection and reconnect right away.
+    /// Notified with `notify_one`, so that a request made while the task is connecting, rather
+    /// than waiting, is kept until it waits next.
+    reconnect: Notify,
+    /// Latest message loaded in the previous session, messages after it are marked as new.
+    last_seen: Mutex<Option<MessageId>>,
+    /// Date of the latest message loaded while the server was shown, later messages are
+    /// unread. `None` if none were, making every message unread.
+    last_read: Mutex<Option<DateTime<Utc>>>,
+    /// Words mentioning the user, see `Config::mention_words`.
+    mention_words: WordMask,
+    board_activity: Mutex<BoardActivity>,
+    /// Messages kept loaded, see `evict_old_messages`.
+    max_messages: usize,
+    /// Whether older messages than the ones loaded were dropped, so they are only on the server.
+    has_older_messages: AtomicBool,
+    /// Of messages sent in this session, by their ID.
+    deletion_tokens: Mutex<HashMap<MessageId, Box<str>>>,
+    /// Messages marked in the message list of the TUI, kept across refreshes.
+    marked: Mutex<HashSet<MessageId>>,
+    /// Tallies of reactions to messages that were shown, `None` while being fetched.
+    /// Messages missing here are fetched once shown.
+    reactions: Mutex<HashMap<MessageId, Option<Box<[ReactionTally]>>>>,
+    /// Profiles of message authors, by session ID, `None` while being fetched.
+    profiles: Mutex<HashMap<u64, Option<Profile>>>,
 }
 
 impl AppState {
@@ -30,13 +181,66 @@ impl AppState {
         &self.api
     }
 
-    pub fn with_server(server_url: String) -> Arc<Self> {
+    /// State of the server at `server_url`, one of `config.server_urls`.
+    pub fn with_config(
+        config: &Config,
+        server_url: String,
+        stats: Option<Arc<Stats>>,
+    ) -> Arc<Self> {
+        let ui_state = UIState::default();
+        let toasts = ui_state.toasts().clone();
+        let connector = Connector::new(config.ip_family, config.proxy.proxy_for(&server_url));
         let self_ = Arc::new(Self {
-            api: api::Client::with_server(server_url),
+            api: api::Client::with_server(server_url)
+                .http_version(config.http_version)
+                .connector(connector),
             messages: Mutex::new(VecDeque::new()),
             start_date: Utc::now(),
-            ui_state: Mutex::new(UIState::default()),
+            ui_state: Mutex::new(ui_state),
             is_fetching_message: false.into(),
+            status_error: Mutex::new(None),
+            toasts,
+            topic: Mutex::new(None),
+            announcement: Mutex::new(None),
+            read_only: Mutex::new(None),
+            archived: false.into(),
+            time_formatter: config.time_formatter.clone(),
+            theme: config.theme.clone(),
+            stats,
+            clock_offset: Mutex::new(chrono::Duration::zero()),
+            latency: Mutex::new(None),
+            latency_warning: config.latency_warning,
+            pending_messages: Mutex::new(Vec::new()),
+            next_client_tag: AtomicU64::new(0),
+            audio: Arc::new(config.audio.clone()),
+            density: Mutex::new(config.density),
+            grouping: config.grouping,
+            word_mask: config.mask_words.clone(),
+            is_masking: true.into(),
+            draft_attachments: Mutex::new(Vec::new()),
+            upload: Mutex::new(None),
+            capabilities: Mutex::new(Box::default()),
+            limits: Mutex::new(Limits::default()),
+            fetch_batch_size: Mutex::new(config.fetch_batch_size),
+            poll_interval: Mutex::new(
+                config
+                    .poll_interval
+                    .clamp(MIN_POLL_INTERVAL, MAX_POLL_INTERVAL),
+            ),
+            last_activity: Mutex::new(Instant::now()),
+            activity: Notify::new(),
+            is_streaming_messages: false.into(),
+            reconnect: Notify::new(),
+            last_seen: Mutex::new(None),
+            last_read: Mutex::new(None),
+            mention_words: config.mention_words.clone(),
+            board_activity: Mutex::new(BoardActivity::default()),
+            max_messages: config.max_messages,
+            has_older_messages: false.into(),
+            deletion_tokens: Mutex::new(HashMap::new()),
+            marked: Mutex::new(HashSet::new()),
+            reactions: Mutex::new(HashMap::new()),
+            profiles: Mutex::new(HashMap::new()),
         });
         self_
             .ui_state
@@ -54,13 +258,37 @@ impl AppState {
         self.ui_state.lock().pretty_unwrap()
     }
 
-    pub async fn fetch_new_messages_if_needed(&self) -> DynResult<()> {
+    pub fn toasts(&self) -> &Toasts {
+        &self.toasts
+    }
+
+    /// Show an error toast for a failed request.
+    pub fn toast_error(&self, context: &str, error: &ClientError) {
+        match error {
+            ClientError::Status { status, .. } if status.as_u16() == 429 => self
+                .toasts
+                .error(format!("{context}: rate limited, slow down")),
+            error => self.toasts.error(format!("{context}: {error}")),
+        }
+    }
+
+    pub async fn fetch_new_messages_if_needed(&self) -> ClientResult<()> {
         if self.is_fetching_message() {
             return Ok(());
         }
         self.set_is_fetching_message();
+        let result = self.fetch_new_messages().await;
+        self.unset_is_fetching_message();
+        result
+    }
+
+    async fn fetch_new_messages(&self) -> ClientResult<()> {
         let local_latest = self.lock_messages().back().map(|message| message.date);
+        let request_start = Instant::now();
         let remote_latest = self.api.fetch_latest_update_date().await?;
+        if let Some(stats) = &self.stats {
+            stats.record_latency(request_start.elapsed());
+        }
         let need_update = match (local_latest, remote_latest) {
             (Some(local), Some(remote)) => remote >= local,
             (None, None) => false,
@@ -70,7 +298,13 @@ impl AppState {
             "local: {local_latest:?}, remote: {remote_latest:?}, need_update: {need_update}"
         );
         if need_update {
-            let new_messages = self.api.fetch_messages(100, local_latest).await?;
+            let new_messages = self
+                .api
+                .fetch_messages(self.fetch_batch_size(), local_latest)
+                .await?;
+            if !new_messages.is_empty() {
+                self.record_activity();
+            }
             let mut messages = self.lock_messages();
             let messages: &mut VecDeque<Message> = &mut messages;
             // To pervent latest message being repeated.
@@ -79,11 +313,916 @@ impl AppState {
                 messages.retain(|message| message.date != local_latest);
             }
             new_messages.into_vec().into_iter().collect_into(messages);
+            self.evict_old_messages(messages);
         }
+        self.prune_pending_messages();
+        Ok(())
+    }
+
+    /// Catch up after the system slept, without trusting what was learnt before it: the clock
+    /// and capabilities are fetched anew, the websocket is reconnected and every message missed
+    /// is fetched, up to the server's limit. If more were missed, the messages loaded before are
+    /// dropped, so that no gap is left in between.
+    /// Returns the number of new messages.
+    pub async fn resync(&self) -> ClientResult<usize> {
+        self.reconnect.notify_one();
+        self.ping().await?;
+        self.fetch_capabilities().await?;
+        let local_latest = self.lock_messages().back().map(|message| message.date);
+        let max_count = self.limits().max_fetch_count;
+        self.set_is_fetching_message();
+        let result = self.api.fetch_messages(max_count, local_latest).await;
         self.unset_is_fetching_message();
+        let new_messages = result?;
+        let mut messages = self.lock_messages();
+        // Messages are fetched since the latest one loaded, which is missing if there is a gap.
+        let has_gap = local_latest.is_some()
+            && new_messages.len() >= max_count as usize
+            && new_messages.first().map(|message| message.date) != local_latest;
+        if has_gap {
+            log::info!("Missed more than {max_count} messages, dropping the older ones");
+            messages.clear();
+            self.has_older_messages.store(true, Ordering::Relaxed);
+        }
+        let loaded: HashSet<MessageId> = messages.iter().map(|message| message.id).collect();
+        let count_before = messages.len();
+        messages.extend(
+            new_messages
+                .into_vec()
+                .into_iter()
+                .filter(|message| !loaded.contains(&message.id)),
+        );
+        let new_count = messages.len() - count_before;
+        self.evict_old_messages(&mut messages);
+        drop(messages);
+        if new_count != 0 {
+            self.record_activity();
+        }
+        self.prune_pending_messages();
+        // Updates to reactions may have been missed too.
+        self.reactions.lock().pretty_unwrap().clear();
+        Ok(new_count)
+    }
+
+    /// Check the messages restored from the cache against the server, before connecting.
+    /// They are replaced with the server's since the oldest cached one, so that messages deleted
+    /// or expired while the client was closed disappear and changes to the others show. If more
+    /// messages were posted since than can be fetched at once, the older cached ones can't be
+    /// checked and are dropped, as `resync` does on a gap.
+    pub async fn reconcile_cached_messages(&self) -> ClientResult<()> {
+        let Some(oldest) = self.lock_messages().front().map(|message| message.date) else {
+            return Ok(());
+        };
+        let max_count = self.limits().max_fetch_count;
+        self.set_is_fetching_message();
+        let result = self.api.fetch_messages(max_count, Some(oldest)).await;
+        self.unset_is_fetching_message();
+        let fetched = result?;
+        let is_partial = fetched.len() >= max_count as usize
+            && fetched.first().map(|message| message.date) != Some(oldest);
+        if is_partial {
+            log::info!("Missed more than {max_count} messages, dropping the older cached ones");
+            self.has_older_messages.store(true, Ordering::Relaxed);
+        }
+        let mut messages = self.lock_messages();
+        let count_before = messages.len();
+        messages.clear();
+        messages.extend(fetched.into_vec());
+        log::info!(
+            "Reconciled {count_before} cached messages, {} loaded now",
+            messages.len()
+        );
+        self.evict_old_messages(&mut messages);
+        drop(messages);
+        self.reactions.lock().pretty_unwrap().clear();
+        Ok(())
+    }
+
+    /// Completes when `resync` asks for the websocket to reconnect.
+    pub async fn reconnect_requested(&self) {
+        self.reconnect.notified().await;
+    }
+
+    pub fn status_error(&self) -> Option<Box<str>> {
+        self.status_error.lock().pretty_unwrap().clone()
+    }
+
+    pub fn set_status_error(&self, error: impl Into<Box<str>>) {
+        *self.status_error.lock().pretty_unwrap() = Some(error.into());
+    }
+
+    pub fn clear_status_error(&self) {
+        *self.status_error.lock().pretty_unwrap() = None;
+    }
+
+    pub fn topic(&self) -> Option<Box<str>> {
+        self.topic.lock().pretty_unwrap().clone()
+    }
+
+    pub async fn fetch_topic(&self) -> ClientResult<()> {
+        let topic = self.api.fetch_topic().await?;
+        *self.topic.lock().pretty_unwrap() = topic;
+        Ok(())
+    }
+
+    pub async fn fetch_capabilities(&self) -> ClientResult<()> {
+        let response = self.api.fetch_capabilities().await?;
+        *self.capabilities.lock().pretty_unwrap() = response.capabilities;
+        *self.limits.lock().pretty_unwrap() = response.limits;
+        Ok(())
+    }
+
+    pub fn limits(&self) -> Limits {
+        *self.limits.lock().pretty_unwrap()
+    }
+
+    /// Messages fetched per request, between 1 and the server's `max_fetch_count`.
+    pub fn fetch_batch_size(&self) -> u32 {
+        let max_fetch_count = self.limits().max_fetch_count;
+        match *self.fetch_batch_size.lock().pretty_unwrap() {
+            Some(count) => count.clamp(1, max_fetch_count.max(1)),
+            None => max_fetch_count,
+        }
+    }
+
+    /// Returns the batch size now in effect, after clamping.
+    pub fn set_fetch_batch_size(&self, count: u32) -> u32 {
+        *self.fetch_batch_size.lock().pretty_unwrap() = Some(count);
+        self.fetch_batch_size()
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        *self.poll_interval.lock().pretty_unwrap()
+    }
+
+    /// Returns the interval now in effect, after clamping.
+    pub fn set_poll_interval(&self, interval: Duration) -> Duration {
+        let interval = interval.clamp(MIN_POLL_INTERVAL, MAX_POLL_INTERVAL);
+        *self.poll_interval.lock().pretty_unwrap() = interval;
+        interval
+    }
+
+    /// `poll_interval`, stretched along `IDLE_BACKOFF` while idle. Stretched all the way while
+    /// messages are pushed over an event stream.
+    pub fn effective_poll_interval(&self) -> Duration {
+        let poll_interval = self.poll_interval();
+        let idle_for = if self.is_streaming_messages.load(Ordering::Acquire) {
+            Duration::MAX
+        } else {
+            self.last_activity.lock().pretty_unwrap().elapsed()
+        };
+        let backoff = IDLE_BACKOFF
+            .iter()
+            .rev()
+            .find(|(idle, _)| idle_for >= *idle)
+            .map_or(Duration::ZERO, |&(_, interval)| interval);
+        poll_interval.max(backoff)
+    }
+
+    /// Snap back to fast polling on user activity.
+    pub fn record_user_activity(&self) {
+        let is_backed_off = self.effective_poll_interval() > self.poll_interval();
+        self.record_activity();
+        if is_backed_off {
+            self.activity.notify_one();
+        }
+    }
+
+    fn record_activity(&self) {
+        *self.last_activity.lock().pretty_unwrap() = Instant::now();
+    }
+
+    pub fn set_streaming_messages(&self, is_streaming: bool) {
+        self.is_streaming_messages
+            .store(is_streaming, Ordering::Release);
+    }
+
+    /// Does the server support `capability`, one of `interface::capabilities`?
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities
+            .lock()
+            .pretty_unwrap()
+            .iter()
+            .any(|supported| &**supported == capability)
+    }
+
+    /// Tell the user that `feature` can't be used with this server.
+    pub fn toast_unsupported(&self, feature: &str) {
+        self.toasts
+            .info(format!("{feature} not supported by this server"));
+    }
+
+    pub fn announcement(&self) -> Option<Announcement> {
+        self.announcement.lock().pretty_unwrap().clone()
+    }
+
+    pub async fn fetch_announcement(&self) -> ClientResult<()> {
+        let announcement = self.api.fetch_announcement().await?;
+        *self.announcement.lock().pretty_unwrap() = announcement;
+        Ok(())
+    }
+
+    pub fn read_only(&self) -> Option<Box<str>> {
+        self.read_only.lock().pretty_unwrap().clone()
+    }
+
+    pub fn is_archived(&self) -> bool {
+        self.archived.load(Ordering::Relaxed)
+    }
+
+    /// Fetches whether the board is archived too.
+    pub async fn fetch_read_only(&self) -> ClientResult<()> {
+        let server_info = self.api.fetch_server_info().await?;
+        *self.read_only.lock().pretty_unwrap() = server_info.read_only;
+        self.archived.store(server_info.archived, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Remember that the server is read-only if `result` says so, in case the event was missed.
+    fn note_read_only<T>(&self, result: &ClientResult<T>) {
+        if let Err(ClientError::ReadOnly { reason }) = result {
+            *self.read_only.lock().pretty_unwrap() = Some(reason.clone());
+        }
+    }
+
+    /// Add a message pushed from the server, unless it's already there.
+    pub fn receive_message(&self, message: Message) {
+        let mut messages = self.lock_messages();
+        if !messages.iter().any(|existing| existing.id == message.id) {
+            messages.push_back(message);
+            self.evict_old_messages(&mut messages);
+            self.record_activity();
+        }
+        drop(messages);
+        self.prune_pending_messages();
+    }
+
+    /// Drop the oldest messages beyond `max_messages`, so that a long running client doesn't
+    /// grow without bounds. Called as new messages are added, so that older messages loaded
+    /// by searching stay until then.
+    fn evict_old_messages(&self, messages: &mut VecDeque<Message>) {
+        let excess = messages.len().saturating_sub(self.max_messages);
+        if excess == 0 {
+            return;
+        }
+        let mut reactions = self.reactions.lock().pretty_unwrap();
+        for message in messages.drain(..excess) {
+            reactions.remove(&message.id);
+        }
+        if !self.has_older_messages.swap(true, Ordering::Relaxed) {
+            log::info!(
+                "Over {} messages loaded, dropping the oldest",
+                self.max_messages
+            );
+        }
+    }
+
+    /// Whether older messages than the ones loaded were dropped, and can be loaded again by
+    /// searching the server.
+    pub fn has_older_messages(&self) -> bool {
+        self.has_older_messages.load(Ordering::Relaxed)
+    }
+
+    /// Search the server for messages matching `search` that are older than any loaded, and
+    /// insert them in place. Returns the number of messages found, and whether the server
+    /// stopped searching early.
+    pub async fn search_older_messages(&self, search: &Search) -> ClientResult<(usize, bool)> {
+        let form = SearchMessagesForm {
+            query: search.text().trim().into(),
+            max_count: self.fetch_batch_size(),
+            before: self.lock_messages().front().map(|message| message.date),
+            regex: search.is_regex(),
+            case_sensitive: search.is_case_sensitive(),
+            author: search.author().map(Box::from),
+        };
+        let response = self.api.search_messages(form).await?;
+        let count = response.messages.len();
+        let mut messages = self.lock_messages();
+        for message in response.messages.into_vec() {
+            if messages.iter().any(|existing| existing.id == message.id) {
+                continue;
+            }
+            let index = messages.partition_point(|existing| existing.date <= message.date);
+            messages.insert(index, message);
+        }
+        Ok((count, response.timed_out))
+    }
+
+    pub fn pending_messages(&self) -> Vec<PendingMessage> {
+        self.pending_messages.lock().pretty_unwrap().clone()
+    }
+
+    /// Forget delivered pending messages that have shown up in the messages.
+    fn prune_pending_messages(&self) {
+        let messages = self.lock_messages();
+        self.pending_messages
+            .lock()
+            .pretty_unwrap()
+            .retain(|pending| match pending.delivered_as {
+                Some(id) => !messages.iter().any(|message| message.id == id),
+                None => true,
+            });
+    }
+
+    /// Handle an event pushed from the server.
+    pub fn handle_event(&self, event: Event) {
+        match event {
+            Event::MessagesAdded { messages } => {
+                for message in messages.into_vec() {
+                    self.receive_message(message);
+                }
+            }
+            Event::TopicChanged { topic } => {
+                if let Some(topic) = &topic {
+                    self.toasts.info(format!("Topic changed to: {topic}"));
+                }
+                *self.topic.lock().pretty_unwrap() = topic;
+            }
+            Event::Announcement { announcement } => {
+                if let Some(announcement) = &announcement {
+                    self.toasts
+                        .info(format!("Announcement: {}", announcement.content));
+                }
+                *self.announcement.lock().pretty_unwrap() = announcement;
+            }
+            Event::ReadOnly { reason } => {
+                match &reason {
+                    Some(reason) => self.toasts.info(format!("Board is read-only: {reason}")),
+                    None => self.toasts.info("Board is no longer read-only"),
+                }
+                *self.read_only.lock().pretty_unwrap() = reason;
+            }
+            Event::Archived { archived } => {
+                match archived {
+                    true => self
+                        .toasts
+                        .info("Board was archived, it is read-only for good"),
+                    false => self.toasts.info("Board is no longer archived"),
+                }
+                self.archived.store(archived, Ordering::Relaxed);
+            }
+            Event::MessagesDeleted { ids } => {
+                self.lock_messages()
+                    .retain(|message| !ids.contains(&message.id));
+                let mut reactions = self.reactions.lock().pretty_unwrap();
+                for id in ids.iter() {
+                    reactions.remove(id);
+                }
+            }
+            Event::MessagesAnonymized { ids } => {
+                for message in self.lock_messages().iter_mut() {
+                    if ids.contains(&message.id) {
+                        message.author = None;
+                    }
+                }
+            }
+            Event::PollUpdated { id, poll } => {
+                let mut messages = self.lock_messages();
+                if let Some(message) = messages.iter_mut().find(|message| message.id == id) {
+                    message.poll = Some(poll);
+                }
+            }
+            // Fetched again if shown.
+            Event::ReactionsUpdated { id } => {
+                self.reactions.lock().pretty_unwrap().remove(&id);
+            }
+            Event::Delivered { client_tag, id } => {
+                if let Some(pending) = self
+                    .pending_messages
+                    .lock()
+                    .pretty_unwrap()
+                    .iter_mut()
+                    .find(|pending| pending.client_tag == client_tag)
+                {
+                    pending.delivered_as = Some(id);
+                }
+                self.prune_pending_messages();
+            }
+            Event::Rejected { client_tag, error } => {
+                let mut pending_messages = self.pending_messages.lock().pretty_unwrap();
+                let count_before = pending_messages.len();
+                pending_messages.retain(|pending| pending.client_tag != client_tag);
+                if pending_messages.len() != count_before {
+                    self.toasts
+                        .error(format!("Failed to send message: {error}"));
+                }
+            }
+            Event::LinkPreview { id, link_preview } => {
+                let mut messages = self.lock_messages();
+                if let Some(message) = messages.iter_mut().find(|message| message.id == id) {
+                    message.link_preview = Some(link_preview);
+                }
+            }
+            Event::Unknown => log::debug!("Ignoring event of unknown kind"),
+        }
+    }
+
+    pub fn time_formatter(&self) -> &TimeFormatter {
+        &self.time_formatter
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    pub fn stats(&self) -> Option<&Stats> {
+        self.stats.as_deref()
+    }
+
+    pub fn record_message_sent(&self) {
+        if let Some(stats) = &self.stats {
+            stats.record_message_sent();
+        }
+    }
+
+    pub async fn send_message(
+        &self,
+        content: Box<str>,
+        content_kind: ContentKind,
+        expires_in: Option<Duration>,
+    ) -> ClientResult<()> {
+        let attachments: Box<[Attachment]> =
+            std::mem::take(&mut *self.draft_attachments.lock().pretty_unwrap()).into();
+        if !self.supports(capabilities::DELIVERY_RECEIPTS) {
+            let result = self
+                .api
+                .send_message(content, content_kind, expires_in, None, attachments.clone())
+                .await;
+            self.note_read_only(&result);
+            self.restore_draft_attachments(attachments, &result);
+            self.record_deletion_token(result?);
+            self.record_message_sent();
+            return Ok(());
+        }
+        let client_tag: Box<str> = format!(
+            "{}-{}",
+            self.start_date.timestamp_micros(),
+            self.next_client_tag.fetch_add(1, Ordering::Relaxed)
+        )
+        .into();
+        self.pending_messages
+            .lock()
+            .pretty_unwrap()
+            .push(PendingMessage {
+                client_tag: client_tag.clone(),
+                content: content.clone(),
+                content_kind,
+                delivered_as: None,
+            });
+        let result = self
+            .api
+            .send_message(
+                content,
+                content_kind,
+                expires_in,
+                Some(client_tag.clone()),
+                attachments.clone(),
+            )
+            .await;
+        // `Event::Delivered` never comes without a websocket or event stream, the message shows
+        // up with the next fetch then.
+        self.pending_messages
+            .lock()
+            .pretty_unwrap()
+            .retain(|pending| pending.client_tag != client_tag || pending.delivered_as.is_some());
+        self.note_read_only(&result);
+        self.restore_draft_attachments(attachments, &result);
+        self.record_deletion_token(result?);
+        self.record_message_sent();
         Ok(())
     }
 
+    fn record_deletion_token(&self, response: SendMessageResponse) {
+        if let (Some(id), Some(deletion_token)) = (response.id, response.deletion_token) {
+            self.deletion_tokens
+                .lock()
+                .pretty_unwrap()
+                .insert(id, deletion_token);
+        }
+    }
+
+    /// Whether message `id` was sent in this session, and can be deleted.
+    pub fn is_own_message(&self, id: MessageId) -> bool {
+        self.deletion_tokens
+            .lock()
+            .pretty_unwrap()
+            .contains_key(&id)
+    }
+
+    /// Delete the messages of `ids` that were sent in this session, skipping the others.
+    /// Returns the number of messages deleted.
+    pub async fn delete_own_messages(&self, ids: &[MessageId]) -> ClientResult<usize> {
+        let deletions: Box<[MessageDeletion]> = {
+            let deletion_tokens = self.deletion_tokens.lock().pretty_unwrap();
+            ids.iter()
+                .filter_map(|&id| {
+                    let deletion_token = deletion_tokens.get(&id)?.clone();
+                    Some(MessageDeletion { id, deletion_token })
+                })
+                .collect()
+        };
+        if deletions.is_empty() {
+            return Ok(0);
+        }
+        let deleted = self.api.delete_messages(deletions).await?;
+        self.lock_messages()
+            .retain(|message| !deleted.contains(&message.id));
+        let mut deletion_tokens = self.deletion_tokens.lock().pretty_unwrap();
+        let mut marked = self.marked.lock().pretty_unwrap();
+        for id in deleted.iter() {
+            deletion_tokens.remove(id);
+            marked.remove(id);
+        }
+        Ok(deleted.len())
+    }
+
+    /// Messages marked in the message list, for bulk actions.
+    pub fn marked(&self) -> HashSet<MessageId> {
+        self.marked.lock().pretty_unwrap().clone()
+    }
+
+    pub fn toggle_marked(&self, id: MessageId) {
+        let mut marked = self.marked.lock().pretty_unwrap();
+        if !marked.remove(&id) {
+            marked.insert(id);
+        }
+    }
+
+    /// Returns whether any message was marked.
+    pub fn clear_marked(&self) -> bool {
+        let mut marked = self.marked.lock().pretty_unwrap();
+        let was_marked = !marked.is_empty();
+        marked.clear();
+        was_marked
+    }
+
+    /// The marked messages that are loaded, oldest first.
+    pub fn marked_messages(&self) -> Vec<Message> {
+        let marked = self.marked.lock().pretty_unwrap();
+        self.lock_messages()
+            .iter()
+            .filter(|message| marked.contains(&message.id))
+            .cloned()
+            .collect()
+    }
+
+    /// Tallies of reactions to the message, empty until fetched by `load_reactions`.
+    pub fn reactions(&self, id: MessageId) -> Box<[ReactionTally]> {
+        self.reactions
+            .lock()
+            .pretty_unwrap()
+            .get(&id)
+            .cloned()
+            .flatten()
+            .unwrap_or_default()
+    }
+
+    /// Fetch reactions to the messages that weren't fetched yet, in the background.
+    /// Called with the messages in view, so that only reactions to those are fetched.
+    pub fn load_reactions(self: &Arc<Self>, ids: &[MessageId]) {
+        if !self.supports(capabilities::REACTIONS) {
+            return;
+        }
+        let mut reactions = self.reactions.lock().pretty_unwrap();
+        let ids: Vec<MessageId> = ids
+            .iter()
+            .copied()
+            .filter(|id| !reactions.contains_key(id))
+            .collect();
+        if ids.is_empty() {
+            return;
+        }
+        for &id in &ids {
+            reactions.insert(id, None);
+        }
+        drop(reactions);
+        let self_ = Arc::clone(self);
+        tokio::spawn(async move {
+            let fetched = match self_.api.fetch_reactions(&ids).await {
+                Ok(fetched) => fetched,
+                Err(e) => {
+                    // Taken as without reactions rather than fetched again on every render,
+                    // until they are updated.
+                    log::error!("Error fetching reactions: {e}");
+                    Box::default()
+                }
+            };
+            let mut fetched: HashMap<MessageId, Box<[ReactionTally]>> = fetched
+                .into_vec()
+                .into_iter()
+                .map(|reactions| (reactions.message_id, reactions.tallies))
+                .collect();
+            let mut reactions = self_.reactions.lock().pretty_unwrap();
+            for id in ids {
+                // Entries removed meanwhile were updated, and are to be fetched again.
+                if let Some(entry) = reactions.get_mut(&id).filter(|entry| entry.is_none()) {
+                    *entry = Some(fetched.remove(&id).unwrap_or_default());
+                }
+            }
+        });
+    }
+
+    /// React to the message, or take the reaction back if already added.
+    /// Returns whether it was added.
+    pub async fn react(&self, id: MessageId, reaction: &str) -> ClientResult<bool> {
+        let added = self.api.react(id, reaction.into()).await?;
+        // Fetched again when shown, as no event comes when polling.
+        self.reactions.lock().pretty_unwrap().remove(&id);
+        Ok(added)
+    }
+
+    /// Profile of the session with ID `user`, `None` until fetched by `load_profile`.
+    /// Profiles that couldn't be fetched are empty.
+    pub fn profile(&self, user: u64) -> Option<Profile> {
+        self.profiles
+            .lock()
+            .pretty_unwrap()
+            .get(&user)
+            .cloned()
+            .flatten()
+    }
+
+    /// Fetch the profile of the session with ID `user` in the background.
+    /// Fetched again every time, as profiles change without events.
+    pub fn load_profile(self: &Arc<Self>, user: u64) {
+        self.profiles.lock().pretty_unwrap().insert(user, None);
+        let self_ = Arc::clone(self);
+        tokio::spawn(async move {
+            let profile = match self_.api.fetch_profile(user).await {
+                Ok(profile) => profile,
+                Err(e) => {
+                    log::error!("Error fetching profile of {user}: {e}");
+                    Profile::default()
+                }
+            };
+            self_
+                .profiles
+                .lock()
+                .pretty_unwrap()
+                .insert(user, Some(profile));
+        });
+    }
+
+    /// Put back the attachments of a message that failed to send, so they aren't lost.
+    fn restore_draft_attachments<T>(
+        &self,
+        attachments: Box<[Attachment]>,
+        result: &ClientResult<T>,
+    ) {
+        if result.is_err() {
+            let mut draft_attachments = self.draft_attachments.lock().pretty_unwrap();
+            draft_attachments.splice(0..0, attachments.into_vec());
+        }
+    }
+
+    /// Restore where the user left off in the previous session.
+    /// Messages after the ones loaded back then become unread.
+    pub fn resume(&self, position: ServerPosition) {
+        *self.last_seen.lock().pretty_unwrap() = position.last_seen;
+        let last_seen = self
+            .lock_messages()
+            .iter()
+            .find(|message| Some(message.id) == position.last_seen)
+            .map(|message| message.date);
+        if let Some(last_seen) = last_seen {
+            *self.last_read.lock().pretty_unwrap() = Some(last_seen);
+        }
+        if let Some(anchor) = position.anchor {
+            self.lock_ui_state().resume_at(anchor);
+        }
+    }
+
+    /// Load messages cached in the previous session, before connecting. Expired ones are dropped
+    /// right away, the rest are checked against the server by `reconcile_cached_messages`.
+    pub fn restore_messages(&self, mut cached: Vec<Message>) {
+        let now = Utc::now();
+        cached.retain(|message| message.expires_at.is_none_or(|expires_at| expires_at > now));
+        let mut messages = self.lock_messages();
+        messages.extend(cached);
+        self.evict_old_messages(&mut messages);
+    }
+
+    /// Remove messages past their `expires_at`, which the server only deletes in its periodic
+    /// retention sweep.
+    fn remove_expired_messages(&self) {
+        let now = Utc::now();
+        let mut messages = self.lock_messages();
+        let is_expired = |message: &Message| message.expires_at.is_some_and(|at| at <= now);
+        if !messages.iter().any(is_expired) {
+            return;
+        }
+        let mut reactions = self.reactions.lock().pretty_unwrap();
+        messages.retain(|message| {
+            let expired = is_expired(message);
+            if expired {
+                reactions.remove(&message.id);
+            }
+            !expired
+        });
+    }
+
+    /// Where the user is, to be restored with `resume` in the next session.
+    pub fn position(&self) -> ServerPosition {
+        ServerPosition {
+            anchor: self.lock_ui_state().anchor(),
+            last_seen: self.lock_messages().back().map(|message| message.id),
+        }
+    }
+
+    pub fn last_seen(&self) -> Option<MessageId> {
+        *self.last_seen.lock().pretty_unwrap()
+    }
+
+    /// Count the messages loaded so far as read, called while the server is shown.
+    pub fn mark_read(&self) {
+        let latest = self.lock_messages().back().map(|message| message.date);
+        if latest.is_some() {
+            *self.last_read.lock().pretty_unwrap() = latest;
+        }
+    }
+
+    /// Messages loaded since the server was last shown, kept up to date by the background
+    /// updates of every server, shown or not.
+    pub fn unread(&self) -> Unread {
+        let last_read = *self.last_read.lock().pretty_unwrap();
+        let messages = self.lock_messages();
+        let unread = messages
+            .iter()
+            .rev()
+            .take_while(|message| last_read.map_or(true, |last_read| message.date > last_read));
+        let mut count = Unread::default();
+        for message in unread {
+            count.messages += 1;
+            if self.mention_words.is_match(&message.content) {
+                count.mentions += 1;
+            }
+        }
+        count
+    }
+
+    pub fn board_activity(&self) -> BoardActivity {
+        self.board_activity.lock().pretty_unwrap().clone()
+    }
+
+    /// Whether the board activity is due to be refreshed, in which case it's marked as being
+    /// refreshed and `fetch_board_activity` should follow.
+    pub fn should_refresh_board_activity(&self) -> bool {
+        let mut board_activity = self.board_activity.lock().pretty_unwrap();
+        let is_due = board_activity.requested_at.map_or(true, |requested_at| {
+            requested_at.elapsed() >= BOARD_ACTIVITY_REFRESH_INTERVAL
+        });
+        if is_due {
+            board_activity.requested_at = Some(Instant::now());
+        }
+        is_due
+    }
+
+    pub async fn fetch_board_activity(&self) {
+        let result = self.api.fetch_stats().await;
+        let mut board_activity = self.board_activity.lock().pretty_unwrap();
+        match result {
+            Ok(response) => {
+                board_activity.messages_per_hour = Some(response.messages_per_hour);
+                board_activity.error = None;
+            }
+            Err(e) => {
+                log::error!("Error fetching stats: {e}");
+                board_activity.error = Some(e.to_string().into());
+            }
+        }
+    }
+
+    pub fn density(&self) -> Density {
+        *self.density.lock().pretty_unwrap()
+    }
+
+    pub fn set_density(&self, density: Density) {
+        *self.density.lock().pretty_unwrap() = density;
+    }
+
+    pub fn grouping(&self) -> Grouping {
+        self.grouping
+    }
+
+    /// `text` with masked words replaced, unless masking is toggled off.
+    pub fn mask<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match self.is_masking.load(Ordering::Relaxed) {
+            true => self.word_mask.mask(text),
+            false => Cow::Borrowed(text),
+        }
+    }
+
+    pub fn is_masking(&self) -> bool {
+        self.is_masking.load(Ordering::Relaxed)
+    }
+
+    /// Returns whether masking is on now, `None` if there are no words to mask.
+    pub fn toggle_masking(&self) -> Option<bool> {
+        if self.word_mask.is_empty() {
+            return None;
+        }
+        Some(!self.is_masking.fetch_xor(true, Ordering::Relaxed))
+    }
+
+    /// For recording and playing voice notes.
+    pub fn audio(&self) -> &Arc<dyn AudioBackend> {
+        &self.audio
+    }
+
+    /// Attachments to be sent along with the next message.
+    pub fn draft_attachments(&self) -> Vec<Attachment> {
+        self.draft_attachments.lock().pretty_unwrap().clone()
+    }
+
+    /// The upload in progress, if any.
+    pub fn upload(&self) -> Option<Upload> {
+        self.upload.lock().pretty_unwrap().clone()
+    }
+
+    /// Upload the file at `path` and add it to the draft attachments.
+    /// Only one file is uploaded at a time.
+    pub async fn attach(&self, path: &Path) -> ClientResult<Attachment> {
+        let max_attachments = self.limits().max_attachments as usize;
+        if self.draft_attachments.lock().pretty_unwrap().len() >= max_attachments {
+            return Err(ValidationError::TooManyAttachments.into());
+        }
+        let name: Box<str> = match path.file_name() {
+            Some(name) => name.to_string_lossy().into(),
+            None => path.to_string_lossy().into(),
+        };
+        let size = tokio::fs::metadata(path).await?.len();
+        let sent = Arc::new(AtomicU64::new(0));
+        {
+            let mut upload = self.upload.lock().pretty_unwrap();
+            if upload.is_some() {
+                return Err(ClientError::UploadInProgress);
+            }
+            *upload = Some(Upload {
+                name: name.clone(),
+                size,
+                sent: Arc::clone(&sent),
+            });
+        }
+        let result = self.api.upload_attachment(path, &name, sent).await;
+        *self.upload.lock().pretty_unwrap() = None;
+        self.note_read_only(&result);
+        let attachment = result?;
+        self.draft_attachments
+            .lock()
+            .pretty_unwrap()
+            .push(attachment.clone());
+        Ok(attachment)
+    }
+
+    pub fn latency(&self) -> Option<Duration> {
+        *self.latency.lock().pretty_unwrap()
+    }
+
+    /// Whether `latency` is high enough to warn the user.
+    pub fn is_latency_high(&self, latency: Duration) -> bool {
+        latency > self.latency_warning
+    }
+
+    /// Ping the server and update the latency.
+    /// Warns with a toast when the latency becomes high.
+    /// Also measures the skew of our clock if the server tells its time.
+    pub async fn ping(&self) -> ClientResult<Duration> {
+        let latency = match self.supports(capabilities::SERVER_TIME) {
+            true => self.sync_clock().await?,
+            false => self.api.ping().await?,
+        };
+        let previous = self.latency.lock().pretty_unwrap().replace(latency);
+        let was_high = previous.is_some_and(|previous| self.is_latency_high(previous));
+        if self.is_latency_high(latency) && !was_high {
+            self.toasts
+                .info(format!("High latency to server: {}ms", latency.as_millis()));
+        }
+        Ok(latency)
+    }
+
+    /// Measure the offset of the server's clock from ours, returning the round-trip time.
+    /// The server is assumed to have read its clock halfway through the round trip.
+    async fn sync_clock(&self) -> ClientResult<Duration> {
+        let (server_time, round_trip) = self.api.fetch_time().await?;
+        let received_at = Utc::now();
+        let half_round_trip = chrono::Duration::from_std(round_trip / 2).unwrap_or_default();
+        let offset = server_time + half_round_trip - received_at;
+        let previous = std::mem::replace(&mut *self.clock_offset.lock().pretty_unwrap(), offset);
+        if (offset - previous).num_milliseconds().abs() > CLOCK_OFFSET_LOG_THRESHOLD {
+            log::info!(
+                "Server's clock is {}ms ahead of ours",
+                offset.num_milliseconds()
+            );
+        }
+        Ok(round_trip)
+    }
+
+    /// Current time by the server's clock, as far as we know it.
+    /// For comparing with dates from the server, like when messages expire.
+    pub fn server_now(&self) -> DateTime<Utc> {
+        Utc::now() + *self.clock_offset.lock().pretty_unwrap()
+    }
+
     pub fn start_date(&self) -> DateTime<Utc> {
         self.start_date
     }
@@ -101,16 +1240,114 @@ impl AppState {
     }
 }
 
+/// Spawns the background update task, restarting it if it panics.
+/// Errors and panics are shown in the status bar instead of taking down the client.
 pub fn setup_background_update(app_state: Arc<AppState>) {
-    let app_state = app_state.clone();
     tokio::spawn(async move {
-        let mut interval = time::interval(time::Duration::from_secs(1));
+        loop {
+            let task = tokio::spawn(background_update(Arc::clone(&app_state)));
+            match task.await {
+                Err(error) if error.is_panic() => {
+                    app_state.set_status_error("Background update crashed, restarting");
+                }
+                _ => return,
+            }
+        }
+    });
+}
+
+/// Ping the server periodically to keep the latency in the status bar up to date.
+pub fn setup_latency_probe(app_state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = time::interval(LATENCY_PROBE_INTERVAL);
+        loop {
+            interval.tick().await;
+            // Connection errors are already reported by the background update.
+            if let Err(error) = app_state.ping().await {
+                log::warn!("Latency probe failed: {error}");
+            }
+        }
+    });
+}
+
+/// Remove expired messages periodically, see `AppState::remove_expired_messages`.
+pub fn setup_expiry(app_state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = time::interval(EXPIRY_CHECK_INTERVAL);
         loop {
             interval.tick().await;
-            app_state
-                .fetch_new_messages_if_needed()
-                .await
-                .pretty_unwrap();
+            app_state.remove_expired_messages();
         }
     });
 }
+
+/// Tells whether the system was suspended, e.g. a laptop slept, from the wall clock moving on
+/// further than the monotonic clock, which stands still meanwhile.
+#[derive(Debug)]
+struct SleepDetector {
+    checked_at: Instant,
+    checked_at_date: DateTime<Utc>,
+}
+
+impl SleepDetector {
+    fn new() -> Self {
+        Self {
+            checked_at: Instant::now(),
+            checked_at_date: Utc::now(),
+        }
+    }
+
+    /// Returns for how long the system slept since the last check, if it did.
+    /// Setting the clock forward looks the same, which only costs an unneeded resync.
+    fn check(&mut self) -> Option<Duration> {
+        let previous = std::mem::replace(self, Self::new());
+        let elapsed = self.checked_at - previous.checked_at;
+        let elapsed_date = (self.checked_at_date - previous.checked_at_date)
+            .to_std()
+            .unwrap_or_default();
+        Some(elapsed_date.saturating_sub(elapsed)).filter(|&slept| slept > SLEEP_THRESHOLD)
+    }
+}
+
+async fn background_update(app_state: Arc<AppState>) {
+    let mut sleep_detector = SleepDetector::new();
+    // Set after sleeping until a resync succeeds, as failing ones are likely while the network
+    // comes back up.
+    let mut needs_resync = false;
+    loop {
+        // Slept anew every time, so that changes by `/interval` and backoff apply right away.
+        tokio::select! {
+            _ = time::sleep(app_state.effective_poll_interval()) => (),
+            _ = app_state.activity.notified() => (),
+        }
+        // Incremental updates assume nothing was missed, which doesn't hold after sleeping.
+        if let Some(slept) = sleep_detector.check() {
+            log::info!("System slept for about {}s, resyncing", slept.as_secs());
+            needs_resync = true;
+        }
+        let result = match needs_resync {
+            true => app_state.resync().await.map(|new_count| {
+                needs_resync = false;
+                app_state
+                    .toasts()
+                    .info(format!("Resynced after sleep, {new_count} new messages"));
+            }),
+            false => app_state.fetch_new_messages_if_needed().await,
+        };
+        match result {
+            Ok(()) => {
+                if app_state.status_error().is_some() {
+                    app_state.toasts().info("Reconnected");
+                }
+                app_state.clear_status_error();
+            }
+            Err(error) => {
+                log::error!("Error fetching messages: {error}");
+                if app_state.status_error().is_none() {
+                    app_state.toast_error("Can't fetch messages", &error);
+                }
+                app_state.set_status_error(format!("Can't fetch messages: {error}"));
+            }
+        }
+    }
+}
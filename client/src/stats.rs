@@ -0,0 +1,99 @@
+//! Opt-in local usage statistics.
+//! Stats are only ever written to the client's data directory, nothing is sent to the server.
+
+use std::{
+    fmt::{self, Display},
+    fs, io,
+    path::PathBuf,
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::PrettyUnwrap;
+
+/// What gets persisted across sessions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct UsageStats {
+    messages_sent: u64,
+    sessions: u64,
+    /// Total uptime of past sessions.
+    uptime: Duration,
+    latency_total: Duration,
+    latency_samples: u64,
+}
+
+#[derive(Debug)]
+pub struct Stats {
+    path: PathBuf,
+    session_start: Instant,
+    inner: Mutex<UsageStats>,
+}
+
+impl Stats {
+    /// Path of the stats file, `None` if the platform has no data directory.
+    pub fn default_path() -> Option<PathBuf> {
+        Some(dirs::data_dir()?.join("message_board").join("stats.json"))
+    }
+
+    /// Load stats from `path` and count a new session.
+    /// A missing or corrupted file starts the stats over.
+    pub fn load(path: PathBuf) -> Self {
+        let mut stats: UsageStats = fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        stats.sessions += 1;
+        Self {
+            path,
+            session_start: Instant::now(),
+            inner: Mutex::new(stats),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<UsageStats> {
+        self.inner.lock().pretty_unwrap()
+    }
+
+    pub fn record_message_sent(&self) {
+        self.lock().messages_sent += 1;
+    }
+
+    pub fn record_latency(&self, latency: Duration) {
+        let mut stats = self.lock();
+        stats.latency_total += latency;
+        stats.latency_samples += 1;
+    }
+
+    /// Write the stats to disk, including uptime of the current session.
+    pub fn save(&self) -> io::Result<()> {
+        let mut stats = self.lock().clone();
+        stats.uptime += self.session_start.elapsed();
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_vec_pretty(&stats)?)
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stats = self.lock();
+        let session_uptime = self.session_start.elapsed();
+        let average_latency = stats
+            .latency_total
+            .checked_div(u32::try_from(stats.latency_samples).unwrap_or(u32::MAX))
+            .unwrap_or_default();
+        writeln!(f, "Messages sent:   {}", stats.messages_sent)?;
+        writeln!(f, "Sessions:        {}", stats.sessions)?;
+        writeln!(f, "Session uptime:  {}s", session_uptime.as_secs())?;
+        writeln!(
+            f,
+            "Total uptime:    {}s",
+            (stats.uptime + session_uptime).as_secs()
+        )?;
+        writeln!(f, "Average latency: {}ms", average_latency.as_millis())?;
+        write!(f, "Stats file:      {}", self.path.display())
+    }
+}
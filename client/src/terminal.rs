@@ -0,0 +1,89 @@
+//! Terminal setup and restoration.
+//! The terminal must be restored no matter how the client exits, including panics.
+
+use std::{
+    io::{self, Stdout},
+    mem,
+    ops::{Deref, DerefMut},
+    panic,
+    sync::OnceLock,
+    thread::{self, ThreadId},
+};
+
+use ratatui::{
+    backend::CrosstermBackend,
+    crossterm::{
+        event::DisableMouseCapture,
+        execute,
+        terminal::{disable_raw_mode, LeaveAlternateScreen},
+    },
+    Terminal,
+};
+
+/// The thread that owns the terminal.
+static UI_THREAD: OnceLock<ThreadId> = OnceLock::new();
+
+/// Leave raw mode and alternate screen, disable mouse capture.
+/// Errors are ignored, as this may be called during a panic. Calling this more than once is
+/// harmless.
+pub fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let _ = execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture);
+}
+
+/// Install a panic hook that restores the terminal before printing the panic message.
+/// Panics on threads other than the one calling this are only logged, they are expected to be
+/// caught by whoever spawned the task so the UI can keep running.
+pub fn install_panic_hook() {
+    UI_THREAD.get_or_init(|| thread::current().id());
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |panic_info| {
+        log::error!("{panic_info}");
+        if UI_THREAD.get() == Some(&thread::current().id()) {
+            restore_terminal();
+            default_hook(panic_info);
+        }
+    }));
+}
+
+/// Sets up the terminal on creation, restores it on drop.
+pub struct TerminalGuard {
+    terminal: Option<Terminal<CrosstermBackend<Stdout>>>,
+}
+
+impl TerminalGuard {
+    pub fn new() -> Self {
+        Self {
+            terminal: Some(domtui::setup_terminal()),
+        }
+    }
+}
+
+impl Deref for TerminalGuard {
+    type Target = Terminal<CrosstermBackend<Stdout>>;
+
+    fn deref(&self) -> &Self::Target {
+        self.terminal.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for TerminalGuard {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.terminal.as_mut().unwrap()
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let Some(terminal) = self.terminal.take() else {
+            return;
+        };
+        if thread::panicking() {
+            // Anything that may panic again here would abort, so skip domtui's restore.
+            mem::forget(terminal);
+        } else {
+            domtui::restore_terminal(terminal);
+        }
+        restore_terminal();
+    }
+}
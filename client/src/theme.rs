@@ -0,0 +1,147 @@
+//! Styles of the TUI.
+//! Views should take their styles from a `Theme` instead of hardcoding colors, so that the
+//! monochrome theme can replace colors with other emphasis.
+
+use std::env;
+
+use ratatui::{
+    style::{Color::*, Modifier, Style},
+    widgets::BorderType,
+};
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    /// Message content and other normal text.
+    pub text: Style,
+    /// Less important text, like dates, quotes and link previews.
+    pub dim: Style,
+    /// Code blocks that are not syntax highlighted.
+    pub code: Style,
+    /// Patched onto the selected message or text.
+    pub selected: Style,
+    /// Patched onto matches of the search in the list of messages.
+    pub search_match: Style,
+    /// Patched onto messages marked for bulk actions, before `selected`.
+    pub marked: Style,
+    pub border: Style,
+    pub focused_border: Style,
+    pub focused_border_type: BorderType,
+    pub poll_bar_filled: Style,
+    pub poll_bar_empty: Style,
+    /// Bars of the activity chart on the stats screen.
+    pub chart_bar: Style,
+    /// Messages consisting solely of emoji, padded to stand out.
+    pub large_emoji: Style,
+    pub announcement: Style,
+    /// Banner shown while the server is read-only.
+    pub read_only: Style,
+    pub status_error: Style,
+    pub toast_info: Style,
+    pub toast_error: Style,
+    pub syntax_highlighting: bool,
+    /// Styles of author names, each author getting one picked by `author_style`.
+    /// Empty for no per-author styles.
+    pub author_palette: &'static [Style],
+}
+
+/// Distinct enough from each other and from the rest of the colored theme.
+const AUTHOR_PALETTE: &[Style] = &[
+    Style::new().fg(LightRed),
+    Style::new().fg(LightGreen),
+    Style::new().fg(LightYellow),
+    Style::new().fg(LightBlue),
+    Style::new().fg(LightMagenta),
+    Style::new().fg(LightCyan),
+    Style::new().fg(Red),
+    Style::new().fg(Green),
+    Style::new().fg(Yellow),
+    Style::new().fg(Magenta),
+];
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::colored()
+    }
+}
+
+impl Theme {
+    pub fn colored() -> Self {
+        Self {
+            text: Style::new().fg(White),
+            dim: Style::new().fg(DarkGray),
+            code: Style::new().fg(Gray),
+            selected: Style::new().add_modifier(Modifier::REVERSED),
+            search_match: Style::new().fg(Black).bg(LightYellow),
+            marked: Style::new().bg(Blue),
+            border: Style::new().fg(White),
+            focused_border: Style::new().fg(LightYellow),
+            focused_border_type: BorderType::Plain,
+            poll_bar_filled: Style::new().fg(LightCyan),
+            poll_bar_empty: Style::new().fg(DarkGray),
+            chart_bar: Style::new().fg(LightCyan),
+            large_emoji: Style::new().bg(DarkGray).add_modifier(Modifier::BOLD),
+            announcement: Style::new()
+                .fg(Black)
+                .bg(LightMagenta)
+                .add_modifier(Modifier::BOLD),
+            read_only: Style::new()
+                .fg(Black)
+                .bg(LightYellow)
+                .add_modifier(Modifier::BOLD),
+            status_error: Style::new().fg(LightRed),
+            toast_info: Style::new().fg(Black).bg(LightBlue),
+            toast_error: Style::new().fg(White).bg(Red),
+            syntax_highlighting: true,
+            author_palette: AUTHOR_PALETTE,
+        }
+    }
+
+    /// No colors at all, only bold, underline and reverse video.
+    /// Focus is shown with thick bold borders, selection with reverse video.
+    pub fn monochrome() -> Self {
+        Self {
+            text: Style::new(),
+            dim: Style::new(),
+            code: Style::new(),
+            selected: Style::new().add_modifier(Modifier::REVERSED | Modifier::BOLD),
+            search_match: Style::new().add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            marked: Style::new().add_modifier(Modifier::UNDERLINED),
+            border: Style::new(),
+            focused_border: Style::new().add_modifier(Modifier::BOLD),
+            focused_border_type: BorderType::Thick,
+            poll_bar_filled: Style::new(),
+            poll_bar_empty: Style::new(),
+            chart_bar: Style::new(),
+            large_emoji: Style::new().add_modifier(Modifier::BOLD),
+            announcement: Style::new().add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            read_only: Style::new().add_modifier(Modifier::REVERSED | Modifier::BOLD),
+            status_error: Style::new().add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            toast_info: Style::new().add_modifier(Modifier::REVERSED),
+            toast_error: Style::new().add_modifier(Modifier::REVERSED | Modifier::BOLD),
+            syntax_highlighting: false,
+            author_palette: &[],
+        }
+    }
+
+    /// Style of the author `name`, the same every time for the same name.
+    pub fn author_style(&self, name: &str) -> Style {
+        // FNV-1a, as `DefaultHasher` isn't guaranteed to be stable across Rust versions.
+        let hash = name.bytes().fold(0xcbf29ce484222325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+        });
+        match self.author_palette.len() {
+            0 => Style::new(),
+            len => self.author_palette[(hash % len as u64) as usize],
+        }
+    }
+
+    /// Is this the `monochrome` theme, or one as colorless?
+    pub fn is_monochrome(&self) -> bool {
+        self.text.fg.is_none()
+    }
+
+    /// Whether the `NO_COLOR` env var asks for no colors, see <https://no-color.org>.
+    pub fn no_color_requested() -> bool {
+        env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+    }
+}
@@ -0,0 +1,82 @@
+//! Formatting of timestamps. Every view should go through `TimeFormatter` to show a date, so
+//! that they all agree on timezone and format.
+
+use std::str::FromStr;
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, FixedOffset, Local, Utc,
+};
+
+pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d %H:%M";
+pub const DEFAULT_PRECISE_DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Timezone {
+    /// Timezone of the system.
+    #[default]
+    Local,
+    Utc,
+    /// A fixed offset from UTC, e.g. `+08:00`.
+    Fixed(FixedOffset),
+}
+
+impl FromStr for Timezone {
+    type Err = chrono::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "local" => Ok(Self::Local),
+            "utc" => Ok(Self::Utc),
+            _ => s.parse().map(Self::Fixed),
+        }
+    }
+}
+
+/// Returns `false` if `format` contains an invalid strftime specifier.
+/// Formatting with such a string would panic.
+pub fn is_valid_format(format: &str) -> bool {
+    !StrftimeItems::new(format).any(|item| matches!(item, Item::Error))
+}
+
+#[derive(Debug, Clone)]
+pub struct TimeFormatter {
+    pub timezone: Timezone,
+    /// Format of dates shown between messages.
+    pub date_format: Box<str>,
+    /// Format of dates where seconds matter, like in the error log.
+    pub precise_date_format: Box<str>,
+}
+
+impl Default for TimeFormatter {
+    fn default() -> Self {
+        Self {
+            timezone: Timezone::default(),
+            date_format: DEFAULT_DATE_FORMAT.into(),
+            precise_date_format: DEFAULT_PRECISE_DATE_FORMAT.into(),
+        }
+    }
+}
+
+impl TimeFormatter {
+    pub fn format(&self, date: DateTime<Utc>) -> String {
+        self.format_with(date, &self.date_format)
+    }
+
+    pub fn format_precise(&self, date: DateTime<Utc>) -> String {
+        self.format_with(date, &self.precise_date_format)
+    }
+
+    /// Hour of the day, e.g. for labels of charts.
+    pub fn format_hour(&self, date: DateTime<Utc>) -> String {
+        self.format_with(date, "%H")
+    }
+
+    fn format_with(&self, date: DateTime<Utc>, format: &str) -> String {
+        match self.timezone {
+            Timezone::Local => date.with_timezone(&Local).format(format).to_string(),
+            Timezone::Utc => date.format(format).to_string(),
+            Timezone::Fixed(offset) => date.with_timezone(&offset).format(format).to_string(),
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Transient info/error banners shown at the top of the screen.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::utils::PrettyUnwrap;
+
+/// How long a toast stays on screen.
+const TOAST_DURATION: Duration = Duration::from_secs(5);
+
+/// Number of past errors kept for the error log screen.
+const ERROR_LOG_CAPACITY: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToastKind {
+    Info,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct Toast {
+    pub kind: ToastKind,
+    pub message: Box<str>,
+    pub shown_at: Instant,
+}
+
+#[derive(Debug, Default)]
+struct ToastsInner {
+    queue: VecDeque<Toast>,
+    error_log: VecDeque<(DateTime<Utc>, Box<str>)>,
+}
+
+/// The toast queue.
+/// Cloning this gives another handle to the same queue, so tasks that can't lock `UIState` can
+/// still push toasts.
+#[derive(Debug, Clone, Default)]
+pub struct Toasts {
+    inner: Arc<Mutex<ToastsInner>>,
+}
+
+impl Toasts {
+    fn lock(&self) -> MutexGuard<ToastsInner> {
+        self.inner.lock().pretty_unwrap()
+    }
+
+    pub fn info(&self, message: impl Into<Box<str>>) {
+        self.push(ToastKind::Info, message.into());
+    }
+
+    /// Error toasts are also recorded in the error log.
+    pub fn error(&self, message: impl Into<Box<str>>) {
+        self.push(ToastKind::Error, message.into());
+    }
+
+    fn push(&self, kind: ToastKind, message: Box<str>) {
+        let mut inner = self.lock();
+        if kind == ToastKind::Error {
+            if inner.error_log.len() == ERROR_LOG_CAPACITY {
+                inner.error_log.pop_front();
+            }
+            inner.error_log.push_back((Utc::now(), message.clone()));
+        }
+        inner.queue.push_back(Toast {
+            kind,
+            message,
+            shown_at: Instant::now(),
+        });
+    }
+
+    /// Toasts that should currently be on screen, oldest first.
+    /// Expired toasts are dismissed.
+    pub fn visible(&self) -> Vec<Toast> {
+        let mut inner = self.lock();
+        inner
+            .queue
+            .retain(|toast| toast.shown_at.elapsed() < TOAST_DURATION);
+        inner.queue.iter().cloned().collect()
+    }
+
+    /// Dismiss all toasts currently on screen.
+    pub fn dismiss_all(&self) {
+        self.lock().queue.clear();
+    }
+
+    /// Past errors, oldest first.
+    pub fn error_log(&self) -> Vec<(DateTime<Utc>, Box<str>)> {
+        self.lock().error_log.iter().cloned().collect()
+    }
+}
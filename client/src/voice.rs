@@ -0,0 +1,88 @@
+//! Recording and playing voice notes.
+//! Audio I/O goes through an `AudioBackend`, external commands by default, so that the client
+//! doesn't depend on any audio library and builds wherever the rest of it does.
+
+use std::{
+    fmt::Debug,
+    io,
+    path::Path,
+    process::{Command, ExitStatus, Stdio},
+    time::Duration,
+};
+
+/// Longest voice note that can be recorded at once.
+pub const MAX_RECORDING_LENGTH: Duration = Duration::from_secs(5 * 60);
+
+/// Extension of recorded voice notes, recorders are expected to write this format.
+pub const RECORDING_EXTENSION: &str = "ogg";
+
+#[derive(Debug, thiserror::Error)]
+pub enum VoiceError {
+    #[error("no recorder configured, set one with --recorder")]
+    NoRecorder,
+    #[error("no player configured, set one with --player")]
+    NoPlayer,
+    #[error("can't run {command:?}: {error}")]
+    Spawn { command: Box<str>, error: io::Error },
+    #[error("{command:?} exited with {status}")]
+    Failed {
+        command: Box<str>,
+        status: ExitStatus,
+    },
+}
+
+/// Records and plays audio files. Both block until done.
+pub trait AudioBackend: Debug + Send + Sync {
+    /// Record `length` of audio into a new file at `path`.
+    fn record(&self, path: &Path, length: Duration) -> Result<(), VoiceError>;
+    fn play(&self, path: &Path) -> Result<(), VoiceError>;
+}
+
+/// Record and play by running user-configured commands.
+/// In the commands, `{path}` is replaced by the path of the audio file and `{seconds}` by the
+/// length to record. They are split on whitespace and run directly, not through a shell.
+#[derive(Debug, Clone, Default)]
+pub struct ExternalCommands {
+    /// e.g. `rec -q {path} trim 0 {seconds}`.
+    pub recorder: Option<Box<str>>,
+    /// e.g. `play -q {path}`.
+    pub player: Option<Box<str>>,
+}
+
+impl AudioBackend for ExternalCommands {
+    fn record(&self, path: &Path, length: Duration) -> Result<(), VoiceError> {
+        let recorder = self.recorder.as_deref().ok_or(VoiceError::NoRecorder)?;
+        run(recorder, path, length)
+    }
+
+    fn play(&self, path: &Path) -> Result<(), VoiceError> {
+        let player = self.player.as_deref().ok_or(VoiceError::NoPlayer)?;
+        run(player, path, Duration::ZERO)
+    }
+}
+
+fn run(command: &str, path: &Path, length: Duration) -> Result<(), VoiceError> {
+    let path = path.to_string_lossy();
+    let seconds = length.as_secs().to_string();
+    let mut args = command
+        .split_whitespace()
+        .map(|arg| arg.replace("{path}", &path).replace("{seconds}", &seconds));
+    let program = args.next().unwrap_or_default();
+    let status = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|error| VoiceError::Spawn {
+            command: command.into(),
+            error,
+        })?;
+    if !status.success() {
+        return Err(VoiceError::Failed {
+            command: command.into(),
+            status,
+        });
+    }
+    Ok(())
+}
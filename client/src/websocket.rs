@@ -0,0 +1,147 @@
+//! Subscription to server events over websocket.
+
+use std::{sync::Arc, time::Duration};
+
+use futures_util::StreamExt;
+use interface::{capabilities, CloseReason, Envelope, Event};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{tungstenite::Message as WsMessage, MaybeTlsStream, WebSocketStream};
+
+use crate::{error::ClientResult, sse, state::AppState};
+
+type WebSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Wait this long before reconnecting after the connection is lost.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Spawns a task that keeps a websocket connection with the server, reconnecting if needed, or
+/// right away if `AppState::resync` asks for it. Gives up if the server closes the connection
+/// for a `CloseReason` that would close it again, leaving polling to fetch messages.
+/// Falls back to server-sent events if the websocket can't be established, e.g. when a proxy
+/// doesn't let the upgrade through, and the server supports them.
+pub fn setup_websocket(app_state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut last_event_id = None;
+        loop {
+            let is_enveloped = app_state.supports(capabilities::EVENT_ENVELOPES);
+            let url = app_state.api().websocket_url(is_enveloped);
+            match connect(&app_state, url).await {
+                Ok(stream) => {
+                    // Older servers only push other events, messages still need to be polled.
+                    let is_streaming = app_state.supports(capabilities::MESSAGE_EVENTS);
+                    app_state.set_streaming_messages(is_streaming);
+                    let result = tokio::select! {
+                        result = run(&app_state, stream, is_enveloped) => Some(result),
+                        _ = app_state.reconnect_requested() => None,
+                    };
+                    app_state.set_streaming_messages(false);
+                    match result {
+                        Some(Ok(Some(reason))) if !reason.should_reconnect() => {
+                            log::warn!("Websocket closed by server, not reconnecting: {reason}");
+                            app_state
+                                .toasts()
+                                .error(format!("Disconnected from the server: {reason}"));
+                            return;
+                        }
+                        Some(Ok(Some(CloseReason::IdleTimeout))) => {
+                            log::info!("Websocket closed by server as idle, reconnecting");
+                            continue;
+                        }
+                        Some(Ok(Some(reason))) => {
+                            log::info!("Websocket closed by server: {reason}");
+                            app_state
+                                .toasts()
+                                .info(format!("Disconnected, {reason}, reconnecting"));
+                        }
+                        Some(Ok(None)) => log::info!("Websocket closed by server"),
+                        Some(Err(error)) => log::error!("Websocket error: {error}"),
+                        None => {
+                            log::info!("Reconnecting websocket to resync");
+                            continue;
+                        }
+                    }
+                }
+                Err(error) if !app_state.supports(capabilities::SERVER_SENT_EVENTS) => {
+                    log::error!("Can't connect websocket: {error}");
+                }
+                Err(error) => {
+                    log::warn!("Can't connect websocket, using server-sent events: {error}");
+                    // Messages are pushed over the event stream, polling only needs to catch up.
+                    app_state.set_streaming_messages(true);
+                    let result = tokio::select! {
+                        result = sse::run(&app_state, &mut last_event_id, is_enveloped) => {
+                            Some(result)
+                        }
+                        _ = app_state.reconnect_requested() => None,
+                    };
+                    app_state.set_streaming_messages(false);
+                    match result {
+                        Some(Ok(())) => log::info!("Event stream closed by server"),
+                        Some(Err(error)) => log::error!("Event stream error: {error}"),
+                        None => {
+                            log::info!("Reconnecting event stream to resync");
+                            continue;
+                        }
+                    }
+                }
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(RECONNECT_DELAY) => (),
+                _ = app_state.reconnect_requested() => (),
+            }
+        }
+    });
+}
+
+/// Like `tokio_tungstenite::connect_async`, but connecting like other requests, see `connector`.
+pub async fn connect(app_state: &AppState, url: String) -> ClientResult<WebSocket> {
+    let stream = app_state.api().connect_tcp(&url.parse()?).await?;
+    let (stream, _) = tokio_tungstenite::client_async(url, MaybeTlsStream::Plain(stream)).await?;
+    Ok(stream)
+}
+
+/// Returns why the server closed the connection, if it told.
+async fn run(
+    app_state: &AppState,
+    mut stream: WebSocket,
+    is_enveloped: bool,
+) -> ClientResult<Option<CloseReason>> {
+    log::info!("Websocket connected");
+    // Events may have been missed while disconnected.
+    app_state.fetch_topic().await?;
+    app_state.fetch_announcement().await?;
+    app_state.fetch_read_only().await?;
+    while let Some(message) = stream.next().await {
+        let message = message?;
+        app_state.api().bandwidth().record_received(message.len());
+        match message {
+            WsMessage::Text(text) => match parse_event(&text, is_enveloped) {
+                Ok(Some(event)) => app_state.handle_event(event),
+                Ok(None) => (),
+                Err(error) => log::warn!("Unrecognized websocket event {text:?}: {error}"),
+            },
+            WsMessage::Close(frame) => {
+                return Ok(frame.and_then(|frame| CloseReason::from_code(frame.code.into())));
+            }
+            _ => (),
+        }
+    }
+    Ok(None)
+}
+
+/// Parse an event pushed by the server, in an `Envelope` if `is_enveloped`.
+/// Returns `None` for envelopes of a version newer than this client understands.
+pub fn parse_event(json: &str, is_enveloped: bool) -> serde_json::Result<Option<Event>> {
+    if !is_enveloped {
+        return serde_json::from_str(json).map(Some);
+    }
+    let envelope: Envelope = serde_json::from_str(json)?;
+    if envelope.v > Envelope::VERSION {
+        log::warn!(
+            "Ignoring event in envelope of unsupported version {}",
+            envelope.v
+        );
+        return Ok(None);
+    }
+    Ok(Some(envelope.event))
+}
@@ -0,0 +1,404 @@
+use chrono::Utc;
+use interface::{
+    limits, routes, ErrorResponse, FetchAnnouncementForm, FetchAnnouncementResponse,
+    FetchAuditLogForm, FetchCapabilitiesForm, FetchCapabilitiesResponse, FetchLatestUpdateDateForm,
+    FetchLatestUpdateDateResponse, FetchMessagesForm, FetchMessagesResponse, FetchServerInfoForm,
+    FetchServerInfoResponse, FetchTopicForm, FetchTopicResponse, MessageId, SendMessageForm,
+    SendMessageResponse, SetTopicForm, VoteForm, VoteResponse, WebhookForm,
+    EXPECTED_RESPONSE_TO_HELLO,
+};
+use reqwest::{Method, Response, StatusCode};
+
+use crate::{target::Target, CheckResult, Outcome};
+
+/// Fail the check with a formatted reason unless `condition` holds.
+macro_rules! ensure {
+    ($condition:expr, $($reason:tt)+) => {
+        if !$condition {
+            return Ok(Outcome::Fail(format!($($reason)+)));
+        }
+    };
+}
+
+/// Larger than any reasonable request body limit.
+const OVERSIZED_BODY_LENGTH: usize = 16 * 1024 * 1024;
+
+pub async fn hello(target: &Target) -> CheckResult {
+    let response = target.request(routes::HELLO).send().await?;
+    ensure!(
+        response.status() == StatusCode::OK,
+        "expected status 200, got {}",
+        response.status()
+    );
+    let body = response.text().await?;
+    ensure!(
+        body == EXPECTED_RESPONSE_TO_HELLO,
+        "expected {EXPECTED_RESPONSE_TO_HELLO:?}, got {body:?}"
+    );
+    Ok(Outcome::Pass)
+}
+
+pub async fn send_and_fetch(target: &Target) -> CheckResult {
+    if is_invite_only(target).await? {
+        return Ok(invite_only_skip());
+    }
+    let content = format!("conformance test message {}", Utc::now().timestamp_micros());
+    let response = send_message(target, &content, None).await?;
+    ensure!(
+        response.status() == StatusCode::OK,
+        "expected status 200, got {}",
+        response.status()
+    );
+    let response: SendMessageResponse = response.json().await?;
+    ensure!(response.ok, "`ok` is false");
+    let messages = fetch_messages(target).await?;
+    ensure!(
+        messages.iter().any(|message| *message.content == *content),
+        "sent message is missing from the latest messages"
+    );
+    Ok(Outcome::Pass)
+}
+
+pub async fn fetch_latest_update_date(target: &Target) -> CheckResult {
+    let response = target
+        .request(routes::FETCH_LATEST_UPDATE_DATE)
+        .json(&FetchLatestUpdateDateForm {})
+        .send()
+        .await?;
+    ensure!(
+        response.status() == StatusCode::OK,
+        "expected status 200, got {}",
+        response.status()
+    );
+    let response: FetchLatestUpdateDateResponse = response.json().await?;
+    let messages = fetch_messages(target).await?;
+    let latest_message_date = messages.iter().map(|message| message.date).max();
+    ensure!(
+        response.latest_update_date >= latest_message_date,
+        "latest update date {:?} is before the latest message {latest_message_date:?}",
+        response.latest_update_date
+    );
+    Ok(Outcome::Pass)
+}
+
+pub async fn fetch_topic(target: &Target) -> CheckResult {
+    let response = target
+        .request(routes::FETCH_TOPIC)
+        .json(&FetchTopicForm {})
+        .send()
+        .await?;
+    ensure!(
+        response.status() == StatusCode::OK,
+        "expected status 200, got {}",
+        response.status()
+    );
+    let _: FetchTopicResponse = response.json().await?;
+    Ok(Outcome::Pass)
+}
+
+pub async fn fetch_announcement(target: &Target) -> CheckResult {
+    let response = target
+        .request(routes::FETCH_ANNOUNCEMENT)
+        .json(&FetchAnnouncementForm {})
+        .send()
+        .await?;
+    ensure!(
+        response.status() == StatusCode::OK,
+        "expected status 200, got {}",
+        response.status()
+    );
+    let _: FetchAnnouncementResponse = response.json().await?;
+    Ok(Outcome::Pass)
+}
+
+pub async fn fetch_server_info(target: &Target) -> CheckResult {
+    let response = target
+        .request(routes::FETCH_SERVER_INFO)
+        .json(&FetchServerInfoForm {})
+        .send()
+        .await?;
+    ensure!(
+        response.status() == StatusCode::OK,
+        "expected status 200, got {}",
+        response.status()
+    );
+    let _: FetchServerInfoResponse = response.json().await?;
+    Ok(Outcome::Pass)
+}
+
+pub async fn fetch_capabilities(target: &Target) -> CheckResult {
+    let response = target
+        .request(routes::FETCH_CAPABILITIES)
+        .json(&FetchCapabilitiesForm {})
+        .send()
+        .await?;
+    ensure!(
+        response.status() == StatusCode::OK,
+        "expected status 200, got {}",
+        response.status()
+    );
+    let _: FetchCapabilitiesResponse = response.json().await?;
+    Ok(Outcome::Pass)
+}
+
+pub async fn poll_and_vote(target: &Target) -> CheckResult {
+    if is_invite_only(target).await? {
+        return Ok(invite_only_skip());
+    }
+    let question = format!("conformance test poll {}", Utc::now().timestamp_micros());
+    let options: Box<[Box<str>]> = Box::new(["yes".into(), "no".into()]);
+    let response = send_message(target, &question, Some(options)).await?;
+    ensure!(
+        response.status() == StatusCode::OK,
+        "expected status 200 sending poll, got {}",
+        response.status()
+    );
+    let messages = fetch_messages(target).await?;
+    let Some(message) = messages
+        .iter()
+        .find(|message| *message.content == *question)
+    else {
+        return Ok(Outcome::Fail(
+            "sent poll is missing from the latest messages".into(),
+        ));
+    };
+    ensure!(message.poll.is_some(), "sent poll has no `poll`");
+    let vote = VoteForm {
+        message_id: message.id,
+        option: 0,
+    };
+    let response = target.request(routes::VOTE).json(&vote).send().await?;
+    ensure!(
+        response.status() == StatusCode::OK,
+        "expected status 200 voting, got {}",
+        response.status()
+    );
+    let response: VoteResponse = response.json().await?;
+    ensure!(response.ok, "`ok` is false");
+    let response = target.request(routes::VOTE).json(&vote).send().await?;
+    expect_error(response, StatusCode::CONFLICT).await
+}
+
+pub async fn invalid_json(target: &Target) -> CheckResult {
+    let response = target
+        .request(routes::SEND_MESSAGE)
+        .header("Content-Type", "application/json")
+        .body("{\"content\": ")
+        .send()
+        .await?;
+    expect_client_error(&response)
+}
+
+pub async fn missing_field(target: &Target) -> CheckResult {
+    let response = target
+        .request(routes::SEND_MESSAGE)
+        .json(&serde_json::json!({ "expires_in": null }))
+        .send()
+        .await?;
+    expect_client_error(&response)
+}
+
+pub async fn blank_message(target: &Target) -> CheckResult {
+    if is_invite_only(target).await? {
+        return Ok(invite_only_skip());
+    }
+    let response = send_message(target, "  \n ", None).await?;
+    expect_error(response, StatusCode::UNPROCESSABLE_ENTITY).await
+}
+
+pub async fn topic_too_long(target: &Target) -> CheckResult {
+    if is_invite_only(target).await? {
+        return Ok(invite_only_skip());
+    }
+    let form = SetTopicForm::new("a".repeat(limits::MAX_TOPIC_LENGTH as usize + 1));
+    let response = target.request(routes::SET_TOPIC).json(&form).send().await?;
+    expect_error(response, StatusCode::UNPROCESSABLE_ENTITY).await
+}
+
+pub async fn message_too_long(target: &Target) -> CheckResult {
+    if is_invite_only(target).await? {
+        return Ok(invite_only_skip());
+    }
+    let content = "a".repeat(limits::MAX_MESSAGE_LENGTH as usize + 1);
+    let response = send_message(target, &content, None).await?;
+    expect_error(response, StatusCode::UNPROCESSABLE_ENTITY).await
+}
+
+pub async fn invalid_poll(target: &Target) -> CheckResult {
+    if is_invite_only(target).await? {
+        return Ok(invite_only_skip());
+    }
+    let options: Box<[Box<str>]> = Box::new(["only option".into()]);
+    let response = send_message(target, "invalid poll", Some(options)).await?;
+    expect_error(response, StatusCode::UNPROCESSABLE_ENTITY).await
+}
+
+pub async fn vote_on_missing_message(target: &Target) -> CheckResult {
+    if is_invite_only(target).await? {
+        return Ok(invite_only_skip());
+    }
+    let vote = VoteForm {
+        message_id: MessageId(u64::MAX),
+        option: 0,
+    };
+    let response = target.request(routes::VOTE).json(&vote).send().await?;
+    expect_error(response, StatusCode::NOT_FOUND).await
+}
+
+pub async fn invalid_token(target: &Target) -> CheckResult {
+    let response = target
+        .request(routes::FETCH_AUDIT_LOG)
+        .bearer_auth("not a valid token")
+        .json(&audit_log_form())
+        .send()
+        .await?;
+    expect_error(response, StatusCode::UNAUTHORIZED).await
+}
+
+pub async fn guest_denied(target: &Target) -> CheckResult {
+    let response = target
+        .request(routes::FETCH_AUDIT_LOG)
+        .json(&audit_log_form())
+        .send()
+        .await?;
+    expect_error(response, StatusCode::FORBIDDEN).await
+}
+
+pub async fn unknown_webhook(target: &Target) -> CheckResult {
+    let (method, _) = routes::WEBHOOK;
+    let form = WebhookForm {
+        content: "conformance test webhook message".into(),
+    };
+    let response = target
+        .request((method, "/webhook/not-a-webhook-token"))
+        .json(&form)
+        .send()
+        .await?;
+    expect_error(response, StatusCode::NOT_FOUND).await
+}
+
+pub async fn oversized_body(target: &Target) -> CheckResult {
+    let form = SendMessageForm::new("a".repeat(OVERSIZED_BODY_LENGTH));
+    let response = target
+        .request(routes::SEND_MESSAGE)
+        .json(&form)
+        .send()
+        .await?;
+    ensure!(
+        response.status() == StatusCode::PAYLOAD_TOO_LARGE,
+        "expected status 413, got {}",
+        response.status()
+    );
+    Ok(Outcome::Pass)
+}
+
+pub async fn wrong_method(target: &Target) -> CheckResult {
+    let (_, path) = routes::SEND_MESSAGE;
+    let response = target.request_with_method(Method::GET, path).send().await?;
+    ensure!(
+        response.status() == StatusCode::METHOD_NOT_ALLOWED,
+        "expected status 405 for GET {path}, got {}",
+        response.status()
+    );
+    let (_, path) = routes::FETCH_TOPIC;
+    let response = target
+        .request_with_method(Method::DELETE, path)
+        .send()
+        .await?;
+    ensure!(
+        response.status() == StatusCode::METHOD_NOT_ALLOWED,
+        "expected status 405 for DELETE {path}, got {}",
+        response.status()
+    );
+    Ok(Outcome::Pass)
+}
+
+pub async fn unknown_route(target: &Target) -> CheckResult {
+    let response = target
+        .request_with_method(Method::GET, "/no_such_route")
+        .send()
+        .await?;
+    ensure!(
+        response.status() == StatusCode::NOT_FOUND,
+        "expected status 404, got {}",
+        response.status()
+    );
+    Ok(Outcome::Pass)
+}
+
+async fn send_message(
+    target: &Target,
+    content: &str,
+    poll_options: Option<Box<[Box<str>]>>,
+) -> Result<Response, reqwest::Error> {
+    let form = SendMessageForm {
+        poll_options,
+        ..SendMessageForm::new(content)
+    };
+    target
+        .request(routes::SEND_MESSAGE)
+        .json(&form)
+        .send()
+        .await
+}
+
+async fn fetch_messages(target: &Target) -> Result<Box<[interface::Message]>, reqwest::Error> {
+    let response: FetchMessagesResponse = target
+        .request(routes::FETCH_MESSAGES)
+        .json(&FetchMessagesForm::new(100))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(response.messages)
+}
+
+async fn is_invite_only(target: &Target) -> Result<bool, reqwest::Error> {
+    let response: FetchServerInfoResponse = target
+        .request(routes::FETCH_SERVER_INFO)
+        .json(&FetchServerInfoForm {})
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(response.invite_only)
+}
+
+fn invite_only_skip() -> Outcome {
+    Outcome::Skip("server is invite-only, guests can't post")
+}
+
+fn audit_log_form() -> FetchAuditLogForm {
+    FetchAuditLogForm {
+        count: 1,
+        after: None,
+    }
+}
+
+/// Any 4xx status.
+fn expect_client_error(response: &Response) -> CheckResult {
+    ensure!(
+        response.status().is_client_error(),
+        "expected a 4xx status, got {}",
+        response.status()
+    );
+    Ok(Outcome::Pass)
+}
+
+/// `status` with an `ErrorResponse` body.
+async fn expect_error(response: Response, status: StatusCode) -> CheckResult {
+    ensure!(
+        response.status() == status,
+        "expected status {}, got {}",
+        status.as_u16(),
+        response.status()
+    );
+    let body = response.text().await?;
+    ensure!(
+        serde_json::from_str::<ErrorResponse>(&body).is_ok(),
+        "expected an `ErrorResponse` body, got {body:?}"
+    );
+    Ok(Outcome::Pass)
+}
@@ -0,0 +1,147 @@
+//! Runs a battery of requests against a board server and reports which interface behaviors it
+//! conforms to. Useful for testing alternative server implementations.
+//!
+//! Usage: `conformance <server url>`. Note that this posts a few messages to the board.
+
+mod checks;
+mod target;
+
+use std::{env, future::Future, process::ExitCode};
+
+use target::Target;
+
+/// Result of a single check.
+#[derive(Debug)]
+pub enum Outcome {
+    Pass,
+    Fail(String),
+    /// The check doesn't apply to this server, e.g. posting to an invite-only server.
+    Skip(&'static str),
+}
+
+pub type CheckResult = Result<Outcome, reqwest::Error>;
+
+#[derive(Debug, Default)]
+struct Report {
+    passed: usize,
+    failed: usize,
+    skipped: usize,
+}
+
+impl Report {
+    async fn run(&mut self, name: &str, check: impl Future<Output = CheckResult>) {
+        match check.await {
+            Ok(Outcome::Pass) => {
+                self.passed += 1;
+                println!("PASS {name}");
+            }
+            Ok(Outcome::Fail(reason)) => {
+                self.failed += 1;
+                println!("FAIL {name}: {reason}");
+            }
+            Err(error) => {
+                self.failed += 1;
+                println!("FAIL {name}: request error: {error}");
+            }
+            Ok(Outcome::Skip(reason)) => {
+                self.skipped += 1;
+                println!("SKIP {name}: {reason}");
+            }
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let Some(url) = env::args().nth(1) else {
+        eprintln!("Usage: conformance <server url>");
+        return ExitCode::from(2);
+    };
+    let target = Target::new(url);
+    let mut report = Report::default();
+
+    // Valid requests.
+    report.run("hello", checks::hello(&target)).await;
+    report
+        .run("send and fetch message", checks::send_and_fetch(&target))
+        .await;
+    report
+        .run(
+            "fetch latest update date",
+            checks::fetch_latest_update_date(&target),
+        )
+        .await;
+    report
+        .run("fetch topic", checks::fetch_topic(&target))
+        .await;
+    report
+        .run("fetch announcement", checks::fetch_announcement(&target))
+        .await;
+    report
+        .run("fetch server info", checks::fetch_server_info(&target))
+        .await;
+    report
+        .run("fetch capabilities", checks::fetch_capabilities(&target))
+        .await;
+    report
+        .run("poll and vote", checks::poll_and_vote(&target))
+        .await;
+
+    // Malformed requests.
+    report
+        .run("invalid JSON", checks::invalid_json(&target))
+        .await;
+    report
+        .run("missing field", checks::missing_field(&target))
+        .await;
+    report
+        .run("blank message", checks::blank_message(&target))
+        .await;
+    report
+        .run("topic too long", checks::topic_too_long(&target))
+        .await;
+    report
+        .run("message too long", checks::message_too_long(&target))
+        .await;
+    report
+        .run("invalid poll", checks::invalid_poll(&target))
+        .await;
+    report
+        .run(
+            "vote on missing message",
+            checks::vote_on_missing_message(&target),
+        )
+        .await;
+    report
+        .run("invalid session token", checks::invalid_token(&target))
+        .await;
+    report
+        .run("guest denied audit log", checks::guest_denied(&target))
+        .await;
+    report
+        .run("unknown webhook", checks::unknown_webhook(&target))
+        .await;
+
+    // Oversized requests.
+    report
+        .run("oversized body", checks::oversized_body(&target))
+        .await;
+
+    // Wrong methods and routes.
+    report
+        .run("wrong method", checks::wrong_method(&target))
+        .await;
+    report
+        .run("unknown route", checks::unknown_route(&target))
+        .await;
+
+    println!(
+        "{} passed, {} failed, {} skipped",
+        report.passed, report.failed, report.skipped
+    );
+    if report.failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
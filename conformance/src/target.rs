@@ -0,0 +1,29 @@
+use interface::HttpMethod;
+use reqwest::{Method, RequestBuilder};
+
+/// The server under test.
+#[derive(Debug)]
+pub struct Target {
+    http: reqwest::Client,
+    url: String,
+}
+
+impl Target {
+    pub fn new(url: String) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            url: url.trim_end_matches('/').to_owned(),
+        }
+    }
+
+    /// Request to a route with the method it expects.
+    pub fn request(&self, (method, path): (HttpMethod, &str)) -> RequestBuilder {
+        let method = Method::try_from(method).expect("routes have known methods");
+        self.request_with_method(method, path)
+    }
+
+    /// Request to any path with any method, for requests that are meant to be wrong.
+    pub fn request_with_method(&self, method: Method, path: &str) -> RequestBuilder {
+        self.http.request(method, format!("{}{path}", self.url))
+    }
+}
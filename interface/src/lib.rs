@@ -1,7 +1,11 @@
-use std::fmt::{self, Debug, Display};
+use std::{
+    fmt::{self, Debug, Display},
+    str::FromStr,
+    time::Duration,
+};
 
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 /// HTTP Methods.
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
@@ -115,32 +119,455 @@ pub mod routes {
     pub const FETCH_LATEST_UPDATE_DATE: (HttpMethod, &str) =
         (HttpMethod::Get, "/fetch_latest_update_date");
     pub const WS: (HttpMethod, &str) = (HttpMethod::Get, "/ws");
+    /// Moderators and up.
+    pub const SET_TOPIC: (HttpMethod, &str) = (HttpMethod::Post, "/set_topic");
+    pub const FETCH_TOPIC: (HttpMethod, &str) = (HttpMethod::Get, "/fetch_topic");
+    pub const VOTE: (HttpMethod, &str) = (HttpMethod::Post, "/vote");
+    /// Moderators and up.
+    pub const ANNOUNCE: (HttpMethod, &str) = (HttpMethod::Post, "/announce");
+    pub const FETCH_ANNOUNCEMENT: (HttpMethod, &str) = (HttpMethod::Get, "/fetch_announcement");
+    /// Admin only.
+    pub const SET_READ_ONLY: (HttpMethod, &str) = (HttpMethod::Post, "/set_read_only");
+    /// Admin only.
+    pub const SET_ARCHIVED: (HttpMethod, &str) = (HttpMethod::Post, "/set_archived");
+    /// Moderators and up.
+    pub const FETCH_AUDIT_LOG: (HttpMethod, &str) = (HttpMethod::Get, "/fetch_audit_log");
+    /// Admin only.
+    pub const CREATE_SESSION: (HttpMethod, &str) = (HttpMethod::Post, "/create_session");
+    /// Admin only.
+    pub const REVOKE_SESSION: (HttpMethod, &str) = (HttpMethod::Post, "/revoke_session");
+    /// Admin only.
+    pub const CREATE_API_TOKEN: (HttpMethod, &str) = (HttpMethod::Post, "/create_api_token");
+    /// Admin only.
+    pub const REVOKE_API_TOKEN: (HttpMethod, &str) = (HttpMethod::Post, "/revoke_api_token");
+    /// Admin only.
+    pub const CREATE_INVITE: (HttpMethod, &str) = (HttpMethod::Post, "/create_invite");
+    pub const REGISTER: (HttpMethod, &str) = (HttpMethod::Post, "/register");
+    pub const FETCH_SERVER_INFO: (HttpMethod, &str) = (HttpMethod::Get, "/fetch_server_info");
+    /// Current time of the server, for clients to correct the skew of their clock.
+    pub const FETCH_TIME: (HttpMethod, &str) = (HttpMethod::Get, "/time");
+    /// See `capabilities`.
+    pub const FETCH_CAPABILITIES: (HttpMethod, &str) = (HttpMethod::Get, "/fetch_capabilities");
+    /// Admin only.
+    pub const CREATE_WEBHOOK: (HttpMethod, &str) = (HttpMethod::Post, "/create_webhook");
+    /// Admin only.
+    pub const DELETE_WEBHOOK: (HttpMethod, &str) = (HttpMethod::Post, "/delete_webhook");
+    /// Admin only.
+    pub const CREATE_SUBSCRIPTION: (HttpMethod, &str) = (HttpMethod::Post, "/create_subscription");
+    /// Admin only.
+    pub const DELETE_SUBSCRIPTION: (HttpMethod, &str) = (HttpMethod::Post, "/delete_subscription");
+    /// Admin only.
+    pub const LIST_ARCHIVES: (HttpMethod, &str) = (HttpMethod::Get, "/list_archives");
+    /// Admin only.
+    pub const RESTORE_ARCHIVE: (HttpMethod, &str) = (HttpMethod::Post, "/restore_archive");
+    /// Admin only.
+    pub const FETCH_SNAPSHOT_METRICS: (HttpMethod, &str) =
+        (HttpMethod::Get, "/fetch_snapshot_metrics");
+    /// Admin only.
+    pub const FETCH_STATS: (HttpMethod, &str) = (HttpMethod::Get, "/fetch_stats");
+    /// `/webhook/<token>`, with token of the webhook returned by `CREATE_WEBHOOK`.
+    pub const WEBHOOK: (HttpMethod, &str) = (HttpMethod::Post, "/webhook/:token");
+    /// Server-sent events, for when websockets are blocked. See `sse_events`.
+    /// Takes query parameters of `EventStreamForm`, as does `WS`.
+    pub const EVENTS: (HttpMethod, &str) = (HttpMethod::Get, "/events");
+    /// The body is the content of the file, with query parameters of `UploadAttachmentForm`.
+    pub const UPLOAD_ATTACHMENT: (HttpMethod, &str) = (HttpMethod::Post, "/upload_attachment");
+    /// `/attachment/<id>`, responding with the content of the file.
+    pub const FETCH_ATTACHMENT: (HttpMethod, &str) = (HttpMethod::Get, "/attachment/:id");
+    /// Senders deleting their own messages, with the tokens from `SendMessageResponse`.
+    pub const DELETE_MESSAGES: (HttpMethod, &str) = (HttpMethod::Post, "/delete_messages");
+    /// Messages containing a text, for history older than what clients have loaded.
+    pub const SEARCH_MESSAGES: (HttpMethod, &str) = (HttpMethod::Get, "/search_messages");
+    /// Admin only. Websocket clients connected.
+    pub const LIST_CONNECTIONS: (HttpMethod, &str) = (HttpMethod::Get, "/list_connections");
+    /// Admin only.
+    pub const DISCONNECT_CLIENT: (HttpMethod, &str) = (HttpMethod::Post, "/disconnect_client");
+    /// Adds a reaction to a message, or takes it back if already added.
+    pub const REACT: (HttpMethod, &str) = (HttpMethod::Post, "/react");
+    /// Takes query parameters of `FetchReactionsForm`.
+    /// Reactions aren't part of `Message`, so clients fetch them only for messages they show.
+    pub const FETCH_REACTIONS: (HttpMethod, &str) = (HttpMethod::Get, "/reactions");
+    /// `/profile/<session ID>`, as in `Message::author`.
+    pub const FETCH_PROFILE: (HttpMethod, &str) = (HttpMethod::Get, "/profile/:user");
+    /// Sessions only, each setting its own profile.
+    pub const SET_PROFILE: (HttpMethod, &str) = (HttpMethod::Put, "/profile");
+    /// Sessions only, replacing their own token.
+    pub const ROTATE_TOKEN: (HttpMethod, &str) = (HttpMethod::Post, "/rotate_token");
+    /// Sessions only, deleting themselves.
+    pub const DELETE_ACCOUNT: (HttpMethod, &str) = (HttpMethod::Delete, "/account");
+    /// Sessions only, each having its own block list.
+    pub const FETCH_BLOCKS: (HttpMethod, &str) = (HttpMethod::Get, "/blocks");
+    /// Sessions only, each having its own block list.
+    pub const SET_BLOCKS: (HttpMethod, &str) = (HttpMethod::Put, "/blocks");
+    pub const FORWARD_MESSAGE: (HttpMethod, &str) = (HttpMethod::Post, "/forward_message");
+    /// `/message/<ID>`, with the ID formatted as in `MessageId`.
+    /// Lets other boards fetch the original of a message forwarded from this one.
+    pub const FETCH_MESSAGE: (HttpMethod, &str) = (HttpMethod::Get, "/message/:id");
+    /// Admins only, the member list of a private board.
+    pub const FETCH_MEMBERS: (HttpMethod, &str) = (HttpMethod::Get, "/members");
+    /// Admins only, the member list of a private board.
+    pub const UPDATE_MEMBERS: (HttpMethod, &str) = (HttpMethod::Put, "/members");
+    /// Moderators and up. Deletes messages by ID, without their deletion tokens.
+    /// Responds with a `DeleteMessagesResponse`.
+    pub const REMOVE_MESSAGES: (HttpMethod, &str) = (HttpMethod::Post, "/remove_messages");
+    /// Moderators and up.
+    pub const BAN: (HttpMethod, &str) = (HttpMethod::Post, "/ban");
+    /// Moderators and up.
+    pub const UNBAN: (HttpMethod, &str) = (HttpMethod::Post, "/unban");
+    /// Admin only.
+    pub const SET_ROLE: (HttpMethod, &str) = (HttpMethod::Post, "/set_role");
+}
+
+/// Names of the server-sent events on `routes::EVENTS`.
+pub mod sse_events {
+    /// A new `Message`, with its `MessageId` in hex as event ID.
+    /// Clients can resume from the last message they received with header `Last-Event-ID`.
+    /// In an `Envelope` as `Event::MessagesAdded` if asked for with `EventStreamForm::v`.
+    pub const MESSAGE: &str = "message";
+    /// An `Event`, same as the ones over websocket.
+    pub const EVENT: &str = "event";
+}
+
+/// Names of optional features, as listed by `routes::FETCH_CAPABILITIES`.
+/// Servers predating the route support none of them.
+pub mod capabilities {
+    /// Polls, sent with `SendMessageForm::poll_options`, and `routes::VOTE`.
+    pub const POLLS: &str = "polls";
+    /// `SendMessageForm::expires_in`.
+    pub const DISAPPEARING_MESSAGES: &str = "disappearing_messages";
+    /// `Event::Delivered` for messages sent with `SendMessageForm::client_tag`.
+    pub const DELIVERY_RECEIPTS: &str = "delivery_receipts";
+    /// `routes::EVENTS`.
+    pub const SERVER_SENT_EVENTS: &str = "server_sent_events";
+    /// `SendMessageForm::content_kind`. Other servers treat every message as plain text.
+    pub const CONTENT_KINDS: &str = "content_kinds";
+    /// API tokens for bots, created with `routes::CREATE_API_TOKEN`.
+    pub const API_TOKENS: &str = "api_tokens";
+    /// `Event::MessagesAdded` over websocket.
+    pub const MESSAGE_EVENTS: &str = "message_events";
+    /// `routes::UPLOAD_ATTACHMENT` and `SendMessageForm::attachments`.
+    pub const ATTACHMENTS: &str = "attachments";
+    /// `routes::FETCH_TIME`.
+    pub const SERVER_TIME: &str = "server_time";
+    /// `Envelope`s, asked for with `EventStreamForm::v`.
+    pub const EVENT_ENVELOPES: &str = "event_envelopes";
+    /// `routes::SEARCH_MESSAGES`.
+    pub const SEARCH: &str = "search";
+    /// `SearchMessagesForm::regex`, `SearchMessagesForm::case_sensitive` and
+    /// `SearchMessagesForm::author`.
+    pub const REGEX_SEARCH: &str = "regex_search";
+    /// `SendMessageResponse::deletion_token` and `routes::DELETE_MESSAGES`.
+    pub const DELETE_MESSAGES: &str = "delete_messages";
+    /// `routes::REACT`, `routes::FETCH_REACTIONS` and `Event::ReactionsUpdated`.
+    pub const REACTIONS: &str = "reactions";
+    /// `routes::FETCH_PROFILE`, `routes::SET_PROFILE` and `Message::author`.
+    pub const PROFILES: &str = "profiles";
+    /// `routes::ROTATE_TOKEN` and `routes::DELETE_ACCOUNT`.
+    pub const ACCOUNTS: &str = "accounts";
+    /// `routes::FETCH_BLOCKS` and `routes::SET_BLOCKS`.
+    pub const BLOCKS: &str = "blocks";
+    /// `routes::FORWARD_MESSAGE` and `Message::forwarded_from`.
+    pub const FORWARDING: &str = "forwarding";
+    /// `routes::FETCH_MESSAGE`, `ForwardMessageForm::board` and `ForwardedFrom::board`.
+    pub const CROSS_BOARD_FORWARDING: &str = "cross_board_forwarding";
+    /// `routes::FETCH_MEMBERS`, `routes::UPDATE_MEMBERS` and
+    /// `FetchServerInfoResponse::is_private`.
+    pub const MEMBERS: &str = "members";
+    /// `routes::SET_ARCHIVED`, `FetchServerInfoResponse::archived` and `Event::Archived`.
+    pub const ARCHIVING: &str = "archiving";
+}
+
+/// Limits of the protocol, checked by `ValidationError`s.
+/// Servers list the limits they enforce in `FetchCapabilitiesResponse::limits`.
+pub mod limits {
+    /// Most messages `routes::FETCH_MESSAGES` responds with, larger `max_count`s are clamped.
+    pub const MAX_FETCH_COUNT: u32 = 100;
+    /// In characters.
+    pub const MAX_MESSAGE_LENGTH: u32 = 4000;
+    pub const MAX_POLL_OPTIONS: u32 = 10;
+    /// In characters, after trimming whitespaces.
+    pub const MAX_POLL_OPTION_LENGTH: u32 = 100;
+    /// In characters, after trimming whitespaces.
+    pub const MAX_TOPIC_LENGTH: u32 = 200;
+    pub const MAX_ATTACHMENTS: u32 = 10;
+    /// In characters, of `SearchMessagesForm::query`.
+    pub const MAX_SEARCH_QUERY_LENGTH: u32 = 200;
+    /// In characters, after trimming whitespaces. Enough for emojis joined by zero-width joiners.
+    pub const MAX_REACTION_LENGTH: u32 = 16;
+    /// In characters, after trimming whitespaces, as are the other fields of `Profile`.
+    pub const MAX_DISPLAY_NAME_LENGTH: u32 = 32;
+    pub const MAX_PRONOUNS_LENGTH: u32 = 16;
+    /// Enough for an emoji or a few letters.
+    pub const MAX_AVATAR_LENGTH: u32 = 8;
+    pub const MAX_BIO_LENGTH: u32 = 300;
+    /// Sessions a session can block.
+    pub const MAX_BLOCKS: u32 = 1000;
+    /// Sessions and API tokens added and removed by one `UpdateMembersForm`.
+    pub const MAX_MEMBER_CHANGES: u32 = 1000;
+    /// In seconds, of `BanForm::seconds`. Longer bans are up to admins, in the server's settings.
+    pub const MAX_BAN_DURATION: u32 = 30 * 24 * 60 * 60;
+    /// Messages deleted by one `RemoveMessagesForm`.
+    pub const MAX_REMOVED_MESSAGES: u32 = 1000;
 }
 
 pub const EXPECTED_RESPONSE_TO_HELLO: &str = "HELLO, WORLD";
 
+/// Body of any non-2xx response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ErrorResponse {
+    pub error: Box<str>,
+    /// Reason given by the admin, if the request was refused as the server is read-only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_only: Option<Box<str>>,
+    /// Why an upload to `routes::UPLOAD_ATTACHMENT` was refused, if it was for its file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachment_rejection: Option<AttachmentRejection>,
+}
+
+/// Why an upload to `routes::UPLOAD_ATTACHMENT` was refused.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "type")]
+pub enum AttachmentRejection {
+    #[error("file is larger than {max_size} bytes")]
+    TooLarge { max_size: u64 },
+    /// Uploads from the same address are limited to `quota` bytes a day.
+    #[error("daily upload quota of {quota} bytes exceeded")]
+    QuotaExceeded { quota: u64 },
+    /// `content_type` is the one detected by the server, which may differ from the one claimed.
+    #[error("files of type {content_type} are not allowed")]
+    TypeNotAllowed { content_type: Box<str> },
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct SendMessageForm {
     pub content: Box<str>,
+    /// Make the message disappear after some time.
+    #[serde(default)]
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<Object>))]
+    pub expires_in: Option<Duration>,
+    /// Make the message a poll with these options, `content` being the question.
+    #[serde(default)]
+    pub poll_options: Option<Box<[Box<str>]>>,
+    /// Chosen by the sender to recognize its message in `Event::Delivered`.
+    #[serde(default)]
+    pub client_tag: Option<Box<str>>,
+    /// How clients should render `content`. `ContentKind::System` is refused.
+    #[serde(default)]
+    pub content_kind: ContentKind,
+    /// Files uploaded with `routes::UPLOAD_ATTACHMENT` beforehand.
+    #[serde(default)]
+    pub attachments: Box<[Attachment]>,
+}
+
+impl SendMessageForm {
+    /// A plain text message that doesn't expire.
+    pub fn new(content: impl Into<Box<str>>) -> Self {
+        Self {
+            content: content.into(),
+            expires_in: None,
+            poll_options: None,
+            client_tag: None,
+            content_kind: ContentKind::default(),
+            attachments: Box::new([]),
+        }
+    }
+
+    pub fn expires_in(self, expires_in: Duration) -> Self {
+        Self {
+            expires_in: Some(expires_in),
+            ..self
+        }
+    }
+
+    /// Make the message a poll, `content` being the question.
+    pub fn poll(self, options: impl Into<Box<[Box<str>]>>) -> Self {
+        Self {
+            poll_options: Some(options.into()),
+            ..self
+        }
+    }
+
+    pub fn client_tag(self, client_tag: impl Into<Box<str>>) -> Self {
+        Self {
+            client_tag: Some(client_tag.into()),
+            ..self
+        }
+    }
+
+    pub fn content_kind(self, content_kind: ContentKind) -> Self {
+        Self {
+            content_kind,
+            ..self
+        }
+    }
+
+    pub fn attachments(self, attachments: impl Into<Box<[Attachment]>>) -> Self {
+        Self {
+            attachments: attachments.into(),
+            ..self
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.content_kind == ContentKind::System {
+            return Err(ValidationError::SystemMessage);
+        }
+        validate_content(&self.content)?;
+        if let Some(poll_options) = &self.poll_options {
+            validate_poll_options(poll_options)?;
+        }
+        let attachment_count_is_valid = u32::try_from(self.attachments.len())
+            .is_ok_and(|count| count <= limits::MAX_ATTACHMENTS);
+        if !attachment_count_is_valid {
+            return Err(ValidationError::TooManyAttachments);
+        }
+        Ok(())
+    }
+}
+
+/// Why a form is invalid.
+/// Checked by the server, and by clients before sending so they don't have to wait for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ValidationError {
+    #[error("message is empty or consists only of whitespaces")]
+    BlankMessage,
+    #[error("message is longer than {} characters", limits::MAX_MESSAGE_LENGTH)]
+    MessageTooLong,
+    #[error(
+        "polls need 2 to {} non-blank options of at most {} characters",
+        limits::MAX_POLL_OPTIONS,
+        limits::MAX_POLL_OPTION_LENGTH
+    )]
+    InvalidPoll,
+    #[error("topic is longer than {} characters", limits::MAX_TOPIC_LENGTH)]
+    TopicTooLong,
+    #[error("topic contains control characters, such as line breaks")]
+    TopicHasControlCharacters,
+    #[error("only the server can send system messages")]
+    SystemMessage,
+    #[error("messages can have at most {} attachments", limits::MAX_ATTACHMENTS)]
+    TooManyAttachments,
+    #[error(
+        "search query is longer than {} characters",
+        limits::MAX_SEARCH_QUERY_LENGTH
+    )]
+    SearchQueryTooLong,
+    #[error(
+        "reactions are 1 to {} characters, not counting whitespaces",
+        limits::MAX_REACTION_LENGTH
+    )]
+    InvalidReaction,
+    #[error(
+        "profiles have names of at most {} characters, pronouns of {}, avatars of {} and bios of \
+         {}",
+        limits::MAX_DISPLAY_NAME_LENGTH,
+        limits::MAX_PRONOUNS_LENGTH,
+        limits::MAX_AVATAR_LENGTH,
+        limits::MAX_BIO_LENGTH
+    )]
+    ProfileTooLong,
+    #[error("at most {} sessions can be blocked", limits::MAX_BLOCKS)]
+    TooManyBlocks,
+    #[error(
+        "at most {} members can be added or removed at once",
+        limits::MAX_MEMBER_CHANGES
+    )]
+    TooManyMemberChanges,
+    #[error("bans last 1 to {} seconds", limits::MAX_BAN_DURATION)]
+    InvalidBanDuration,
+    #[error(
+        "at most {} messages can be removed at once",
+        limits::MAX_REMOVED_MESSAGES
+    )]
+    TooManyRemovedMessages,
+}
+
+/// Length in characters, saturating at `u32::MAX`.
+fn char_count(s: &str) -> u32 {
+    u32::try_from(s.chars().count()).unwrap_or(u32::MAX)
+}
+
+pub fn validate_content(content: &str) -> Result<(), ValidationError> {
+    if content.trim().is_empty() {
+        return Err(ValidationError::BlankMessage);
+    }
+    if char_count(content) > limits::MAX_MESSAGE_LENGTH {
+        return Err(ValidationError::MessageTooLong);
+    }
+    Ok(())
+}
+
+pub fn validate_poll_options(options: &[Box<str>]) -> Result<(), ValidationError> {
+    let option_is_valid = |option: &Box<str>| {
+        let option = option.trim();
+        !option.is_empty() && char_count(option) <= limits::MAX_POLL_OPTION_LENGTH
+    };
+    let count_is_valid = u32::try_from(options.len())
+        .is_ok_and(|count| (2..=limits::MAX_POLL_OPTIONS).contains(&count));
+    if !count_is_valid || !options.iter().all(option_is_valid) {
+        return Err(ValidationError::InvalidPoll);
+    }
+    Ok(())
+}
+
+/// Blank topics are valid, they clear the topic.
+pub fn validate_topic(topic: &str) -> Result<(), ValidationError> {
+    if char_count(topic.trim()) > limits::MAX_TOPIC_LENGTH {
+        return Err(ValidationError::TopicTooLong);
+    }
+    if topic.chars().any(char::is_control) {
+        return Err(ValidationError::TopicHasControlCharacters);
+    }
+    Ok(())
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct SendMessageResponse {
     pub ok: bool,
+    /// ID the message was added with. Missing from older servers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<MessageId>,
+    /// Given to the sender only, to delete the message with `routes::DELETE_MESSAGES`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deletion_token: Option<Box<str>>,
 }
 
 impl SendMessageResponse {
     pub const fn ok() -> Self {
-        Self { ok: true }
+        Self {
+            ok: true,
+            id: None,
+            deletion_token: None,
+        }
     }
     pub const fn not_ok() -> Self {
-        Self { ok: false }
+        Self {
+            ok: false,
+            id: None,
+            deletion_token: None,
+        }
+    }
+
+    pub fn sent(id: MessageId, deletion_token: Box<str>) -> Self {
+        Self {
+            ok: true,
+            id: Some(id),
+            deletion_token: Some(deletion_token),
+        }
     }
 }
 
 /// `MessageId`s are made from hashing, they're discrete and their `Ord` implementation doesn't
 /// mean anything.
-#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// Formatted and serialized as 16 lowercase hex digits, as JavaScript can't represent every
+/// `u64` as a number.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "openapi", schema(value_type = String, example = "00c0ffee00c0ffee"))]
 pub struct MessageId(pub u64);
 
 impl Debug for MessageId {
@@ -149,14 +576,533 @@ impl Debug for MessageId {
     }
 }
 
+impl Display for MessageId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("message IDs are 1 to 16 hex digits")]
+pub struct InvalidMessageId;
+
+impl FromStr for MessageId {
+    type Err = InvalidMessageId;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // `from_str_radix` also accepts a sign.
+        if s.is_empty() || s.len() > 16 || !s.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(InvalidMessageId);
+        }
+        u64::from_str_radix(s, 16)
+            .map(Self)
+            .map_err(|_| InvalidMessageId)
+    }
+}
+
+impl Serialize for MessageId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for MessageId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        /// Numbers are still accepted, from peers and files predating hex IDs.
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Hex(String),
+            Number(u64),
+        }
+        match Repr::deserialize(deserializer)? {
+            Repr::Hex(hex) => hex.parse().map_err(de::Error::custom),
+            Repr::Number(number) => Ok(Self(number)),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct Message {
     pub id: MessageId,
     pub content: Box<str>,
     pub date: DateTime<Utc>,
+    /// Preview of the first link in the message.
+    /// Usually arrives later with an `Event::LinkPreview`, as it takes time to fetch.
+    #[serde(default)]
+    pub link_preview: Option<LinkPreview>,
+    /// For disappearing messages, when the message will be deleted.
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// If the message is a poll, the options and their tallies.
+    #[serde(default)]
+    pub poll: Option<Poll>,
+    /// Display name of the webhook that posted the message.
+    /// Messages from clients are anonymous, unless `author` is set.
+    #[serde(default)]
+    pub webhook_name: Option<Box<str>>,
+    /// Session ID of the poster, for fetching its profile with `routes::FETCH_PROFILE`.
+    /// Only set for sessions that had a profile when posting, others stay anonymous.
+    /// Session IDs are random and never given to another session.
+    #[serde(default)]
+    pub author: Option<u64>,
+    /// Where the message was first posted, if it was forwarded with `routes::FORWARD_MESSAGE`.
+    #[serde(default)]
+    pub forwarded_from: Option<ForwardedFrom>,
+    #[serde(default)]
+    pub content_kind: ContentKind,
+    #[serde(default)]
+    pub attachments: Box<[Attachment]>,
+}
+
+/// Attachments are content-addressed, `AttachmentId` being the BLAKE3 hash of the content.
+/// Identical files share the same ID, and are only stored once.
+/// Formatted and serialized as 64 lowercase hex digits.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "openapi", schema(value_type = String))]
+pub struct AttachmentId(pub [u8; 32]);
+
+impl Debug for AttachmentId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "attachment{self}")
+    }
+}
+
+impl Display for AttachmentId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("attachment IDs are 64 hex digits")]
+pub struct InvalidAttachmentId;
+
+impl FromStr for AttachmentId {
+    type Err = InvalidAttachmentId;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // `from_str_radix` alone would accept a sign.
+        if s.len() != 64 || !s.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(InvalidAttachmentId);
+        }
+        let mut bytes = [0; 32];
+        for (byte, hex) in bytes.iter_mut().zip(s.as_bytes().chunks(2)) {
+            // `hex` is ASCII, so it's valid UTF-8.
+            let hex = std::str::from_utf8(hex).unwrap();
+            *byte = u8::from_str_radix(hex, 16).map_err(|_| InvalidAttachmentId)?;
+        }
+        Ok(Self(bytes))
+    }
+}
+
+impl Serialize for AttachmentId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for AttachmentId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// A file attached to a message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Attachment {
+    pub id: AttachmentId,
+    /// File name, as given by the uploader.
+    pub name: Box<str>,
+    /// MIME type, detected by the server from the content where possible.
+    pub content_type: Box<str>,
+    /// In bytes.
+    pub size: u64,
+}
+
+impl Attachment {
+    pub fn kind(&self) -> AttachmentKind {
+        let (kind, _) = self.content_type.split_once('/').unwrap_or_default();
+        match kind {
+            "audio" => AttachmentKind::Audio,
+            _ => AttachmentKind::File,
+        }
+    }
+}
+
+/// How clients present an `Attachment`, from its `content_type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttachmentKind {
+    /// Audio, e.g. voice notes, which clients can offer to play.
+    Audio,
+    File,
+}
+
+/// Query parameters of `routes::UPLOAD_ATTACHMENT`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UploadAttachmentForm {
+    pub name: Box<str>,
+}
+
+/// To be sent in `SendMessageForm::attachments`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UploadAttachmentResponse {
+    pub attachment: Attachment,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Poll {
+    pub options: Box<[PollOption]>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PollOption {
+    pub text: Box<str>,
+    pub votes: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct VoteForm {
+    pub message_id: MessageId,
+    /// Index of the option.
+    pub option: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct VoteResponse {
+    pub ok: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ReactForm {
+    pub message_id: MessageId,
+    /// Usually an emoji, trimmed by the server.
+    pub reaction: Box<str>,
+}
+
+impl ReactForm {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let reaction = self.reaction.trim();
+        if reaction.is_empty() || char_count(reaction) > limits::MAX_REACTION_LENGTH {
+            return Err(ValidationError::InvalidReaction);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ReactResponse {
+    /// Whether the reaction was added, `false` if it was taken back.
+    pub added: bool,
+}
+
+/// Query parameters of `routes::FETCH_REACTIONS`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchReactionsForm {
+    /// Comma-separated, as query parameters can't hold lists.
+    /// Only the first `limits::MAX_FETCH_COUNT` are looked up.
+    pub message_ids: Box<str>,
+}
+
+impl FetchReactionsForm {
+    pub fn new(message_ids: &[MessageId]) -> Self {
+        let message_ids: Vec<String> = message_ids.iter().map(MessageId::to_string).collect();
+        Self {
+            message_ids: message_ids.join(",").into(),
+        }
+    }
+
+    pub fn message_ids(&self) -> Result<Vec<MessageId>, InvalidMessageId> {
+        self.message_ids
+            .split(',')
+            .filter(|id| !id.is_empty())
+            .take(limits::MAX_FETCH_COUNT as usize)
+            .map(str::parse)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchReactionsResponse {
+    /// Messages without reactions, or that don't exist, are left out.
+    pub reactions: Box<[MessageReactions]>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MessageReactions {
+    pub message_id: MessageId,
+    /// Most reacted first.
+    pub tallies: Box<[ReactionTally]>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ReactionTally {
+    pub reaction: Box<str>,
+    pub count: u32,
+}
+
+/// What a session shows about itself to others, looked up from `Message::author`.
+/// Fields are `None` if not set.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Profile {
+    #[serde(default)]
+    pub display_name: Option<Box<str>>,
+    #[serde(default)]
+    pub pronouns: Option<Box<str>>,
+    /// Text standing in for a picture, usually an emoji.
+    #[serde(default)]
+    pub avatar: Option<Box<str>>,
+    #[serde(default)]
+    pub bio: Option<Box<str>>,
+}
+
+impl Profile {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchProfileForm {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchProfileResponse {
+    pub profile: Profile,
+}
+
+/// Fields left `None` are kept as they are, empty ones are cleared.
+/// Values are trimmed by the server.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SetProfileForm {
+    #[serde(default)]
+    pub display_name: Option<Box<str>>,
+    #[serde(default)]
+    pub pronouns: Option<Box<str>>,
+    #[serde(default)]
+    pub avatar: Option<Box<str>>,
+    #[serde(default)]
+    pub bio: Option<Box<str>>,
+}
+
+impl SetProfileForm {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let fits = |field: &Option<Box<str>>, max_length| {
+            field
+                .as_deref()
+                .map_or(true, |value| char_count(value.trim()) <= max_length)
+        };
+        if fits(&self.display_name, limits::MAX_DISPLAY_NAME_LENGTH)
+            && fits(&self.pronouns, limits::MAX_PRONOUNS_LENGTH)
+            && fits(&self.avatar, limits::MAX_AVATAR_LENGTH)
+            && fits(&self.bio, limits::MAX_BIO_LENGTH)
+        {
+            Ok(())
+        } else {
+            Err(ValidationError::ProfileTooLong)
+        }
+    }
+
+    /// `profile` with the changes of the form applied.
+    pub fn apply(&self, mut profile: Profile) -> Profile {
+        let apply = |field: &mut Option<Box<str>>, value: &Option<Box<str>>| {
+            if let Some(value) = value {
+                let value = value.trim();
+                *field = (!value.is_empty()).then(|| value.into());
+            }
+        };
+        apply(&mut profile.display_name, &self.display_name);
+        apply(&mut profile.pronouns, &self.pronouns);
+        apply(&mut profile.avatar, &self.avatar);
+        apply(&mut profile.bio, &self.bio);
+        profile
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SetProfileResponse {
+    /// The profile after the changes.
+    pub profile: Profile,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchBlocksForm {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchBlocksResponse {
+    /// Session IDs, as in `Message::author`, in ascending order.
+    pub blocked: Box<[u64]>,
+}
+
+/// Replace the block list of the session. Messages of blocked sessions are left out of what
+/// the server sends to the session, both fetched and pushed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SetBlocksForm {
+    /// Session IDs, as in `Message::author`.
+    pub blocked: Box<[u64]>,
+}
+
+impl SetBlocksForm {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.blocked.len() > limits::MAX_BLOCKS as usize {
+            return Err(ValidationError::TooManyBlocks);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SetBlocksResponse {
+    /// The block list after the change, as in `FetchBlocksResponse`.
+    pub blocked: Box<[u64]>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchMembersForm {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchMembersResponse {
+    /// Session IDs, in ascending order.
+    pub members: Box<[u64]>,
+    /// IDs of the API tokens that are members, in ascending order.
+    #[serde(default)]
+    pub api_tokens: Box<[u64]>,
+}
+
+/// Change the member list of a private board. Sessions both added and removed are removed.
+/// The list is kept on boards that aren't private too, taking effect once they are.
+/// API tokens are members only if added here, like sessions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UpdateMembersForm {
+    /// Session IDs, as in `Message::author`.
+    #[serde(default)]
+    pub add: Box<[u64]>,
+    #[serde(default)]
+    pub remove: Box<[u64]>,
+    /// API token IDs, as in `CreateApiTokenResponse::id`.
+    #[serde(default)]
+    pub add_api_tokens: Box<[u64]>,
+    #[serde(default)]
+    pub remove_api_tokens: Box<[u64]>,
+}
+
+impl UpdateMembersForm {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let changes = self.add.len()
+            + self.remove.len()
+            + self.add_api_tokens.len()
+            + self.remove_api_tokens.len();
+        if changes > limits::MAX_MEMBER_CHANGES as usize {
+            return Err(ValidationError::TooManyMemberChanges);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UpdateMembersResponse {
+    /// The member list after the change, as in `FetchMembersResponse`.
+    pub members: Box<[u64]>,
+    #[serde(default)]
+    pub api_tokens: Box<[u64]>,
+}
+
+/// How clients render `Message::content`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ContentKind {
+    /// Kinds added after this version of the interface are read as plain text.
+    #[default]
+    #[serde(other)]
+    PlainText,
+    Markdown,
+    /// Shown as a block of code.
+    Code,
+    /// Notices from the server itself, clients can't send them.
+    System,
+}
+
+/// The original of a forwarded message, as found by the server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ForwardedFrom {
+    /// ID of the original message, which may have been deleted since.
+    pub id: MessageId,
+    pub date: DateTime<Utc>,
+    /// URL of the board the original was posted on, `None` if it's this board.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub board: Option<Box<str>>,
+}
+
+/// Repost a message as a new message, of this board or of the board at `board`. The server copies
+/// the content of the original, fetching it from `board` with `routes::FETCH_MESSAGE` as a guest,
+/// and attributes the new message to it, so attributions can't be made up.
+/// Attachments, polls and reactions of the original aren't carried over, its expiry is, and
+/// forwarding a forwarded message attributes it to the first original. Server notices, and
+/// messages of private boards, can't be forwarded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ForwardMessageForm {
+    pub message_id: MessageId,
+    /// URL of the board the message is on, as clients connect to it. `None` for this board.
+    /// Needs `capabilities::CROSS_BOARD_FORWARDING`, other servers look for the message on their
+    /// own board.
+    #[serde(default)]
+    pub board: Option<Box<str>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchMessageForm {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchMessageResponse {
+    pub message: Message,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LinkPreview {
+    pub url: Box<str>,
+    pub title: Box<str>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct FetchMessagesForm {
     /// Maximum number of recent messages to fetch.
     pub max_count: u32,
@@ -165,15 +1111,1025 @@ pub struct FetchMessagesForm {
     pub since: Option<DateTime<Utc>>,
 }
 
+impl FetchMessagesForm {
+    pub fn new(max_count: u32) -> Self {
+        Self {
+            max_count,
+            since: None,
+        }
+    }
+
+    pub fn since(self, since: DateTime<Utc>) -> Self {
+        Self {
+            since: Some(since),
+            ..self
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct FetchMessagesResponse {
     pub messages: Box<[Message]>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct FetchLatestUpdateDateForm {}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeleteMessagesForm {
+    pub messages: Box<[MessageDeletion]>,
+}
+
+/// A message to delete, and the proof of having sent it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MessageDeletion {
+    pub id: MessageId,
+    /// From `SendMessageResponse::deletion_token`.
+    pub deletion_token: Box<str>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeleteMessagesResponse {
+    /// Messages with invalid tokens, or already deleted, are left out.
+    pub deleted: Box<[MessageId]>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RemoveMessagesForm {
+    pub ids: Box<[MessageId]>,
+}
+
+impl RemoveMessagesForm {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let count_is_valid =
+            u32::try_from(self.ids.len()).is_ok_and(|count| count <= limits::MAX_REMOVED_MESSAGES);
+        if !count_is_valid {
+            return Err(ValidationError::TooManyRemovedMessages);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SearchMessagesForm {
+    /// Matched against the content of messages, as text or as a regex if `regex`.
+    pub query: Box<str>,
+    /// Maximum number of matches, the most recent ones first.
+    /// Clamped to `limits::MAX_FETCH_COUNT`, as for `FetchMessagesForm`.
+    pub max_count: u32,
+    /// Only search messages older than this, to page through matches.
+    #[serde(default)]
+    pub before: Option<DateTime<Utc>>,
+    /// Treat `query` as a regex, in the syntax of the `regex` crate. Servers without
+    /// `capabilities::REGEX_SEARCH` search for it as text.
+    #[serde(default)]
+    pub regex: bool,
+    /// Queries are matched ignoring case by default.
+    #[serde(default)]
+    pub case_sensitive: bool,
+    /// Only messages from the webhook with this display name, ignoring case.
+    #[serde(default)]
+    pub author: Option<Box<str>>,
+}
+
+impl SearchMessagesForm {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if char_count(&self.query) > limits::MAX_SEARCH_QUERY_LENGTH {
+            return Err(ValidationError::SearchQueryTooLong);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SearchMessagesResponse {
+    /// Oldest first, as with `FetchMessagesResponse`.
+    pub messages: Box<[Message]>,
+    /// Whether the search was cut short by the server's time limit, in which case older
+    /// messages may match too. Search again with `SearchMessagesForm::before` to continue.
+    #[serde(default)]
+    pub timed_out: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct FetchLatestUpdateDateResponse {
     pub latest_update_date: Option<DateTime<Utc>>,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SetTopicForm {
+    /// Empty topic clears the topic.
+    pub topic: Box<str>,
+}
+
+impl SetTopicForm {
+    pub fn new(topic: impl Into<Box<str>>) -> Self {
+        Self {
+            topic: topic.into(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_topic(&self.topic)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SetTopicResponse {
+    pub ok: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchTopicForm {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchTopicResponse {
+    pub topic: Option<Box<str>>,
+}
+
+/// Announcement from the operator, pinned on top of the message list.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Announcement {
+    pub content: Box<str>,
+    pub date: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AnnounceForm {
+    /// Blank content takes down the current announcement.
+    pub content: Box<str>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AnnounceResponse {
+    pub ok: bool,
+}
+
+/// Put the server into read-only mode, e.g. for maintenance, or take it out of it.
+/// In read-only mode fetches work as usual, but messages, votes and topic changes are refused.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SetReadOnlyForm {
+    /// Shown to users, `None` leaves read-only mode.
+    pub reason: Option<Box<str>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SetReadOnlyResponse {
+    pub ok: bool,
+}
+
+/// Archive the board, or bring it back. An archived board is read-only regardless of
+/// `SetReadOnlyForm`, and clients leave it out of their server lists unless asked to show
+/// archived servers. Its messages can still be fetched as usual.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SetArchivedForm {
+    pub archived: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SetArchivedResponse {
+    pub ok: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchAnnouncementForm {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchAnnouncementResponse {
+    pub announcement: Option<Announcement>,
+}
+
+/// Role of a session, ordered from least to most privileged.
+/// Requests without a session token are guests.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    #[default]
+    Guest,
+    User,
+    Moderator,
+    Admin,
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Guest => write!(f, "guest"),
+            Self::User => write!(f, "user"),
+            Self::Moderator => write!(f, "moderator"),
+            Self::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+/// What an API token may be used for.
+/// Interactive sessions and guests aren't restricted by scopes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ApiScope {
+    /// Send messages, vote and set the topic.
+    Send,
+    /// Fetch messages and subscribe to them.
+    Read,
+}
+
+impl Display for ApiScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Send => write!(f, "send"),
+            Self::Read => write!(f, "read"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateSessionForm {
+    pub role: Role,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateSessionResponse {
+    /// Public ID of the session, for revoking it and in the audit log.
+    pub id: u64,
+    /// Secret to be sent as `Authorization: Bearer <token>`.
+    pub token: Box<str>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RevokeSessionForm {
+    pub id: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RevokeSessionResponse {
+    pub ok: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SetRoleForm {
+    /// `CreateSessionResponse::id`.
+    pub id: u64,
+    pub role: Role,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SetRoleResponse {
+    pub ok: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateApiTokenForm {
+    /// Who the token is for, e.g. the name of a bot. Only shown in the audit log.
+    pub name: Box<str>,
+    pub scopes: Box<[ApiScope]>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateApiTokenResponse {
+    /// Public ID of the token, for revoking it and in the audit log.
+    pub id: u64,
+    /// Secret to be sent as `Authorization: Bearer <token>`. Doesn't expire.
+    pub token: Box<str>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RevokeApiTokenForm {
+    pub id: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RevokeApiTokenResponse {
+    pub ok: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateInviteForm {
+    /// Number of times the code can be used to register.
+    pub uses: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateInviteResponse {
+    pub code: Box<str>,
+}
+
+/// Redeem an invite code for a session with role `User`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RegisterForm {
+    pub invite_code: Box<str>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RegisterResponse {
+    pub id: u64,
+    /// Secret to be sent as `Authorization: Bearer <token>`.
+    pub token: Box<str>,
+}
+
+/// Replace the token of the session with a new one, the old one no longer being valid.
+/// Sessions have no credential other than their token, so the request's token is all it takes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RotateTokenForm {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RotateTokenResponse {
+    pub token: Box<str>,
+}
+
+/// Revoke the session and drop its profile. Its messages are deleted or made anonymous, as
+/// the server is configured to. Like `RotateTokenForm`, only the request's token is needed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeleteAccountForm {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeleteAccountResponse {
+    /// Messages of the session that were deleted.
+    pub deleted: Box<[MessageId]>,
+    /// Messages of the session that were kept but made anonymous, `Message::author` being
+    /// cleared. Also told to clients with `Event::MessagesAnonymized`.
+    pub anonymized: Box<[MessageId]>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchServerInfoForm {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchServerInfoResponse {
+    /// Only sessions of role `User` or higher can post, guests have to register with an invite
+    /// code first.
+    pub invite_only: bool,
+    /// Reason the server is read-only, `None` if it isn't.
+    #[serde(default)]
+    pub read_only: Option<Box<str>>,
+    /// See `SetArchivedForm`.
+    #[serde(default)]
+    pub archived: bool,
+    /// Only members, added by admins with `routes::UPDATE_MEMBERS`, moderators and admins can
+    /// read and post. Others only get `routes::FETCH_SERVER_INFO` and the like.
+    #[serde(default)]
+    pub is_private: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchTimeForm {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchTimeResponse {
+    /// In UTC, when the server handled the request.
+    pub now: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchCapabilitiesForm {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchCapabilitiesResponse {
+    /// Names from `capabilities`. Unknown names should be ignored.
+    pub capabilities: Box<[Box<str>]>,
+    /// Servers predating the field enforce the defaults.
+    #[serde(default)]
+    pub limits: Limits,
+}
+
+/// Limits enforced by a server, see `limits`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(default)]
+pub struct Limits {
+    pub max_fetch_count: u32,
+    pub max_message_length: u32,
+    pub max_poll_options: u32,
+    pub max_poll_option_length: u32,
+    pub max_topic_length: u32,
+    pub max_attachments: u32,
+    pub max_search_query_length: u32,
+    pub max_reaction_length: u32,
+    pub max_display_name_length: u32,
+    pub max_pronouns_length: u32,
+    pub max_avatar_length: u32,
+    pub max_bio_length: u32,
+    pub max_blocks: u32,
+    pub max_member_changes: u32,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_fetch_count: limits::MAX_FETCH_COUNT,
+            max_message_length: limits::MAX_MESSAGE_LENGTH,
+            max_poll_options: limits::MAX_POLL_OPTIONS,
+            max_poll_option_length: limits::MAX_POLL_OPTION_LENGTH,
+            max_topic_length: limits::MAX_TOPIC_LENGTH,
+            max_attachments: limits::MAX_ATTACHMENTS,
+            max_search_query_length: limits::MAX_SEARCH_QUERY_LENGTH,
+            max_reaction_length: limits::MAX_REACTION_LENGTH,
+            max_display_name_length: limits::MAX_DISPLAY_NAME_LENGTH,
+            max_pronouns_length: limits::MAX_PRONOUNS_LENGTH,
+            max_avatar_length: limits::MAX_AVATAR_LENGTH,
+            max_bio_length: limits::MAX_BIO_LENGTH,
+            max_blocks: limits::MAX_BLOCKS,
+            max_member_changes: limits::MAX_MEMBER_CHANGES,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateWebhookForm {
+    /// Shown with messages posted through the webhook.
+    pub display_name: Box<str>,
+    /// Maximum number of messages per minute.
+    pub rate_limit: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateWebhookResponse {
+    pub id: u64,
+    /// Secret that goes in the webhook's URL.
+    pub token: Box<str>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeleteWebhookForm {
+    pub id: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeleteWebhookResponse {
+    pub ok: bool,
+}
+
+/// Body of posts to `/webhook/<token>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct WebhookForm {
+    pub content: Box<str>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct WebhookResponse {
+    pub ok: bool,
+}
+
+/// Subscribe an external URL to events of the board.
+/// The server POSTs a `SubscriptionEvent` as JSON to the URL for every event, with header
+/// `X-Message-Board-Signature: sha256=<HMAC-SHA256 of the body keyed with the secret, in hex>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateSubscriptionForm {
+    pub url: Box<str>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateSubscriptionResponse {
+    pub id: u64,
+    /// Key of the signatures.
+    pub secret: Box<str>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeleteSubscriptionForm {
+    pub id: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeleteSubscriptionResponse {
+    pub ok: bool,
+}
+
+/// An archive of messages deleted by the retention task, written right before deleting them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ArchiveInfo {
+    /// File name, starting with the date of the purge.
+    pub name: Box<str>,
+    /// In bytes.
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ListArchivesForm {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ListArchivesResponse {
+    /// Oldest first.
+    pub archives: Box<[ArchiveInfo]>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RestoreArchiveForm {
+    /// `ArchiveInfo::name`.
+    pub name: Box<str>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RestoreArchiveResponse {
+    /// Number of messages added back, messages that weren't deleted are skipped.
+    pub restored: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchSnapshotMetricsForm {}
+
+/// Snapshots written since the server started, including compactions of the write-ahead log.
+/// All zero if the server keeps messages in memory only.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchSnapshotMetricsResponse {
+    /// Successful snapshots.
+    pub snapshots: u64,
+    pub failures: u64,
+    /// Durations of successful snapshots, `None` if there are none yet.
+    pub last_duration_ms: Option<u64>,
+    pub max_duration_ms: Option<u64>,
+    pub average_duration_ms: Option<u64>,
+    pub last_snapshot_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchStatsForm {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchStatsResponse {
+    /// Addresses seen in the last hour or banned, busiest first.
+    pub quotas: Box<[QuotaUsage]>,
+    /// Messages posted in each of the last 24 hours, oldest first, the last one being the hour up
+    /// to now. Messages deleted since aren't counted.
+    #[serde(default)]
+    pub messages_per_hour: Box<[u64]>,
+}
+
+/// Recent usage of the server by an IP address.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct QuotaUsage {
+    pub address: Box<str>,
+    pub requests_last_minute: u64,
+    pub messages_last_hour: u64,
+    /// Request bodies, as told by their `Content-Length`.
+    pub bytes_sent_last_hour: u64,
+    #[serde(default)]
+    pub bytes_uploaded_last_day: u64,
+    /// End of a temporary ban for going far over the rate limit.
+    pub banned_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ListConnectionsForm {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ListConnectionsResponse {
+    /// Oldest first.
+    pub connections: Box<[ConnectionInfo]>,
+}
+
+/// A websocket client connected to the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ConnectionInfo {
+    /// For `DisconnectClientForm`, not reused while the server is running.
+    pub id: u64,
+    pub address: Box<str>,
+    pub connected_at: DateTime<Utc>,
+    /// Of the session the client connected with.
+    pub role: Role,
+    /// Whether the client asked for events in `Envelope`s.
+    pub is_enveloped: bool,
+    /// Events waiting to be sent to the client, as of the last one sent.
+    pub queued_events: u64,
+    /// Events the client missed by lagging too far behind, since it connected.
+    pub dropped_events: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DisconnectClientForm {
+    /// `ConnectionInfo::id`.
+    pub id: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DisconnectClientResponse {
+    pub ok: bool,
+}
+
+/// Bans an IP address for a while, closing its connections.
+/// Bans are kept in memory, permanent ones are in the server's settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BanForm {
+    /// E.g. `ConnectionInfo::address`, or the address of an `AuditActor::Client`.
+    pub address: Box<str>,
+    /// At most `limits::MAX_BAN_DURATION`.
+    pub seconds: u64,
+}
+
+impl BanForm {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if !(1..=u64::from(limits::MAX_BAN_DURATION)).contains(&self.seconds) {
+            return Err(ValidationError::InvalidBanDuration);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BanResponse {
+    pub ok: bool,
+}
+
+/// Lifts a ban made with `BanForm`, or for going over the rate limit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UnbanForm {
+    pub address: Box<str>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UnbanResponse {
+    /// `false` if the address wasn't banned.
+    pub ok: bool,
+}
+
+/// Events delivered to subscribed URLs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "type")]
+pub enum SubscriptionEvent {
+    NewMessage { message: Message },
+}
+
+/// Who performed an audited action.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "type")]
+pub enum AuditActor {
+    /// Holder of a session token. Session 0 is the admin token from the server's config.
+    Session { id: u64, role: Role },
+    /// The server itself, e.g. the retention task.
+    System,
+    /// An anonymous client, identified by IP address.
+    Client { address: Box<str> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "type")]
+pub enum AuditAction {
+    /// `content` is `None` if the announcement was taken down.
+    Announce {
+        content: Option<Box<str>>,
+    },
+    SetTopic {
+        topic: Box<str>,
+    },
+    SetReadOnly {
+        reason: Option<Box<str>>,
+    },
+    SetArchived {
+        archived: bool,
+    },
+    /// Messages deleted by the retention task, as they expired or got too old.
+    PurgeExpired {
+        ids: Box<[MessageId]>,
+    },
+    CreateSession {
+        id: u64,
+        role: Role,
+    },
+    RevokeSession {
+        id: u64,
+    },
+    CreateApiToken {
+        id: u64,
+        name: Box<str>,
+        scopes: Box<[ApiScope]>,
+    },
+    RevokeApiToken {
+        id: u64,
+    },
+    CreateInvite {
+        uses: u32,
+    },
+    /// A guest redeemed an invite code for session `id`.
+    Register {
+        id: u64,
+    },
+    /// Session `id` deleted itself, with the numbers of its messages deleted and made anonymous.
+    DeleteAccount {
+        id: u64,
+        deleted: u64,
+        anonymized: u64,
+    },
+    CreateWebhook {
+        id: u64,
+        display_name: Box<str>,
+    },
+    DeleteWebhook {
+        id: u64,
+    },
+    CreateSubscription {
+        id: u64,
+        url: Box<str>,
+    },
+    DeleteSubscription {
+        id: u64,
+    },
+    RestoreArchive {
+        name: Box<str>,
+        restored: u64,
+    },
+    /// An address was banned for going far over the rate limit.
+    TemporaryBan {
+        address: Box<str>,
+        seconds: u64,
+    },
+    DisconnectClient {
+        id: u64,
+        address: Box<str>,
+    },
+    UpdateMembers {
+        added: Box<[u64]>,
+        removed: Box<[u64]>,
+        #[serde(default)]
+        added_api_tokens: Box<[u64]>,
+        #[serde(default)]
+        removed_api_tokens: Box<[u64]>,
+    },
+    /// Messages deleted by a moderator, rather than by their senders.
+    RemoveMessages {
+        ids: Box<[MessageId]>,
+    },
+    Ban {
+        address: Box<str>,
+        seconds: u64,
+    },
+    Unban {
+        address: Box<str>,
+    },
+    SetRole {
+        id: u64,
+        role: Role,
+    },
+}
+
+/// An entry in the server's append-only audit log of moderation actions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AuditEntry {
+    pub date: DateTime<Utc>,
+    pub actor: AuditActor,
+    pub action: AuditAction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchAuditLogForm {
+    /// Maximum number of entries to fetch, latest entries are kept.
+    pub count: usize,
+    /// Only fetch entries after this date.
+    #[serde(default)]
+    pub after: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FetchAuditLogResponse {
+    /// Oldest first.
+    pub entries: Box<[AuditEntry]>,
+}
+
+/// Query parameters of `routes::WS` and `routes::EVENTS`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EventStreamForm {
+    /// Latest `Envelope::VERSION` the client understands.
+    /// Events are sent bare if `None`, for clients predating envelopes.
+    #[serde(default)]
+    pub v: Option<u16>,
+}
+
+/// Wraps every `Event` pushed to clients that asked for it with `EventStreamForm::v`.
+/// Kinds of events added later deserialize as `Event::Unknown`, so that they can be sent without
+/// breaking older clients. `v` is only bumped on incompatible changes to existing kinds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Envelope {
+    pub v: u16,
+    pub event: Event,
+}
+
+impl Envelope {
+    pub const VERSION: u16 = 1;
+
+    pub fn new(event: Event) -> Self {
+        Self {
+            v: Self::VERSION,
+            event,
+        }
+    }
+}
+
+/// Events pushed from server to clients over websocket, serialized as JSON text messages.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "type")]
+pub enum Event {
+    /// New messages, oldest first. Messages posted in a burst are batched into one event.
+    /// Not sent over server-sent events, which carry each message as its own event.
+    /// Messages posted by sessions blocked by the client's session are left out, and the event
+    /// isn't sent if none are left.
+    MessagesAdded {
+        messages: Box<[Message]>,
+    },
+    TopicChanged {
+        topic: Option<Box<str>>,
+    },
+    LinkPreview {
+        id: MessageId,
+        link_preview: LinkPreview,
+    },
+    Announcement {
+        announcement: Option<Announcement>,
+    },
+    /// The server entered read-only mode for `reason`, or left it if `None`.
+    ReadOnly {
+        reason: Option<Box<str>>,
+    },
+    /// The board was archived, or brought back if `false`, see `SetArchivedForm`.
+    Archived {
+        archived: bool,
+    },
+    MessagesDeleted {
+        ids: Box<[MessageId]>,
+    },
+    /// Messages no longer attributed to anyone, as their author deleted its account.
+    /// `Message::author` of each is cleared.
+    MessagesAnonymized {
+        ids: Box<[MessageId]>,
+    },
+    PollUpdated {
+        id: MessageId,
+        poll: Poll,
+    },
+    /// Reactions to a message changed. Tallies aren't included, clients showing the message
+    /// fetch them with `routes::FETCH_REACTIONS`.
+    ReactionsUpdated {
+        id: MessageId,
+    },
+    /// A message sent with a `client_tag` has been added.
+    /// Only sent to clients of the session it was sent by, or of the address for guests.
+    Delivered {
+        client_tag: Box<str>,
+        id: MessageId,
+    },
+    /// A message sent over websocket with a `client_tag` was rejected.
+    /// Only sent to the websocket the message came from.
+    Rejected {
+        client_tag: Box<str>,
+        error: Box<str>,
+    },
+    /// An event of a kind this version of the interface doesn't know, to be ignored.
+    /// Never sent.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Requests from clients to server over websocket, serialized as JSON text messages.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "type")]
+pub enum WsRequest {
+    /// Same as `routes::SEND_MESSAGE`, but without a response.
+    /// Set `client_tag` to be told whether the message was delivered.
+    SendMessage(SendMessageForm),
+}
+
+/// Why the server closed a websocket, sent as the code of the close frame, with the `Display` of
+/// the reason as its reason text. Codes are in the range left for applications.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseReason {
+    /// The server is stopping, and is likely back soon.
+    ServerShutdown,
+    /// The client's address is banned.
+    Banned,
+    /// The client sent something that isn't part of the protocol, e.g. binary messages.
+    ProtocolError,
+    /// The client stopped answering pings.
+    IdleTimeout,
+    /// An admin disconnected the client with `routes::DISCONNECT_CLIENT`.
+    Disconnected,
+}
+
+impl CloseReason {
+    pub const fn code(self) -> u16 {
+        match self {
+            Self::ServerShutdown => 4000,
+            Self::Banned => 4001,
+            Self::ProtocolError => 4002,
+            Self::IdleTimeout => 4003,
+            Self::Disconnected => 4004,
+        }
+    }
+
+    /// `None` for codes not in this version of the interface.
+    pub const fn from_code(code: u16) -> Option<Self> {
+        match code {
+            4000 => Some(Self::ServerShutdown),
+            4001 => Some(Self::Banned),
+            4002 => Some(Self::ProtocolError),
+            4003 => Some(Self::IdleTimeout),
+            4004 => Some(Self::Disconnected),
+            _ => None,
+        }
+    }
+
+    /// Whether clients should reconnect, or give up as they would be closed again.
+    pub const fn should_reconnect(self) -> bool {
+        match self {
+            Self::ServerShutdown | Self::IdleTimeout => true,
+            Self::Banned | Self::ProtocolError | Self::Disconnected => false,
+        }
+    }
+}
+
+impl Display for CloseReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ServerShutdown => write!(f, "server is shutting down"),
+            Self::Banned => write!(f, "banned from this server"),
+            Self::ProtocolError => write!(f, "protocol error"),
+            Self::IdleTimeout => write!(f, "idle for too long"),
+            Self::Disconnected => write!(f, "disconnected by an admin"),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use interface::{routes, sse_events, Message, WebhookForm};
+use reqwest::header::ACCEPT;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{config::Config, error::RelayResult};
+
+/// Talks to the board server.
+#[derive(Debug)]
+pub struct Board {
+    http: reqwest::Client,
+    board_url: String,
+    webhook_url: String,
+    webhook_name: String,
+}
+
+impl Board {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            board_url: config.board_url.clone(),
+            webhook_url: config.board_webhook_url.clone(),
+            webhook_name: config.board_webhook_name.clone(),
+        }
+    }
+
+    /// Send new messages on the board to `sender` as they are posted, excluding the ones posted
+    /// by the relay, until the event stream is closed.
+    /// `last_event_id` is kept across reconnects, so that the board replays the messages posted
+    /// in between. Without one, only messages posted from now on are sent.
+    pub async fn follow(
+        &self,
+        last_event_id: &mut Option<String>,
+        sender: &UnboundedSender<Message>,
+    ) -> RelayResult<()> {
+        let (_, path) = routes::EVENTS;
+        let mut request = self
+            .http
+            .get(format!("{}{path}", self.board_url))
+            .header(ACCEPT, "text/event-stream");
+        if let Some(last_event_id) = last_event_id {
+            request = request.header("Last-Event-ID", last_event_id.as_str());
+        }
+        let mut response = request.send().await?.error_for_status()?;
+        log::info!("Following the board's event stream");
+        let mut buffer: Vec<u8> = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            buffer.extend_from_slice(&chunk);
+            // Events are separated by blank lines.
+            while let Some(end) = buffer.windows(2).position(|window| window == b"\n\n") {
+                let block: Vec<u8> = buffer.drain(..end + 2).collect();
+                let block = match String::from_utf8(block) {
+                    Ok(block) => block,
+                    Err(error) => {
+                        log::warn!("Event stream sent invalid UTF-8: {error}");
+                        continue;
+                    }
+                };
+                if let Some(message) = self.parse_block(&block, last_event_id) {
+                    if sender.send(message).is_err() {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The message in a server-sent event, if it is one to mirror.
+    fn parse_block(&self, block: &str, last_event_id: &mut Option<String>) -> Option<Message> {
+        let mut name = sse_events::MESSAGE;
+        let mut id = None;
+        let mut data = String::new();
+        for line in block.lines() {
+            let (field, value) = line.split_once(':').unwrap_or((line, ""));
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match field {
+                "event" => name = value,
+                "id" => id = Some(value),
+                "data" => {
+                    if !data.is_empty() {
+                        data.push('\n');
+                    }
+                    data.push_str(value);
+                }
+                // Comments, which the server sends as keep-alive, and unknown fields.
+                _ => (),
+            }
+        }
+        if let Some(id) = id {
+            *last_event_id = Some(id.to_owned());
+        }
+        if name != sse_events::MESSAGE || data.is_empty() {
+            return None;
+        }
+        let message: Message = match serde_json::from_str(&data) {
+            Ok(message) => message,
+            Err(error) => {
+                log::warn!("Unrecognized message {data:?}: {error}");
+                return None;
+            }
+        };
+        // Loop prevention: our own messages came from Matrix.
+        if message.webhook_name.as_deref() == Some(self.webhook_name.as_str()) {
+            return None;
+        }
+        Some(message)
+    }
+
+    pub async fn post(&self, content: &str) -> RelayResult<()> {
+        self.http
+            .post(&self.webhook_url)
+            .json(&WebhookForm {
+                content: content.into(),
+            })
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
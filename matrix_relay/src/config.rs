@@ -0,0 +1,42 @@
+use std::env;
+
+use crate::error::RelayError;
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// URL of the board server.
+    pub board_url: String,
+    /// URL of a webhook on the board, `<board url>/webhook/<token>`.
+    /// Messages from Matrix are posted through it, so that they can be told apart from other
+    /// messages by their webhook name.
+    pub board_webhook_url: String,
+    /// Display name of the webhook. Board messages with this webhook name are not mirrored back.
+    pub board_webhook_name: String,
+    /// e.g. `https://matrix.org`.
+    pub matrix_homeserver: String,
+    pub matrix_access_token: String,
+    /// e.g. `!abcdefg:matrix.org`.
+    pub matrix_room_id: String,
+}
+
+impl Config {
+    /// Read config from env vars `BOARD_URL`, `BOARD_WEBHOOK_URL`, `BOARD_WEBHOOK_NAME`,
+    /// `MATRIX_HOMESERVER`, `MATRIX_ACCESS_TOKEN` and `MATRIX_ROOM_ID`.
+    pub fn from_env() -> Result<Self, RelayError> {
+        fn var(name: &'static str) -> Result<String, RelayError> {
+            env::var(name)
+                .ok()
+                .filter(|value| !value.is_empty())
+                .ok_or(RelayError::MissingEnvVar(name))
+        }
+        let trim_slash = |url: String| url.trim_end_matches('/').to_owned();
+        Ok(Self {
+            board_url: trim_slash(var("BOARD_URL")?),
+            board_webhook_url: var("BOARD_WEBHOOK_URL")?,
+            board_webhook_name: var("BOARD_WEBHOOK_NAME")?,
+            matrix_homeserver: trim_slash(var("MATRIX_HOMESERVER")?),
+            matrix_access_token: var("MATRIX_ACCESS_TOKEN")?,
+            matrix_room_id: var("MATRIX_ROOM_ID")?,
+        })
+    }
+}
@@ -0,0 +1,26 @@
+use reqwest::StatusCode;
+
+pub type RelayResult<T> = Result<T, RelayError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RelayError {
+    #[error("env var {0} is not set")]
+    MissingEnvVar(&'static str),
+    #[error("request error: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("logger error: {0}")]
+    Logger(#[from] flexi_logger::FlexiLoggerError),
+}
+
+impl RelayError {
+    /// Whether the same request may succeed later. Requests refused by the server aren't, except
+    /// for rate limiting.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Request(error) => error.status().is_none_or(|status| {
+                !status.is_client_error() || status == StatusCode::TOO_MANY_REQUESTS
+            }),
+            Self::MissingEnvVar(_) | Self::Logger(_) => false,
+        }
+    }
+}
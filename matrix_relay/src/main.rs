@@ -0,0 +1,102 @@
+//! Mirrors messages between the board and a Matrix room, in both directions.
+
+mod board;
+mod config;
+mod error;
+mod matrix;
+
+use std::{future::Future, sync::Arc, time::Duration};
+
+use board::Board;
+use config::Config;
+use flexi_logger::{Logger, WriteMode};
+use interface::Message;
+use matrix::Matrix;
+use tokio::sync::mpsc::{self, UnboundedSender};
+
+use crate::error::RelayResult;
+
+/// How long to wait before retrying after an error, multiplied by the attempt for mirroring.
+const ERROR_BACKOFF: Duration = Duration::from_secs(5);
+/// Attempts at mirroring a message before giving up on it.
+const MAX_ATTEMPTS: u32 = 5;
+
+#[tokio::main]
+async fn main() -> RelayResult<()> {
+    let _logger = Logger::try_with_str("info")?
+        .write_mode(WriteMode::BufferAndFlush)
+        .start()?;
+
+    let config = Config::from_env()?;
+    let board = Arc::new(Board::new(&config));
+    let matrix = Arc::new(Matrix::connect(&config).await?);
+    tokio::spawn(board_to_matrix(Arc::clone(&board), Arc::clone(&matrix)));
+    matrix_to_board(board, matrix).await;
+    Ok(())
+}
+
+/// Messages are queued as they arrive, so that slow posting to Matrix doesn't hold up the event
+/// stream, which would make the board drop messages for lagging behind.
+async fn board_to_matrix(board: Arc<Board>, matrix: Arc<Matrix>) {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    tokio::spawn(follow_board(board, sender));
+    while let Some(message) = receiver.recv().await {
+        let what = format!("{:?} to Matrix", message.id);
+        // Retries are deduplicated by Matrix, see `Matrix::post`.
+        with_retries(&what, || matrix.post(&message)).await;
+    }
+}
+
+/// Reconnects to the event stream whenever it is closed, resuming after the last message.
+async fn follow_board(board: Arc<Board>, sender: UnboundedSender<Message>) {
+    let mut last_event_id = None;
+    while !sender.is_closed() {
+        match board.follow(&mut last_event_id, &sender).await {
+            Ok(()) => log::warn!("Board closed the event stream"),
+            Err(error) => log::error!("Error following the board: {error}"),
+        }
+        tokio::time::sleep(ERROR_BACKOFF).await;
+    }
+}
+
+async fn matrix_to_board(board: Arc<Board>, matrix: Arc<Matrix>) {
+    loop {
+        let messages = match matrix.new_messages().await {
+            Ok(messages) => messages,
+            Err(error) => {
+                log::error!("Error syncing with Matrix: {error}");
+                tokio::time::sleep(ERROR_BACKOFF).await;
+                continue;
+            }
+        };
+        for message in messages {
+            let content = format!("<{}> {}", message.sender, message.body);
+            // A post that went through without the response arriving is posted twice, as
+            // webhooks can't deduplicate.
+            with_retries("message to board", || board.post(&content)).await;
+        }
+    }
+}
+
+/// Run `mirror` until it succeeds, up to `MAX_ATTEMPTS` times, backing off longer each time.
+/// Errors that can't go away by retrying are given up on right away, see
+/// `RelayError::is_transient`.
+async fn with_retries<F, Fut>(what: &str, mut mirror: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = RelayResult<()>>,
+{
+    for attempt in 1..=MAX_ATTEMPTS {
+        match mirror().await {
+            Ok(()) => return,
+            Err(error) if attempt < MAX_ATTEMPTS && error.is_transient() => {
+                log::warn!("Error mirroring {what}, attempt {attempt} of {MAX_ATTEMPTS}: {error}");
+                tokio::time::sleep(ERROR_BACKOFF * attempt).await;
+            }
+            Err(error) => {
+                log::error!("Error mirroring {what}, giving up: {error}");
+                return;
+            }
+        }
+    }
+}
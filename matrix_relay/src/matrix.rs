@@ -0,0 +1,182 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use interface::Message;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{config::Config, error::RelayResult};
+
+/// Key of the board message ID in the content of events mirrored from the board.
+pub const BOARD_ID_KEY: &str = "org.message_board.id";
+/// How long the homeserver may hold a sync request waiting for new events.
+const SYNC_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Deserialize)]
+struct WhoAmI {
+    user_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SyncResponse {
+    next_batch: String,
+    #[serde(default)]
+    rooms: Rooms,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Rooms {
+    #[serde(default)]
+    join: HashMap<String, JoinedRoom>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JoinedRoom {
+    #[serde(default)]
+    timeline: Timeline,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Timeline {
+    #[serde(default)]
+    events: Vec<RoomEvent>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RoomEvent {
+    #[serde(rename = "type")]
+    type_: String,
+    sender: String,
+    #[serde(default)]
+    content: Value,
+}
+
+/// A text message from the Matrix room.
+#[derive(Debug, Clone)]
+pub struct MatrixMessage {
+    pub sender: String,
+    pub body: String,
+}
+
+/// Talks to the Matrix homeserver, with the client-server API.
+#[derive(Debug)]
+pub struct Matrix {
+    http: reqwest::Client,
+    homeserver: String,
+    access_token: String,
+    room_id: String,
+    user_id: String,
+    /// `None` before the first sync.
+    next_batch: Mutex<Option<String>>,
+}
+
+impl Matrix {
+    pub async fn connect(config: &Config) -> RelayResult<Self> {
+        let http = reqwest::Client::new();
+        let whoami: WhoAmI = http
+            .get(format!(
+                "{}/_matrix/client/v3/account/whoami",
+                config.matrix_homeserver
+            ))
+            .bearer_auth(&config.matrix_access_token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        log::info!("Logged in to Matrix as {}", whoami.user_id);
+        Ok(Self {
+            http,
+            homeserver: config.matrix_homeserver.clone(),
+            access_token: config.matrix_access_token.clone(),
+            room_id: config.matrix_room_id.clone(),
+            user_id: whoami.user_id,
+            next_batch: Mutex::new(None),
+        })
+    }
+
+    /// Wait for new messages in the room, excluding the ones posted by the relay.
+    /// The first call only catches up, and returns no messages.
+    pub async fn new_messages(&self) -> RelayResult<Vec<MatrixMessage>> {
+        let mut request = self
+            .http
+            .get(format!("{}/_matrix/client/v3/sync", self.homeserver))
+            .bearer_auth(&self.access_token)
+            .timeout(SYNC_TIMEOUT * 2);
+        let next_batch = self.next_batch.lock().unwrap().clone();
+        let is_initial_sync = next_batch.is_none();
+        match &next_batch {
+            Some(since) => {
+                let timeout = SYNC_TIMEOUT.as_millis().to_string();
+                request = request.query(&[("since", since.as_str()), ("timeout", &timeout)]);
+            }
+            None => {
+                let filter = json!({ "room": { "timeline": { "limit": 0 } } }).to_string();
+                request = request.query(&[("filter", filter)]);
+            }
+        }
+        let mut response: SyncResponse = request.send().await?.error_for_status()?.json().await?;
+        *self.next_batch.lock().unwrap() = Some(response.next_batch);
+        if is_initial_sync {
+            return Ok(Vec::new());
+        }
+        let Some(room) = response.rooms.join.remove(&self.room_id) else {
+            return Ok(Vec::new());
+        };
+        let messages = room
+            .timeline
+            .events
+            .into_iter()
+            .filter(|event| event.type_ == "m.room.message" && event.sender != self.user_id)
+            // Loop prevention: events tagged with a board message ID came from the board.
+            .filter(|event| event.content.get(BOARD_ID_KEY).is_none())
+            .filter_map(|event| {
+                let body = event.content.get("body")?.as_str()?.to_owned();
+                Some(MatrixMessage {
+                    sender: event.sender,
+                    body,
+                })
+            })
+            .collect();
+        Ok(messages)
+    }
+
+    /// Post a board message to the room, tagged with its ID.
+    pub async fn post(&self, message: &Message) -> RelayResult<()> {
+        let body = match &message.webhook_name {
+            Some(webhook_name) => format!("{webhook_name}: {}", message.content),
+            None => message.content.to_string(),
+        };
+        // Transaction ID derived from the message ID, so that retries are deduplicated by the
+        // homeserver.
+        let transaction_id = format!("board-{}", message.id);
+        self.http
+            .put(format!(
+                "{}/_matrix/client/v3/rooms/{}/send/m.room.message/{transaction_id}",
+                self.homeserver,
+                encode_path_segment(&self.room_id)
+            ))
+            .bearer_auth(&self.access_token)
+            .json(&json!({
+                "msgtype": "m.text",
+                "body": body,
+                BOARD_ID_KEY: message.id,
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Percent-encode `segment` to put it in a URL path, e.g. room IDs, as `!abc:matrix.org`.
+fn encode_path_segment(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                char::from(byte).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
@@ -0,0 +1,119 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use interface::ArchiveInfo;
+
+use crate::error::{ServerError, ServerResult};
+
+const EXTENSION: &str = ".ndjson";
+const GZIP_EXTENSION: &str = ".ndjson.gz";
+
+/// Directory of archives of purged messages, one NDJSON file per purge, optionally gzipped.
+#[derive(Debug)]
+pub struct Archives {
+    dir: PathBuf,
+    gzip: bool,
+}
+
+impl Archives {
+    pub fn new(dir: PathBuf, gzip: bool) -> Self {
+        Self { dir, gzip }
+    }
+
+    /// Write `messages` to a new archive named after `date`.
+    /// Returns the name of the archive.
+    pub fn write(
+        &self,
+        messages: &[interface::Message],
+        date: DateTime<Utc>,
+    ) -> io::Result<Box<str>> {
+        fs::create_dir_all(&self.dir)?;
+        let extension = if self.gzip { GZIP_EXTENSION } else { EXTENSION };
+        let name = format!("messages-{}{extension}", date.format("%Y%m%dT%H%M%S%.3fZ"));
+        let mut file = BufWriter::new(File::create_new(self.dir.join(&name))?);
+        if self.gzip {
+            let mut encoder = GzEncoder::new(file, Compression::default());
+            write_lines(&mut encoder, messages)?;
+            encoder.finish()?.flush()?;
+        } else {
+            write_lines(&mut file, messages)?;
+            file.flush()?;
+        }
+        Ok(name.into())
+    }
+
+    /// Oldest first.
+    pub fn list(&self) -> io::Result<Vec<ArchiveInfo>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            // Nothing has been archived yet.
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error),
+        };
+        let mut archives = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let Some(name) = entry.file_name().to_str().map(Box::<str>::from) else {
+                continue;
+            };
+            if !is_archive_name(&name) {
+                continue;
+            }
+            archives.push(ArchiveInfo {
+                name,
+                size: entry.metadata()?.len(),
+            });
+        }
+        // Names start with the date.
+        archives.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(archives)
+    }
+
+    /// Read the messages of the archive `name`, one of the names from `list`.
+    pub fn read(&self, name: &str) -> ServerResult<Vec<interface::Message>> {
+        // Checked against the listing, so `name` can't point outside the directory.
+        if !self.list()?.iter().any(|archive| &*archive.name == name) {
+            return Err(ServerError::NoSuchArchive);
+        }
+        let file = File::open(self.dir.join(name))?;
+        let reader: Box<dyn Read> = if name.ends_with(GZIP_EXTENSION) {
+            Box::new(GzDecoder::new(file))
+        } else {
+            Box::new(file)
+        };
+        read_lines(BufReader::new(reader))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+fn is_archive_name(name: &str) -> bool {
+    name.starts_with("messages-") && (name.ends_with(EXTENSION) || name.ends_with(GZIP_EXTENSION))
+}
+
+fn write_lines(writer: &mut impl Write, messages: &[interface::Message]) -> io::Result<()> {
+    for message in messages {
+        serde_json::to_writer(&mut *writer, message)?;
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+fn read_lines(reader: impl BufRead) -> ServerResult<Vec<interface::Message>> {
+    let mut messages = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        messages.push(serde_json::from_str(&line)?);
+    }
+    Ok(messages)
+}
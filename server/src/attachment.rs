@@ -0,0 +1,178 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use interface::AttachmentId;
+
+/// Name of the directory of attachments in `ServerConfig::data_dir`.
+pub const DIR_NAME: &str = "attachments";
+
+/// Unreferenced attachments are kept this long after they were last uploaded, so that the message
+/// referencing them can be sent.
+const UPLOAD_GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug)]
+struct Blob {
+    /// In bytes.
+    size: u64,
+    /// Number of messages referencing the attachment.
+    references: usize,
+    /// `None` for attachments found on disk at startup.
+    last_uploaded: Option<Instant>,
+}
+
+/// Content-addressed attachments, one file per distinct content named after its `AttachmentId`,
+/// so identical uploads share storage.
+/// Files no message references anymore are deleted by `collect_garbage`.
+#[derive(Debug)]
+pub struct Attachments {
+    dir: PathBuf,
+    blobs: Mutex<HashMap<AttachmentId, Blob>>,
+    /// For unique names of temporary files.
+    next_upload: AtomicU64,
+}
+
+impl Attachments {
+    /// Index the attachments already in `dir`, created on the first upload, deleting temporary
+    /// files left in it.
+    /// References are counted afterwards with `retain`, from the messages loaded.
+    pub fn open(dir: PathBuf) -> io::Result<Self> {
+        let mut blobs = HashMap::new();
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => Some(entries),
+            // Nothing has been uploaded yet.
+            Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+            Err(error) => return Err(error),
+        };
+        for entry in entries.into_iter().flatten() {
+            let entry = entry?;
+            let name = entry.file_name();
+            let Some(name) = name.to_str() else {
+                continue;
+            };
+            // Left by uploads interrupted by a crash, nothing writes to them anymore.
+            if name.ends_with(".tmp") {
+                if let Err(error) = fs::remove_file(entry.path()) {
+                    log::warn!("Can't delete temporary attachment {name:?}: {error}");
+                }
+                continue;
+            }
+            let Ok(id) = name.parse() else {
+                continue;
+            };
+            let blob = Blob {
+                size: entry.metadata()?.len(),
+                references: 0,
+                last_uploaded: None,
+            };
+            blobs.insert(id, blob);
+        }
+        Ok(Self {
+            dir,
+            blobs: Mutex::new(blobs),
+            next_upload: AtomicU64::new(0),
+        })
+    }
+
+    /// Store `content`, unless an identical file is already stored.
+    pub fn store(&self, content: &[u8]) -> io::Result<AttachmentId> {
+        let id = AttachmentId(*blake3::hash(content).as_bytes());
+        // Marked as uploaded while locked, so that it isn't collected as garbage meanwhile.
+        if let Some(blob) = self.blobs.lock().unwrap().get_mut(&id) {
+            blob.last_uploaded = Some(Instant::now());
+            return Ok(id);
+        }
+        fs::create_dir_all(&self.dir)?;
+        // Written to a temporary file first, so that no partial file is ever found under the
+        // final name, even if the same content is uploaded twice at once.
+        let upload = self.next_upload.fetch_add(1, Ordering::Relaxed);
+        let temp_path = self.dir.join(format!("{id}.{upload}.tmp"));
+        fs::write(&temp_path, content)?;
+        fs::rename(&temp_path, self.path(id))?;
+        let blob = Blob {
+            size: content.len() as u64,
+            references: 0,
+            last_uploaded: Some(Instant::now()),
+        };
+        self.blobs.lock().unwrap().insert(id, blob);
+        Ok(id)
+    }
+
+    /// Returns `None` if there's no such attachment.
+    pub fn read(&self, id: AttachmentId) -> io::Result<Option<Vec<u8>>> {
+        if !self.blobs.lock().unwrap().contains_key(&id) {
+            return Ok(None);
+        }
+        match fs::read(self.path(id)) {
+            Ok(content) => Ok(Some(content)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// In bytes, `None` if there's no such attachment.
+    pub fn size(&self, id: AttachmentId) -> Option<u64> {
+        self.blobs.lock().unwrap().get(&id).map(|blob| blob.size)
+    }
+
+    /// Count a reference from a new message to each of `ids`.
+    pub fn retain(&self, ids: impl IntoIterator<Item = AttachmentId>) {
+        let mut blobs = self.blobs.lock().unwrap();
+        for id in ids {
+            if let Some(blob) = blobs.get_mut(&id) {
+                blob.references += 1;
+            }
+        }
+    }
+
+    /// Drop a reference from a deleted message to each of `ids`.
+    pub fn release(&self, ids: impl IntoIterator<Item = AttachmentId>) {
+        let mut blobs = self.blobs.lock().unwrap();
+        for id in ids {
+            if let Some(blob) = blobs.get_mut(&id) {
+                blob.references = blob.references.saturating_sub(1);
+            }
+        }
+    }
+
+    /// Delete attachments without references that weren't uploaded recently.
+    /// Returns the number of attachments deleted.
+    pub fn collect_garbage(&self) -> usize {
+        let mut blobs = self.blobs.lock().unwrap();
+        let garbage: Vec<AttachmentId> = blobs
+            .iter()
+            .filter(|(_, blob)| {
+                blob.references == 0
+                    && blob
+                        .last_uploaded
+                        .map_or(true, |uploaded| uploaded.elapsed() >= UPLOAD_GRACE_PERIOD)
+            })
+            .map(|(&id, _)| id)
+            .collect();
+        let mut deleted = 0;
+        for id in garbage {
+            match fs::remove_file(self.path(id)) {
+                Ok(()) => (),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => (),
+                Err(error) => {
+                    log::error!("Can't delete attachment {id}: {error}");
+                    continue;
+                }
+            }
+            blobs.remove(&id);
+            deleted += 1;
+        }
+        deleted
+    }
+
+    fn path(&self, id: AttachmentId) -> PathBuf {
+        self.dir.join(id.to_string())
+    }
+}
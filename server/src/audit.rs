@@ -0,0 +1,72 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use chrono::{DateTime, Utc};
+use interface::{AuditAction, AuditActor, AuditEntry};
+
+/// Append-only log of moderation actions.
+/// Entries are kept in memory for querying, and also appended to a file as JSON lines if one is
+/// configured, so that the log survives restarts.
+#[derive(Debug, Default)]
+pub struct AuditLog {
+    entries: Mutex<Vec<AuditEntry>>,
+    file: Option<Mutex<File>>,
+}
+
+impl AuditLog {
+    /// Open the audit log file at `path`, loading existing entries from it.
+    /// Lines that fail to parse are skipped with a warning, instead of refusing to start.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let entries = match fs::read_to_string(path) {
+            Ok(text) => text
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .filter_map(|line| match serde_json::from_str(line) {
+                    Ok(entry) => Some(entry),
+                    Err(error) => {
+                        log::warn!("Skipping bad audit log line {line:?}: {error}");
+                        None
+                    }
+                })
+                .collect(),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(error) => return Err(error),
+        };
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            entries: Mutex::new(entries),
+            file: Some(Mutex::new(file)),
+        })
+    }
+
+    pub fn record(&self, actor: AuditActor, action: AuditAction) {
+        let entry = AuditEntry {
+            date: Utc::now(),
+            actor,
+            action,
+        };
+        if let Some(file) = &self.file {
+            let mut line = serde_json::to_string(&entry).unwrap();
+            line.push('\n');
+            if let Err(error) = file.lock().unwrap().write_all(line.as_bytes()) {
+                log::error!("Error writing to audit log: {error}");
+            }
+        }
+        self.entries.lock().unwrap().push(entry);
+    }
+
+    /// At most `count` latest entries after `after`, oldest first.
+    pub fn fetch(&self, count: usize, after: Option<DateTime<Utc>>) -> Vec<AuditEntry> {
+        let entries = self.entries.lock().unwrap();
+        let start = match after {
+            Some(after) => entries.partition_point(|entry| entry.date <= after),
+            None => 0,
+        };
+        let start = start.max(entries.len().saturating_sub(count));
+        entries[start..].to_vec()
+    }
+}
@@ -0,0 +1,546 @@
+use std::{collections::HashMap, path::PathBuf, sync::Mutex};
+
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
+use interface::{ApiScope, AuditActor, Role};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::ServerConfig,
+    error::{AppError, ServerError, ServerResult},
+    json_file::JsonFile,
+    members::Members,
+    utils::to_hex,
+    ServerState,
+};
+
+/// Session ID of the admin token from the server's config.
+const CONFIG_ADMIN_SESSION_ID: u64 = 0;
+
+/// Name of the file in `ServerConfig::data_dir` sessions are saved to.
+pub const SESSIONS_FILE_NAME: &str = "sessions.json";
+/// Name of the file in `ServerConfig::data_dir` API tokens are saved to.
+pub const API_TOKENS_FILE_NAME: &str = "api_tokens.json";
+/// Name of the file in `ServerConfig::data_dir` invites are saved to.
+pub const INVITES_FILE_NAME: &str = "invites.json";
+
+/// Things that require a minimum role.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    Announce,
+    SetTopic,
+    /// Delete any message by ID, rather than only one's own with its deletion token.
+    DeleteMessages,
+    /// Ban and unban IP addresses.
+    Ban,
+    SetReadOnly,
+    SetArchived,
+    ViewAuditLog,
+    ManageSessions,
+    ManageApiTokens,
+    CreateInvites,
+    ManageWebhooks,
+    ManageSubscriptions,
+    ManageArchives,
+    ViewSnapshotMetrics,
+    ViewStats,
+    ManageConnections,
+    /// The board has no channels and no owners, admins own the one board there is and are the
+    /// only ones who can change its member list.
+    ManageMembers,
+}
+
+impl Permission {
+    pub fn min_role(self) -> Role {
+        match self {
+            Self::Announce
+            | Self::SetTopic
+            | Self::DeleteMessages
+            | Self::Ban
+            | Self::ViewAuditLog => Role::Moderator,
+            Self::ManageSessions
+            | Self::SetReadOnly
+            | Self::SetArchived
+            | Self::ManageApiTokens
+            | Self::CreateInvites
+            | Self::ManageWebhooks
+            | Self::ManageSubscriptions
+            | Self::ManageArchives
+            | Self::ViewSnapshotMetrics
+            | Self::ViewStats
+            | Self::ManageConnections
+            | Self::ManageMembers => Role::Admin,
+        }
+    }
+}
+
+/// 32 random bytes in hex.
+pub fn random_secret() -> Box<str> {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    to_hex(&bytes).into()
+}
+
+/// Session tokens handed out by admins, each with a role.
+/// The admin token from the server's config is not stored here, it is always session 0.
+/// Saved with the messages if they're persisted, see `Profiles`.
+#[derive(Debug, Default)]
+pub struct Sessions {
+    /// Token -> (ID, role).
+    sessions: Mutex<HashMap<Box<str>, (u64, Role)>>,
+    file: JsonFile,
+}
+
+impl Sessions {
+    /// Load the sessions saved at `path`, if any, and save changes there.
+    /// Kept in memory only if `path` is `None`.
+    pub fn open(path: Option<PathBuf>) -> ServerResult<Self> {
+        let (file, sessions) = JsonFile::open(path)?;
+        Ok(Self {
+            sessions: Mutex::new(sessions),
+            file,
+        })
+    }
+
+    /// Returns the ID and the token of the new session.
+    /// IDs are random rather than counted, as persisted messages keep the IDs of sessions that are
+    /// gone, e.g. revoked ones, and a counter would hand their IDs to new sessions.
+    pub fn create(&self, role: Role) -> (u64, Box<str>) {
+        let token = random_secret();
+        let mut sessions = self.sessions.lock().unwrap();
+        let id = loop {
+            let id = rand::random::<u64>();
+            let is_taken = id == CONFIG_ADMIN_SESSION_ID
+                || sessions.values().any(|&(session_id, _)| session_id == id);
+            if !is_taken {
+                break id;
+            }
+        };
+        sessions.insert(token.clone(), (id, role));
+        self.file.save(&*sessions);
+        (id, token)
+    }
+
+    /// Returns `false` if there's no such session.
+    pub fn revoke(&self, id: u64) -> bool {
+        let mut sessions = self.sessions.lock().unwrap();
+        let len_before = sessions.len();
+        sessions.retain(|_, (session_id, _)| *session_id != id);
+        let is_revoked = sessions.len() != len_before;
+        if is_revoked {
+            self.file.save(&*sessions);
+        }
+        is_revoked
+    }
+
+    fn get(&self, token: &str) -> Option<(u64, Role)> {
+        self.sessions.lock().unwrap().get(token).copied()
+    }
+
+    /// Returns `false` if there's no such session.
+    pub fn set_role(&self, id: u64, role: Role) -> bool {
+        let mut sessions = self.sessions.lock().unwrap();
+        let Some((_, session_role)) = sessions
+            .values_mut()
+            .find(|(session_id, _)| *session_id == id)
+        else {
+            return false;
+        };
+        *session_role = role;
+        self.file.save(&*sessions);
+        true
+    }
+
+    /// Replace the token of session `id`.
+    /// Returns the new token, `None` if there's no such session.
+    pub fn rotate(&self, id: u64) -> Option<Box<str>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let (token, role) = sessions
+            .iter()
+            .find(|(_, &(session_id, _))| session_id == id)
+            .map(|(token, &(_, role))| (token.clone(), role))?;
+        sessions.remove(&token);
+        let new_token = random_secret();
+        sessions.insert(new_token.clone(), (id, role));
+        self.file.save(&*sessions);
+        Some(new_token)
+    }
+}
+
+/// Scopes of an API token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Scopes {
+    pub send: bool,
+    pub read: bool,
+}
+
+impl Scopes {
+    pub fn new(scopes: &[ApiScope]) -> Self {
+        Self {
+            send: scopes.contains(&ApiScope::Send),
+            read: scopes.contains(&ApiScope::Read),
+        }
+    }
+
+    pub fn contains(self, scope: ApiScope) -> bool {
+        match scope {
+            ApiScope::Send => self.send,
+            ApiScope::Read => self.read,
+        }
+    }
+}
+
+/// Long-lived tokens for bots, each limited to some scopes.
+/// Unlike sessions they have no role, bots act as users within their scopes.
+/// Saved with the messages if they're persisted, see `Profiles`.
+#[derive(Debug, Default)]
+pub struct ApiTokens {
+    tokens: Mutex<TokenList>,
+    file: JsonFile,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TokenList {
+    /// Token -> (ID, scopes).
+    tokens: HashMap<Box<str>, (u64, Scopes)>,
+    next_id: u64,
+}
+
+impl Default for TokenList {
+    fn default() -> Self {
+        Self {
+            tokens: HashMap::new(),
+            next_id: 1,
+        }
+    }
+}
+
+impl ApiTokens {
+    /// Load the API tokens saved at `path`, if any, and save changes there.
+    /// Kept in memory only if `path` is `None`.
+    pub fn open(path: Option<PathBuf>) -> ServerResult<Self> {
+        let (file, tokens) = JsonFile::open(path)?;
+        Ok(Self {
+            tokens: Mutex::new(tokens),
+            file,
+        })
+    }
+
+    /// Returns the ID and the token of the new API token.
+    pub fn create(&self, scopes: Scopes) -> (u64, Box<str>) {
+        let token = random_secret();
+        let mut list = self.tokens.lock().unwrap();
+        let id = list.next_id;
+        list.next_id += 1;
+        list.tokens.insert(token.clone(), (id, scopes));
+        self.file.save(&*list);
+        (id, token)
+    }
+
+    /// Returns `false` if there's no such token.
+    pub fn revoke(&self, id: u64) -> bool {
+        let mut list = self.tokens.lock().unwrap();
+        let len_before = list.tokens.len();
+        list.tokens.retain(|_, (token_id, _)| *token_id != id);
+        let is_revoked = list.tokens.len() != len_before;
+        if is_revoked {
+            self.file.save(&*list);
+        }
+        is_revoked
+    }
+
+    fn get(&self, token: &str) -> Option<(u64, Scopes)> {
+        self.tokens.lock().unwrap().tokens.get(token).copied()
+    }
+}
+
+/// Invite codes with their remaining number of uses.
+/// Saved with the messages if they're persisted, see `Profiles`.
+#[derive(Debug, Default)]
+pub struct Invites {
+    codes: Mutex<HashMap<Box<str>, u32>>,
+    file: JsonFile,
+}
+
+impl Invites {
+    /// Load the invites saved at `path`, if any, and save changes there.
+    /// Kept in memory only if `path` is `None`.
+    pub fn open(path: Option<PathBuf>) -> ServerResult<Self> {
+        let (file, codes) = JsonFile::open(path)?;
+        Ok(Self {
+            codes: Mutex::new(codes),
+            file,
+        })
+    }
+
+    pub fn create(&self, uses: u32) -> Box<str> {
+        let code = random_secret();
+        let mut codes = self.codes.lock().unwrap();
+        codes.insert(code.clone(), uses);
+        self.file.save(&*codes);
+        code
+    }
+
+    /// Use up one use of `code`. Returns `false` if the code is invalid or used up.
+    pub fn redeem(&self, code: &str) -> bool {
+        let mut codes = self.codes.lock().unwrap();
+        let Some(uses) = codes.get_mut(code) else {
+            return false;
+        };
+        *uses -= 1;
+        if *uses == 0 {
+            codes.remove(code);
+        }
+        self.file.save(&*codes);
+        true
+    }
+}
+
+/// Extractor for the session of a request, from header `Authorization: Bearer <token>`.
+/// The token is either a session token or an API token.
+/// Requests without a token are guests, requests with an unknown token are rejected.
+#[derive(Debug, Clone, Copy)]
+pub struct Session {
+    /// `None` for guests and API tokens.
+    pub id: Option<u64>,
+    pub role: Role,
+    /// Scopes of the API token, `None` if the request didn't use one.
+    pub scopes: Option<Scopes>,
+    /// ID of the API token, `None` if the request didn't use one.
+    pub api_token_id: Option<u64>,
+}
+
+impl Session {
+    /// Look up the session of a token, `None` being a guest.
+    pub fn from_token(
+        server_state: &ServerState,
+        token: Option<&str>,
+    ) -> Result<Self, ServerError> {
+        let Some(token) = token else {
+            return Ok(Session {
+                id: None,
+                role: Role::Guest,
+                scopes: None,
+                api_token_id: None,
+            });
+        };
+        if let Some(admin_token) = &server_state.config.admin_token {
+            if constant_time_eq(token.as_bytes(), admin_token.as_bytes()) {
+                return Ok(Session {
+                    id: Some(CONFIG_ADMIN_SESSION_ID),
+                    role: Role::Admin,
+                    scopes: None,
+                    api_token_id: None,
+                });
+            }
+        }
+        // Tokens are looked up in a hash map, so timing only leaks the hash of the token.
+        if let Some((id, role)) = server_state.sessions.get(token) {
+            return Ok(Session {
+                id: Some(id),
+                role,
+                scopes: None,
+                api_token_id: None,
+            });
+        }
+        match server_state.api_tokens.get(token) {
+            Some((id, scopes)) => Ok(Session {
+                id: None,
+                role: Role::User,
+                scopes: Some(scopes),
+                api_token_id: Some(id),
+            }),
+            None => Err(ServerError::InvalidToken),
+        }
+    }
+
+    /// Check that the API token of the session, if any, has `scope`.
+    pub fn require_scope(&self, scope: ApiScope) -> Result<(), ServerError> {
+        match self.scopes {
+            Some(scopes) if !scopes.contains(scope) => Err(ServerError::MissingScope { scope }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that the session has `permission`.
+    /// Returns who to record in the audit log as the actor.
+    pub fn require(&self, permission: Permission) -> Result<AuditActor, ServerError> {
+        match self.id {
+            Some(id) if self.role >= permission.min_role() => Ok(AuditActor::Session {
+                id,
+                role: self.role,
+            }),
+            _ => Err(ServerError::PermissionDenied {
+                required: permission.min_role(),
+            }),
+        }
+    }
+
+    /// Check that this is a session handed out by the server, which can change itself, and
+    /// returns its ID. Guests and API tokens have no session, and the admin token from the
+    /// config can only be changed there.
+    pub fn require_own_session(&self) -> Result<u64, ServerError> {
+        match self.id {
+            Some(CONFIG_ADMIN_SESSION_ID) => Err(ServerError::ConfigSession),
+            Some(id) => Ok(id),
+            None => Err(ServerError::PermissionDenied {
+                required: Role::User,
+            }),
+        }
+    }
+
+    /// Check that the session can read and post on a private board, see `is_member`.
+    pub fn require_access(&self, server_state: &ServerState) -> Result<(), ServerError> {
+        if server_state.config.private && !self.is_member(&server_state.members) {
+            Err(ServerError::NotAMember)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Whether the session or API token is on the member list, or is a moderator or admin.
+    fn is_member(&self, members: &Members) -> bool {
+        self.role >= Role::Moderator
+            || self.id.is_some_and(|id| members.contains_session(id))
+            || self
+                .api_token_id
+                .is_some_and(|id| members.contains_api_token(id))
+    }
+
+    /// Check that the session can post, i.e. is not a guest if the server is invite-only.
+    pub fn require_member(&self, config: &ServerConfig) -> Result<(), ServerError> {
+        if config.invite_only && self.role < Role::User {
+            Err(ServerError::InviteRequired)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check that the session can send messages, on any transport: `require_member`,
+    /// `ApiScope::Send` and `require_access`.
+    pub fn require_sender(&self, server_state: &ServerState) -> Result<(), ServerError> {
+        self.require_member(&server_state.config)?;
+        self.require_scope(ApiScope::Send)?;
+        self.require_access(server_state)
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<ServerState> for Session {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        server_state: &ServerState,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(Session::from_token(server_state, bearer_token(parts))?)
+    }
+}
+
+fn bearer_token(parts: &Parts) -> Option<&str> {
+    parts
+        .headers
+        .get(axum::http::header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+}
+
+/// Compare without short-circuiting, so the time taken doesn't leak how much of the token is right.
+fn constant_time_eq(x: &[u8], y: &[u8]) -> bool {
+    x.len() == y.len() && x.iter().zip(y).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use interface::UpdateMembersForm;
+
+    use super::*;
+
+    fn session(role: Role, id: Option<u64>) -> Session {
+        Session {
+            id,
+            role,
+            scopes: None,
+            api_token_id: None,
+        }
+    }
+
+    fn api_token(id: u64) -> Session {
+        Session {
+            id: None,
+            role: Role::User,
+            scopes: Some(Scopes::new(&[ApiScope::Read, ApiScope::Send])),
+            api_token_id: Some(id),
+        }
+    }
+
+    #[test]
+    fn set_role_applies_to_the_session_token() {
+        let sessions = Sessions::default();
+        let (id, token) = sessions.create(Role::User);
+        assert!(sessions.set_role(id, Role::Moderator));
+        assert_eq!(sessions.get(&token), Some((id, Role::Moderator)));
+        assert!(!sessions.set_role(id + 1, Role::Admin));
+    }
+
+    #[test]
+    fn guests_are_not_members() {
+        let members = Members::default();
+        assert!(!session(Role::Guest, None).is_member(&members));
+    }
+
+    #[test]
+    fn moderators_and_admins_are_members() {
+        let members = Members::default();
+        assert!(session(Role::Moderator, Some(1)).is_member(&members));
+        assert!(session(Role::Admin, Some(CONFIG_ADMIN_SESSION_ID)).is_member(&members));
+    }
+
+    #[test]
+    fn sessions_are_members_while_on_the_list() {
+        let members = Members::default();
+        let user = session(Role::User, Some(7));
+        assert!(!user.is_member(&members));
+        members.update(&UpdateMembersForm {
+            add: Box::new([7]),
+            ..Default::default()
+        });
+        assert!(user.is_member(&members));
+        members.forget(7);
+        assert!(!user.is_member(&members));
+    }
+
+    #[test]
+    fn api_tokens_are_members_only_if_added() {
+        let members = Members::default();
+        let bot = api_token(3);
+        assert!(!bot.is_member(&members));
+        // A session with the same ID doesn't make the token a member.
+        members.update(&UpdateMembersForm {
+            add: Box::new([3]),
+            ..Default::default()
+        });
+        assert!(!bot.is_member(&members));
+        members.update(&UpdateMembersForm {
+            add_api_tokens: Box::new([3]),
+            ..Default::default()
+        });
+        assert!(bot.is_member(&members));
+        members.forget_api_token(3);
+        assert!(!bot.is_member(&members));
+    }
+
+    #[test]
+    fn removing_wins_over_adding() {
+        let members = Members::default();
+        let (sessions, api_tokens) = members.update(&UpdateMembersForm {
+            add: Box::new([1, 2]),
+            remove: Box::new([2]),
+            add_api_tokens: Box::new([5]),
+            remove_api_tokens: Box::new([5]),
+        });
+        assert_eq!(*sessions, [1]);
+        assert!(api_tokens.is_empty());
+    }
+}
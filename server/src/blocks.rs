@@ -0,0 +1,104 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use interface::Message;
+
+use crate::{auth::Session, database::DataBase, error::ServerResult, json_file::JsonFile};
+
+/// Name of the file in `ServerConfig::data_dir` block lists are saved to.
+pub const FILE_NAME: &str = "blocks.json";
+
+/// Block lists of sessions, by session ID.
+/// Saved with the messages if they're persisted, like sessions themselves.
+#[derive(Debug, Default)]
+pub struct Blocks {
+    /// Lists are replaced rather than changed, so that `Blocked` can share them.
+    blocks: Mutex<HashMap<u64, Arc<BTreeSet<u64>>>>,
+    file: JsonFile,
+}
+
+impl Blocks {
+    /// Load the block lists saved at `path`, if any, and save changes there.
+    /// Kept in memory only if `path` is `None`.
+    pub fn open(path: Option<PathBuf>) -> ServerResult<Self> {
+        let (file, blocks) = JsonFile::open(path)?;
+        Ok(Self {
+            blocks: Mutex::new(blocks),
+            file,
+        })
+    }
+
+    /// Sessions blocked by session `session_id`, in ascending order.
+    pub fn get(&self, session_id: u64) -> Box<[u64]> {
+        self.blocks
+            .lock()
+            .unwrap()
+            .get(&session_id)
+            .map(|blocked| blocked.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Replace the block list of session `session_id`, returning it as `get` would.
+    /// Sessions can't block themselves, their ID is left out.
+    pub fn set(&self, session_id: u64, blocked: &[u64]) -> Box<[u64]> {
+        let blocked: BTreeSet<u64> = blocked
+            .iter()
+            .copied()
+            .filter(|&id| id != session_id)
+            .collect();
+        let list = blocked.iter().copied().collect();
+        let mut blocks = self.blocks.lock().unwrap();
+        if blocked.is_empty() {
+            blocks.remove(&session_id);
+        } else {
+            blocks.insert(session_id, Arc::new(blocked));
+        }
+        self.file.save(&*blocks);
+        list
+    }
+
+    /// Drop the block list of a revoked session.
+    pub fn forget(&self, session_id: u64) {
+        let mut blocks = self.blocks.lock().unwrap();
+        if blocks.remove(&session_id).is_some() {
+            self.file.save(&*blocks);
+        }
+    }
+
+    /// Sessions blocked by `session`, nobody for guests and API tokens.
+    pub fn blocked_by(&self, session: &Session) -> Blocked {
+        let blocked = session
+            .id
+            .and_then(|id| self.blocks.lock().unwrap().get(&id).cloned());
+        Blocked(blocked)
+    }
+}
+
+/// Block list of one session, taken when it's needed.
+/// Cheap to take, as it shares the list with `Blocks`.
+#[derive(Debug, Clone, Default)]
+pub struct Blocked(Option<Arc<BTreeSet<u64>>>);
+
+impl Blocked {
+    pub fn is_empty(&self) -> bool {
+        self.0.as_ref().map_or(true, |blocked| blocked.is_empty())
+    }
+
+    /// Whether messages posted by `poster` are left out, messages not posted by a session never
+    /// are. See `database::Message::poster`.
+    pub fn hides(&self, poster: Option<u64>) -> bool {
+        poster.is_some_and(|poster| {
+            self.0
+                .as_ref()
+                .is_some_and(|blocked| blocked.contains(&poster))
+        })
+    }
+
+    /// Like `hides`, for messages as sent to clients, which don't say who posted them.
+    pub fn hides_message(&self, message: &Message, database: &DataBase) -> bool {
+        !self.is_empty() && self.hides(database.poster(message.id))
+    }
+}
@@ -0,0 +1,338 @@
+use std::{env, path::PathBuf, str::FromStr, time::Duration};
+
+use crate::{middleware::Middleware, plugin::BuiltinPlugin, sanitize::Sanitization};
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Address the listener binds to.
+    pub bind_address: String,
+    /// Only speak HTTP/1.1, for debugging with tools that don't understand HTTP/2.
+    /// By default both HTTP/1.1 and HTTP/2 (h2c with prior knowledge) are accepted.
+    pub http1_only: bool,
+    /// Token of the bootstrap admin session, read from env var `MESSAGE_BOARD_ADMIN_TOKEN`.
+    /// Other sessions can only be created by an admin, so nobody can be moderator or admin if
+    /// this is `None`.
+    pub admin_token: Option<String>,
+    /// Path of the audit log file, read from env var `MESSAGE_BOARD_AUDIT_LOG`.
+    /// The audit log is only kept in memory if this is `None`.
+    pub audit_log_path: Option<PathBuf>,
+    /// Only sessions of role `User` or higher can post, which guests get by redeeming invite
+    /// codes minted by admins.
+    pub invite_only: bool,
+    /// Only sessions and API tokens on the member list, and moderators and admins themselves,
+    /// can read and post. Admins manage the list, as there are no channel owners.
+    /// See `members::Members`.
+    pub private: bool,
+    /// Address of the IRC gateway, disabled if `None`.
+    pub irc_address: Option<String>,
+    /// Address of the gRPC service, disabled if `None`.
+    /// Requires building with feature `grpc`.
+    pub grpc_address: Option<String>,
+    /// Address of the experimental HTTP/3 listener, disabled if `None`.
+    /// Requires building with feature `http3`, and `tls_cert_path` and `tls_key_path`.
+    pub http3_address: Option<String>,
+    /// PEM certificate chain for HTTP/3, read from env var `MESSAGE_BOARD_TLS_CERT`.
+    pub tls_cert_path: Option<PathBuf>,
+    /// PEM private key for HTTP/3, read from env var `MESSAGE_BOARD_TLS_KEY`.
+    pub tls_key_path: Option<PathBuf>,
+    /// Layers wrapping every route, outermost first.
+    pub middleware: Vec<Middleware>,
+    /// Requests per minute per IP address, if `Middleware::RateLimit` is used.
+    /// Overridden by `Settings::rate_limit`.
+    pub rate_limit: u32,
+    /// Path of the settings file, read from env var `MESSAGE_BOARD_CONFIG`.
+    /// See `settings::Settings`.
+    pub settings_path: Option<PathBuf>,
+    /// Directory messages are archived to before the retention task deletes them, read from env
+    /// var `MESSAGE_BOARD_ARCHIVE_DIR`. Messages are deleted without archiving if `None`.
+    pub archive_dir: Option<PathBuf>,
+    /// Gzip archives.
+    pub gzip_archives: bool,
+    /// How messages survive restarts.
+    pub persistence: Persistence,
+    /// Directory persisted messages are kept in, read from env var `MESSAGE_BOARD_DATA_DIR`.
+    pub data_dir: PathBuf,
+    /// How often a snapshot is written if persistence is `Persistence::Snapshot`.
+    pub snapshot_interval: Duration,
+    /// Messages posted within this window are broadcast to websocket clients as one event.
+    pub broadcast_batch_window: Duration,
+    /// Clean-ups applied to messages, poll options and topics, see `sanitize::Sanitizer`.
+    pub sanitize: Vec<Sanitization>,
+    /// Plugins shipped with the server to register at startup, see `plugin::ServerPlugin`.
+    pub plugins: Vec<BuiltinPlugin>,
+    /// Command every new message is piped to as JSON, which can reject it, see
+    /// `script::ScriptHook`. Disabled if `None`.
+    pub script: Option<Box<str>>,
+    /// Scripts running longer are killed, and the message rejected.
+    pub script_timeout: Duration,
+    /// Messages wait for a running script to exit beyond this many.
+    pub max_running_scripts: usize,
+    /// Only run the startup self-check, without binding addresses, and exit.
+    pub check_config: bool,
+    /// Warnings about unknown or invalid arguments, which were ignored. Errors of the self-check
+    /// with `check_config`.
+    pub ignored_args: Vec<String>,
+    /// What happens to the messages of sessions deleting themselves.
+    pub deleted_account_messages: DeletedAccountMessages,
+}
+
+/// How messages survive restarts.
+/// Unless they're kept in memory, sessions, API tokens, invites, profiles, block lists and the
+/// member list are saved next to them, see `json_file::JsonFile`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Persistence {
+    /// Messages are lost on restart.
+    #[default]
+    Memory,
+    /// All messages are written to a snapshot in `ServerConfig::data_dir` every
+    /// `ServerConfig::snapshot_interval`. Messages since the last snapshot are lost on a crash.
+    Snapshot,
+    /// Every change to messages is appended to a write-ahead log in `ServerConfig::data_dir`,
+    /// which is compacted into a snapshot periodically. See `wal::Wal`.
+    Wal,
+}
+
+impl FromStr for Persistence {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "memory" => Ok(Self::Memory),
+            "snapshot" => Ok(Self::Snapshot),
+            "wal" => Ok(Self::Wal),
+            _ => Err(()),
+        }
+    }
+}
+
+/// What happens to the messages of sessions deleting themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeletedAccountMessages {
+    /// Deleted like by their senders.
+    Delete,
+    /// Kept, but no longer attributed to the session.
+    #[default]
+    Anonymize,
+}
+
+impl FromStr for DeletedAccountMessages {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "delete" => Ok(Self::Delete),
+            "anonymize" => Ok(Self::Anonymize),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: String::from("0.0.0.0:3000"),
+            http1_only: false,
+            admin_token: None,
+            audit_log_path: None,
+            invite_only: false,
+            private: false,
+            irc_address: None,
+            grpc_address: None,
+            http3_address: None,
+            tls_cert_path: None,
+            tls_key_path: None,
+            middleware: Middleware::DEFAULT.to_vec(),
+            rate_limit: 300,
+            settings_path: None,
+            archive_dir: None,
+            gzip_archives: false,
+            persistence: Persistence::default(),
+            data_dir: PathBuf::from("data"),
+            snapshot_interval: Duration::from_secs(60),
+            broadcast_batch_window: Duration::from_millis(50),
+            sanitize: Sanitization::DEFAULT.to_vec(),
+            plugins: Vec::new(),
+            script: None,
+            script_timeout: Duration::from_secs(2),
+            max_running_scripts: 4,
+            check_config: false,
+            ignored_args: Vec::new(),
+            deleted_account_messages: DeletedAccountMessages::default(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Read config from command line arguments and environment variables.
+    /// ```txt
+    /// server [--check-config] [--http1] [--invite-only] [--private] [--gzip-archives]
+    ///     [--irc=ADDRESS] [--grpc=ADDRESS] [--http3=ADDRESS]
+    ///     [--middleware=logging,rate-limit,compression,cors] [--rate-limit=REQUESTS_PER_MINUTE]
+    ///     [--persistence=memory|snapshot|wal] [--snapshot-interval=SECONDS]
+    ///     [--broadcast-batch=MILLISECONDS]
+    ///     [--sanitize=bidi,zero-width,nfc] [--plugins=no-repeats,activity]
+    ///     [--script=COMMAND] [--script-timeout=MILLISECONDS] [--script-concurrency=COUNT]
+    ///     [--deleted-account-messages=delete|anonymize]
+    ///     [BIND_ADDRESS]
+    /// ```
+    /// `--middleware=` and `--sanitize=` with an empty list disable all middleware and
+    /// sanitization.
+    pub fn from_args() -> Self {
+        let mut config = Self {
+            admin_token: env::var("MESSAGE_BOARD_ADMIN_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty()),
+            audit_log_path: env::var_os("MESSAGE_BOARD_AUDIT_LOG")
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            tls_cert_path: env::var_os("MESSAGE_BOARD_TLS_CERT")
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            tls_key_path: env::var_os("MESSAGE_BOARD_TLS_KEY")
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            settings_path: env::var_os("MESSAGE_BOARD_CONFIG")
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            archive_dir: env::var_os("MESSAGE_BOARD_ARCHIVE_DIR")
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            data_dir: env::var_os("MESSAGE_BOARD_DATA_DIR")
+                .filter(|path| !path.is_empty())
+                .map_or_else(|| Self::default().data_dir, PathBuf::from),
+            ..Self::default()
+        };
+        let mut ignored = Vec::new();
+        for arg in env::args().skip(1) {
+            match arg.as_str() {
+                "--check-config" => config.check_config = true,
+                "--http1" => config.http1_only = true,
+                "--invite-only" => config.invite_only = true,
+                "--private" => config.private = true,
+                "--gzip-archives" => config.gzip_archives = true,
+                arg if arg.starts_with("--irc=") => {
+                    config.irc_address = Some(arg["--irc=".len()..].to_owned());
+                }
+                arg if arg.starts_with("--grpc=") => {
+                    config.grpc_address = Some(arg["--grpc=".len()..].to_owned());
+                }
+                arg if arg.starts_with("--http3=") => {
+                    config.http3_address = Some(arg["--http3=".len()..].to_owned());
+                }
+                arg if arg.starts_with("--middleware=") => {
+                    config.middleware =
+                        parse_list(&arg["--middleware=".len()..], "middleware", &mut ignored);
+                }
+                arg if arg.starts_with("--sanitize=") => {
+                    config.sanitize =
+                        parse_list(&arg["--sanitize=".len()..], "sanitization", &mut ignored);
+                }
+                arg if arg.starts_with("--plugins=") => {
+                    config.plugins = parse_list(&arg["--plugins=".len()..], "plugin", &mut ignored);
+                }
+                arg if arg.starts_with("--script=") => {
+                    let script = arg["--script=".len()..].trim();
+                    config.script = (!script.is_empty()).then(|| script.into());
+                }
+                arg if arg.starts_with("--script-timeout=") => {
+                    match arg["--script-timeout=".len()..].parse() {
+                        Ok(milliseconds) if milliseconds > 0 => {
+                            config.script_timeout = Duration::from_millis(milliseconds);
+                        }
+                        _ => ignore(
+                            &mut ignored,
+                            format!("Ignoring {arg}, expected a number of milliseconds"),
+                        ),
+                    }
+                }
+                arg if arg.starts_with("--script-concurrency=") => {
+                    match arg["--script-concurrency=".len()..].parse() {
+                        Ok(count) if count > 0 => config.max_running_scripts = count,
+                        _ => ignore(
+                            &mut ignored,
+                            format!("Ignoring {arg}, expected a positive number of scripts"),
+                        ),
+                    }
+                }
+                arg if arg.starts_with("--rate-limit=") => {
+                    match arg["--rate-limit=".len()..].parse() {
+                        Ok(rate_limit) => config.rate_limit = rate_limit,
+                        Err(_) => ignore(
+                            &mut ignored,
+                            format!("Ignoring {arg}, expected a number of requests"),
+                        ),
+                    }
+                }
+                arg if arg.starts_with("--persistence=") => {
+                    match arg["--persistence=".len()..].parse() {
+                        Ok(persistence) => config.persistence = persistence,
+                        Err(()) => {
+                            ignore(
+                                &mut ignored,
+                                format!("Ignoring {arg}, expected `memory`, `snapshot` or `wal`"),
+                            );
+                        }
+                    }
+                }
+                arg if arg.starts_with("--snapshot-interval=") => {
+                    match arg["--snapshot-interval=".len()..].parse() {
+                        Ok(seconds) if seconds > 0 => {
+                            config.snapshot_interval = Duration::from_secs(seconds);
+                        }
+                        _ => ignore(
+                            &mut ignored,
+                            format!("Ignoring {arg}, expected a positive number of seconds"),
+                        ),
+                    }
+                }
+                arg if arg.starts_with("--broadcast-batch=") => {
+                    match arg["--broadcast-batch=".len()..].parse() {
+                        Ok(milliseconds) => {
+                            config.broadcast_batch_window = Duration::from_millis(milliseconds);
+                        }
+                        Err(_) => ignore(
+                            &mut ignored,
+                            format!("Ignoring {arg}, expected a number of milliseconds"),
+                        ),
+                    }
+                }
+                arg if arg.starts_with("--deleted-account-messages=") => {
+                    match arg["--deleted-account-messages=".len()..].parse() {
+                        Ok(policy) => config.deleted_account_messages = policy,
+                        Err(()) => ignore(
+                            &mut ignored,
+                            format!("Ignoring {arg}, expected `delete` or `anonymize`"),
+                        ),
+                    }
+                }
+                arg if arg.starts_with("--") => {
+                    ignore(&mut ignored, format!("Ignoring unknown flag {arg}"));
+                }
+                _ => config.bind_address = arg,
+            }
+        }
+        config.ignored_args = ignored;
+        config
+    }
+}
+
+/// Parse a comma-separated list of names, such as `Middleware`s.
+/// Unknown names are ignored, see `ignore`, `what` being what the names are of.
+fn parse_list<T: FromStr<Err = ()>>(list: &str, what: &str, ignored: &mut Vec<String>) -> Vec<T> {
+    list.split(',')
+        .filter(|name| !name.is_empty())
+        .filter_map(|name| match name.parse() {
+            Ok(item) => Some(item),
+            Err(()) => {
+                ignore(ignored, format!("Ignoring unknown {what} {name:?}"));
+                None
+            }
+        })
+        .collect()
+}
+
+/// Warn about an argument that is ignored, and keep the warning for `ServerConfig::ignored_args`.
+fn ignore(ignored: &mut Vec<String>, warning: String) {
+    log::warn!("{warning}");
+    ignored.push(warning);
+}
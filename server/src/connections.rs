@@ -0,0 +1,168 @@
+use std::{
+    collections::BTreeMap,
+    net::{IpAddr, SocketAddr},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use chrono::{DateTime, Utc};
+use interface::{CloseReason, ConnectionInfo, Role};
+use tokio::sync::Notify;
+
+#[derive(Debug)]
+struct Connection {
+    address: SocketAddr,
+    connected_at: DateTime<Utc>,
+    role: Role,
+    is_enveloped: bool,
+    /// Updated by the socket task as it sends events.
+    queued_events: AtomicU64,
+    dropped_events: AtomicU64,
+    /// Set before notifying `close`.
+    close_reason: Mutex<Option<CloseReason>>,
+    /// Notified to have the socket task close the connection.
+    close: Notify,
+}
+
+impl Connection {
+    fn close(&self, reason: CloseReason) {
+        *self.close_reason.lock().unwrap() = Some(reason);
+        self.close.notify_one();
+    }
+}
+
+/// Websocket clients connected, for admins to audit and disconnect.
+#[derive(Debug, Default)]
+pub struct Connections {
+    connections: Mutex<BTreeMap<u64, Arc<Connection>>>,
+    next_id: AtomicU64,
+}
+
+impl Connections {
+    /// The client stays listed until the returned handle is dropped.
+    pub fn register(
+        self: &Arc<Self>,
+        address: SocketAddr,
+        role: Role,
+        is_enveloped: bool,
+    ) -> ConnectionHandle {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let connection = Arc::new(Connection {
+            address,
+            connected_at: Utc::now(),
+            role,
+            is_enveloped,
+            queued_events: AtomicU64::new(0),
+            dropped_events: AtomicU64::new(0),
+            close_reason: Mutex::new(None),
+            close: Notify::new(),
+        });
+        self.connections
+            .lock()
+            .unwrap()
+            .insert(id, Arc::clone(&connection));
+        ConnectionHandle {
+            id,
+            connection,
+            connections: Arc::clone(self),
+        }
+    }
+
+    /// Oldest first.
+    pub fn list(&self) -> Vec<ConnectionInfo> {
+        self.connections
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(&id, connection)| ConnectionInfo {
+                id,
+                address: connection.address.to_string().into(),
+                connected_at: connection.connected_at,
+                role: connection.role,
+                is_enveloped: connection.is_enveloped,
+                queued_events: connection.queued_events.load(Ordering::Relaxed),
+                dropped_events: connection.dropped_events.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    /// Returns the address of the client, `None` if there's no such connection.
+    /// Clients are closed by their socket tasks, shortly after.
+    pub fn disconnect(&self, id: u64) -> Option<SocketAddr> {
+        let connections = self.connections.lock().unwrap();
+        let connection = connections.get(&id)?;
+        connection.close(CloseReason::Disconnected);
+        Some(connection.address)
+    }
+
+    /// Close every connection from `address`, e.g. as it was just banned.
+    pub fn close_address(&self, address: IpAddr, reason: CloseReason) {
+        for connection in self.connections.lock().unwrap().values() {
+            if connection.address.ip() == address {
+                connection.close(reason);
+            }
+        }
+    }
+
+    pub fn close_all(&self, reason: CloseReason) {
+        for connection in self.connections.lock().unwrap().values() {
+            connection.close(reason);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.connections.lock().unwrap().is_empty()
+    }
+}
+
+/// Held by the socket task of a connection.
+#[derive(Debug)]
+pub struct ConnectionHandle {
+    id: u64,
+    connection: Arc<Connection>,
+    connections: Arc<Connections>,
+}
+
+impl ConnectionHandle {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn is_enveloped(&self) -> bool {
+        self.connection.is_enveloped
+    }
+
+    pub fn set_queued_events(&self, count: usize) {
+        self.connection
+            .queued_events
+            .store(count as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_dropped_events(&self, count: u64) {
+        self.connection
+            .dropped_events
+            .fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Completes once the connection is to be closed, e.g. by an admin.
+    pub async fn close_requested(&self) -> CloseReason {
+        self.connection.close.notified().await;
+        self.connection
+            .close_reason
+            .lock()
+            .unwrap()
+            .unwrap_or(CloseReason::Disconnected)
+    }
+}
+
+impl Drop for ConnectionHandle {
+    fn drop(&mut self) {
+        self.connections
+            .connections
+            .lock()
+            .unwrap()
+            .remove(&self.id);
+    }
+}
@@ -1,42 +1,197 @@
 #![allow(dead_code)]
 
 use std::{
-    collections::VecDeque,
+    collections::{HashSet, VecDeque},
+    fs,
     hash::{Hash, Hasher},
+    io,
+    net::IpAddr,
+    path::Path,
     sync::{Arc, Mutex, MutexGuard},
 };
 
 use chrono::{DateTime, Duration, Utc};
-use interface::MessageId;
+use interface::{
+    Announcement, Attachment, ContentKind, ForwardedFrom, LinkPreview, MessageId, Poll, PollOption,
+    ValidationError,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::ServerResult,
+    snapshot,
+    wal::{Record, Wal},
+};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
     pub id: MessageId,
     pub content: Arc<str>,
     pub date: DateTime<Utc>,
+    pub link_preview: Option<LinkPreview>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub poll: Option<PollState>,
+    pub webhook_name: Option<Box<str>>,
+    /// Missing from snapshots predating it.
+    #[serde(default)]
+    pub content_kind: ContentKind,
+    /// Missing from snapshots predating it.
+    #[serde(default)]
+    pub attachments: Box<[Attachment]>,
+    /// Missing from snapshots predating it.
+    #[serde(default)]
+    pub author: Option<u64>,
+    /// Session that posted the message, whether or not it's shown as `author`.
+    /// Kept on the server, for block lists and deleting accounts. Missing from snapshots
+    /// predating it.
+    #[serde(default)]
+    pub poster: Option<u64>,
+    /// Missing from snapshots predating it.
+    #[serde(default)]
+    pub forwarded_from: Option<ForwardedFrom>,
+    /// Restored from an archive, so not purged again for being older than
+    /// `Settings::max_message_age`. Missing from snapshots predating it.
+    #[serde(default)]
+    pub restored: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PollState {
+    options: Vec<(Box<str>, u32)>,
+    /// Each IP address can only vote once. Not persisted, so that snapshots and the WAL hold no
+    /// addresses, addresses can vote again after a restart.
+    #[serde(skip)]
+    voters: HashSet<IpAddr>,
+}
+
+impl PollState {
+    pub fn new(options: Box<[Box<str>]>) -> Result<Self, DatabaseError> {
+        interface::validate_poll_options(&options)?;
+        let options = options
+            .into_vec()
+            .into_iter()
+            .map(|option| (option.trim().into(), 0))
+            .collect();
+        Ok(Self {
+            options,
+            voters: HashSet::new(),
+        })
+    }
+
+    fn from_interface(poll: Poll) -> Self {
+        Self {
+            options: poll
+                .options
+                .into_vec()
+                .into_iter()
+                .map(|option| (option.text, option.votes))
+                .collect(),
+            voters: HashSet::new(),
+        }
+    }
+
+    pub fn to_interface(&self) -> Poll {
+        Poll {
+            options: self
+                .options
+                .iter()
+                .map(|(text, votes)| PollOption {
+                    text: text.clone(),
+                    votes: *votes,
+                })
+                .collect(),
+        }
+    }
 }
 
 impl Message {
     pub fn new(content: Arc<str>) -> Self {
         let date = Utc::now();
-        let id = {
-            let mut hasher = std::collections::hash_map::DefaultHasher::new();
-            content.hash(&mut hasher);
-            date.hash(&mut hasher);
-            hasher.finish()
-        };
         Self {
-            id: MessageId(id),
+            id: hash_id(&content, date, 0),
             content,
             date,
+            link_preview: None,
+            expires_at: None,
+            poll: None,
+            webhook_name: None,
+            content_kind: ContentKind::default(),
+            attachments: Box::new([]),
+            author: None,
+            poster: None,
+            forwarded_from: None,
+            restored: false,
+        }
+    }
+
+    pub fn to_interface(&self) -> interface::Message {
+        interface::Message {
+            id: self.id,
+            content: self.content.as_ref().into(),
+            date: self.date,
+            link_preview: self.link_preview.clone(),
+            expires_at: self.expires_at,
+            poll: self.poll.as_ref().map(PollState::to_interface),
+            webhook_name: self.webhook_name.clone(),
+            content_kind: self.content_kind,
+            attachments: self.attachments.clone(),
+            author: self.author,
+            forwarded_from: self.forwarded_from.clone(),
+        }
+    }
+
+    /// For restoring archived messages.
+    /// Votes are kept, but who voted isn't archived, so everyone can vote again. Neither is who
+    /// posted the message, beyond its `author`.
+    pub fn from_interface(message: interface::Message) -> Self {
+        Self {
+            id: message.id,
+            content: message.content.into(),
+            date: message.date,
+            link_preview: message.link_preview,
+            expires_at: message.expires_at,
+            poll: message.poll.map(PollState::from_interface),
+            webhook_name: message.webhook_name,
+            content_kind: message.content_kind,
+            attachments: message.attachments,
+            author: message.author,
+            poster: message.author,
+            forwarded_from: message.forwarded_from,
+            restored: false,
+        }
+    }
+
+    /// Messages with expiry dates that are too far away never expire.
+    pub fn expires_in(self, expires_in: Duration) -> Self {
+        Self {
+            expires_at: self.date.checked_add_signed(expires_in),
+            ..self
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum DatabaseError {
+    #[error(transparent)]
+    Invalid(#[from] ValidationError),
+    #[error("no such message")]
+    NoSuchMessage,
+    #[error("message is not a poll")]
+    NotAPoll,
+    #[error("no such option in the poll")]
+    NoSuchPollOption,
+    #[error("already voted in this poll")]
+    AlreadyVoted,
+}
+
 #[derive(Debug, Default)]
 pub struct DataBase {
     /// Messages are ordered by date.
     messages: Mutex<VecDeque<Message>>,
+    topic: Mutex<Option<Arc<str>>>,
+    announcement: Mutex<Option<Announcement>>,
+    /// Changes to messages are logged here if persistence is `Persistence::Wal`.
+    wal: Option<Wal>,
 }
 
 fn vec_deque_remove_before<T>(vec: &mut VecDeque<T>, idx: usize) {
@@ -46,6 +201,72 @@ fn vec_deque_remove_before<T>(vec: &mut VecDeque<T>, idx: usize) {
 }
 
 impl DataBase {
+    /// Rebuild messages from the snapshot and WAL in `dir`, and log changes to them there.
+    /// The WAL is compacted right away, so it doesn't start with an incomplete record.
+    /// Only messages are persisted, not the topic or announcement.
+    pub fn open_with_wal(dir: &Path) -> ServerResult<Self> {
+        let (wal, messages, records) = Wal::open(dir)?;
+        let mut messages = VecDeque::from(messages);
+        let record_count = records.len();
+        for record in records {
+            replay(&mut messages, record);
+        }
+        log::info!(
+            "Loaded {} messages from {}, replayed {record_count} records",
+            messages.len(),
+            dir.display(),
+        );
+        wal.compact(messages.iter().cloned().collect()).wait()?;
+        Ok(Self {
+            messages: Mutex::new(messages),
+            wal: Some(wal),
+            ..Self::default()
+        })
+    }
+
+    /// Load messages from the snapshot in `dir`, if there is one.
+    pub fn open_from_snapshot(dir: &Path) -> ServerResult<Self> {
+        fs::create_dir_all(dir)?;
+        let messages = snapshot::load(&dir.join(snapshot::FILE_NAME))?;
+        log::info!("Loaded {} messages from {}", messages.len(), dir.display());
+        Ok(Self {
+            messages: Mutex::new(messages.into()),
+            ..Self::default()
+        })
+    }
+
+    /// Replace the snapshot with the current messages and empty the WAL, blocking until done.
+    /// Does nothing if there's no WAL.
+    pub fn compact_wal(&self) -> io::Result<()> {
+        let Some(wal) = &self.wal else {
+            return Ok(());
+        };
+        let messages = self.messages();
+        let compaction = wal.compact(messages.iter().cloned().collect());
+        drop(messages);
+        compaction.wait()
+    }
+
+    /// Wait for the changes logged so far to be written and synced, see `Wal::flush`.
+    pub fn flush_wal(&self) -> io::Result<()> {
+        match &self.wal {
+            Some(wal) => wal.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Copies of all messages, oldest first.
+    pub fn all_messages(&self) -> Vec<Message> {
+        self.messages().iter().cloned().collect()
+    }
+
+    /// Callers hold the lock on the messages while logging.
+    fn log(&self, record: Record) {
+        if let Some(wal) = &self.wal {
+            wal.append(&record);
+        }
+    }
+
     /// Delete all messages before a date.
     pub fn purge_before(&self, before_date: DateTime<Utc>) {
         let mut messages = self.messages.lock().unwrap();
@@ -56,10 +277,88 @@ impl DataBase {
             .iter()
             .position(|message| message.date > before_date)
         {
+            let ids = messages.range(..idx).map(|message| message.id).collect();
+            self.log(Record::Delete { ids });
             vec_deque_remove_before(&mut messages, idx);
         };
     }
 
+    /// Messages that have expired by `now`, or were sent before `cutoff` and not restored.
+    pub fn purgeable_messages(
+        &self,
+        now: DateTime<Utc>,
+        cutoff: Option<DateTime<Utc>>,
+    ) -> Vec<Message> {
+        self.messages()
+            .iter()
+            .filter(|message| {
+                message
+                    .expires_at
+                    .is_some_and(|expires_at| expires_at <= now)
+                    || cutoff.is_some_and(|cutoff| !message.restored && message.date < cutoff)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Returns IDs of the messages that were found and deleted.
+    pub fn delete_messages(&self, ids: &HashSet<MessageId>) -> Vec<MessageId> {
+        let mut messages = self.messages();
+        let mut deleted = Vec::new();
+        messages.retain(|message| {
+            if ids.contains(&message.id) {
+                deleted.push(message.id);
+                false
+            } else {
+                true
+            }
+        });
+        if !deleted.is_empty() {
+            self.log(Record::Delete {
+                ids: deleted.as_slice().into(),
+            });
+        }
+        deleted
+    }
+
+    /// Clear the author and poster of the messages posted by session `poster`.
+    /// Returns IDs of the messages that were made anonymous.
+    pub fn anonymize_messages(&self, poster: u64) -> Vec<MessageId> {
+        let mut messages = self.messages();
+        let mut anonymized = Vec::new();
+        for message in messages.iter_mut() {
+            if message.poster == Some(poster) {
+                message.author = None;
+                message.poster = None;
+                anonymized.push(message.id);
+            }
+        }
+        if !anonymized.is_empty() {
+            self.log(Record::Anonymize {
+                ids: anonymized.as_slice().into(),
+            });
+        }
+        anonymized
+    }
+
+    /// Add back messages that were deleted, keeping messages ordered by date.
+    /// Returns the messages restored, messages that weren't deleted are skipped.
+    pub fn restore_messages(&self, restored: Vec<Message>) -> Vec<Message> {
+        let mut messages = self.messages();
+        let mut added = Vec::new();
+        for message in restored {
+            if messages.iter().any(|existing| existing.id == message.id) {
+                continue;
+            }
+            self.log(Record::Add {
+                message: message.clone(),
+            });
+            insert_sorted(&mut messages, message.clone());
+            added.push(message);
+        }
+        added
+    }
+
     pub fn purge_6_hours_ago(&self) {
         let six_hours_ago = Utc::now() - Duration::hours(6);
         self.purge_before(six_hours_ago);
@@ -70,12 +369,47 @@ impl DataBase {
         self.messages.lock().unwrap()
     }
 
-    pub fn add_message(&self, message: Message) {
-        let is_invisible =
-            message.content.is_empty() || !message.content.chars().any(|c| !c.is_whitespace());
-        if !is_invisible {
-            self.messages().push_back(message);
+    /// Returns the ID the message is added with, which differs from `message.id` if that's taken,
+    /// e.g. by the same content posted at the same instant, or by a message of another date with a
+    /// colliding hash, restored ones included. Every message is compared, like `message` does.
+    pub fn add_message(&self, mut message: Message) -> Result<MessageId, DatabaseError> {
+        interface::validate_content(&message.content)?;
+        let mut messages = self.messages();
+        let end = messages.partition_point(|existing| existing.date <= message.date);
+        let mut nonce = 0;
+        while messages.iter().any(|existing| existing.id == message.id) {
+            nonce += 1;
+            message.id = hash_id(&message.content, message.date, nonce);
         }
+        let id = message.id;
+        self.log(Record::Add {
+            message: message.clone(),
+        });
+        messages.insert(end, message);
+        Ok(id)
+    }
+
+    /// Session that posted the message with ID `id`, see `Message::poster`.
+    /// Messages are looked for from the newest, as it's mostly asked of new ones.
+    pub fn poster(&self, id: MessageId) -> Option<u64> {
+        self.messages()
+            .iter()
+            .rev()
+            .find(|message| message.id == id)
+            .and_then(|message| message.poster)
+    }
+
+    /// The message with ID `id`, looked for from the newest like `poster`.
+    pub fn message(&self, id: MessageId) -> Option<Message> {
+        self.messages()
+            .iter()
+            .rev()
+            .find(|message| message.id == id)
+            .cloned()
+    }
+
+    pub fn contains_message(&self, id: MessageId) -> bool {
+        self.messages().iter().any(|message| message.id == id)
     }
 
     pub fn message_count(&self) -> usize {
@@ -94,6 +428,42 @@ impl DataBase {
         messages.range(range).take(count).cloned().collect()
     }
 
+    /// Messages after the one with ID `id`, oldest first.
+    /// Returns `None` if there's no message with this ID, e.g. it has expired.
+    pub fn messages_after(&self, id: MessageId) -> Option<Vec<Message>> {
+        let messages = self.messages();
+        let index = messages.iter().position(|message| message.id == id)?;
+        Some(messages.range(index + 1..).cloned().collect())
+    }
+
+    /// Copies of the messages older than `before`, oldest first.
+    /// For going through them without holding the lock, as searching does.
+    pub fn messages_before(&self, before: Option<DateTime<Utc>>) -> Vec<Message> {
+        let messages = self.messages();
+        let end = match before {
+            Some(before) => messages.partition_point(|message| message.date < before),
+            None => messages.len(),
+        };
+        messages.range(..end).cloned().collect()
+    }
+
+    /// Messages in each of the `hours` hours up to `now`, oldest first.
+    pub fn messages_per_hour(&self, now: DateTime<Utc>, hours: usize) -> Vec<u64> {
+        let mut counts = vec![0; hours];
+        for message in self.messages().iter() {
+            let Ok(hours_ago) = usize::try_from((now - message.date).num_hours()) else {
+                continue;
+            };
+            if let Some(count) = hours
+                .checked_sub(hours_ago + 1)
+                .and_then(|index| counts.get_mut(index))
+            {
+                *count += 1;
+            }
+        }
+        counts
+    }
+
     /// Returns `None` if there are no messages.
     pub fn latest_message_date(&self) -> Option<DateTime<Utc>> {
         let messages = self.messages();
@@ -101,4 +471,202 @@ impl DataBase {
         drop(messages);
         date
     }
+
+    /// Returns `false` if there's no message with this ID.
+    pub fn set_link_preview(&self, id: MessageId, link_preview: LinkPreview) -> bool {
+        let mut messages = self.messages();
+        let Some(message) = messages.iter_mut().find(|message| message.id == id) else {
+            return false;
+        };
+        message.link_preview = Some(link_preview.clone());
+        self.log(Record::SetLinkPreview { id, link_preview });
+        true
+    }
+
+    /// Returns the updated poll.
+    pub fn vote(&self, id: MessageId, option: u32, voter: IpAddr) -> Result<Poll, DatabaseError> {
+        let mut messages = self.messages();
+        let message = messages
+            .iter_mut()
+            .find(|message| message.id == id)
+            .ok_or(DatabaseError::NoSuchMessage)?;
+        let poll = message.poll.as_mut().ok_or(DatabaseError::NotAPoll)?;
+        let (_, votes) = poll
+            .options
+            .get_mut(option as usize)
+            .ok_or(DatabaseError::NoSuchPollOption)?;
+        if !poll.voters.insert(voter) {
+            return Err(DatabaseError::AlreadyVoted);
+        }
+        *votes += 1;
+        let poll = poll.to_interface();
+        self.log(Record::Vote { id, option });
+        Ok(poll)
+    }
+
+    pub fn topic(&self) -> Option<Arc<str>> {
+        self.topic.lock().unwrap().clone()
+    }
+
+    /// Blank topic clears the topic.
+    pub fn set_topic(&self, topic: &str) -> Result<(), DatabaseError> {
+        interface::validate_topic(topic)?;
+        let topic = topic.trim();
+        *self.topic.lock().unwrap() = (!topic.is_empty()).then(|| topic.into());
+        Ok(())
+    }
+
+    pub fn announcement(&self) -> Option<Announcement> {
+        self.announcement.lock().unwrap().clone()
+    }
+
+    /// Blank content takes down the announcement.
+    /// Returns the new announcement.
+    pub fn set_announcement(&self, content: &str) -> Option<Announcement> {
+        let content = content.trim();
+        let announcement = (!content.is_empty()).then(|| Announcement {
+            content: content.into(),
+            date: Utc::now(),
+        });
+        self.announcement.lock().unwrap().clone_from(&announcement);
+        announcement
+    }
+}
+
+/// `nonce` is bumped to get another ID if one is taken.
+fn hash_id(content: &str, date: DateTime<Utc>, nonce: u64) -> MessageId {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    content.hash(&mut hasher);
+    date.hash(&mut hasher);
+    nonce.hash(&mut hasher);
+    MessageId(hasher.finish())
+}
+
+/// Keeps messages ordered by date, after those of the same date.
+fn insert_sorted(messages: &mut VecDeque<Message>, message: Message) {
+    let index = messages.partition_point(|existing| existing.date <= message.date);
+    messages.insert(index, message);
+}
+
+/// Apply a record read back from the WAL.
+/// Records were only logged for changes that succeeded, so they aren't validated again.
+fn replay(messages: &mut VecDeque<Message>, record: Record) {
+    match record {
+        Record::Add { message } => insert_sorted(messages, message),
+        Record::Delete { ids } => messages.retain(|message| !ids.contains(&message.id)),
+        Record::SetLinkPreview { id, link_preview } => {
+            if let Some(message) = messages.iter_mut().find(|message| message.id == id) {
+                message.link_preview = Some(link_preview);
+            }
+        }
+        Record::Vote { id, option } => {
+            let poll = messages
+                .iter_mut()
+                .find(|message| message.id == id)
+                .and_then(|message| message.poll.as_mut());
+            if let Some(poll) = poll {
+                if let Some((_, votes)) = poll.options.get_mut(option as usize) {
+                    *votes += 1;
+                }
+            }
+        }
+        Record::Anonymize { ids } => {
+            for message in messages
+                .iter_mut()
+                .filter(|message| ids.contains(&message.id))
+            {
+                message.author = None;
+                message.poster = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dates(database: &DataBase) -> Vec<DateTime<Utc>> {
+        database
+            .all_messages()
+            .iter()
+            .map(|message| message.date)
+            .collect()
+    }
+
+    #[test]
+    fn same_content_at_the_same_instant_gets_distinct_ids() {
+        let database = DataBase::default();
+        let message = Message::new("hello".into());
+        let ids = [
+            database.add_message(message.clone()).unwrap(),
+            database.add_message(message.clone()).unwrap(),
+            database.add_message(message).unwrap(),
+        ];
+        assert_ne!(ids[0], ids[1]);
+        assert_ne!(ids[0], ids[2]);
+        assert_ne!(ids[1], ids[2]);
+        assert_eq!(database.message_count(), 3);
+        for id in ids {
+            assert!(database.contains_message(id));
+        }
+    }
+
+    #[test]
+    fn same_content_at_other_instants_keeps_its_id() {
+        let database = DataBase::default();
+        let mut message = Message::new("hello".into());
+        let first = database.add_message(message.clone()).unwrap();
+        message.date += Duration::seconds(1);
+        message.id = hash_id(&message.content, message.date, 0);
+        let second = database.add_message(message.clone()).unwrap();
+        assert_eq!(second, message.id);
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn ids_colliding_with_messages_of_other_dates_are_replaced() {
+        let database = DataBase::default();
+        let first = Message::new("hello".into());
+        let first_id = database.add_message(first.clone()).unwrap();
+        // Forced, as finding a real 64-bit collision would take too long.
+        let mut second = Message::new("world".into());
+        second.date = first.date - Duration::days(1);
+        second.id = first_id;
+        let second_id = database.add_message(second).unwrap();
+        assert_ne!(first_id, second_id);
+        assert_eq!(database.message_count(), 2);
+        assert!(database.contains_message(first_id));
+        assert!(database.contains_message(second_id));
+    }
+
+    #[test]
+    fn messages_stay_ordered_by_date() {
+        let database = DataBase::default();
+        let now = Utc::now();
+        for seconds in [2, 0, 1, 0] {
+            let mut message = Message::new(format!("{seconds}").into());
+            message.date = now + Duration::seconds(seconds);
+            database.add_message(message).unwrap();
+        }
+        let expected: Vec<_> = [0, 0, 1, 2]
+            .into_iter()
+            .map(|seconds| now + Duration::seconds(seconds))
+            .collect();
+        assert_eq!(dates(&database), expected);
+    }
+
+    #[test]
+    fn messages_of_the_same_date_stay_in_insertion_order() {
+        let database = DataBase::default();
+        let message = Message::new("hello".into());
+        let first = database.add_message(message.clone()).unwrap();
+        let second = database.add_message(message).unwrap();
+        let ids: Vec<_> = database
+            .all_messages()
+            .iter()
+            .map(|message| message.id)
+            .collect();
+        assert_eq!(ids, [first, second]);
+    }
 }
@@ -0,0 +1,41 @@
+use hmac::{Hmac, Mac};
+use interface::MessageId;
+use sha2::Sha256;
+
+use crate::{auth::random_secret, utils::to_hex};
+
+/// Deletion tokens are HMACs of message IDs, so that nothing needs to be stored per message.
+/// The secret is generated at startup, so tokens are only valid until the server restarts.
+#[derive(Debug)]
+pub struct DeletionTokens {
+    secret: Box<str>,
+}
+
+impl Default for DeletionTokens {
+    fn default() -> Self {
+        Self {
+            secret: random_secret(),
+        }
+    }
+}
+
+impl DeletionTokens {
+    /// Only to be given to the sender of message `id`.
+    pub fn token(&self, id: MessageId) -> Box<str> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(&id.0.to_be_bytes());
+        to_hex(&mac.finalize().into_bytes()).into()
+    }
+
+    /// Compares in constant time, not to leak how much of a guessed token is right.
+    pub fn verify(&self, id: MessageId, token: &str) -> bool {
+        let expected = self.token(id);
+        expected.len() == token.len()
+            && expected
+                .bytes()
+                .zip(token.bytes())
+                .fold(0, |difference, (a, b)| difference | (a ^ b))
+                == 0
+    }
+}
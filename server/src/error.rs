@@ -0,0 +1,214 @@
+use axum::{
+    async_trait,
+    extract::{rejection::JsonRejection, FromRequest, Request},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use interface::{AttachmentRejection, ErrorResponse};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{database::DatabaseError, unfurl::UnfurlError};
+
+pub type ServerResult<T> = Result<T, ServerError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ServerError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("logger error: {0}")]
+    Logger(#[from] flexi_logger::FlexiLoggerError),
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
+    /// Of the settings file, archives, snapshots and the WAL, hence not named after any of them.
+    #[error("invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("self-check found {count} problems")]
+    SelfCheck { count: usize },
+    #[error("invalid session token")]
+    InvalidToken,
+    #[error("permission denied, requires role {required} or higher")]
+    PermissionDenied { required: interface::Role },
+    #[error("API token lacks scope {scope}")]
+    MissingScope { scope: interface::ApiScope },
+    #[error("no such session")]
+    NoSuchSession,
+    #[error("no such API token")]
+    NoSuchApiToken,
+    #[error("this server is invite-only, register with an invite code first")]
+    InviteRequired,
+    #[error("invalid or used up invite code")]
+    InvalidInvite,
+    #[error("no such webhook")]
+    NoSuchWebhook,
+    #[error("rate limited, slow down")]
+    RateLimited,
+    #[error("a message can have at most {max} different reactions")]
+    TooManyReactions { max: usize },
+    #[error("invalid URL, expected a HTTP or HTTPS URL")]
+    InvalidUrl,
+    #[error("no such subscription")]
+    NoSuchSubscription,
+    #[error("your address is banned from this server")]
+    Banned,
+    #[error("message contains a blocked word")]
+    BlockedWord,
+    #[error("the board is read-only: {reason}")]
+    ReadOnly { reason: Box<str> },
+    #[error("archiving is disabled on this server")]
+    ArchivesDisabled,
+    #[error("no such archive")]
+    NoSuchArchive,
+    #[error("no such attachment")]
+    NoSuchAttachment,
+    #[error(transparent)]
+    AttachmentRejected(#[from] AttachmentRejection),
+    #[error("rejected by plugin {plugin}: {reason}")]
+    RejectedByPlugin { plugin: Box<str>, reason: Box<str> },
+    #[error("no such connection")]
+    NoSuchConnection,
+    #[error("invalid IP address: {0}")]
+    InvalidAddress(#[from] std::net::AddrParseError),
+    #[error("invalid search pattern: {0}")]
+    InvalidPattern(#[from] regex::Error),
+    #[error(transparent)]
+    InvalidMessageId(#[from] interface::InvalidMessageId),
+    #[error("no such profile")]
+    NoSuchProfile,
+    #[error("the admin token from the server's config can only be changed there")]
+    ConfigSession,
+    #[error("this board is private, ask an admin to add your session to its members")]
+    NotAMember,
+    #[error("request body is larger than {max_size} bytes")]
+    BodyTooLarge { max_size: u64 },
+    #[error("can't read request body: {0}")]
+    Body(#[from] axum::Error),
+    #[error("invalid request: {0}")]
+    InvalidRequest(#[from] JsonRejection),
+    #[error("background task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+    /// Of the original of a message forwarded from another board.
+    #[error("can't fetch the message from its board: {0}")]
+    RemoteMessage(#[from] UnfurlError),
+}
+
+impl ServerError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Io(_)
+            | Self::Logger(_)
+            | Self::Json(_)
+            | Self::SelfCheck { .. }
+            | Self::Task(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Database(
+                DatabaseError::Invalid(_)
+                | DatabaseError::NotAPoll
+                | DatabaseError::NoSuchPollOption,
+            ) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::Database(DatabaseError::NoSuchMessage) => StatusCode::NOT_FOUND,
+            Self::Database(DatabaseError::AlreadyVoted) => StatusCode::CONFLICT,
+            Self::InvalidToken => StatusCode::UNAUTHORIZED,
+            Self::PermissionDenied { .. }
+            | Self::MissingScope { .. }
+            | Self::InviteRequired
+            | Self::InvalidInvite
+            | Self::ConfigSession
+            | Self::NotAMember => StatusCode::FORBIDDEN,
+            Self::NoSuchSession
+            | Self::NoSuchApiToken
+            | Self::NoSuchWebhook
+            | Self::NoSuchSubscription
+            | Self::ArchivesDisabled
+            | Self::NoSuchArchive
+            | Self::NoSuchAttachment
+            | Self::NoSuchConnection
+            | Self::NoSuchProfile => StatusCode::NOT_FOUND,
+            Self::InvalidUrl
+            | Self::BlockedWord
+            | Self::RejectedByPlugin { .. }
+            | Self::InvalidPattern(_)
+            | Self::InvalidMessageId(_)
+            | Self::InvalidAddress(_)
+            | Self::TooManyReactions { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::RemoteMessage(UnfurlError::InvalidUrl | UnfurlError::ForbiddenAddress) => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            Self::RemoteMessage(_) => StatusCode::BAD_GATEWAY,
+            Self::Body(_) => StatusCode::BAD_REQUEST,
+            Self::InvalidRequest(rejection) => rejection.status(),
+            Self::Banned => StatusCode::FORBIDDEN,
+            Self::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            Self::ReadOnly { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            Self::BodyTooLarge { .. }
+            | Self::AttachmentRejected(AttachmentRejection::TooLarge { .. }) => {
+                StatusCode::PAYLOAD_TOO_LARGE
+            }
+            Self::AttachmentRejected(AttachmentRejection::QuotaExceeded { .. }) => {
+                StatusCode::TOO_MANY_REQUESTS
+            }
+            Self::AttachmentRejected(AttachmentRejection::TypeNotAllowed { .. }) => {
+                StatusCode::UNSUPPORTED_MEDIA_TYPE
+            }
+        }
+    }
+}
+
+/// Error type of request handlers.
+/// Responds with the status code from `ServerError::status_code` and an `ErrorResponse` body.
+#[derive(Debug)]
+pub struct AppError(pub ServerError);
+
+impl<E: Into<ServerError>> From<E> for AppError {
+    fn from(error: E) -> Self {
+        Self(error.into())
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.0.status_code();
+        if status.is_server_error() {
+            log::error!("Error handling request: {}", self.0);
+        } else {
+            log::info!("Rejected request: {}", self.0);
+        }
+        let read_only = match &self.0 {
+            ServerError::ReadOnly { reason } => Some(reason.clone()),
+            _ => None,
+        };
+        let attachment_rejection = match &self.0 {
+            ServerError::AttachmentRejected(rejection) => Some(rejection.clone()),
+            _ => None,
+        };
+        let body = ErrorResponse {
+            error: self.0.to_string().into(),
+            read_only,
+            attachment_rejection,
+        };
+        (status, Json(body)).into_response()
+    }
+}
+
+/// `axum::Json`, except that requests it can't read are rejected with an `ErrorResponse` like
+/// other errors, instead of plain text.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for Json<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::Json(value) = axum::Json::<T>::from_request(request, state).await?;
+        Ok(Self(value))
+    }
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
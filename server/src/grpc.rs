@@ -0,0 +1,299 @@
+use std::pin::Pin;
+
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use interface::{ApiScope, ContentKind, FetchMessagesForm, SendMessageForm, SubscriptionEvent};
+use tokio::net::TcpListener;
+use tokio_stream::{
+    wrappers::{errors::BroadcastStreamRecvError, BroadcastStream, TcpListenerStream},
+    Stream, StreamExt,
+};
+use tonic::{transport::Server, Request, Response, Status};
+
+use crate::{
+    auth::Session,
+    error::{AppError, ServerError},
+    handlers, middleware,
+    plugin::Transport,
+    ServerState,
+};
+
+use proto::{
+    board_server::{Board, BoardServer},
+    FetchMessagesReply, FetchMessagesRequest, SendMessageReply, SendMessageRequest,
+    SubscribeRequest,
+};
+
+/// Generated from `proto/board.proto`.
+pub mod proto {
+    tonic::include_proto!("message_board");
+}
+
+pub fn setup_grpc_server(server_state: ServerState, address: String) {
+    tokio::spawn(async move {
+        let listener = match TcpListener::bind(&address).await {
+            Ok(listener) => listener,
+            Err(error) => {
+                log::error!("gRPC server can't listen on {address}: {error}");
+                return;
+            }
+        };
+        log::info!("gRPC server listening on {address}");
+        // Connections from banned addresses are dropped right away, see `session` for bans
+        // after that.
+        let state = server_state.clone();
+        let incoming = TcpListenerStream::new(listener).filter(move |stream| match stream {
+            Ok(stream) => stream
+                .peer_addr()
+                .is_ok_and(|address| !middleware::is_banned(&state, address.ip())),
+            Err(_) => true,
+        });
+        let service = BoardServer::new(BoardService { server_state });
+        let result = Server::builder()
+            .add_service(service)
+            .serve_with_incoming(incoming)
+            .await;
+        if let Err(error) = result {
+            log::error!("gRPC server stopped: {error}");
+        }
+    });
+}
+
+struct BoardService {
+    server_state: ServerState,
+}
+
+type MessageStream = Pin<Box<dyn Stream<Item = Result<proto::Message, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl Board for BoardService {
+    async fn send_message(
+        &self,
+        request: Request<SendMessageRequest>,
+    ) -> Result<Response<SendMessageReply>, Status> {
+        let session = session(&self.server_state, &request)?;
+        session.require_sender(&self.server_state).map_err(status)?;
+        let Some(remote_address) = request.remote_addr() else {
+            return Err(Status::failed_precondition("client address unknown"));
+        };
+        let form = SendMessageForm::try_from(request.into_inner())?;
+        log::info!("gRPC SendMessage request: {:?}", &form.content);
+        let server_state = self.server_state.clone();
+        handlers::submit_message(server_state, &session, remote_address.ip(), form)
+            .await
+            .map_err(|AppError(error)| status(error))?;
+        Ok(Response::new(SendMessageReply { ok: true }))
+    }
+
+    async fn fetch_messages(
+        &self,
+        request: Request<FetchMessagesRequest>,
+    ) -> Result<Response<FetchMessagesReply>, Status> {
+        let session = session(&self.server_state, &request)?;
+        session
+            .require_scope(ApiScope::Read)
+            .and_then(|()| session.require_access(&self.server_state))
+            .map_err(status)?;
+        let form = FetchMessagesForm::try_from(request.into_inner())?;
+        let messages = handlers::latest_messages(&self.server_state, &session, form);
+        Ok(Response::new(FetchMessagesReply {
+            messages: messages.into_vec().into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    type SubscribeStream = MessageStream;
+
+    async fn subscribe(
+        &self,
+        request: Request<SubscribeRequest>,
+    ) -> Result<Response<MessageStream>, Status> {
+        let session = session(&self.server_state, &request)?;
+        session
+            .require_scope(ApiScope::Read)
+            .and_then(|()| session.require_access(&self.server_state))
+            .map_err(status)?;
+        let receiver = self.server_state.subscriptions.subscribe_locally();
+        self.server_state.plugins.client_connected(Transport::Grpc);
+        let server_state = self.server_state.clone();
+        let state = self.server_state.clone();
+        let stream = BroadcastStream::new(receiver)
+            // Members removed from a private board stop getting messages right away.
+            .take_while(move |_| session.require_access(&server_state).is_ok())
+            .filter_map(move |event| match event {
+                Ok(SubscriptionEvent::NewMessage { message }) => {
+                    let is_blocked = state
+                        .blocks
+                        .blocked_by(&session)
+                        .hides_message(&message, &state.database);
+                    (!is_blocked).then(|| Ok(message.into()))
+                }
+                Err(BroadcastStreamRecvError::Lagged(count)) => {
+                    log::warn!("gRPC subscriber lagged behind, skipped {count} messages");
+                    None
+                }
+            });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+/// Session from metadata `authorization: Bearer <token>`, like the HTTP API.
+/// Refused if the client's address was banned since it connected.
+fn session<T>(server_state: &ServerState, request: &Request<T>) -> Result<Session, Status> {
+    if let Some(address) = request.remote_addr() {
+        if middleware::is_banned(server_state, address.ip()) {
+            return Err(status(ServerError::Banned));
+        }
+    }
+    let token = request
+        .metadata()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    Session::from_token(server_state, token).map_err(status)
+}
+
+/// The gRPC counterpart of `ServerError::status_code`.
+fn status(error: ServerError) -> Status {
+    let message = error.to_string();
+    match error.status_code() {
+        StatusCode::UNAUTHORIZED => Status::unauthenticated(message),
+        StatusCode::FORBIDDEN => Status::permission_denied(message),
+        StatusCode::NOT_FOUND => Status::not_found(message),
+        StatusCode::CONFLICT => Status::already_exists(message),
+        StatusCode::UNPROCESSABLE_ENTITY => Status::invalid_argument(message),
+        StatusCode::TOO_MANY_REQUESTS => Status::resource_exhausted(message),
+        StatusCode::SERVICE_UNAVAILABLE => Status::unavailable(message),
+        _ => {
+            log::error!("Error handling gRPC request: {message}");
+            Status::internal(message)
+        }
+    }
+}
+
+fn timestamp(date: DateTime<Utc>) -> prost_types::Timestamp {
+    prost_types::Timestamp {
+        seconds: date.timestamp(),
+        nanos: date.timestamp_subsec_nanos() as i32,
+    }
+}
+
+fn date(timestamp: prost_types::Timestamp) -> Result<DateTime<Utc>, Status> {
+    u32::try_from(timestamp.nanos)
+        .ok()
+        .and_then(|nanos| DateTime::from_timestamp(timestamp.seconds, nanos))
+        .ok_or_else(|| Status::invalid_argument("timestamp out of range"))
+}
+
+impl From<interface::Message> for proto::Message {
+    fn from(message: interface::Message) -> Self {
+        Self {
+            id: message.id.0,
+            content: message.content.into(),
+            date: Some(timestamp(message.date)),
+            link_preview: message.link_preview.map(Into::into),
+            expires_at: message.expires_at.map(timestamp),
+            poll: message.poll.map(Into::into),
+            webhook_name: message.webhook_name.map(Into::into),
+            content_kind: proto::ContentKind::from(message.content_kind).into(),
+            author: message.author,
+            forwarded_from: message.forwarded_from.map(Into::into),
+        }
+    }
+}
+
+impl From<interface::ForwardedFrom> for proto::ForwardedFrom {
+    fn from(forwarded_from: interface::ForwardedFrom) -> Self {
+        Self {
+            id: forwarded_from.id.0,
+            date: Some(timestamp(forwarded_from.date)),
+            board: forwarded_from.board.map(Into::into),
+        }
+    }
+}
+
+impl From<ContentKind> for proto::ContentKind {
+    fn from(content_kind: ContentKind) -> Self {
+        match content_kind {
+            ContentKind::PlainText => Self::PlainText,
+            ContentKind::Markdown => Self::Markdown,
+            ContentKind::Code => Self::Code,
+            ContentKind::System => Self::System,
+        }
+    }
+}
+
+impl From<proto::ContentKind> for ContentKind {
+    fn from(content_kind: proto::ContentKind) -> Self {
+        match content_kind {
+            proto::ContentKind::PlainText => Self::PlainText,
+            proto::ContentKind::Markdown => Self::Markdown,
+            proto::ContentKind::Code => Self::Code,
+            proto::ContentKind::System => Self::System,
+        }
+    }
+}
+
+impl From<interface::LinkPreview> for proto::LinkPreview {
+    fn from(link_preview: interface::LinkPreview) -> Self {
+        Self {
+            url: link_preview.url.into(),
+            title: link_preview.title.into(),
+        }
+    }
+}
+
+impl From<interface::Poll> for proto::Poll {
+    fn from(poll: interface::Poll) -> Self {
+        Self {
+            options: poll
+                .options
+                .into_vec()
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+        }
+    }
+}
+
+impl From<interface::PollOption> for proto::PollOption {
+    fn from(option: interface::PollOption) -> Self {
+        Self {
+            text: option.text.into(),
+            votes: option.votes,
+        }
+    }
+}
+
+impl TryFrom<SendMessageRequest> for SendMessageForm {
+    type Error = Status;
+
+    fn try_from(request: SendMessageRequest) -> Result<Self, Status> {
+        let expires_in = request
+            .expires_in
+            .map(std::time::Duration::try_from)
+            .transpose()
+            .map_err(|_| Status::invalid_argument("`expires_in` is negative"))?;
+        let poll_options = (!request.poll_options.is_empty())
+            .then(|| request.poll_options.into_iter().map(Into::into).collect());
+        Ok(Self {
+            content: request.content.into(),
+            expires_in,
+            poll_options,
+            client_tag: request.client_tag.map(Into::into),
+            content_kind: proto::ContentKind::try_from(request.content_kind)
+                .map_or(ContentKind::PlainText, Into::into),
+            attachments: Box::new([]),
+        })
+    }
+}
+
+impl TryFrom<FetchMessagesRequest> for FetchMessagesForm {
+    type Error = Status;
+
+    fn try_from(request: FetchMessagesRequest) -> Result<Self, Status> {
+        Ok(Self {
+            max_count: request.max_count,
+            since: request.since.map(date).transpose()?,
+        })
+    }
+}
@@ -0,0 +1,1354 @@
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    net::{IpAddr, SocketAddr},
+    sync::{atomic::Ordering, Arc},
+    time::Instant,
+};
+
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Path, Query, State},
+    http::{header, HeaderMap},
+    response::IntoResponse,
+};
+use chrono::{Duration, Utc};
+use futures_util::TryStreamExt;
+use interface::{
+    capabilities, limits, AnnounceForm, AnnounceResponse, ApiScope, Attachment, AttachmentId,
+    AttachmentRejection, AuditAction, AuditActor, BanForm, BanResponse, ContentKind,
+    CreateApiTokenForm, CreateApiTokenResponse, CreateInviteForm, CreateInviteResponse,
+    CreateSessionForm, CreateSessionResponse, CreateSubscriptionForm, CreateSubscriptionResponse,
+    CreateWebhookForm, CreateWebhookResponse, DeleteAccountForm, DeleteAccountResponse,
+    DeleteMessagesForm, DeleteMessagesResponse, DeleteSubscriptionForm, DeleteSubscriptionResponse,
+    DeleteWebhookForm, DeleteWebhookResponse, DisconnectClientForm, DisconnectClientResponse,
+    Event, FetchAnnouncementForm, FetchAnnouncementResponse, FetchAuditLogForm,
+    FetchAuditLogResponse, FetchBlocksResponse, FetchCapabilitiesForm, FetchCapabilitiesResponse,
+    FetchLatestUpdateDateForm, FetchLatestUpdateDateResponse, FetchMembersResponse,
+    FetchMessageResponse, FetchMessagesForm, FetchMessagesResponse, FetchProfileResponse,
+    FetchReactionsForm, FetchReactionsResponse, FetchServerInfoForm, FetchServerInfoResponse,
+    FetchSnapshotMetricsForm, FetchStatsForm, FetchStatsResponse, FetchTimeForm, FetchTimeResponse,
+    FetchTopicForm, FetchTopicResponse, ForwardMessageForm, ForwardedFrom, Limits,
+    ListArchivesForm, ListArchivesResponse, ListConnectionsForm, ListConnectionsResponse,
+    MessageId, ReactForm, ReactResponse, RegisterForm, RegisterResponse, RemoveMessagesForm,
+    RestoreArchiveForm, RestoreArchiveResponse, RevokeApiTokenForm, RevokeApiTokenResponse,
+    RevokeSessionForm, RevokeSessionResponse, Role, RotateTokenForm, RotateTokenResponse,
+    SearchMessagesForm, SearchMessagesResponse, SendMessageForm, SendMessageResponse,
+    SetArchivedForm, SetArchivedResponse, SetBlocksForm, SetBlocksResponse, SetProfileForm,
+    SetProfileResponse, SetReadOnlyForm, SetReadOnlyResponse, SetRoleForm, SetRoleResponse,
+    SetTopicForm, SetTopicResponse, SubscriptionEvent, UnbanForm, UnbanResponse, UpdateMembersForm,
+    UpdateMembersResponse, UploadAttachmentForm, UploadAttachmentResponse, ValidationError,
+    VoteForm, VoteResponse, WebhookForm, WebhookResponse,
+};
+
+use crate::{
+    auth::{Permission, Scopes, Session},
+    config::DeletedAccountMessages,
+    database::{DatabaseError, Message, PollState},
+    error::{AppError, Json, ServerError},
+    middleware, mime, reactions, search, unfurl,
+    webhook::WebhookPost,
+    websocket::Recipient,
+    ServerState,
+};
+
+/// Hours in `FetchStatsResponse::messages_per_hour`.
+const ACTIVITY_HOURS: usize = 24;
+
+/// Add a new message, then fetch its link preview and notify subscriptions in the background.
+/// Refused if the message contains a word blocked by `Settings::blocked_words`, or if `sender`
+/// went over `Settings::max_messages_per_hour`. `sender` is `None` for webhooks, which are rate
+/// limited by their token instead.
+/// Returns the ID the message is added with, see `DataBase::add_message`.
+/// Plugins check the message on a blocking thread, as they may run scripts, see
+/// `ServerPlugin::on_message_received`.
+pub async fn post_message(
+    server_state: ServerState,
+    mut message: Message,
+    sender: Option<IpAddr>,
+) -> Result<MessageId, AppError> {
+    require_writable(&server_state)?;
+    if let Some(sender) = sender {
+        require_message_quota(&server_state, sender)?;
+    }
+    if let Cow::Owned(content) = server_state.sanitizer.sanitize(&message.content) {
+        message.content = content.into();
+    }
+    if server_state
+        .settings
+        .get()
+        .contains_blocked_word(&message.content)
+    {
+        return Err(ServerError::BlockedWord.into());
+    }
+    let content = Arc::clone(&message.content);
+    let mut interface_message = message.to_interface();
+    let plugins = Arc::clone(&server_state.plugins);
+    let received = interface_message.clone();
+    tokio::task::spawn_blocking(move || plugins.message_received(&received)).await??;
+    let attachment_ids: Vec<AttachmentId> = message
+        .attachments
+        .iter()
+        .map(|attachment| attachment.id)
+        .collect();
+    let id = match server_state.database.add_message(message) {
+        Ok(id) => id,
+        Err(error) => {
+            server_state.plugins.message_dropped(&interface_message);
+            return Err(error.into());
+        }
+    };
+    server_state.attachments.retain(attachment_ids);
+    interface_message.id = id;
+    server_state.plugins.message_stored(&interface_message);
+    let event = SubscriptionEvent::NewMessage {
+        message: interface_message,
+    };
+    server_state.subscriptions.notify(event);
+    unfurl::spawn_unfurl(server_state, id, &content);
+    Ok(id)
+}
+
+/// Count a message from `address`, refusing it if that's more than
+/// `Settings::max_messages_per_hour`. Addresses going far over it are banned for a while, see
+/// `middleware::ban_temporarily`.
+fn require_message_quota(server_state: &ServerState, address: IpAddr) -> Result<(), ServerError> {
+    let messages = server_state.quotas.record_message(address);
+    let Some(max_messages) = server_state.settings.get().max_messages_per_hour else {
+        return Ok(());
+    };
+    if messages > u64::from(max_messages) * middleware::BAN_FACTOR {
+        middleware::ban_temporarily(server_state, address);
+        return Err(ServerError::Banned);
+    }
+    if messages > u64::from(max_messages) {
+        return Err(ServerError::RateLimited);
+    }
+    Ok(())
+}
+
+/// Refuse changes while the server is read-only or archived.
+fn require_writable(server_state: &ServerState) -> Result<(), ServerError> {
+    if server_state.archived.load(Ordering::Relaxed) {
+        return Err(ServerError::ReadOnly {
+            reason: "the board is archived".into(),
+        });
+    }
+    match &*server_state.read_only.read().unwrap() {
+        Some(reason) => Err(ServerError::ReadOnly {
+            reason: reason.clone(),
+        }),
+        None => Ok(()),
+    }
+}
+
+pub async fn hello() -> impl IntoResponse {
+    "HELLO, WORLD"
+}
+
+pub async fn send_message(
+    session: Session,
+    State(server_state): State<ServerState>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Json(form): Json<SendMessageForm>,
+) -> Result<impl IntoResponse, AppError> {
+    session.require_sender(&server_state)?;
+    log::info!("/send_message request: {:?}", &form.content);
+    let deletion_tokens = Arc::clone(&server_state.deletion_tokens);
+    let id = submit_message(server_state, &session, remote_address.ip(), form).await?;
+    Ok(Json(SendMessageResponse::sent(
+        id,
+        deletion_tokens.token(id),
+    )))
+}
+
+/// Like `send_message`, counting towards the same rate limit.
+/// The content and attribution are taken from the original message, never from the client.
+pub async fn forward_message(
+    session: Session,
+    State(server_state): State<ServerState>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Json(form): Json<ForwardMessageForm>,
+) -> Result<impl IntoResponse, AppError> {
+    session.require_sender(&server_state)?;
+    log::info!(
+        "/forward_message request: {} of board {:?}",
+        form.message_id,
+        form.board
+    );
+    let board: Option<Box<str>> = form.board.map(|board| board.trim_end_matches('/').into());
+    let original = match &board {
+        Some(board) => {
+            server_state
+                .unfurler
+                .fetch_message(board, form.message_id)
+                .await?
+        }
+        None => server_state
+            .database
+            .message(form.message_id)
+            .ok_or(DatabaseError::NoSuchMessage)?
+            .to_interface(),
+    };
+    if original.content_kind == ContentKind::System {
+        return Err(DatabaseError::from(ValidationError::SystemMessage).into());
+    }
+    let mut message = new_message(&server_state, &session, original.content.into());
+    message.content_kind = original.content_kind;
+    // Copies disappear with the original, or forwarding would make them permanent.
+    message.expires_at = original.expires_at;
+    message.forwarded_from = Some(match original.forwarded_from {
+        // The first original is on the board of the forwarded one, unless its board is given.
+        Some(forwarded_from) => ForwardedFrom {
+            board: forwarded_from.board.or(board),
+            ..forwarded_from
+        },
+        None => ForwardedFrom {
+            id: original.id,
+            date: original.date,
+            board,
+        },
+    });
+    let deletion_tokens = Arc::clone(&server_state.deletion_tokens);
+    let id = post_message(server_state, message, Some(remote_address.ip())).await?;
+    Ok(Json(SendMessageResponse::sent(
+        id,
+        deletion_tokens.token(id),
+    )))
+}
+
+/// A message of this board, for other boards to forward it, see `ForwardMessageForm`.
+/// Messages hidden from `session` by its block list aren't found.
+pub async fn fetch_message(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    session.require_scope(ApiScope::Read)?;
+    session.require_access(&server_state)?;
+    let id: MessageId = id.parse()?;
+    let message = server_state
+        .database
+        .message(id)
+        .filter(|message| {
+            !server_state
+                .blocks
+                .blocked_by(&session)
+                .hides(message.poster)
+        })
+        .ok_or(DatabaseError::NoSuchMessage)?;
+    Ok(Json(FetchMessageResponse {
+        message: message.to_interface(),
+    }))
+}
+
+/// A new message sent by `session`.
+/// Messages are attributed to `session` only if it has a profile, others stay anonymous, but
+/// the session is recorded as `Message::poster` either way.
+fn new_message(server_state: &ServerState, session: &Session, content: Arc<str>) -> Message {
+    let mut message = Message::new(content);
+    message.author = session.id.filter(|&id| server_state.profiles.has(id));
+    message.poster = session.id;
+    message
+}
+
+/// Post a message from a `SendMessageForm`, shared by every transport.
+/// Sends `Event::Delivered` to the clients of `session` if the form has a `client_tag`.
+pub async fn submit_message(
+    server_state: ServerState,
+    session: &Session,
+    sender: IpAddr,
+    form: SendMessageForm,
+) -> Result<MessageId, AppError> {
+    form.validate().map_err(DatabaseError::from)?;
+    let mut message = new_message(&server_state, session, form.content.into());
+    message.content_kind = form.content_kind;
+    if let Some(expires_in) = form.expires_in {
+        let expires_in = Duration::from_std(expires_in).unwrap_or(Duration::MAX);
+        message = message.expires_in(expires_in);
+    }
+    if let Some(poll_options) = form.poll_options {
+        let poll_options = poll_options
+            .iter()
+            .map(|option| server_state.sanitizer.sanitize(option).into())
+            .collect();
+        message.poll = Some(PollState::new(poll_options)?);
+    }
+    message.attachments = form
+        .attachments
+        .into_vec()
+        .into_iter()
+        .map(|attachment| {
+            // The size is the server's to know, and the name is user-provided text.
+            let size = server_state
+                .attachments
+                .size(attachment.id)
+                .ok_or(ServerError::NoSuchAttachment)?;
+            Ok(Attachment {
+                size,
+                name: server_state.sanitizer.sanitize(&attachment.name).into(),
+                ..attachment
+            })
+        })
+        .collect::<Result<_, ServerError>>()?;
+    let id = post_message(server_state.clone(), message, Some(sender)).await?;
+    if let Some(client_tag) = form.client_tag {
+        let recipient = Recipient::new(session, sender);
+        server_state
+            .broadcaster
+            .send_to(recipient, Event::Delivered { client_tag, id });
+    }
+    Ok(id)
+}
+
+pub async fn fetch_messages(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(form): Json<FetchMessagesForm>,
+) -> Result<impl IntoResponse, AppError> {
+    session.require_scope(ApiScope::Read)?;
+    session.require_access(&server_state)?;
+    let messages = latest_messages(&server_state, &session, form);
+    log::info!(
+        "Responding fetch messages request with {} messages",
+        messages.len()
+    );
+    Ok(Json(FetchMessagesResponse { messages }))
+}
+
+/// Messages matching a `FetchMessagesForm`, shared by every transport.
+/// Messages posted by sessions blocked by `session` are left out.
+pub fn latest_messages(
+    server_state: &ServerState,
+    session: &Session,
+    form: FetchMessagesForm,
+) -> Box<[interface::Message]> {
+    let count = u32::min(form.max_count, limits::MAX_FETCH_COUNT);
+    let blocked = server_state.blocks.blocked_by(session);
+    server_state
+        .database
+        .latest_messages(count as usize)
+        .into_iter()
+        .filter(|message| {
+            // FIXME: optimize this with the assumption of messages being ordered chronologically.
+            form.since
+                .map(|since| message.date >= since)
+                .unwrap_or(true)
+        })
+        .filter(|message| !blocked.hides(message.poster))
+        .map(|message| message.to_interface())
+        .collect()
+}
+
+/// Deletes the messages whose deletion tokens are valid, skipping the others.
+pub async fn delete_messages(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(form): Json<DeleteMessagesForm>,
+) -> Result<impl IntoResponse, AppError> {
+    session.require_scope(ApiScope::Send)?;
+    session.require_access(&server_state)?;
+    require_writable(&server_state)?;
+    let ids: HashSet<MessageId> = form
+        .messages
+        .iter()
+        .filter(|deletion| {
+            server_state
+                .deletion_tokens
+                .verify(deletion.id, &deletion.deletion_token)
+        })
+        .map(|deletion| deletion.id)
+        .collect();
+    let deleted = remove_messages_by_id(&server_state, &ids);
+    log::info!(
+        "Deleted {} messages on request of their sender",
+        deleted.len()
+    );
+    Ok(Json(DeleteMessagesResponse { deleted }))
+}
+
+pub async fn remove_messages(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(form): Json<RemoveMessagesForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = session.require(Permission::DeleteMessages)?;
+    require_writable(&server_state)?;
+    form.validate().map_err(DatabaseError::from)?;
+    let ids: HashSet<MessageId> = form.ids.iter().copied().collect();
+    let deleted = remove_messages_by_id(&server_state, &ids);
+    log::info!(
+        "Deleted {} messages on request of a moderator",
+        deleted.len()
+    );
+    if !deleted.is_empty() {
+        server_state.audit_log.record(
+            actor,
+            AuditAction::RemoveMessages {
+                ids: deleted.clone(),
+            },
+        );
+    }
+    Ok(Json(DeleteMessagesResponse { deleted }))
+}
+
+/// Delete messages, releasing their attachments and reactions, and tell clients about it.
+/// Returns IDs of the messages that were found and deleted.
+fn remove_messages_by_id(server_state: &ServerState, ids: &HashSet<MessageId>) -> Box<[MessageId]> {
+    let mut attachment_ids = Vec::new();
+    server_state.database.for_each_message(|message| {
+        if ids.contains(&message.id) {
+            attachment_ids.extend(
+                message
+                    .attachments
+                    .iter()
+                    .map(|attachment| (message.id, attachment.id)),
+            );
+        }
+    });
+    let deleted = server_state.database.delete_messages(ids);
+    // Messages deleted meanwhile by the retention task had their attachments released by it.
+    server_state.attachments.release(
+        attachment_ids
+            .into_iter()
+            .filter(|(message_id, _)| deleted.contains(message_id))
+            .map(|(_, attachment_id)| attachment_id),
+    );
+    let deleted: Box<[MessageId]> = deleted.into();
+    server_state.reactions.forget(&deleted);
+    if !deleted.is_empty() {
+        server_state.broadcaster.broadcast(Event::MessagesDeleted {
+            ids: deleted.clone(),
+        });
+    }
+    deleted
+}
+
+/// Refuses patterns that don't compile, or would take too long to.
+/// Messages are matched on a blocking thread, on copies of them, so posting isn't held up.
+pub async fn search_messages(
+    session: Session,
+    State(server_state): State<ServerState>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Json(form): Json<SearchMessagesForm>,
+) -> Result<impl IntoResponse, AppError> {
+    session.require_scope(ApiScope::Read)?;
+    session.require_access(&server_state)?;
+    form.validate().map_err(DatabaseError::from)?;
+    let searches = server_state.quotas.record_search(remote_address.ip());
+    if searches > search::MAX_SEARCHES_PER_MINUTE {
+        return Err(ServerError::RateLimited.into());
+    }
+    let matcher = search::Matcher::new(&form)?;
+    let blocked = server_state.blocks.blocked_by(&session);
+    let (messages, timed_out) = match matcher.is_empty() {
+        true => (Vec::new(), false),
+        false => {
+            let count = u32::min(form.max_count, limits::MAX_FETCH_COUNT);
+            let messages = server_state.database.messages_before(form.before);
+            let deadline = Instant::now() + search::TIME_LIMIT;
+            tokio::task::spawn_blocking(move || {
+                search::search(
+                    messages,
+                    |message| !blocked.hides(message.poster) && matcher.matches(message),
+                    count as usize,
+                    deadline,
+                )
+            })
+            .await?
+        }
+    };
+    log::info!(
+        "Responding search messages request with {} messages{}",
+        messages.len(),
+        if timed_out { ", timed out" } else { "" }
+    );
+    Ok(Json(SearchMessagesResponse {
+        messages: messages
+            .into_iter()
+            .map(|message| message.to_interface())
+            .collect(),
+        timed_out,
+    }))
+}
+
+pub async fn fetch_latest_update_date(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(_): Json<FetchLatestUpdateDateForm>,
+) -> Result<impl IntoResponse, AppError> {
+    session.require_scope(ApiScope::Read)?;
+    session.require_access(&server_state)?;
+    Ok(Json(FetchLatestUpdateDateResponse {
+        latest_update_date: server_state.database.latest_message_date(),
+    }))
+}
+
+pub async fn vote(
+    session: Session,
+    State(server_state): State<ServerState>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Json(form): Json<VoteForm>,
+) -> Result<impl IntoResponse, AppError> {
+    session.require_sender(&server_state)?;
+    require_writable(&server_state)?;
+    let poll = server_state
+        .database
+        .vote(form.message_id, form.option, remote_address.ip())?;
+    server_state.broadcaster.broadcast(Event::PollUpdated {
+        id: form.message_id,
+        poll,
+    });
+    Ok(Json(VoteResponse { ok: true }))
+}
+
+/// Each IP address counts once per reaction, as with votes.
+pub async fn react(
+    session: Session,
+    State(server_state): State<ServerState>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Json(form): Json<ReactForm>,
+) -> Result<impl IntoResponse, AppError> {
+    session.require_sender(&server_state)?;
+    require_writable(&server_state)?;
+    form.validate().map_err(DatabaseError::from)?;
+    if !server_state.database.contains_message(form.message_id) {
+        return Err(DatabaseError::NoSuchMessage.into());
+    }
+    let reactions = server_state.quotas.record_reaction(remote_address.ip());
+    if reactions > reactions::MAX_REACTIONS_PER_MINUTE {
+        return Err(ServerError::RateLimited.into());
+    }
+    let reaction = form.reaction.trim();
+    let added = server_state
+        .reactions
+        .toggle(form.message_id, reaction, remote_address.ip())?;
+    server_state.broadcaster.broadcast(Event::ReactionsUpdated {
+        id: form.message_id,
+    });
+    Ok(Json(ReactResponse { added }))
+}
+
+pub async fn fetch_reactions(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Query(form): Query<FetchReactionsForm>,
+) -> Result<impl IntoResponse, AppError> {
+    session.require_scope(ApiScope::Read)?;
+    session.require_access(&server_state)?;
+    let ids = form.message_ids()?;
+    Ok(Json(FetchReactionsResponse {
+        reactions: server_state.reactions.tallies(&ids).into(),
+    }))
+}
+
+pub async fn fetch_profile(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Path(user): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    session.require_scope(ApiScope::Read)?;
+    session.require_access(&server_state)?;
+    let user: u64 = user.parse().map_err(|_| ServerError::NoSuchProfile)?;
+    let profile = server_state
+        .profiles
+        .get(user)
+        .ok_or(ServerError::NoSuchProfile)?;
+    Ok(Json(FetchProfileResponse { profile }))
+}
+
+/// Only sessions have profiles, guests and API tokens have nothing to attach one to.
+pub async fn set_profile(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(form): Json<SetProfileForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let Some(id) = session.id else {
+        return Err(ServerError::PermissionDenied {
+            required: Role::User,
+        }
+        .into());
+    };
+    require_writable(&server_state)?;
+    form.validate().map_err(DatabaseError::from)?;
+    let sanitize =
+        |field: Option<Box<str>>| field.map(|value| server_state.sanitizer.sanitize(&value).into());
+    let form = SetProfileForm {
+        display_name: sanitize(form.display_name),
+        pronouns: sanitize(form.pronouns),
+        avatar: sanitize(form.avatar),
+        bio: sanitize(form.bio),
+    };
+    let profile = server_state.profiles.update(id, &form);
+    Ok(Json(SetProfileResponse { profile }))
+}
+
+pub async fn fetch_blocks(
+    session: Session,
+    State(server_state): State<ServerState>,
+) -> Result<impl IntoResponse, AppError> {
+    let id = session.require_own_session()?;
+    Ok(Json(FetchBlocksResponse {
+        blocked: server_state.blocks.get(id),
+    }))
+}
+
+pub async fn set_blocks(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(form): Json<SetBlocksForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let id = session.require_own_session()?;
+    form.validate().map_err(DatabaseError::from)?;
+    let blocked = server_state.blocks.set(id, &form.blocked);
+    Ok(Json(SetBlocksResponse { blocked }))
+}
+
+pub async fn rotate_token(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(_): Json<RotateTokenForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let id = session.require_own_session()?;
+    let token = server_state
+        .sessions
+        .rotate(id)
+        .ok_or(ServerError::InvalidToken)?;
+    log::info!("Session {id} rotated its token");
+    Ok(Json(RotateTokenResponse { token }))
+}
+
+/// Messages posted by the session are deleted or made anonymous per
+/// `ServerConfig::deleted_account_messages`.
+pub async fn delete_account(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(_): Json<DeleteAccountForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let id = session.require_own_session()?;
+    require_writable(&server_state)?;
+    server_state.sessions.revoke(id);
+    server_state.profiles.forget(id);
+    server_state.blocks.forget(id);
+    server_state.members.forget(id);
+    let (deleted, anonymized) = match server_state.config.deleted_account_messages {
+        DeletedAccountMessages::Delete => {
+            let mut ids = HashSet::new();
+            server_state.database.for_each_message(|message| {
+                if message.poster == Some(id) {
+                    ids.insert(message.id);
+                }
+            });
+            (remove_messages_by_id(&server_state, &ids), Box::default())
+        }
+        DeletedAccountMessages::Anonymize => {
+            let anonymized: Box<[MessageId]> = server_state.database.anonymize_messages(id).into();
+            if !anonymized.is_empty() {
+                server_state
+                    .broadcaster
+                    .broadcast(Event::MessagesAnonymized {
+                        ids: anonymized.clone(),
+                    });
+            }
+            (Box::default(), anonymized)
+        }
+    };
+    log::info!(
+        "Session {id} deleted itself, {} messages deleted and {} made anonymous",
+        deleted.len(),
+        anonymized.len()
+    );
+    server_state.audit_log.record(
+        AuditActor::Session {
+            id,
+            role: session.role,
+        },
+        AuditAction::DeleteAccount {
+            id,
+            deleted: deleted.len() as u64,
+            anonymized: anonymized.len() as u64,
+        },
+    );
+    Ok(Json(DeleteAccountResponse {
+        deleted,
+        anonymized,
+    }))
+}
+
+pub async fn set_topic(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(form): Json<SetTopicForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = session.require(Permission::SetTopic)?;
+    require_writable(&server_state)?;
+    log::info!("/set_topic request: {:?}", &form.topic);
+    let topic = server_state.sanitizer.sanitize(&form.topic);
+    server_state.database.set_topic(&topic)?;
+    server_state.audit_log.record(
+        actor,
+        AuditAction::SetTopic {
+            topic: topic.into(),
+        },
+    );
+    let topic = server_state.database.topic();
+    server_state.broadcaster.broadcast(Event::TopicChanged {
+        topic: topic.map(|topic| topic.as_ref().into()),
+    });
+    Ok(Json(SetTopicResponse { ok: true }))
+}
+
+pub async fn fetch_topic(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(_): Json<FetchTopicForm>,
+) -> Result<impl IntoResponse, AppError> {
+    session.require_access(&server_state)?;
+    Ok(Json(FetchTopicResponse {
+        topic: server_state
+            .database
+            .topic()
+            .map(|topic| topic.as_ref().into()),
+    }))
+}
+
+pub async fn announce(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(form): Json<AnnounceForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = session.require(Permission::Announce)?;
+    log::info!("/announce request: {:?}", &form.content);
+    let announcement = server_state.database.set_announcement(&form.content);
+    server_state.audit_log.record(
+        actor,
+        AuditAction::Announce {
+            content: announcement
+                .as_ref()
+                .map(|announcement| announcement.content.clone()),
+        },
+    );
+    server_state
+        .broadcaster
+        .broadcast(Event::Announcement { announcement });
+    Ok(Json(AnnounceResponse { ok: true }))
+}
+
+pub async fn set_read_only(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(form): Json<SetReadOnlyForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = session.require(Permission::SetReadOnly)?;
+    let reason = form.reason.filter(|reason| !reason.trim().is_empty());
+    match &reason {
+        Some(reason) => log::info!("Entering read-only mode: {reason:?}"),
+        None => log::info!("Leaving read-only mode"),
+    }
+    server_state.read_only.write().unwrap().clone_from(&reason);
+    server_state.audit_log.record(
+        actor,
+        AuditAction::SetReadOnly {
+            reason: reason.clone(),
+        },
+    );
+    server_state
+        .broadcaster
+        .broadcast(Event::ReadOnly { reason });
+    Ok(Json(SetReadOnlyResponse { ok: true }))
+}
+
+pub async fn set_archived(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(form): Json<SetArchivedForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = session.require(Permission::SetArchived)?;
+    let archived = form.archived;
+    if server_state.archived.swap(archived, Ordering::Relaxed) == archived {
+        return Ok(Json(SetArchivedResponse { ok: true }));
+    }
+    match archived {
+        true => log::info!("Archived the board"),
+        false => log::info!("Unarchived the board"),
+    }
+    server_state
+        .audit_log
+        .record(actor, AuditAction::SetArchived { archived });
+    server_state
+        .broadcaster
+        .broadcast(Event::Archived { archived });
+    Ok(Json(SetArchivedResponse { ok: true }))
+}
+
+pub async fn fetch_announcement(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(_): Json<FetchAnnouncementForm>,
+) -> Result<impl IntoResponse, AppError> {
+    session.require_access(&server_state)?;
+    Ok(Json(FetchAnnouncementResponse {
+        announcement: server_state.database.announcement(),
+    }))
+}
+
+pub async fn fetch_audit_log(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(form): Json<FetchAuditLogForm>,
+) -> Result<impl IntoResponse, AppError> {
+    session.require(Permission::ViewAuditLog)?;
+    Ok(Json(FetchAuditLogResponse {
+        entries: server_state.audit_log.fetch(form.count, form.after).into(),
+    }))
+}
+
+pub async fn create_session(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(form): Json<CreateSessionForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = session.require(Permission::ManageSessions)?;
+    let (id, token) = server_state.sessions.create(form.role);
+    log::info!("Created session {id} with role {}", form.role);
+    server_state.audit_log.record(
+        actor,
+        AuditAction::CreateSession {
+            id,
+            role: form.role,
+        },
+    );
+    Ok(Json(CreateSessionResponse { id, token }))
+}
+
+pub async fn revoke_session(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(form): Json<RevokeSessionForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = session.require(Permission::ManageSessions)?;
+    if !server_state.sessions.revoke(form.id) {
+        return Err(ServerError::NoSuchSession.into());
+    }
+    server_state.profiles.forget(form.id);
+    server_state.blocks.forget(form.id);
+    server_state.members.forget(form.id);
+    log::info!("Revoked session {}", form.id);
+    server_state
+        .audit_log
+        .record(actor, AuditAction::RevokeSession { id: form.id });
+    Ok(Json(RevokeSessionResponse { ok: true }))
+}
+
+/// Takes effect on the next request of the session, websockets keep the role they connected with.
+pub async fn set_role(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(form): Json<SetRoleForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = session.require(Permission::ManageSessions)?;
+    if !server_state.sessions.set_role(form.id, form.role) {
+        return Err(ServerError::NoSuchSession.into());
+    }
+    log::info!("Set role of session {} to {}", form.id, form.role);
+    server_state.audit_log.record(
+        actor,
+        AuditAction::SetRole {
+            id: form.id,
+            role: form.role,
+        },
+    );
+    Ok(Json(SetRoleResponse { ok: true }))
+}
+
+pub async fn create_api_token(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(form): Json<CreateApiTokenForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = session.require(Permission::ManageApiTokens)?;
+    let (id, token) = server_state.api_tokens.create(Scopes::new(&form.scopes));
+    log::info!("Created API token {id} for {:?}", form.name);
+    server_state.audit_log.record(
+        actor,
+        AuditAction::CreateApiToken {
+            id,
+            name: form.name,
+            scopes: form.scopes,
+        },
+    );
+    Ok(Json(CreateApiTokenResponse { id, token }))
+}
+
+pub async fn revoke_api_token(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(form): Json<RevokeApiTokenForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = session.require(Permission::ManageApiTokens)?;
+    if !server_state.api_tokens.revoke(form.id) {
+        return Err(ServerError::NoSuchApiToken.into());
+    }
+    server_state.members.forget_api_token(form.id);
+    log::info!("Revoked API token {}", form.id);
+    server_state
+        .audit_log
+        .record(actor, AuditAction::RevokeApiToken { id: form.id });
+    Ok(Json(RevokeApiTokenResponse { ok: true }))
+}
+
+pub async fn create_invite(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(form): Json<CreateInviteForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = session.require(Permission::CreateInvites)?;
+    // An invite that can't be used would be useless, so it's good for one use at least.
+    let uses = form.uses.max(1);
+    let code = server_state.invites.create(uses);
+    server_state
+        .audit_log
+        .record(actor, AuditAction::CreateInvite { uses });
+    Ok(Json(CreateInviteResponse { code }))
+}
+
+pub async fn fetch_members(
+    session: Session,
+    State(server_state): State<ServerState>,
+) -> Result<impl IntoResponse, AppError> {
+    session.require(Permission::ManageMembers)?;
+    let (members, api_tokens) = server_state.members.list();
+    Ok(Json(FetchMembersResponse {
+        members,
+        api_tokens,
+    }))
+}
+
+pub async fn update_members(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(form): Json<UpdateMembersForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = session.require(Permission::ManageMembers)?;
+    form.validate().map_err(DatabaseError::from)?;
+    let (members, api_tokens) = server_state.members.update(&form);
+    server_state.audit_log.record(
+        actor,
+        AuditAction::UpdateMembers {
+            added: form.add,
+            removed: form.remove,
+            added_api_tokens: form.add_api_tokens,
+            removed_api_tokens: form.remove_api_tokens,
+        },
+    );
+    Ok(Json(UpdateMembersResponse {
+        members,
+        api_tokens,
+    }))
+}
+
+pub async fn register(
+    State(server_state): State<ServerState>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Json(form): Json<RegisterForm>,
+) -> Result<impl IntoResponse, AppError> {
+    if !server_state.invites.redeem(&form.invite_code) {
+        return Err(ServerError::InvalidInvite.into());
+    }
+    let (id, token) = server_state.sessions.create(Role::User);
+    log::info!("{remote_address} registered as session {id}");
+    server_state.audit_log.record(
+        AuditActor::Client {
+            address: remote_address.ip().to_string().into(),
+        },
+        AuditAction::Register { id },
+    );
+    Ok(Json(RegisterResponse { id, token }))
+}
+
+pub async fn fetch_server_info(
+    State(server_state): State<ServerState>,
+    Json(_): Json<FetchServerInfoForm>,
+) -> Result<impl IntoResponse, AppError> {
+    Ok(Json(FetchServerInfoResponse {
+        invite_only: server_state.config.invite_only,
+        read_only: server_state.read_only.read().unwrap().clone(),
+        archived: server_state.archived.load(Ordering::Relaxed),
+        is_private: server_state.config.private,
+    }))
+}
+
+pub async fn fetch_time(Json(_): Json<FetchTimeForm>) -> Result<impl IntoResponse, AppError> {
+    Ok(Json(FetchTimeResponse { now: Utc::now() }))
+}
+
+pub async fn fetch_capabilities(
+    Json(_): Json<FetchCapabilitiesForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let capabilities = [
+        capabilities::POLLS,
+        capabilities::DISAPPEARING_MESSAGES,
+        capabilities::DELIVERY_RECEIPTS,
+        capabilities::SERVER_SENT_EVENTS,
+        capabilities::CONTENT_KINDS,
+        capabilities::API_TOKENS,
+        capabilities::MESSAGE_EVENTS,
+        capabilities::ATTACHMENTS,
+        capabilities::SERVER_TIME,
+        capabilities::EVENT_ENVELOPES,
+        capabilities::SEARCH,
+        capabilities::REGEX_SEARCH,
+        capabilities::DELETE_MESSAGES,
+        capabilities::REACTIONS,
+        capabilities::PROFILES,
+        capabilities::ACCOUNTS,
+        capabilities::BLOCKS,
+        capabilities::FORWARDING,
+        capabilities::CROSS_BOARD_FORWARDING,
+        capabilities::MEMBERS,
+        capabilities::ARCHIVING,
+    ];
+    Ok(Json(FetchCapabilitiesResponse {
+        capabilities: capabilities.into_iter().map(Box::from).collect(),
+        limits: Limits::default(),
+    }))
+}
+
+pub async fn create_webhook(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(form): Json<CreateWebhookForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = session.require(Permission::ManageWebhooks)?;
+    let (id, token) = server_state
+        .webhooks
+        .create(form.display_name.clone(), form.rate_limit);
+    log::info!("Created webhook {id} {:?}", form.display_name);
+    server_state.audit_log.record(
+        actor,
+        AuditAction::CreateWebhook {
+            id,
+            display_name: form.display_name,
+        },
+    );
+    Ok(Json(CreateWebhookResponse { id, token }))
+}
+
+pub async fn delete_webhook(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(form): Json<DeleteWebhookForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = session.require(Permission::ManageWebhooks)?;
+    if !server_state.webhooks.delete(form.id) {
+        return Err(ServerError::NoSuchWebhook.into());
+    }
+    log::info!("Deleted webhook {}", form.id);
+    server_state
+        .audit_log
+        .record(actor, AuditAction::DeleteWebhook { id: form.id });
+    Ok(Json(DeleteWebhookResponse { ok: true }))
+}
+
+pub async fn webhook(
+    State(server_state): State<ServerState>,
+    Path(token): Path<String>,
+    Json(form): Json<WebhookForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let display_name = match server_state.webhooks.post(&token) {
+        WebhookPost::Accepted { display_name } => display_name,
+        WebhookPost::RateLimited => return Err(ServerError::RateLimited.into()),
+        WebhookPost::NoSuchWebhook => return Err(ServerError::NoSuchWebhook.into()),
+    };
+    log::info!("Webhook {display_name:?} posted: {:?}", &form.content);
+    let mut message = Message::new(form.content.into());
+    message.webhook_name = Some(display_name);
+    post_message(server_state, message, None).await?;
+    Ok(Json(WebhookResponse { ok: true }))
+}
+
+pub async fn create_subscription(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(form): Json<CreateSubscriptionForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = session.require(Permission::ManageSubscriptions)?;
+    let (id, secret) = server_state
+        .subscriptions
+        .create(&form.url)
+        .ok_or(ServerError::InvalidUrl)?;
+    log::info!("Created subscription {id} to {:?}", form.url);
+    server_state
+        .audit_log
+        .record(actor, AuditAction::CreateSubscription { id, url: form.url });
+    Ok(Json(CreateSubscriptionResponse { id, secret }))
+}
+
+pub async fn delete_subscription(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(form): Json<DeleteSubscriptionForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = session.require(Permission::ManageSubscriptions)?;
+    if !server_state.subscriptions.delete(form.id) {
+        return Err(ServerError::NoSuchSubscription.into());
+    }
+    log::info!("Deleted subscription {}", form.id);
+    server_state
+        .audit_log
+        .record(actor, AuditAction::DeleteSubscription { id: form.id });
+    Ok(Json(DeleteSubscriptionResponse { ok: true }))
+}
+
+pub async fn list_archives(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(_): Json<ListArchivesForm>,
+) -> Result<impl IntoResponse, AppError> {
+    session.require(Permission::ManageArchives)?;
+    let archives = server_state
+        .archives
+        .clone()
+        .ok_or(ServerError::ArchivesDisabled)?;
+    let archives = tokio::task::spawn_blocking(move || archives.list()).await??;
+    Ok(Json(ListArchivesResponse {
+        archives: archives.into(),
+    }))
+}
+
+/// Restored messages no longer expire and are exempt from `Settings::max_message_age`, or they
+/// would be deleted again right away.
+pub async fn restore_archive(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(form): Json<RestoreArchiveForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = session.require(Permission::ManageArchives)?;
+    let archives = server_state
+        .archives
+        .clone()
+        .ok_or(ServerError::ArchivesDisabled)?;
+    let name = form.name.clone();
+    // Gzipped archives take a while to decompress.
+    let messages = tokio::task::spawn_blocking(move || archives.read(&name))
+        .await??
+        .into_iter()
+        .map(|message| Message {
+            expires_at: None,
+            restored: true,
+            ..Message::from_interface(message)
+        })
+        .collect();
+    let restored = server_state.database.restore_messages(messages);
+    // Attachments deleted along with the messages stay missing.
+    server_state.attachments.retain(
+        restored
+            .iter()
+            .flat_map(|message| message.attachments.iter().map(|attachment| attachment.id)),
+    );
+    let restored = restored.len() as u64;
+    log::info!("Restored {restored} messages from archive {}", form.name);
+    server_state.audit_log.record(
+        actor,
+        AuditAction::RestoreArchive {
+            name: form.name,
+            restored,
+        },
+    );
+    Ok(Json(RestoreArchiveResponse { restored }))
+}
+
+pub async fn fetch_snapshot_metrics(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(_): Json<FetchSnapshotMetricsForm>,
+) -> Result<impl IntoResponse, AppError> {
+    session.require(Permission::ViewSnapshotMetrics)?;
+    Ok(Json(server_state.snapshot_metrics.to_interface()))
+}
+
+pub async fn fetch_stats(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(_): Json<FetchStatsForm>,
+) -> Result<impl IntoResponse, AppError> {
+    session.require(Permission::ViewStats)?;
+    let messages_per_hour = server_state
+        .database
+        .messages_per_hour(Utc::now(), ACTIVITY_HOURS);
+    Ok(Json(FetchStatsResponse {
+        quotas: server_state.quotas.to_interface().into(),
+        messages_per_hour: messages_per_hour.into(),
+    }))
+}
+
+pub async fn list_connections(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(_): Json<ListConnectionsForm>,
+) -> Result<impl IntoResponse, AppError> {
+    session.require(Permission::ManageConnections)?;
+    Ok(Json(ListConnectionsResponse {
+        connections: server_state.connections.list().into(),
+    }))
+}
+
+pub async fn disconnect_client(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(form): Json<DisconnectClientForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = session.require(Permission::ManageConnections)?;
+    let address = server_state
+        .connections
+        .disconnect(form.id)
+        .ok_or(ServerError::NoSuchConnection)?;
+    log::info!("Disconnecting client {} at {address}", form.id);
+    server_state.audit_log.record(
+        actor,
+        AuditAction::DisconnectClient {
+            id: form.id,
+            address: address.to_string().into(),
+        },
+    );
+    Ok(Json(DisconnectClientResponse { ok: true }))
+}
+
+pub async fn ban(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(form): Json<BanForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = session.require(Permission::Ban)?;
+    form.validate().map_err(DatabaseError::from)?;
+    let address: IpAddr = form.address.parse()?;
+    middleware::ban(
+        &server_state,
+        address,
+        std::time::Duration::from_secs(form.seconds),
+    );
+    log::info!("Banned {address} for {} seconds", form.seconds);
+    server_state.audit_log.record(
+        actor,
+        AuditAction::Ban {
+            address: address.to_string().into(),
+            seconds: form.seconds,
+        },
+    );
+    Ok(Json(BanResponse { ok: true }))
+}
+
+/// Bans in `Settings::banned_ips` can only be lifted there.
+pub async fn unban(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Json(form): Json<UnbanForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = session.require(Permission::Ban)?;
+    let address: IpAddr = form.address.parse()?;
+    let was_banned = server_state.quotas.unban(address);
+    if was_banned {
+        log::info!("Unbanned {address}");
+        server_state.audit_log.record(
+            actor,
+            AuditAction::Unban {
+                address: address.to_string().into(),
+            },
+        );
+    }
+    Ok(Json(UnbanResponse { ok: was_banned }))
+}
+
+/// Refused with an `AttachmentRejection` if the file is too large, the uploader's daily quota is
+/// used up, or its type isn't allowed by `Settings`.
+pub async fn upload_attachment(
+    session: Session,
+    State(server_state): State<ServerState>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Query(form): Query<UploadAttachmentForm>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<impl IntoResponse, AppError> {
+    session.require_sender(&server_state)?;
+    require_writable(&server_state)?;
+    let (max_size, quota) = {
+        let settings = server_state.settings.get();
+        (
+            settings.max_attachment_size(),
+            settings.attachment_bytes_per_day,
+        )
+    };
+    let address = remote_address.ip();
+    let uploaded = server_state.quotas.bytes_uploaded_last_day(address);
+    // Checked before reading the body too, so that uploads over quota aren't read needlessly.
+    if let Some(quota) = quota.filter(|&quota| uploaded >= quota) {
+        return Err(AttachmentRejection::QuotaExceeded { quota }.into());
+    }
+    let content = read_attachment(body, max_size).await?;
+    let size = content.len() as u64;
+    let claimed_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let content_type = mime::sniff(&content, claimed_type);
+    if !server_state
+        .settings
+        .get()
+        .is_attachment_type_allowed(&content_type)
+    {
+        return Err(AttachmentRejection::TypeNotAllowed { content_type }.into());
+    }
+    if let Err(quota) = server_state.quotas.record_upload(address, size, quota) {
+        return Err(AttachmentRejection::QuotaExceeded { quota }.into());
+    }
+    let attachments = Arc::clone(&server_state.attachments);
+    let id = tokio::task::spawn_blocking(move || attachments.store(&content)).await??;
+    log::info!(
+        "Attachment {:?} ({content_type}) uploaded as {id}",
+        &form.name
+    );
+    Ok(Json(UploadAttachmentResponse {
+        attachment: Attachment {
+            id,
+            name: server_state.sanitizer.sanitize(&form.name).into(),
+            content_type,
+            size,
+        },
+    }))
+}
+
+/// Read `body` whole, refusing it as soon as it's larger than `max_size`, instead of buffering
+/// files of any size.
+async fn read_attachment(body: Body, max_size: u64) -> Result<Vec<u8>, ServerError> {
+    let mut chunks = body.into_data_stream();
+    let mut content = Vec::new();
+    while let Some(chunk) = chunks.try_next().await? {
+        if (content.len() + chunk.len()) as u64 > max_size {
+            return Err(AttachmentRejection::TooLarge { max_size }.into());
+        }
+        content.extend_from_slice(&chunk);
+    }
+    Ok(content)
+}
+
+/// Attachments are served as opaque bytes, clients know their type from `Attachment`.
+pub async fn fetch_attachment(
+    session: Session,
+    State(server_state): State<ServerState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    session.require_scope(ApiScope::Read)?;
+    session.require_access(&server_state)?;
+    let id: AttachmentId = id.parse().map_err(|_| ServerError::NoSuchAttachment)?;
+    let attachments = Arc::clone(&server_state.attachments);
+    let content = tokio::task::spawn_blocking(move || attachments.read(id))
+        .await??
+        .ok_or(ServerError::NoSuchAttachment)?;
+    let headers = [
+        (header::CONTENT_TYPE, "application/octet-stream"),
+        (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+        // The content of an ID never changes.
+        (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+    ];
+    Ok((headers, content))
+}
@@ -0,0 +1,150 @@
+use std::{fs::File, io::BufReader, net::SocketAddr, path::Path, sync::Arc};
+
+use axum::{body::Body, extract::ConnectInfo, response::IntoResponse, Router};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use h3::{error::Code, quic::BidiStream, server::RequestStream};
+use http_body_util::BodyExt;
+use hyper::{Request, Response};
+use quinn::crypto::rustls::QuicServerConfig;
+use tower::ServiceExt;
+
+use crate::{
+    config::ServerConfig,
+    error::{AppError, ServerError},
+    settings::SharedSettings,
+    utils::DynResult,
+};
+
+/// Bodies of requests other than uploads are at most this large, as with axum's
+/// `DefaultBodyLimit` over TCP.
+const MAX_BODY_SIZE: u64 = 2 * 1024 * 1024;
+
+/// Serve `app` over HTTP/3 on `address`.
+/// HTTP/3 requires TLS, so `config.tls_cert_path` and `config.tls_key_path` must be set.
+pub fn setup_http3_listener(
+    config: &ServerConfig,
+    app: Router,
+    settings: SharedSettings,
+    address: String,
+) {
+    let tls_files = (config.tls_cert_path.clone(), config.tls_key_path.clone());
+    tokio::spawn(async move {
+        let (Some(cert_path), Some(key_path)) = tls_files else {
+            log::error!(
+                "HTTP/3 requires MESSAGE_BOARD_TLS_CERT and MESSAGE_BOARD_TLS_KEY to be set"
+            );
+            return;
+        };
+        let endpoint = match bind(&address, &cert_path, &key_path) {
+            Ok(endpoint) => endpoint,
+            Err(error) => {
+                log::error!("HTTP/3 listener can't listen on {address}: {error}");
+                return;
+            }
+        };
+        log::info!("Listening on {address} (HTTP/3, experimental)");
+        while let Some(incoming) = endpoint.accept().await {
+            let app = app.clone();
+            let settings = settings.clone();
+            let remote_address = incoming.remote_address();
+            tokio::spawn(async move {
+                let result = handle_connection(app, settings, incoming, remote_address).await;
+                if let Err(error) = result {
+                    log::warn!("HTTP/3 connection with {remote_address} failed: {error}");
+                }
+            });
+        }
+    });
+}
+
+fn bind(address: &str, cert_path: &Path, key_path: &Path) -> DynResult<quinn::Endpoint> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(cert_path)?))
+        .collect::<Result<Vec<_>, _>>()?;
+    let key = rustls_pemfile::private_key(&mut BufReader::new(File::open(key_path)?))?
+        .ok_or("no private key in MESSAGE_BOARD_TLS_KEY")?;
+    let mut tls_config = rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    tls_config.alpn_protocols = vec![b"h3".to_vec()];
+    let server_config =
+        quinn::ServerConfig::with_crypto(Arc::new(QuicServerConfig::try_from(tls_config)?));
+    let address: SocketAddr = address.parse()?;
+    Ok(quinn::Endpoint::server(server_config, address)?)
+}
+
+async fn handle_connection(
+    app: Router,
+    settings: SharedSettings,
+    incoming: quinn::Incoming,
+    remote_address: SocketAddr,
+) -> DynResult<()> {
+    let connection = incoming.await?;
+    let mut connection =
+        h3::server::Connection::<_, Bytes>::new(h3_quinn::Connection::new(connection)).await?;
+    while let Some((request, stream)) = connection.accept().await? {
+        let app = app.clone();
+        // Uploads are the largest bodies, which `handlers::upload_attachment` limits further.
+        let max_body_size = u64::max(MAX_BODY_SIZE, settings.get().max_attachment_size());
+        tokio::spawn(async move {
+            let result = handle_request(app, request, stream, remote_address, max_body_size).await;
+            if let Err(error) = result {
+                log::warn!("HTTP/3 request from {remote_address} failed: {error}");
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Websocket upgrades aren't supported over HTTP/3, every other route works as over TCP.
+/// Bodies larger than `max_body_size` are refused without reading the rest of them.
+async fn handle_request<S: BidiStream<Bytes>>(
+    app: Router,
+    request: Request<()>,
+    mut stream: RequestStream<S, Bytes>,
+    remote_address: SocketAddr,
+    max_body_size: u64,
+) -> DynResult<()> {
+    let response = match read_body(&mut stream, max_body_size).await? {
+        Some(body) => {
+            let (parts, ()) = request.into_parts();
+            let mut request = Request::from_parts(parts, Body::from(body));
+            request.extensions_mut().insert(ConnectInfo(remote_address));
+            app.oneshot(request).await?
+        }
+        None => {
+            stream.stop_sending(Code::H3_NO_ERROR);
+            let error = ServerError::BodyTooLarge {
+                max_size: max_body_size,
+            };
+            AppError(error).into_response()
+        }
+    };
+    let (parts, mut body) = response.into_parts();
+    stream
+        .send_response(Response::from_parts(parts, ()))
+        .await?;
+    // Streamed frame by frame, for server-sent events.
+    while let Some(frame) = body.frame().await {
+        if let Ok(data) = frame?.into_data() {
+            stream.send_data(data).await?;
+        }
+    }
+    stream.finish().await?;
+    Ok(())
+}
+
+/// Returns `None` as soon as the body turns out to be larger than `max_size`.
+async fn read_body<S: BidiStream<Bytes>>(
+    stream: &mut RequestStream<S, Bytes>,
+    max_size: u64,
+) -> DynResult<Option<Bytes>> {
+    let max_size = usize::try_from(max_size).unwrap_or(usize::MAX);
+    let mut body = BytesMut::new();
+    while let Some(chunk) = stream.recv_data().await? {
+        if body.len().saturating_add(chunk.remaining()) > max_size {
+            return Ok(None);
+        }
+        body.put(chunk);
+    }
+    Ok(Some(body.freeze()))
+}
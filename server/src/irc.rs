@@ -0,0 +1,333 @@
+use std::{collections::HashSet, net::SocketAddr};
+
+use interface::{Event, MessageId, SubscriptionEvent};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{tcp::OwnedWriteHalf, TcpListener, TcpStream},
+    sync::{broadcast::error::RecvError, mpsc},
+};
+
+use crate::{
+    database::Message, error::ServerError, handlers::post_message, middleware, plugin::Transport,
+    ServerState,
+};
+
+/// The board is exposed as this one IRC channel.
+const CHANNEL: &str = "#board";
+const SERVER_NAME: &str = "message_board";
+/// Nick shown for messages from the board, which are anonymous.
+const ANONYMOUS_NICK: &str = "anonymous";
+/// Lines longer than this close the connection. IRC lines are at most 512 bytes, but some
+/// clients ignore that.
+const MAX_LINE_LENGTH: u64 = 4096;
+
+pub fn setup_irc_gateway(server_state: ServerState, address: String) {
+    tokio::spawn(async move {
+        let listener = match TcpListener::bind(&address).await {
+            Ok(listener) => listener,
+            Err(error) => {
+                log::error!("IRC gateway can't listen on {address}: {error}");
+                return;
+            }
+        };
+        log::info!("IRC gateway listening on {address}");
+        loop {
+            let (stream, remote_address) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(error) => {
+                    log::warn!("IRC gateway failed to accept connection: {error}");
+                    continue;
+                }
+            };
+            if middleware::is_banned(&server_state, remote_address.ip()) {
+                log::info!("IRC gateway refused banned address {remote_address}");
+                continue;
+            }
+            let server_state = server_state.clone();
+            tokio::spawn(async move {
+                let connection = Connection::new(server_state, remote_address);
+                if let Err(error) = connection.run(stream).await {
+                    log::info!("IRC connection with {remote_address} ended: {error}");
+                }
+            });
+        }
+    });
+}
+
+struct Connection {
+    server_state: ServerState,
+    remote_address: SocketAddr,
+    nick: Option<Box<str>>,
+    has_user: bool,
+    is_registered: bool,
+    is_joined: bool,
+    /// Messages posted by this connection, which IRC clients don't expect to be echoed back.
+    posted: HashSet<MessageId>,
+}
+
+impl Connection {
+    fn new(server_state: ServerState, remote_address: SocketAddr) -> Self {
+        Self {
+            server_state,
+            remote_address,
+            nick: None,
+            has_user: false,
+            is_registered: false,
+            is_joined: false,
+            posted: HashSet::new(),
+        }
+    }
+
+    fn nick(&self) -> &str {
+        self.nick.as_deref().unwrap_or("*")
+    }
+
+    /// The `nick!user@host` prefix of this connection's own messages.
+    fn prefix(&self) -> String {
+        format!(
+            "{}!{}@{}",
+            self.nick(),
+            self.nick(),
+            self.remote_address.ip()
+        )
+    }
+
+    async fn run(mut self, stream: TcpStream) -> Result<(), ServerError> {
+        let (reader, mut writer) = stream.into_split();
+        let (lines_sender, mut lines) = mpsc::channel::<String>(16);
+        // Read lines in a separate task, as `read_line` is not cancellation safe.
+        tokio::spawn(async move {
+            let mut reader = BufReader::new(reader);
+            loop {
+                let mut line = String::new();
+                match (&mut reader)
+                    .take(MAX_LINE_LENGTH)
+                    .read_line(&mut line)
+                    .await
+                {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) if !line.ends_with('\n') => break,
+                    Ok(_) => (),
+                }
+                if lines_sender.send(line).await.is_err() {
+                    break;
+                }
+            }
+        });
+        let mut new_messages = self.server_state.subscriptions.subscribe_locally();
+        let mut events = self.server_state.broadcaster.subscribe();
+        loop {
+            tokio::select! {
+                line = lines.recv() => match line {
+                    Some(line) => {
+                        if !self.handle_line(line.trim_end(), &mut writer).await? {
+                            return Ok(());
+                        }
+                    }
+                    None => return Ok(()),
+                },
+                event = new_messages.recv() => match event {
+                    Ok(SubscriptionEvent::NewMessage { message }) => {
+                        if self.is_joined && !self.posted.remove(&message.id) {
+                            let nick = message.webhook_name.as_deref().map_or(
+                                ANONYMOUS_NICK.to_owned(),
+                                |name| name.replace(|c: char| !c.is_ascii_alphanumeric(), "_"),
+                            );
+                            for line in message.content.lines() {
+                                let line = format!(
+                                    ":{nick}!{nick}@{SERVER_NAME} PRIVMSG {CHANNEL} :{line}"
+                                );
+                                send(&mut writer, &line).await?;
+                            }
+                        }
+                    }
+                    Err(RecvError::Lagged(count)) => {
+                        log::warn!("IRC connection lagged behind by {count} messages");
+                    }
+                    Err(RecvError::Closed) => return Ok(()),
+                },
+                event = events.recv() => match event.map(|broadcast| broadcast.event) {
+                    Ok(Event::TopicChanged { topic }) if self.is_joined => {
+                        let topic = topic.as_deref().unwrap_or_default();
+                        let line = format!(":{SERVER_NAME} TOPIC {CHANNEL} :{topic}");
+                        send(&mut writer, &line).await?;
+                    }
+                    Ok(Event::Announcement {
+                        announcement: Some(announcement),
+                    }) if self.is_joined => {
+                        for line in announcement.content.lines() {
+                            let line = format!(":{SERVER_NAME} NOTICE {CHANNEL} :{line}");
+                            send(&mut writer, &line).await?;
+                        }
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => (),
+                    Err(RecvError::Closed) => return Ok(()),
+                },
+            }
+        }
+    }
+
+    /// Returns `false` if the client quits.
+    async fn handle_line(
+        &mut self,
+        line: &str,
+        writer: &mut OwnedWriteHalf,
+    ) -> Result<bool, ServerError> {
+        // Drop the `:prefix` clients may send, it's meaningless from clients.
+        let line = match line.strip_prefix(':') {
+            Some(rest) => rest.split_once(' ').map_or("", |(_, rest)| rest),
+            None => line,
+        };
+        let (params, trailing) = match line.split_once(" :") {
+            Some((params, trailing)) => (params, Some(trailing)),
+            None => (line, None),
+        };
+        let mut params = params.split(' ').filter(|param| !param.is_empty());
+        let Some(command) = params.next() else {
+            return Ok(true);
+        };
+        let mut params: Vec<&str> = params.collect();
+        params.extend(trailing);
+        match (command.to_ascii_uppercase().as_str(), params.as_slice()) {
+            ("CAP", ["LS", ..]) => send(writer, &format!(":{SERVER_NAME} CAP * LS :")).await?,
+            ("CAP", _) => (),
+            ("NICK", [nick, ..]) => {
+                self.nick = Some((*nick).into());
+                self.try_register(writer).await?;
+            }
+            ("USER", [..]) => {
+                self.has_user = true;
+                self.try_register(writer).await?;
+            }
+            ("PING", [token, ..]) => {
+                send(
+                    writer,
+                    &format!(":{SERVER_NAME} PONG {SERVER_NAME} :{token}"),
+                )
+                .await?;
+            }
+            ("QUIT", _) => return Ok(false),
+            (_, _) if !self.is_registered => {
+                self.reply(writer, "451", ":You have not registered")
+                    .await?;
+            }
+            ("JOIN", [channels, ..]) => {
+                for channel in channels.split(',') {
+                    if channel == CHANNEL {
+                        self.join(writer).await?;
+                    } else {
+                        self.reply(writer, "403", &format!("{channel} :No such channel"))
+                            .await?;
+                    }
+                }
+            }
+            ("PART", [channels, ..]) if channels.split(',').any(|c| c == CHANNEL) => {
+                self.is_joined = false;
+                send(writer, &format!(":{} PART {CHANNEL}", self.prefix())).await?;
+            }
+            ("TOPIC", [CHANNEL]) => self.send_topic(writer).await?,
+            ("PRIVMSG" | "NOTICE", [CHANNEL, text]) => self.post(text, writer).await?,
+            ("PRIVMSG" | "NOTICE", [target, _]) => {
+                self.reply(writer, "401", &format!("{target} :No such nick/channel"))
+                    .await?;
+            }
+            (command, _) => {
+                self.reply(writer, "421", &format!("{command} :Unknown command"))
+                    .await?;
+            }
+        }
+        Ok(true)
+    }
+
+    async fn try_register(&mut self, writer: &mut OwnedWriteHalf) -> Result<(), ServerError> {
+        if self.is_registered || !self.has_user || self.nick.is_none() {
+            return Ok(());
+        }
+        self.is_registered = true;
+        log::info!(
+            "IRC client {} registered as {}",
+            self.remote_address,
+            self.nick()
+        );
+        self.server_state.plugins.client_connected(Transport::Irc);
+        let welcome = format!(":Welcome to Message_Board, join {CHANNEL} to chat");
+        self.reply(writer, "001", &welcome).await
+    }
+
+    async fn join(&mut self, writer: &mut OwnedWriteHalf) -> Result<(), ServerError> {
+        // IRC users are guests, which are never members.
+        if self.server_state.config.private {
+            let reply = format!("{CHANNEL} :Cannot join channel, the board is private");
+            return self.reply(writer, "473", &reply).await;
+        }
+        self.is_joined = true;
+        send(writer, &format!(":{} JOIN {CHANNEL}", self.prefix())).await?;
+        self.send_topic(writer).await?;
+        let names = format!("= {CHANNEL} :{}", self.nick());
+        self.reply(writer, "353", &names).await?;
+        self.reply(writer, "366", &format!("{CHANNEL} :End of /NAMES list"))
+            .await
+    }
+
+    async fn send_topic(&self, writer: &mut OwnedWriteHalf) -> Result<(), ServerError> {
+        match self.server_state.database.topic() {
+            Some(topic) => {
+                self.reply(writer, "332", &format!("{CHANNEL} :{topic}"))
+                    .await
+            }
+            None => {
+                self.reply(writer, "331", &format!("{CHANNEL} :No topic is set"))
+                    .await
+            }
+        }
+    }
+
+    async fn post(&mut self, text: &str, writer: &mut OwnedWriteHalf) -> Result<(), ServerError> {
+        if !self.is_joined {
+            let reply = format!("{CHANNEL} :Cannot send to channel, join it first");
+            return self.reply(writer, "404", &reply).await;
+        }
+        if middleware::is_banned(&self.server_state, self.remote_address.ip()) {
+            let reply = format!("{CHANNEL} :Cannot send to channel, your address is banned");
+            return self.reply(writer, "404", &reply).await;
+        }
+        // IRC users are guests.
+        if self.server_state.config.invite_only {
+            let reply = format!("{CHANNEL} :Cannot send to channel, server is invite-only");
+            return self.reply(writer, "404", &reply).await;
+        }
+        let message = Message::new(text.into());
+        let sender = Some(self.remote_address.ip());
+        match post_message(self.server_state.clone(), message, sender).await {
+            // Only marked after posting, as the message may get another ID. Its event can't have
+            // been handled yet, as that happens in this task too.
+            Ok(id) => {
+                self.posted.insert(id);
+            }
+            Err(error) => {
+                let reply = format!(":{SERVER_NAME} NOTICE {} :{}", self.nick(), error.0);
+                send(writer, &reply).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Send a numeric reply.
+    async fn reply(
+        &self,
+        writer: &mut OwnedWriteHalf,
+        numeric: &str,
+        params: &str,
+    ) -> Result<(), ServerError> {
+        let line = format!(":{SERVER_NAME} {numeric} {} {params}", self.nick());
+        send(writer, &line).await
+    }
+}
+
+/// Line breaks are dropped from `line`, as they would end it early and start another line.
+async fn send(writer: &mut OwnedWriteHalf, line: &str) -> Result<(), ServerError> {
+    let line = line.replace(['\r', '\n'], "");
+    writer.write_all(line.as_bytes()).await?;
+    writer.write_all(b"\r\n").await?;
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::error::ServerResult;
+
+/// File a store is saved to on every change, nowhere if the store is kept in memory only.
+#[derive(Debug, Default)]
+pub struct JsonFile {
+    path: Option<PathBuf>,
+}
+
+impl JsonFile {
+    /// Load what's saved at `path`, the default if nothing is yet, and save changes there.
+    /// Nothing is loaded or saved if `path` is `None`.
+    pub fn open<T: DeserializeOwned + Default>(path: Option<PathBuf>) -> ServerResult<(Self, T)> {
+        let Some(path) = path else {
+            return Ok((Self::default(), T::default()));
+        };
+        let value = match fs::read(&path) {
+            Ok(bytes) => {
+                log::info!("Loading {}", path.display());
+                serde_json::from_slice(&bytes)?
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => T::default(),
+            Err(error) => return Err(error.into()),
+        };
+        Ok((Self { path: Some(path) }, value))
+    }
+
+    /// Callers hold the lock on the store, so saves happen in the same order as changes.
+    /// Errors are logged, the change is still made in memory.
+    pub fn save<T: Serialize>(&self, value: &T) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if let Err(error) = write(path, value) {
+            log::error!(
+                "Can't save {}, changes will be lost on restart: {error}",
+                path.display()
+            );
+        }
+    }
+}
+
+/// Written to a temporary file first and then renamed over `path`, like snapshots.
+/// Only the owner may read it, as some stores hold tokens.
+fn write<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    let temp_path = path.with_extension("tmp");
+    let mut options = File::options();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut writer = BufWriter::new(options.open(&temp_path)?);
+    serde_json::to_writer(&mut writer, value)?;
+    let file = writer
+        .into_inner()
+        .map_err(io::IntoInnerError::into_error)?;
+    file.sync_all()?;
+    fs::rename(&temp_path, path)
+}
@@ -1,103 +1,532 @@
 #![feature(decl_macro, tuple_trait, never_type)]
 
+/// Archives of messages deleted by the retention task.
+mod archive;
+
+/// Content-addressed storage of attachments.
+mod attachment;
+
+/// Audit log of moderation actions.
+mod audit;
+
+/// Sessions, roles and permissions.
+mod auth;
+
+/// Block lists of sessions.
+mod blocks;
+
+mod config;
+
+/// Registry of connected websocket clients.
+mod connections;
+
 /// Emulates a data base, will swap out with a real one later.
 mod database;
 
+/// Tokens letting senders delete their own messages.
+mod deletion;
+
+mod error;
+
+/// gRPC service mirroring the HTTP API.
+#[cfg(feature = "grpc")]
+mod grpc;
+
+/// Request handlers for every route.
+mod handlers;
+
+/// Experimental HTTP/3 listener.
+#[cfg(feature = "http3")]
+mod http3;
+
+/// IRC gateway.
+mod irc;
+
+/// Stores saved as JSON on every change.
+mod json_file;
+
+/// Member lists of private boards.
+mod members;
+
+/// Cross-cutting layers, selected by config.
+mod middleware;
+
+/// Detection of file types from their content.
+mod mime;
+
+/// OpenAPI document generated from the interface crate.
+mod openapi;
+
+/// Hooks for features living outside the server, and the plugins shipped with it.
+mod plugin;
+
+/// Profiles of sessions.
+mod profiles;
+
+/// Per-IP usage counters and temporary bans.
+mod quota;
+
+/// Reactions to messages.
+mod reactions;
+
+/// Periodic deletion of messages.
+mod retention;
+
+/// Clean-up of user-provided text.
+mod sanitize;
+
+/// Moderation by an external command.
+mod script;
+
+/// Matching of messages for the search route.
+mod search;
+
+/// Settings reloadable at runtime.
+mod settings;
+
+/// Snapshots of all messages, written atomically.
+mod snapshot;
+
+/// Server-sent events, an alternative to websockets.
+mod sse;
+
+/// Self-check and summary printed on boot.
+mod startup;
+
+/// Outgoing event subscriptions.
+mod subscription;
+
+/// Link previews, and messages of other boards to forward.
+mod unfurl;
+
 mod utils;
 
+/// Write-ahead log of changes to messages.
+mod wal;
+
+/// Incoming webhooks.
+mod webhook;
+
 /// Manages everything Websocket.
 mod websocket;
 
-use std::sync::Arc;
+use std::{
+    future, io,
+    sync::{atomic::AtomicBool, Arc, RwLock},
+    time::{Duration, Instant},
+};
 
-use axum::{extract::State, response::IntoResponse, routing, Json, Router};
+use archive::Archives;
+use attachment::Attachments;
+use audit::AuditLog;
+use auth::{ApiTokens, Invites, Sessions};
+use axum::{extract::ConnectInfo, routing, Router};
+use blocks::Blocks;
+use config::{Persistence, ServerConfig};
+use connections::Connections;
 use database::DataBase;
+use deletion::DeletionTokens;
 use flexi_logger::{Logger, WriteMode};
-use interface::{
-    FetchLatestUpdateDateForm, FetchLatestUpdateDateResponse, FetchMessagesForm,
-    FetchMessagesResponse, SendMessageForm, SendMessageResponse,
+use hyper::{body::Incoming, service::service_fn, Request};
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::conn::auto,
 };
+use interface::CloseReason;
+use members::Members;
+use plugin::Plugins;
+use profiles::Profiles;
+use quota::Quotas;
+use reactions::Reactions;
+use sanitize::Sanitizer;
+use settings::{Settings, SharedSettings};
+use snapshot::SnapshotMetrics;
+use subscription::Subscriptions;
+use tower::Service;
+use unfurl::Unfurler;
+use webhook::Webhooks;
+use websocket::Broadcaster;
 
-use crate::{database::Message, utils::DynResult};
+use crate::error::{ServerError, ServerResult};
 
 #[allow(unused_imports)]
 use crate::utils::todo_;
 
-#[derive(Clone, Default)]
+/// On shutdown, websockets are given this long to send their close frames.
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(2);
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Pause after failing to accept a connection, so that running out of file descriptors doesn't
+/// turn into a busy loop.
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_millis(100);
+
+#[derive(Clone)]
 struct ServerState {
+    config: Arc<ServerConfig>,
     database: Arc<DataBase>,
+    broadcaster: Broadcaster,
+    unfurler: Arc<Unfurler>,
+    audit_log: Arc<AuditLog>,
+    sessions: Arc<Sessions>,
+    api_tokens: Arc<ApiTokens>,
+    invites: Arc<Invites>,
+    webhooks: Arc<Webhooks>,
+    subscriptions: Arc<Subscriptions>,
+    settings: SharedSettings,
+    /// Reason the server is read-only, `None` if it isn't.
+    read_only: Arc<RwLock<Option<Box<str>>>>,
+    /// Whether the board is archived, see `interface::SetArchivedForm`. Kept in memory only, like
+    /// `read_only`.
+    archived: Arc<AtomicBool>,
+    /// `None` if archiving is disabled.
+    archives: Option<Arc<Archives>>,
+    snapshot_metrics: Arc<SnapshotMetrics>,
+    sanitizer: Sanitizer,
+    quotas: Arc<Quotas>,
+    attachments: Arc<Attachments>,
+    plugins: Arc<Plugins>,
+    deletion_tokens: Arc<DeletionTokens>,
+    connections: Arc<Connections>,
+    reactions: Arc<Reactions>,
+    profiles: Arc<Profiles>,
+    blocks: Arc<Blocks>,
+    members: Arc<Members>,
+}
+
+impl ServerState {
+    fn new(config: ServerConfig) -> ServerResult<Self> {
+        let audit_log = match &config.audit_log_path {
+            Some(path) => AuditLog::open(path)?,
+            None => AuditLog::default(),
+        };
+        let settings = match &config.settings_path {
+            Some(path) => Settings::load(path)?,
+            None => Settings::default(),
+        };
+        let sanitizer = Sanitizer::new(&config.sanitize);
+        let database = match config.persistence {
+            Persistence::Memory => DataBase::default(),
+            Persistence::Snapshot => DataBase::open_from_snapshot(&config.data_dir)?,
+            Persistence::Wal => DataBase::open_with_wal(&config.data_dir)?,
+        };
+        let archives = config
+            .archive_dir
+            .clone()
+            .map(|dir| Arc::new(Archives::new(dir, config.gzip_archives)));
+        let attachments = Attachments::open(config.data_dir.join(attachment::DIR_NAME))?;
+        database.for_each_message(|message| {
+            attachments.retain(message.attachments.iter().map(|attachment| attachment.id));
+        });
+        let plugins = Plugins::new(&config);
+        // Stores other than messages are saved next to them, if they're persisted.
+        let stored_at = |file_name: &str| match config.persistence {
+            Persistence::Memory => None,
+            Persistence::Snapshot | Persistence::Wal => Some(config.data_dir.join(file_name)),
+        };
+        let sessions = Sessions::open(stored_at(auth::SESSIONS_FILE_NAME))?;
+        let api_tokens = ApiTokens::open(stored_at(auth::API_TOKENS_FILE_NAME))?;
+        let invites = Invites::open(stored_at(auth::INVITES_FILE_NAME))?;
+        let profiles = Profiles::open(stored_at(profiles::FILE_NAME))?;
+        let blocks = Blocks::open(stored_at(blocks::FILE_NAME))?;
+        let members = Members::open(stored_at(members::FILE_NAME))?;
+        Ok(Self {
+            config: Arc::new(config),
+            database: Arc::new(database),
+            broadcaster: Default::default(),
+            unfurler: Default::default(),
+            audit_log: Arc::new(audit_log),
+            sessions: Arc::new(sessions),
+            api_tokens: Arc::new(api_tokens),
+            invites: Arc::new(invites),
+            webhooks: Default::default(),
+            subscriptions: Default::default(),
+            settings: SharedSettings::new(settings),
+            read_only: Default::default(),
+            archived: Default::default(),
+            archives,
+            snapshot_metrics: Default::default(),
+            sanitizer,
+            quotas: Default::default(),
+            attachments: Arc::new(attachments),
+            plugins: Arc::new(plugins),
+            deletion_tokens: Default::default(),
+            connections: Default::default(),
+            reactions: Default::default(),
+            profiles: Arc::new(profiles),
+            blocks: Arc::new(blocks),
+            members: Arc::new(members),
+        })
+    }
 }
 
 #[tokio::main]
-pub async fn main() -> DynResult<()> {
+pub async fn main() -> ServerResult<()> {
     let _logger = Logger::try_with_str("info")?
         .write_mode(WriteMode::BufferAndFlush)
         .start()?;
 
-    let server_state = ServerState::default();
+    let config = ServerConfig::from_args();
+    startup::log_summary(&config);
+    let errors = startup::self_check(&config, !config.check_config);
+    for error in &errors {
+        log::error!("Self-check: {error}");
+    }
+    if !errors.is_empty() {
+        return Err(ServerError::SelfCheck {
+            count: errors.len(),
+        });
+    }
+    if config.check_config {
+        log::info!("Self-check passed");
+        return Ok(());
+    }
+    let server_state = ServerState::new(config.clone())?;
+    retention::setup_retention_task(server_state.clone());
+    websocket::setup_message_batching(server_state.clone(), config.broadcast_batch_window);
+    match config.persistence {
+        Persistence::Memory => (),
+        Persistence::Snapshot => snapshot::setup_snapshot_task(
+            server_state.database.clone(),
+            config.data_dir.join(snapshot::FILE_NAME),
+            config.snapshot_interval,
+            server_state.snapshot_metrics.clone(),
+        ),
+        Persistence::Wal => wal::setup_compaction_task(
+            server_state.database.clone(),
+            server_state.snapshot_metrics.clone(),
+        ),
+    }
+    if let Some(settings_path) = &config.settings_path {
+        settings::setup_reload_on_sighup(
+            server_state.settings.clone(),
+            server_state.connections.clone(),
+            settings_path.clone(),
+        );
+    }
+    if let Some(irc_address) = &config.irc_address {
+        irc::setup_irc_gateway(server_state.clone(), irc_address.clone());
+    }
+    if let Some(grpc_address) = &config.grpc_address {
+        #[cfg(feature = "grpc")]
+        grpc::setup_grpc_server(server_state.clone(), grpc_address.clone());
+        #[cfg(not(feature = "grpc"))]
+        log::warn!("Ignoring --grpc={grpc_address}, built without feature `grpc`");
+    }
     let app = Router::new()
-        .route("/hello", routing::get(hello))
-        .route("/send_message", routing::post(send_message))
-        .route("/fetch_messages", routing::get(fetch_messages))
+        .route("/hello", routing::get(handlers::hello))
+        .route("/send_message", routing::post(handlers::send_message))
+        .route("/fetch_messages", routing::get(handlers::fetch_messages))
         .route(
             "/fetch_latest_update_date",
-            routing::get(fetch_latest_update_date),
+            routing::get(handlers::fetch_latest_update_date),
+        )
+        .route("/set_topic", routing::post(handlers::set_topic))
+        .route("/fetch_topic", routing::get(handlers::fetch_topic))
+        .route("/vote", routing::post(handlers::vote))
+        .route("/ws", routing::get(websocket::handler))
+        .route("/events", routing::get(sse::handler))
+        .route("/announce", routing::post(handlers::announce))
+        .route(
+            "/fetch_announcement",
+            routing::get(handlers::fetch_announcement),
+        )
+        .route("/set_read_only", routing::post(handlers::set_read_only))
+        .route("/set_archived", routing::post(handlers::set_archived))
+        .route("/fetch_audit_log", routing::get(handlers::fetch_audit_log))
+        .route("/create_session", routing::post(handlers::create_session))
+        .route("/revoke_session", routing::post(handlers::revoke_session))
+        .route("/set_role", routing::post(handlers::set_role))
+        .route(
+            "/create_api_token",
+            routing::post(handlers::create_api_token),
+        )
+        .route(
+            "/revoke_api_token",
+            routing::post(handlers::revoke_api_token),
         )
-        .with_state(server_state);
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
-    axum::serve(listener, app).await?;
+        .route("/create_invite", routing::post(handlers::create_invite))
+        .route("/register", routing::post(handlers::register))
+        .route(
+            "/fetch_server_info",
+            routing::get(handlers::fetch_server_info),
+        )
+        .route("/time", routing::get(handlers::fetch_time))
+        .route(
+            "/fetch_capabilities",
+            routing::get(handlers::fetch_capabilities),
+        )
+        .route("/create_webhook", routing::post(handlers::create_webhook))
+        .route("/delete_webhook", routing::post(handlers::delete_webhook))
+        .route("/webhook/:token", routing::post(handlers::webhook))
+        .route(
+            "/create_subscription",
+            routing::post(handlers::create_subscription),
+        )
+        .route(
+            "/delete_subscription",
+            routing::post(handlers::delete_subscription),
+        )
+        .route("/list_archives", routing::get(handlers::list_archives))
+        .route("/restore_archive", routing::post(handlers::restore_archive))
+        .route(
+            "/fetch_snapshot_metrics",
+            routing::get(handlers::fetch_snapshot_metrics),
+        )
+        .route("/fetch_stats", routing::get(handlers::fetch_stats))
+        .route(
+            "/upload_attachment",
+            routing::post(handlers::upload_attachment),
+        )
+        .route("/attachment/:id", routing::get(handlers::fetch_attachment))
+        .route("/delete_messages", routing::post(handlers::delete_messages))
+        .route("/remove_messages", routing::post(handlers::remove_messages))
+        .route("/search_messages", routing::get(handlers::search_messages))
+        .route(
+            "/list_connections",
+            routing::get(handlers::list_connections),
+        )
+        .route(
+            "/disconnect_client",
+            routing::post(handlers::disconnect_client),
+        )
+        .route("/ban", routing::post(handlers::ban))
+        .route("/unban", routing::post(handlers::unban))
+        .route("/react", routing::post(handlers::react))
+        .route("/reactions", routing::get(handlers::fetch_reactions))
+        .route("/profile/:user", routing::get(handlers::fetch_profile))
+        .route("/profile", routing::put(handlers::set_profile))
+        .route("/rotate_token", routing::post(handlers::rotate_token))
+        .route("/account", routing::delete(handlers::delete_account))
+        .route(
+            "/blocks",
+            routing::get(handlers::fetch_blocks).put(handlers::set_blocks),
+        )
+        .route("/forward_message", routing::post(handlers::forward_message))
+        .route("/message/:id", routing::get(handlers::fetch_message))
+        .route(
+            "/members",
+            routing::get(handlers::fetch_members).put(handlers::update_members),
+        )
+        .route(openapi::OPENAPI_JSON, routing::get(openapi::handler));
+    #[cfg(feature = "swagger-ui")]
+    let app = app.merge(openapi::swagger_ui());
+    let app = middleware::apply_all(app.with_state(server_state.clone()), &server_state);
+    if let Some(http3_address) = &config.http3_address {
+        #[cfg(feature = "http3")]
+        http3::setup_http3_listener(
+            &config,
+            app.clone(),
+            server_state.settings.clone(),
+            http3_address.clone(),
+        );
+        #[cfg(not(feature = "http3"))]
+        log::warn!("Ignoring --http3={http3_address}, built without feature `http3`");
+    }
+    tokio::select! {
+        result = serve(&config, app) => result?,
+        () = shutdown_signal() => (),
+    }
+    log::info!("Shutting down");
+    server_state
+        .connections
+        .close_all(CloseReason::ServerShutdown);
+    let deadline = Instant::now() + SHUTDOWN_GRACE_PERIOD;
+    while !server_state.connections.is_empty() && Instant::now() < deadline {
+        tokio::time::sleep(SHUTDOWN_POLL_INTERVAL).await;
+    }
+    match config.persistence {
+        Persistence::Memory => (),
+        // Messages since the last periodic snapshot would be lost otherwise.
+        Persistence::Snapshot => {
+            snapshot::write_database(
+                &server_state.database,
+                &config.data_dir.join(snapshot::FILE_NAME),
+                &server_state.snapshot_metrics,
+            )
+            .await
+        }
+        // Messages are acknowledged once their records are queued, not once they're written.
+        Persistence::Wal => {
+            let database = Arc::clone(&server_state.database);
+            let result = tokio::task::spawn_blocking(move || database.flush_wal())
+                .await
+                .unwrap_or_else(|error| Err(io::Error::other(error)));
+            if let Err(error) = result {
+                log::error!("Can't flush the WAL, recent changes may be lost: {error}");
+            }
+        }
+    }
     Ok(())
 }
 
-async fn hello() -> impl IntoResponse {
-    "HELLO, WORLD"
-}
-
-async fn send_message(
-    State(server_state): State<ServerState>,
-    Json(form): Json<SendMessageForm>,
-) -> impl IntoResponse {
-    log::info!("/send_message request: {:?}", &form.content);
-    let message = Message::new(form.content.into());
-    server_state.database.add_message(message);
-    Json(SendMessageResponse::ok())
+/// Completes on Ctrl-C, or on `SIGTERM` on Unix.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(error) = tokio::signal::ctrl_c().await {
+            log::error!("Can't listen for Ctrl-C: {error}");
+            future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(error) => {
+                log::error!("Can't listen for SIGTERM: {error}");
+                future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = future::pending::<()>();
+    tokio::select! {
+        () = ctrl_c => (),
+        () = terminate => (),
+    }
 }
 
-async fn fetch_messages(
-    State(server_state): State<ServerState>,
-    Json(form): Json<FetchMessagesForm>,
-) -> impl IntoResponse {
-    let count = u32::min(form.max_count, 100);
-    let messages: Vec<interface::Message> = server_state
-        .database
-        .latest_messages(count as usize)
-        .into_iter()
-        .filter(|message| {
-            // FIXME: optimize this with the assumption of messages being ordered chronologically.
-            form.since
-                .map(|since| message.date >= since)
-                .unwrap_or(true)
-        })
-        .map(|message| interface::Message {
-            id: message.id,
-            content: message.content.as_ref().to_owned().into(),
-            date: message.date,
-        })
-        .collect();
+/// Like `axum::serve`, but allows choosing HTTP versions.
+async fn serve(config: &ServerConfig, app: Router) -> Result<!, ServerError> {
+    let listener = tokio::net::TcpListener::bind(&config.bind_address).await?;
     log::info!(
-        "Responding fetch messages request with {} messages",
-        messages.len()
+        "Listening on {} ({})",
+        config.bind_address,
+        if config.http1_only {
+            "HTTP/1.1 only"
+        } else {
+            "HTTP/1.1 and HTTP/2"
+        }
     );
-    Json(FetchMessagesResponse {
-        messages: messages.into(),
-    })
-}
-
-async fn fetch_latest_update_date(
-    State(server_state): State<ServerState>,
-    Json(_): Json<FetchLatestUpdateDateForm>,
-) -> impl IntoResponse {
-    Json(FetchLatestUpdateDateResponse {
-        latest_update_date: server_state.database.latest_message_date(),
-    })
+    loop {
+        // Errors come from the connection being accepted, e.g. reset before it was, or from
+        // running out of file descriptors until other connections close. The listener is fine.
+        let (stream, remote_address) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(error) => {
+                log::error!("Error accepting connection: {error}");
+                tokio::time::sleep(ACCEPT_ERROR_BACKOFF).await;
+                continue;
+            }
+        };
+        let tower_service = app.clone();
+        let service = service_fn(move |mut request: Request<Incoming>| {
+            request.extensions_mut().insert(ConnectInfo(remote_address));
+            tower_service.clone().call(request)
+        });
+        let http1_only = config.http1_only;
+        tokio::spawn(async move {
+            let mut builder = auto::Builder::new(TokioExecutor::new());
+            if http1_only {
+                builder = builder.http1_only();
+            }
+            if let Err(error) = builder
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .await
+            {
+                log::warn!("Connection with {remote_address} failed: {error}");
+            }
+        });
+    }
 }
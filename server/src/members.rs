@@ -0,0 +1,93 @@
+use std::{collections::BTreeSet, path::PathBuf, sync::Mutex};
+
+use interface::UpdateMembersForm;
+use serde::{Deserialize, Serialize};
+
+use crate::{error::ServerResult, json_file::JsonFile};
+
+/// Name of the file in `ServerConfig::data_dir` the member list is saved to.
+pub const FILE_NAME: &str = "members.json";
+
+/// Member list of a private board, by session ID and API token ID.
+/// The two are counted separately and can collide, hence two lists.
+/// Saved with the messages if they're persisted, like sessions and API tokens themselves.
+#[derive(Debug, Default)]
+pub struct Members {
+    lists: Mutex<Lists>,
+    file: JsonFile,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Lists {
+    sessions: BTreeSet<u64>,
+    api_tokens: BTreeSet<u64>,
+}
+
+impl Lists {
+    fn to_boxed(&self) -> (Box<[u64]>, Box<[u64]>) {
+        (
+            self.sessions.iter().copied().collect(),
+            self.api_tokens.iter().copied().collect(),
+        )
+    }
+}
+
+impl Members {
+    /// Load the member list saved at `path`, if any, and save changes there.
+    /// Kept in memory only if `path` is `None`.
+    pub fn open(path: Option<PathBuf>) -> ServerResult<Self> {
+        let (file, lists) = JsonFile::open(path)?;
+        Ok(Self {
+            lists: Mutex::new(lists),
+            file,
+        })
+    }
+
+    /// Session IDs and API token IDs, in ascending order.
+    pub fn list(&self) -> (Box<[u64]>, Box<[u64]>) {
+        self.lists.lock().unwrap().to_boxed()
+    }
+
+    pub fn contains_session(&self, session_id: u64) -> bool {
+        self.lists.lock().unwrap().sessions.contains(&session_id)
+    }
+
+    pub fn contains_api_token(&self, api_token_id: u64) -> bool {
+        self.lists
+            .lock()
+            .unwrap()
+            .api_tokens
+            .contains(&api_token_id)
+    }
+
+    /// Add the added members, then remove the removed ones, returning the lists as `list` would.
+    pub fn update(&self, form: &UpdateMembersForm) -> (Box<[u64]>, Box<[u64]>) {
+        let mut lists = self.lists.lock().unwrap();
+        lists.sessions.extend(form.add.iter());
+        lists.api_tokens.extend(form.add_api_tokens.iter());
+        for session_id in form.remove.iter() {
+            lists.sessions.remove(session_id);
+        }
+        for api_token_id in form.remove_api_tokens.iter() {
+            lists.api_tokens.remove(api_token_id);
+        }
+        self.file.save(&*lists);
+        lists.to_boxed()
+    }
+
+    /// Drop a revoked session from the list.
+    pub fn forget(&self, session_id: u64) {
+        let mut lists = self.lists.lock().unwrap();
+        if lists.sessions.remove(&session_id) {
+            self.file.save(&*lists);
+        }
+    }
+
+    /// Drop a revoked API token from the list.
+    pub fn forget_api_token(&self, api_token_id: u64) {
+        let mut lists = self.lists.lock().unwrap();
+        if lists.api_tokens.remove(&api_token_id) {
+            self.file.save(&*lists);
+        }
+    }
+}
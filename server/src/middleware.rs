@@ -0,0 +1,187 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request, State},
+    middleware::{self, Next},
+    response::Response,
+    Router,
+};
+use futures_util::TryStreamExt;
+use interface::{AuditAction, AuditActor, CloseReason};
+use tower_http::{compression::CompressionLayer, cors::CorsLayer};
+
+use crate::{
+    error::{AppError, ServerError},
+    ServerState,
+};
+
+/// Addresses making this many times the rate limit of requests, or sending this many times
+/// `Settings::max_messages_per_hour`, are banned temporarily.
+pub const BAN_FACTOR: u64 = 5;
+const TEMPORARY_BAN_DURATION: Duration = Duration::from_secs(15 * 60);
+
+/// A layer wrapping every route, enabled by listing it in `ServerConfig::middleware`.
+/// Authentication isn't one of them, as required permissions differ between routes, see
+/// `auth::Session`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Middleware {
+    /// Log method, path, status and duration of every request.
+    Logging,
+    /// Limit requests per IP address to `Settings::rate_limit` or `ServerConfig::rate_limit` per
+    /// minute, counted in `ServerState::quotas`. Addresses going far over the limit are banned
+    /// for a while, with or without this middleware.
+    RateLimit,
+    /// Compress response bodies if the client accepts it. Server-sent events aren't compressed.
+    Compression,
+    /// Allow cross-origin requests from any origin, for web clients.
+    Cors,
+}
+
+impl Middleware {
+    /// Used if `--middleware` isn't given.
+    pub const DEFAULT: &[Self] = &[Self::Logging];
+
+    fn apply(self, router: Router, server_state: &ServerState) -> Router {
+        match self {
+            Self::Logging => router.layer(middleware::from_fn(log_request)),
+            Self::RateLimit => router.layer(middleware::from_fn_with_state(
+                server_state.clone(),
+                rate_limit,
+            )),
+            Self::Compression => router.layer(CompressionLayer::new()),
+            Self::Cors => router.layer(CorsLayer::permissive()),
+        }
+    }
+}
+
+impl FromStr for Middleware {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "logging" => Ok(Self::Logging),
+            "rate-limit" => Ok(Self::RateLimit),
+            "compression" => Ok(Self::Compression),
+            "cors" => Ok(Self::Cors),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Wrap `router` in the middleware of `ServerConfig::middleware`, the first one being the
+/// outermost.
+/// Before any middleware, banned IP addresses are always refused, and requests are always
+/// counted in `ServerState::quotas`, banning addresses going far over the rate limit.
+pub fn apply_all(router: Router, server_state: &ServerState) -> Router {
+    let config = &server_state.config;
+    for middleware in &config.middleware {
+        log::info!("Using middleware {middleware:?}");
+    }
+    config
+        .middleware
+        .iter()
+        .rev()
+        .fold(router, |router, middleware| {
+            middleware.apply(router, server_state)
+        })
+        .layer(middleware::from_fn_with_state(
+            server_state.clone(),
+            account,
+        ))
+}
+
+/// Refuse addresses banned in `Settings::banned_ips` or temporarily, and count the request.
+/// Body bytes are counted as the body is read, rather than as its `Content-Length` claims.
+async fn account(
+    State(server_state): State<ServerState>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let address = remote_address.ip();
+    if is_banned(&server_state, address) {
+        return Err(ServerError::Banned.into());
+    }
+    let requests = server_state.quotas.record_request(address);
+    if requests > u64::from(rate_limit_of(&server_state)) * BAN_FACTOR {
+        log::warn!("Banning {address} for {requests} requests in the last minute");
+        ban_temporarily(&server_state, address);
+        return Err(ServerError::Banned.into());
+    }
+    let quotas = server_state.quotas.clone();
+    let request = request.map(|body| {
+        let chunks = body
+            .into_data_stream()
+            .inspect_ok(move |chunk| quotas.record_bytes_sent(address, chunk.len() as u64));
+        Body::from_stream(chunks)
+    });
+    Ok(next.run(request).await)
+}
+
+/// Whether `address` is banned in `Settings::banned_ips` or temporarily.
+/// Checked here for HTTP, and by the gRPC server and IRC gateway when accepting connections.
+pub fn is_banned(server_state: &ServerState, address: IpAddr) -> bool {
+    server_state.settings.get().is_banned(address) || server_state.quotas.is_banned(address)
+}
+
+/// Requests per minute per IP address, see `Middleware::RateLimit`.
+fn rate_limit_of(server_state: &ServerState) -> u32 {
+    server_state
+        .settings
+        .get()
+        .rate_limit
+        .unwrap_or(server_state.config.rate_limit)
+}
+
+/// Ban `address` for `TEMPORARY_BAN_DURATION`, closing its websockets.
+pub fn ban_temporarily(server_state: &ServerState, address: IpAddr) {
+    ban(server_state, address, TEMPORARY_BAN_DURATION);
+    server_state.audit_log.record(
+        AuditActor::System,
+        AuditAction::TemporaryBan {
+            address: address.to_string().into(),
+            seconds: TEMPORARY_BAN_DURATION.as_secs(),
+        },
+    );
+}
+
+/// Ban `address` for `duration`, closing its websockets. Callers record the ban in the audit log.
+pub fn ban(server_state: &ServerState, address: IpAddr, duration: Duration) {
+    server_state.quotas.ban(address, duration);
+    server_state
+        .connections
+        .close_address(address, CloseReason::Banned);
+}
+
+async fn log_request(request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let path = request.uri().path().to_owned();
+    let start = Instant::now();
+    let response = next.run(request).await;
+    log::info!(
+        "{method} {path} -> {} in {}ms",
+        response.status(),
+        start.elapsed().as_millis()
+    );
+    response
+}
+
+async fn rate_limit(
+    State(server_state): State<ServerState>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let requests = server_state
+        .quotas
+        .requests_last_minute(remote_address.ip());
+    if requests > u64::from(rate_limit_of(&server_state)) {
+        return Err(ServerError::RateLimited.into());
+    }
+    Ok(next.run(request).await)
+}
@@ -0,0 +1,77 @@
+/// Signatures at the start of files, and the MIME type of files starting with them.
+/// Checked in order, the first match wins.
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"OggS", "audio/ogg"),
+    (b"fLaC", "audio/flac"),
+    (b"ID3", "audio/mpeg"),
+    (b"\x1a\x45\xdf\xa3", "video/webm"),
+];
+
+/// Sizes of the BMP info headers in use, after the 14 bytes of the file header.
+const BMP_INFO_HEADER_SIZES: &[u32] = &[12, 40, 52, 56, 64, 108, 124];
+
+const OCTET_STREAM: &str = "application/octet-stream";
+
+/// MIME type of `content`, from its magic bytes. Claimed types are only trusted for text, to tell
+/// apart e.g. Markdown from plain text, as they can't be told apart from the content.
+pub fn sniff(content: &[u8], claimed: &str) -> Box<str> {
+    if let Some(content_type) = sniff_magic(content) {
+        return content_type.into();
+    }
+    let is_text = std::str::from_utf8(content).is_ok_and(|text| !text.contains('\0'));
+    match claimed.split(';').next().unwrap_or_default().trim() {
+        claimed if is_text && claimed.starts_with("text/") => claimed.into(),
+        _ if is_text => "text/plain".into(),
+        _ => OCTET_STREAM.into(),
+    }
+}
+
+fn sniff_magic(content: &[u8]) -> Option<&'static str> {
+    if let Some(&(_, content_type)) = SIGNATURES
+        .iter()
+        .find(|(signature, _)| content.starts_with(signature))
+    {
+        return Some(content_type);
+    }
+    if is_bmp(content) {
+        return Some("image/bmp");
+    }
+    // Containers with the format further in.
+    match (content.get(..4)?, content.get(8..12)?) {
+        (b"RIFF", b"WAVE") => Some("audio/wav"),
+        (b"RIFF", b"WEBP") => Some("image/webp"),
+        (_, _) if content.get(4..8)? == b"ftyp" => Some("video/mp4"),
+        _ => None,
+    }
+}
+
+/// "BM" alone starts plenty of text, so the rest of the file header is checked too: its reserved
+/// bytes are zero, and the info header after it has a known size.
+fn is_bmp(content: &[u8]) -> bool {
+    let Some(header) = content.get(..18) else {
+        return false;
+    };
+    let info_header_size = u32::from_le_bytes(header[14..18].try_into().unwrap());
+    header.starts_with(b"BM")
+        && header[6..10] == [0; 4]
+        && BMP_INFO_HEADER_SIZES.contains(&info_header_size)
+}
+
+/// Does `content_type` match any of `patterns`, which are MIME types or `type/*` wildcards?
+pub fn is_allowed(content_type: &str, patterns: &[Box<str>]) -> bool {
+    patterns
+        .iter()
+        .any(|pattern| match pattern.strip_suffix("/*") {
+            Some(kind) => content_type
+                .split_once('/')
+                .is_some_and(|(content_kind, _)| content_kind.eq_ignore_ascii_case(kind)),
+            None => content_type.eq_ignore_ascii_case(pattern),
+        })
+}
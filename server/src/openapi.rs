@@ -0,0 +1,451 @@
+use std::sync::LazyLock;
+
+use axum::{response::IntoResponse, Json};
+use interface::{
+    routes, AnnounceForm, AnnounceResponse, Announcement, ApiScope, ArchiveInfo, Attachment,
+    AttachmentId, AttachmentRejection, AuditAction, AuditActor, AuditEntry, BanForm, BanResponse,
+    ConnectionInfo, CreateApiTokenForm, CreateApiTokenResponse, CreateInviteForm,
+    CreateInviteResponse, CreateSessionForm, CreateSessionResponse, CreateSubscriptionForm,
+    CreateSubscriptionResponse, CreateWebhookForm, CreateWebhookResponse, DeleteAccountForm,
+    DeleteAccountResponse, DeleteMessagesForm, DeleteMessagesResponse, DeleteSubscriptionForm,
+    DeleteSubscriptionResponse, DeleteWebhookForm, DeleteWebhookResponse, DisconnectClientForm,
+    DisconnectClientResponse, Envelope, ErrorResponse, Event, FetchAnnouncementForm,
+    FetchAnnouncementResponse, FetchAuditLogForm, FetchAuditLogResponse, FetchBlocksForm,
+    FetchBlocksResponse, FetchCapabilitiesForm, FetchCapabilitiesResponse,
+    FetchLatestUpdateDateForm, FetchLatestUpdateDateResponse, FetchMembersForm,
+    FetchMembersResponse, FetchMessageForm, FetchMessageResponse, FetchMessagesForm,
+    FetchMessagesResponse, FetchProfileForm, FetchProfileResponse, FetchReactionsResponse,
+    FetchServerInfoForm, FetchServerInfoResponse, FetchSnapshotMetricsForm,
+    FetchSnapshotMetricsResponse, FetchStatsForm, FetchStatsResponse, FetchTimeForm,
+    FetchTimeResponse, FetchTopicForm, FetchTopicResponse, ForwardMessageForm, ForwardedFrom,
+    HttpMethod, Limits, LinkPreview, ListArchivesForm, ListArchivesResponse, ListConnectionsForm,
+    ListConnectionsResponse, Message, MessageDeletion, MessageId, MessageReactions, Poll,
+    PollOption, Profile, QuotaUsage, ReactForm, ReactResponse, ReactionTally, RegisterForm,
+    RegisterResponse, RemoveMessagesForm, RestoreArchiveForm, RestoreArchiveResponse,
+    RevokeApiTokenForm, RevokeApiTokenResponse, RevokeSessionForm, RevokeSessionResponse, Role,
+    RotateTokenForm, RotateTokenResponse, SearchMessagesForm, SearchMessagesResponse,
+    SendMessageForm, SendMessageResponse, SetArchivedForm, SetArchivedResponse, SetBlocksForm,
+    SetBlocksResponse, SetProfileForm, SetProfileResponse, SetReadOnlyForm, SetReadOnlyResponse,
+    SetRoleForm, SetRoleResponse, SetTopicForm, SetTopicResponse, SubscriptionEvent, UnbanForm,
+    UnbanResponse, UpdateMembersForm, UpdateMembersResponse, UploadAttachmentResponse, VoteForm,
+    VoteResponse, WebhookForm, WebhookResponse,
+};
+use utoipa::{
+    openapi::{
+        path::{OperationBuilder, ParameterBuilder, ParameterIn},
+        request_body::RequestBodyBuilder,
+        ComponentsBuilder, ContentBuilder, InfoBuilder, KnownFormat, ObjectBuilder, OpenApi,
+        OpenApiBuilder, PathItem, PathItemType, PathsBuilder, Ref, Required, ResponseBuilder,
+        SchemaFormat, SchemaType,
+    },
+    ToSchema,
+};
+
+/// Path of the generated OpenAPI document.
+pub const OPENAPI_JSON: &str = "/openapi.json";
+
+/// Built on first request, the interface can't change while the server is running.
+static SPEC: LazyLock<OpenApi> = LazyLock::new(spec);
+
+pub async fn handler() -> impl IntoResponse {
+    Json(&*SPEC)
+}
+
+/// Swagger UI at `/swagger-ui`, reading the document from `OPENAPI_JSON`.
+#[cfg(feature = "swagger-ui")]
+pub fn swagger_ui() -> utoipa_swagger_ui::SwaggerUi {
+    utoipa_swagger_ui::SwaggerUi::new("/swagger-ui")
+        .config(utoipa_swagger_ui::Config::from(OPENAPI_JSON))
+}
+
+fn spec() -> OpenApi {
+    let builder = SpecBuilder::default()
+        .endpoint::<SendMessageForm, SendMessageResponse>(routes::SEND_MESSAGE)
+        .endpoint::<FetchMessagesForm, FetchMessagesResponse>(routes::FETCH_MESSAGES)
+        .endpoint::<FetchLatestUpdateDateForm, FetchLatestUpdateDateResponse>(
+            routes::FETCH_LATEST_UPDATE_DATE,
+        )
+        .endpoint::<SetTopicForm, SetTopicResponse>(routes::SET_TOPIC)
+        .endpoint::<FetchTopicForm, FetchTopicResponse>(routes::FETCH_TOPIC)
+        .endpoint::<VoteForm, VoteResponse>(routes::VOTE)
+        .endpoint::<AnnounceForm, AnnounceResponse>(routes::ANNOUNCE)
+        .endpoint::<FetchAnnouncementForm, FetchAnnouncementResponse>(routes::FETCH_ANNOUNCEMENT)
+        .endpoint::<SetReadOnlyForm, SetReadOnlyResponse>(routes::SET_READ_ONLY)
+        .endpoint::<SetArchivedForm, SetArchivedResponse>(routes::SET_ARCHIVED)
+        .endpoint::<FetchAuditLogForm, FetchAuditLogResponse>(routes::FETCH_AUDIT_LOG)
+        .endpoint::<CreateSessionForm, CreateSessionResponse>(routes::CREATE_SESSION)
+        .endpoint::<RevokeSessionForm, RevokeSessionResponse>(routes::REVOKE_SESSION)
+        .endpoint::<SetRoleForm, SetRoleResponse>(routes::SET_ROLE)
+        .endpoint::<CreateApiTokenForm, CreateApiTokenResponse>(routes::CREATE_API_TOKEN)
+        .endpoint::<RevokeApiTokenForm, RevokeApiTokenResponse>(routes::REVOKE_API_TOKEN)
+        .endpoint::<CreateInviteForm, CreateInviteResponse>(routes::CREATE_INVITE)
+        .endpoint::<RegisterForm, RegisterResponse>(routes::REGISTER)
+        .endpoint::<FetchServerInfoForm, FetchServerInfoResponse>(routes::FETCH_SERVER_INFO)
+        .endpoint::<FetchTimeForm, FetchTimeResponse>(routes::FETCH_TIME)
+        .endpoint::<FetchCapabilitiesForm, FetchCapabilitiesResponse>(routes::FETCH_CAPABILITIES)
+        .endpoint::<CreateWebhookForm, CreateWebhookResponse>(routes::CREATE_WEBHOOK)
+        .endpoint::<DeleteWebhookForm, DeleteWebhookResponse>(routes::DELETE_WEBHOOK)
+        .endpoint::<WebhookForm, WebhookResponse>(routes::WEBHOOK)
+        .endpoint::<CreateSubscriptionForm, CreateSubscriptionResponse>(routes::CREATE_SUBSCRIPTION)
+        .endpoint::<DeleteSubscriptionForm, DeleteSubscriptionResponse>(routes::DELETE_SUBSCRIPTION)
+        .endpoint::<ListArchivesForm, ListArchivesResponse>(routes::LIST_ARCHIVES)
+        .endpoint::<RestoreArchiveForm, RestoreArchiveResponse>(routes::RESTORE_ARCHIVE)
+        .endpoint::<FetchSnapshotMetricsForm, FetchSnapshotMetricsResponse>(
+            routes::FETCH_SNAPSHOT_METRICS,
+        )
+        .endpoint::<FetchStatsForm, FetchStatsResponse>(routes::FETCH_STATS)
+        .endpoint::<SearchMessagesForm, SearchMessagesResponse>(routes::SEARCH_MESSAGES)
+        .endpoint::<DeleteMessagesForm, DeleteMessagesResponse>(routes::DELETE_MESSAGES)
+        .endpoint::<RemoveMessagesForm, DeleteMessagesResponse>(routes::REMOVE_MESSAGES)
+        .endpoint::<ListConnectionsForm, ListConnectionsResponse>(routes::LIST_CONNECTIONS)
+        .endpoint::<DisconnectClientForm, DisconnectClientResponse>(routes::DISCONNECT_CLIENT)
+        .endpoint::<BanForm, BanResponse>(routes::BAN)
+        .endpoint::<UnbanForm, UnbanResponse>(routes::UNBAN)
+        .endpoint::<ReactForm, ReactResponse>(routes::REACT)
+        .endpoint::<FetchProfileForm, FetchProfileResponse>(routes::FETCH_PROFILE)
+        .endpoint::<SetProfileForm, SetProfileResponse>(routes::SET_PROFILE)
+        .endpoint::<RotateTokenForm, RotateTokenResponse>(routes::ROTATE_TOKEN)
+        .endpoint::<DeleteAccountForm, DeleteAccountResponse>(routes::DELETE_ACCOUNT)
+        .endpoint::<FetchBlocksForm, FetchBlocksResponse>(routes::FETCH_BLOCKS)
+        .endpoint::<SetBlocksForm, SetBlocksResponse>(routes::SET_BLOCKS)
+        .endpoint::<ForwardMessageForm, SendMessageResponse>(routes::FORWARD_MESSAGE)
+        .endpoint::<FetchMessageForm, FetchMessageResponse>(routes::FETCH_MESSAGE)
+        .endpoint::<FetchMembersForm, FetchMembersResponse>(routes::FETCH_MEMBERS)
+        .endpoint::<UpdateMembersForm, UpdateMembersResponse>(routes::UPDATE_MEMBERS);
+    let paths = builder
+        .paths
+        .path(routes::HELLO.1, hello_path_item())
+        .path(routes::WS.1, ws_path_item())
+        .path(routes::EVENTS.1, events_path_item())
+        .path(routes::UPLOAD_ATTACHMENT.1, upload_attachment_path_item())
+        .path(routes::FETCH_REACTIONS.1, fetch_reactions_path_item())
+        .path(
+            openapi_path(routes::FETCH_ATTACHMENT.1),
+            fetch_attachment_path_item(),
+        );
+    // Types only referenced from other schemas.
+    let components = builder
+        .components
+        .schema_from::<ErrorResponse>()
+        .schema_from::<MessageId>()
+        .schema_from::<Message>()
+        .schema_from::<Poll>()
+        .schema_from::<PollOption>()
+        .schema_from::<LinkPreview>()
+        .schema_from::<Announcement>()
+        .schema_from::<Role>()
+        .schema_from::<ApiScope>()
+        .schema_from::<AuditEntry>()
+        .schema_from::<AuditActor>()
+        .schema_from::<AuditAction>()
+        .schema_from::<Envelope>()
+        .schema_from::<Event>()
+        .schema_from::<SubscriptionEvent>()
+        .schema_from::<ArchiveInfo>()
+        .schema_from::<Limits>()
+        .schema_from::<QuotaUsage>()
+        .schema_from::<AttachmentId>()
+        .schema_from::<Attachment>()
+        .schema_from::<AttachmentRejection>()
+        .schema_from::<UploadAttachmentResponse>()
+        .schema_from::<MessageDeletion>()
+        .schema_from::<ConnectionInfo>()
+        .schema_from::<FetchReactionsResponse>()
+        .schema_from::<MessageReactions>()
+        .schema_from::<ReactionTally>()
+        .schema_from::<Profile>()
+        .schema_from::<ForwardedFrom>();
+    OpenApiBuilder::new()
+        .info(
+            InfoBuilder::new()
+                .title("message_board")
+                .version(env!("CARGO_PKG_VERSION"))
+                .build(),
+        )
+        .paths(paths.build())
+        .components(Some(components.build()))
+        .build()
+}
+
+#[derive(Default)]
+struct SpecBuilder {
+    paths: PathsBuilder,
+    components: ComponentsBuilder,
+}
+
+impl SpecBuilder {
+    /// Add a route taking a JSON `Form` and responding with a JSON `Response`.
+    fn endpoint<'s, Form: ToSchema<'s>, Response: ToSchema<'s>>(
+        self,
+        (method, path): (HttpMethod, &str),
+    ) -> Self {
+        let (form_name, _) = Form::schema();
+        let (response_name, _) = Response::schema();
+        let mut operation = OperationBuilder::new()
+            .operation_id(Some(operation_id(path)))
+            .request_body(Some(
+                RequestBodyBuilder::new()
+                    .content("application/json", json_content(form_name))
+                    .required(Some(Required::True))
+                    .build(),
+            ))
+            .response(
+                "200",
+                ResponseBuilder::new()
+                    .description("Success.")
+                    .content("application/json", json_content(response_name))
+                    .build(),
+            )
+            .response(
+                "default",
+                ResponseBuilder::new()
+                    .description("Error.")
+                    .content("application/json", json_content("ErrorResponse"))
+                    .build(),
+            );
+        for parameter in path_parameters(path) {
+            operation = operation.parameter(
+                ParameterBuilder::new()
+                    .name(parameter)
+                    .parameter_in(ParameterIn::Path)
+                    .required(Required::True)
+                    .schema(Some(ObjectBuilder::new().schema_type(SchemaType::String))),
+            );
+        }
+        Self {
+            paths: self.paths.path(
+                openapi_path(path),
+                PathItem::new(path_item_type(method), operation),
+            ),
+            components: self
+                .components
+                .schema_from::<Form>()
+                .schema_from::<Response>(),
+        }
+    }
+}
+
+fn hello_path_item() -> PathItem {
+    let operation = OperationBuilder::new()
+        .operation_id(Some("hello"))
+        .response(
+            "200",
+            ResponseBuilder::new()
+                .description(format!(
+                    "Always `{}`.",
+                    interface::EXPECTED_RESPONSE_TO_HELLO
+                ))
+                .content(
+                    "text/plain",
+                    ContentBuilder::new()
+                        .schema(ObjectBuilder::new().schema_type(SchemaType::String))
+                        .build(),
+                )
+                .build(),
+        );
+    PathItem::new(path_item_type(routes::HELLO.0), operation)
+}
+
+fn ws_path_item() -> PathItem {
+    let operation = OperationBuilder::new()
+        .operation_id(Some("ws"))
+        .description(Some(
+            "Upgrades to a websocket, over which the server pushes `Event`s as JSON text messages.",
+        ))
+        .parameter(envelope_version_parameter())
+        .response(
+            "101",
+            ResponseBuilder::new()
+                .description("Switching protocols.")
+                .build(),
+        );
+    PathItem::new(path_item_type(routes::WS.0), operation)
+}
+
+fn events_path_item() -> PathItem {
+    let operation = OperationBuilder::new()
+        .operation_id(Some("events"))
+        .description(Some(
+            "Server-sent events: `message` events with new `Message`s, resumable with \
+             `Last-Event-ID`, and `event` events with `Event`s.",
+        ))
+        .parameter(envelope_version_parameter())
+        .response(
+            "200",
+            ResponseBuilder::new()
+                .description("Event stream.")
+                .content(
+                    "text/event-stream",
+                    ContentBuilder::new()
+                        .schema(ObjectBuilder::new().schema_type(SchemaType::String))
+                        .build(),
+                )
+                .build(),
+        );
+    PathItem::new(path_item_type(routes::EVENTS.0), operation)
+}
+
+/// `EventStreamForm::v`.
+fn envelope_version_parameter() -> ParameterBuilder {
+    ParameterBuilder::new()
+        .name("v")
+        .parameter_in(ParameterIn::Query)
+        .required(Required::False)
+        .description(Some(
+            "Latest `Envelope` version the client understands, to receive events in `Envelope`s.",
+        ))
+        .schema(Some(ObjectBuilder::new().schema_type(SchemaType::Integer)))
+}
+
+fn upload_attachment_path_item() -> PathItem {
+    let operation = OperationBuilder::new()
+        .operation_id(Some(operation_id(routes::UPLOAD_ATTACHMENT.1)))
+        .description(Some(
+            "Upload a file to attach to messages, with the content of the file as body and its \
+             MIME type as `Content-Type`. Identical files share the same `AttachmentId`.",
+        ))
+        .parameter(
+            ParameterBuilder::new()
+                .name("name")
+                .parameter_in(ParameterIn::Query)
+                .required(Required::True)
+                .schema(Some(ObjectBuilder::new().schema_type(SchemaType::String))),
+        )
+        .request_body(Some(
+            RequestBodyBuilder::new()
+                .content(
+                    "application/octet-stream",
+                    ContentBuilder::new().schema(binary_schema()).build(),
+                )
+                .required(Some(Required::True))
+                .build(),
+        ))
+        .response(
+            "200",
+            ResponseBuilder::new()
+                .description("Success.")
+                .content("application/json", json_content("UploadAttachmentResponse"))
+                .build(),
+        )
+        .response(
+            "default",
+            ResponseBuilder::new()
+                .description("Error.")
+                .content("application/json", json_content("ErrorResponse"))
+                .build(),
+        );
+    PathItem::new(path_item_type(routes::UPLOAD_ATTACHMENT.0), operation)
+}
+
+fn fetch_reactions_path_item() -> PathItem {
+    let operation = OperationBuilder::new()
+        .operation_id(Some(operation_id(routes::FETCH_REACTIONS.1)))
+        .description(Some(
+            "Tallies of reactions to the messages, most reacted first. Messages without \
+             reactions are left out.",
+        ))
+        .parameter(
+            ParameterBuilder::new()
+                .name("message_ids")
+                .parameter_in(ParameterIn::Query)
+                .required(Required::True)
+                .description(Some("Comma-separated `MessageId`s."))
+                .schema(Some(ObjectBuilder::new().schema_type(SchemaType::String))),
+        )
+        .response(
+            "200",
+            ResponseBuilder::new()
+                .description("Success.")
+                .content("application/json", json_content("FetchReactionsResponse"))
+                .build(),
+        )
+        .response(
+            "default",
+            ResponseBuilder::new()
+                .description("Error.")
+                .content("application/json", json_content("ErrorResponse"))
+                .build(),
+        );
+    PathItem::new(path_item_type(routes::FETCH_REACTIONS.0), operation)
+}
+
+fn fetch_attachment_path_item() -> PathItem {
+    let operation = OperationBuilder::new()
+        .operation_id(Some(operation_id(routes::FETCH_ATTACHMENT.1)))
+        .parameter(
+            ParameterBuilder::new()
+                .name("id")
+                .parameter_in(ParameterIn::Path)
+                .required(Required::True)
+                .schema(Some(Ref::from_schema_name("AttachmentId"))),
+        )
+        .response(
+            "200",
+            ResponseBuilder::new()
+                .description("Content of the file.")
+                .content(
+                    "application/octet-stream",
+                    ContentBuilder::new().schema(binary_schema()).build(),
+                )
+                .build(),
+        )
+        .response(
+            "default",
+            ResponseBuilder::new()
+                .description("Error.")
+                .content("application/json", json_content("ErrorResponse"))
+                .build(),
+        );
+    PathItem::new(path_item_type(routes::FETCH_ATTACHMENT.0), operation)
+}
+
+fn binary_schema() -> ObjectBuilder {
+    ObjectBuilder::new()
+        .schema_type(SchemaType::String)
+        .format(Some(SchemaFormat::KnownFormat(KnownFormat::Binary)))
+}
+
+fn json_content(schema_name: &str) -> utoipa::openapi::Content {
+    ContentBuilder::new()
+        .schema(Ref::from_schema_name(schema_name))
+        .build()
+}
+
+/// `/webhook/:token` -> `webhook`.
+fn operation_id(path: &str) -> &str {
+    path.trim_start_matches('/')
+        .split('/')
+        .next()
+        .unwrap_or_default()
+}
+
+/// axum's `/webhook/:token` -> OpenAPI's `/webhook/{token}`.
+fn openapi_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| match segment.strip_prefix(':') {
+            Some(parameter) => format!("{{{parameter}}}"),
+            None => segment.to_owned(),
+        })
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+fn path_parameters(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/')
+        .filter_map(|segment| segment.strip_prefix(':'))
+}
+
+fn path_item_type(method: HttpMethod) -> PathItemType {
+    match method {
+        HttpMethod::Get => PathItemType::Get,
+        HttpMethod::Post => PathItemType::Post,
+        HttpMethod::Put => PathItemType::Put,
+        HttpMethod::Delete => PathItemType::Delete,
+        HttpMethod::Head => PathItemType::Head,
+        HttpMethod::Options => PathItemType::Options,
+        HttpMethod::Connect => PathItemType::Connect,
+        HttpMethod::Patch => PathItemType::Patch,
+        HttpMethod::Trace => PathItemType::Trace,
+        HttpMethod::Unknown => panic!("route with unknown HTTP method"),
+    }
+}
@@ -0,0 +1,251 @@
+use std::{
+    fmt::Debug,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use interface::{Message, MessageId};
+
+use crate::{config::ServerConfig, error::ServerError, script::ScriptHook};
+
+/// How a client is connected, for `ServerPlugin::on_client_connected`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    Websocket,
+    ServerSentEvents,
+    Irc,
+    #[cfg(feature = "grpc")]
+    Grpc,
+}
+
+/// Hooks into the server, for features such as filters, bridges and analytics that don't need to
+/// live in the server itself. Hooks only see `interface` types, and are called synchronously from
+/// request handlers and tasks, so anything slow should be spawned, except for
+/// `on_message_received`, which is called on a blocking thread and may wait.
+/// Every hook does nothing by default.
+pub trait ServerPlugin: Debug + Send + Sync {
+    /// Shown in logs and in errors of rejected messages.
+    fn name(&self) -> &str;
+
+    /// Before `message` is stored, after sanitization. Returning an error rejects the message,
+    /// with the error as reason. `message.id` isn't assigned yet.
+    fn on_message_received(&self, _message: &Message) -> Result<(), Box<str>> {
+        Ok(())
+    }
+
+    /// After `message` is stored.
+    fn on_message_stored(&self, _message: &Message) {}
+
+    /// After `message` passed `on_message_received` of this plugin but wasn't stored, as a later
+    /// plugin rejected it or storing it failed.
+    fn on_message_dropped(&self, _message: &Message) {}
+
+    /// After a client subscribed to new messages.
+    fn on_client_connected(&self, _transport: Transport) {}
+
+    /// After the retention task deleted the messages of `ids`.
+    fn on_purge(&self, _ids: &[MessageId]) {}
+}
+
+/// Plugins shipped with the server, enabled by listing them in `ServerConfig::plugins`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuiltinPlugin {
+    /// See `RepeatFilter`.
+    NoRepeats,
+    /// See `ActivityLog`.
+    Activity,
+}
+
+impl FromStr for BuiltinPlugin {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "no-repeats" => Ok(Self::NoRepeats),
+            "activity" => Ok(Self::Activity),
+            _ => Err(()),
+        }
+    }
+}
+
+impl BuiltinPlugin {
+    fn instantiate(self) -> Box<dyn ServerPlugin> {
+        match self {
+            Self::NoRepeats => Box::<RepeatFilter>::default(),
+            Self::Activity => Box::<ActivityLog>::default(),
+        }
+    }
+}
+
+/// Plugins registered at startup, called in order of registration.
+#[derive(Debug, Default)]
+pub struct Plugins {
+    plugins: Vec<Box<dyn ServerPlugin>>,
+}
+
+impl Plugins {
+    /// The plugins enabled by `config`.
+    pub fn new(config: &ServerConfig) -> Self {
+        let mut plugins = Self::default();
+        for &builtin in &config.plugins {
+            plugins.register(builtin.instantiate());
+        }
+        if let Some(script) = &config.script {
+            plugins.register(Box::new(ScriptHook::new(
+                script.clone(),
+                config.script_timeout,
+                config.max_running_scripts,
+            )));
+        }
+        plugins
+    }
+
+    pub fn register(&mut self, plugin: Box<dyn ServerPlugin>) {
+        log::info!("Registered plugin {}", plugin.name());
+        self.plugins.push(plugin);
+    }
+
+    /// Stops at the first plugin rejecting `message`, telling the plugins before it that the
+    /// message was dropped.
+    pub fn message_received(&self, message: &Message) -> Result<(), ServerError> {
+        for (i, plugin) in self.plugins.iter().enumerate() {
+            if let Err(reason) = plugin.on_message_received(message) {
+                for earlier_plugin in &self.plugins[..i] {
+                    earlier_plugin.on_message_dropped(message);
+                }
+                return Err(ServerError::RejectedByPlugin {
+                    plugin: plugin.name().into(),
+                    reason,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn message_stored(&self, message: &Message) {
+        for plugin in &self.plugins {
+            plugin.on_message_stored(message);
+        }
+    }
+
+    /// `message` passed `message_received` but couldn't be stored.
+    pub fn message_dropped(&self, message: &Message) {
+        for plugin in &self.plugins {
+            plugin.on_message_dropped(message);
+        }
+    }
+
+    pub fn client_connected(&self, transport: Transport) {
+        for plugin in &self.plugins {
+            plugin.on_client_connected(transport);
+        }
+    }
+
+    pub fn purged(&self, ids: &[MessageId]) {
+        for plugin in &self.plugins {
+            plugin.on_purge(ids);
+        }
+    }
+}
+
+/// Rejects messages with the same content as the message stored last, a cheap guard against
+/// double posts and copy-paste flooding.
+#[derive(Debug, Default)]
+pub struct RepeatFilter {
+    contents: Mutex<RepeatContents>,
+}
+
+#[derive(Debug, Default)]
+struct RepeatContents {
+    /// Of the message stored last.
+    last: Option<Box<str>>,
+    /// Of messages that passed the check and are yet to be stored or dropped.
+    pending: Vec<Box<str>>,
+}
+
+impl RepeatContents {
+    fn remove_pending(&mut self, content: &str) {
+        if let Some(i) = self
+            .pending
+            .iter()
+            .position(|pending| **pending == *content)
+        {
+            self.pending.swap_remove(i);
+        }
+    }
+}
+
+impl ServerPlugin for RepeatFilter {
+    fn name(&self) -> &str {
+        "no-repeats"
+    }
+
+    /// Marks the content as pending under the same lock as the check, so that two identical
+    /// messages arriving together can't both pass before either is stored. It only becomes the
+    /// previous message once stored, messages rejected later never do.
+    fn on_message_received(&self, message: &Message) -> Result<(), Box<str>> {
+        let mut contents = self.contents.lock().unwrap();
+        let content = &message.content;
+        let is_repeat =
+            contents.last.as_ref() == Some(content) || contents.pending.contains(content);
+        if is_repeat {
+            return Err("same as the previous message".into());
+        }
+        contents.pending.push(content.clone());
+        Ok(())
+    }
+
+    fn on_message_stored(&self, message: &Message) {
+        let mut contents = self.contents.lock().unwrap();
+        contents.remove_pending(&message.content);
+        contents.last = Some(message.content.clone());
+    }
+
+    fn on_message_dropped(&self, message: &Message) {
+        self.contents
+            .lock()
+            .unwrap()
+            .remove_pending(&message.content);
+    }
+}
+
+/// Logs totals of messages, connections and purged messages every `ActivityLog::INTERVAL`
+/// messages stored.
+#[derive(Debug, Default)]
+pub struct ActivityLog {
+    messages: AtomicU64,
+    connections: AtomicU64,
+    purged: AtomicU64,
+}
+
+impl ActivityLog {
+    const INTERVAL: u64 = 100;
+}
+
+impl ServerPlugin for ActivityLog {
+    fn name(&self) -> &str {
+        "activity"
+    }
+
+    fn on_message_stored(&self, _message: &Message) {
+        let messages = self.messages.fetch_add(1, Ordering::Relaxed) + 1;
+        if messages % Self::INTERVAL == 0 {
+            log::info!(
+                "Activity: {messages} messages stored, {} connections, {} messages purged",
+                self.connections.load(Ordering::Relaxed),
+                self.purged.load(Ordering::Relaxed),
+            );
+        }
+    }
+
+    fn on_client_connected(&self, _transport: Transport) {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_purge(&self, ids: &[MessageId]) {
+        self.purged.fetch_add(ids.len() as u64, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,57 @@
+use std::{collections::HashMap, path::PathBuf, sync::Mutex};
+
+use interface::{Profile, SetProfileForm};
+
+use crate::{error::ServerResult, json_file::JsonFile};
+
+/// Name of the file in `ServerConfig::data_dir` profiles are saved to.
+pub const FILE_NAME: &str = "profiles.json";
+
+/// Profiles of sessions, by session ID.
+/// Saved with the messages if they're persisted, so that messages keep showing the profile of
+/// their author after a restart.
+#[derive(Debug, Default)]
+pub struct Profiles {
+    profiles: Mutex<HashMap<u64, Profile>>,
+    file: JsonFile,
+}
+
+impl Profiles {
+    /// Load the profiles saved at `path`, if any, and save changes there.
+    /// Kept in memory only if `path` is `None`.
+    pub fn open(path: Option<PathBuf>) -> ServerResult<Self> {
+        let (file, profiles) = JsonFile::open(path)?;
+        Ok(Self {
+            profiles: Mutex::new(profiles),
+            file,
+        })
+    }
+
+    pub fn get(&self, session_id: u64) -> Option<Profile> {
+        self.profiles.lock().unwrap().get(&session_id).cloned()
+    }
+
+    pub fn has(&self, session_id: u64) -> bool {
+        self.profiles.lock().unwrap().contains_key(&session_id)
+    }
+
+    /// Applies `form` to the profile of the session, returning the updated profile.
+    /// Profiles cleared of every field are removed.
+    pub fn update(&self, session_id: u64, form: &SetProfileForm) -> Profile {
+        let mut profiles = self.profiles.lock().unwrap();
+        let profile = form.apply(profiles.remove(&session_id).unwrap_or_default());
+        if !profile.is_empty() {
+            profiles.insert(session_id, profile.clone());
+        }
+        self.file.save(&*profiles);
+        profile
+    }
+
+    /// Drop the profile of a revoked session.
+    pub fn forget(&self, session_id: u64) {
+        let mut profiles = self.profiles.lock().unwrap();
+        if profiles.remove(&session_id).is_some() {
+            self.file.save(&*profiles);
+        }
+    }
+}
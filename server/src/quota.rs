@@ -0,0 +1,273 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use interface::QuotaUsage;
+
+/// Window of `Usage::requests`, `Usage::searches` and `Usage::reactions`.
+pub const REQUEST_WINDOW: Duration = Duration::from_secs(60);
+/// Window of `Usage::messages` and `Usage::bytes_sent`.
+pub const HOURLY_WINDOW: Duration = Duration::from_secs(60 * 60);
+/// Window of `Usage::bytes_uploaded`.
+pub const DAILY_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+/// Windows are counted in this many buckets, so counts drop gradually.
+const BUCKETS_PER_WINDOW: u32 = 12;
+/// How often addresses without recent activity are forgotten.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Count of events in the last `window`, in buckets of `window / BUCKETS_PER_WINDOW`.
+#[derive(Debug)]
+struct RollingCounter {
+    window: Duration,
+    /// Start of each bucket and its count, oldest first.
+    buckets: VecDeque<(Instant, u64)>,
+}
+
+impl RollingCounter {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            buckets: VecDeque::new(),
+        }
+    }
+
+    fn add(&mut self, now: Instant, amount: u64) {
+        self.expire(now);
+        let bucket_length = self.window / BUCKETS_PER_WINDOW;
+        match self.buckets.back_mut() {
+            Some((start, count)) if now - *start < bucket_length => *count += amount,
+            _ => self.buckets.push_back((now, amount)),
+        }
+    }
+
+    fn count(&mut self, now: Instant) -> u64 {
+        self.expire(now);
+        self.buckets.iter().map(|(_, count)| count).sum()
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while self
+            .buckets
+            .front()
+            .is_some_and(|(start, _)| now - *start >= self.window)
+        {
+            self.buckets.pop_front();
+        }
+    }
+
+    fn is_empty(&mut self, now: Instant) -> bool {
+        self.expire(now);
+        self.buckets.is_empty()
+    }
+}
+
+#[derive(Debug)]
+struct Usage {
+    requests: RollingCounter,
+    searches: RollingCounter,
+    /// Reactions added or taken back.
+    reactions: RollingCounter,
+    messages: RollingCounter,
+    /// Request body bytes, as read.
+    bytes_sent: RollingCounter,
+    /// Bytes of attachments uploaded.
+    bytes_uploaded: RollingCounter,
+    /// Set while temporarily banned.
+    banned_until: Option<Instant>,
+}
+
+impl Default for Usage {
+    fn default() -> Self {
+        Self {
+            requests: RollingCounter::new(REQUEST_WINDOW),
+            searches: RollingCounter::new(REQUEST_WINDOW),
+            reactions: RollingCounter::new(REQUEST_WINDOW),
+            messages: RollingCounter::new(HOURLY_WINDOW),
+            bytes_sent: RollingCounter::new(HOURLY_WINDOW),
+            bytes_uploaded: RollingCounter::new(DAILY_WINDOW),
+            banned_until: None,
+        }
+    }
+}
+
+impl Usage {
+    fn is_banned(&self, now: Instant) -> bool {
+        self.banned_until.is_some_and(|until| now < until)
+    }
+}
+
+/// Rolling usage counters and temporary bans per IP address.
+#[derive(Debug)]
+pub struct Quotas {
+    usages: Mutex<HashMap<IpAddr, Usage>>,
+    last_prune: Mutex<Instant>,
+}
+
+impl Default for Quotas {
+    fn default() -> Self {
+        Self {
+            usages: Mutex::default(),
+            last_prune: Mutex::new(Instant::now()),
+        }
+    }
+}
+
+impl Quotas {
+    /// Count a request from `address`.
+    /// Returns the number of requests from `address` in the last `REQUEST_WINDOW`.
+    pub fn record_request(&self, address: IpAddr) -> u64 {
+        let now = Instant::now();
+        self.prune(now);
+        let mut usages = self.usages.lock().unwrap();
+        let usage = usages.entry(address).or_default();
+        usage.requests.add(now, 1);
+        usage.requests.count(now)
+    }
+
+    /// Count `length` bytes of a request body read from `address`.
+    pub fn record_bytes_sent(&self, address: IpAddr, length: u64) {
+        let now = Instant::now();
+        let mut usages = self.usages.lock().unwrap();
+        usages
+            .entry(address)
+            .or_default()
+            .bytes_sent
+            .add(now, length);
+    }
+
+    /// Requests from `address` in the last `REQUEST_WINDOW`.
+    pub fn requests_last_minute(&self, address: IpAddr) -> u64 {
+        let now = Instant::now();
+        let mut usages = self.usages.lock().unwrap();
+        usages
+            .get_mut(&address)
+            .map_or(0, |usage| usage.requests.count(now))
+    }
+
+    /// Count a search from `address`.
+    /// Returns the number of searches from `address` in the last `REQUEST_WINDOW`.
+    pub fn record_search(&self, address: IpAddr) -> u64 {
+        let now = Instant::now();
+        let mut usages = self.usages.lock().unwrap();
+        let usage = usages.entry(address).or_default();
+        usage.searches.add(now, 1);
+        usage.searches.count(now)
+    }
+
+    /// Count a reaction added or taken back from `address`.
+    /// Returns the number of them from `address` in the last `REQUEST_WINDOW`.
+    pub fn record_reaction(&self, address: IpAddr) -> u64 {
+        let now = Instant::now();
+        let mut usages = self.usages.lock().unwrap();
+        let usage = usages.entry(address).or_default();
+        usage.reactions.add(now, 1);
+        usage.reactions.count(now)
+    }
+
+    /// Count a message from `address`.
+    /// Returns the number of messages from `address` in the last `HOURLY_WINDOW`.
+    pub fn record_message(&self, address: IpAddr) -> u64 {
+        let now = Instant::now();
+        let mut usages = self.usages.lock().unwrap();
+        let usage = usages.entry(address).or_default();
+        usage.messages.add(now, 1);
+        usage.messages.count(now)
+    }
+
+    /// Bytes of attachments uploaded from `address` in the last `DAILY_WINDOW`.
+    pub fn bytes_uploaded_last_day(&self, address: IpAddr) -> u64 {
+        let now = Instant::now();
+        let mut usages = self.usages.lock().unwrap();
+        usages
+            .get_mut(&address)
+            .map_or(0, |usage| usage.bytes_uploaded.count(now))
+    }
+
+    /// Count an attachment of `size` bytes uploaded from `address`, unless that takes its uploads
+    /// in the last `DAILY_WINDOW` over `quota`, which is returned then.
+    /// Checked and counted under one lock, so that concurrent uploads can't both fit in what's
+    /// left of the quota.
+    pub fn record_upload(&self, address: IpAddr, size: u64, quota: Option<u64>) -> Result<(), u64> {
+        let now = Instant::now();
+        let mut usages = self.usages.lock().unwrap();
+        let bytes_uploaded = &mut usages.entry(address).or_default().bytes_uploaded;
+        if let Some(quota) = quota.filter(|&quota| bytes_uploaded.count(now) + size > quota) {
+            return Err(quota);
+        }
+        bytes_uploaded.add(now, size);
+        Ok(())
+    }
+
+    pub fn ban(&self, address: IpAddr, duration: Duration) {
+        let now = Instant::now();
+        let mut usages = self.usages.lock().unwrap();
+        usages.entry(address).or_default().banned_until = Some(now + duration);
+    }
+
+    /// Returns `false` if `address` wasn't banned.
+    pub fn unban(&self, address: IpAddr) -> bool {
+        let now = Instant::now();
+        let mut usages = self.usages.lock().unwrap();
+        usages
+            .get_mut(&address)
+            .and_then(|usage| usage.banned_until.take())
+            .is_some_and(|until| now < until)
+    }
+
+    /// Is `address` temporarily banned? Bans in `Settings::banned_ips` aren't tracked here.
+    pub fn is_banned(&self, address: IpAddr) -> bool {
+        let now = Instant::now();
+        let usages = self.usages.lock().unwrap();
+        usages
+            .get(&address)
+            .is_some_and(|usage| usage.is_banned(now))
+    }
+
+    /// Usage of every address seen recently, busiest first.
+    pub fn to_interface(&self) -> Vec<QuotaUsage> {
+        let now = Instant::now();
+        let mut usages = self.usages.lock().unwrap();
+        let mut quotas: Vec<QuotaUsage> = usages
+            .iter_mut()
+            .map(|(address, usage)| QuotaUsage {
+                address: address.to_string().into(),
+                requests_last_minute: usage.requests.count(now),
+                messages_last_hour: usage.messages.count(now),
+                bytes_sent_last_hour: usage.bytes_sent.count(now),
+                bytes_uploaded_last_day: usage.bytes_uploaded.count(now),
+                banned_until: usage
+                    .banned_until
+                    .filter(|_| usage.is_banned(now))
+                    .and_then(|until| chrono::Duration::from_std(until - now).ok())
+                    .map(|remaining| Utc::now() + remaining),
+            })
+            .collect();
+        quotas.sort_by(|a, b| b.requests_last_minute.cmp(&a.requests_last_minute));
+        quotas
+    }
+
+    /// Forget addresses without activity in the last `HOURLY_WINDOW`, no uploads in the last
+    /// `DAILY_WINDOW` and no ban, at most once every `PRUNE_INTERVAL`, so the map doesn't grow
+    /// forever.
+    fn prune(&self, now: Instant) {
+        let mut last_prune = self.last_prune.lock().unwrap();
+        if now - *last_prune < PRUNE_INTERVAL {
+            return;
+        }
+        *last_prune = now;
+        drop(last_prune);
+        self.usages.lock().unwrap().retain(|_, usage| {
+            !(usage.requests.is_empty(now)
+                && usage.searches.is_empty(now)
+                && usage.reactions.is_empty(now)
+                && usage.messages.is_empty(now)
+                && usage.bytes_sent.is_empty(now)
+                && usage.bytes_uploaded.is_empty(now)
+                && !usage.is_banned(now))
+        });
+    }
+}
@@ -0,0 +1,87 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    net::IpAddr,
+    sync::Mutex,
+};
+
+use interface::{MessageId, MessageReactions, ReactionTally};
+
+use crate::error::ServerError;
+
+/// Reactions added or taken back per minute per IP address, beyond which they're refused as
+/// rate limited.
+pub const MAX_REACTIONS_PER_MINUTE: u64 = 60;
+
+/// Distinct reactions to one message. Reactions already on the message can still be added to.
+pub const MAX_REACTIONS_PER_MESSAGE: usize = 20;
+
+/// Reactions to messages, by who reacted, so that each IP address counts once per reaction.
+/// Kept in memory only, like the topic and announcement.
+#[derive(Debug, Default)]
+pub struct Reactions {
+    reactions: Mutex<HashMap<MessageId, BTreeMap<Box<str>, HashSet<IpAddr>>>>,
+}
+
+impl Reactions {
+    /// Adds the reaction, or takes it back if `reactor` already added it.
+    /// Returns whether it was added.
+    pub fn toggle(
+        &self,
+        id: MessageId,
+        reaction: &str,
+        reactor: IpAddr,
+    ) -> Result<bool, ServerError> {
+        let mut reactions = self.reactions.lock().unwrap();
+        let message_reactions = reactions.entry(id).or_default();
+        if message_reactions.len() >= MAX_REACTIONS_PER_MESSAGE
+            && !message_reactions.contains_key(reaction)
+        {
+            return Err(ServerError::TooManyReactions {
+                max: MAX_REACTIONS_PER_MESSAGE,
+            });
+        }
+        let reactors = message_reactions.entry(reaction.into()).or_default();
+        let added = reactors.insert(reactor);
+        if !added {
+            reactors.remove(&reactor);
+            if reactors.is_empty() {
+                message_reactions.remove(reaction);
+            }
+            if message_reactions.is_empty() {
+                reactions.remove(&id);
+            }
+        }
+        Ok(added)
+    }
+
+    /// Messages without reactions are left out.
+    pub fn tallies(&self, ids: &[MessageId]) -> Vec<MessageReactions> {
+        let reactions = self.reactions.lock().unwrap();
+        ids.iter()
+            .filter_map(|&id| {
+                let mut tallies: Vec<ReactionTally> = reactions
+                    .get(&id)?
+                    .iter()
+                    .map(|(reaction, reactors)| ReactionTally {
+                        reaction: reaction.clone(),
+                        count: u32::try_from(reactors.len()).unwrap_or(u32::MAX),
+                    })
+                    .collect();
+                // Stable, so that ties stay in the order of the reactions.
+                tallies.sort_by(|a, b| b.count.cmp(&a.count));
+                Some(MessageReactions {
+                    message_id: id,
+                    tallies: tallies.into(),
+                })
+            })
+            .collect()
+    }
+
+    /// Drop reactions to messages that were deleted.
+    pub fn forget(&self, ids: &[MessageId]) {
+        let mut reactions = self.reactions.lock().unwrap();
+        for id in ids {
+            reactions.remove(id);
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use std::{collections::HashSet, io, sync::Arc, time::Duration};
+
+use chrono::Utc;
+use interface::{AuditAction, AuditActor, Event};
+
+use crate::{database::Message, ServerState};
+
+/// How often the retention task looks for messages to delete.
+const RETENTION_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Deletes expired messages, and messages older than `Settings::max_message_age`.
+/// If archiving is enabled, messages are archived first, and kept if that fails.
+/// Attachments no message references anymore are deleted too.
+pub fn setup_retention_task(server_state: ServerState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RETENTION_INTERVAL);
+        loop {
+            interval.tick().await;
+            let collected = server_state.attachments.collect_garbage();
+            if collected != 0 {
+                log::info!("Deleted {collected} unreferenced attachments");
+            }
+            let now = Utc::now();
+            let max_message_age = server_state.settings.get().max_message_age();
+            let cutoff = max_message_age
+                .map(|max_message_age| now.checked_sub_signed(max_message_age).unwrap_or_default());
+            let purgeable = server_state.database.purgeable_messages(now, cutoff);
+            if purgeable.is_empty() {
+                continue;
+            }
+            if let Some(archives) = &server_state.archives {
+                let messages: Vec<_> = purgeable.iter().map(Message::to_interface).collect();
+                let count = messages.len();
+                // Written off the async runtime, as gzipping takes a while.
+                let archiving = Arc::clone(archives);
+                let written = tokio::task::spawn_blocking(move || archiving.write(&messages, now))
+                    .await
+                    .unwrap_or_else(|error| Err(io::Error::other(error)));
+                match written {
+                    Ok(name) => log::info!(
+                        "Archived {count} messages to {}",
+                        archives.dir().join(&*name).display()
+                    ),
+                    Err(error) => {
+                        log::error!("Can't archive messages, not deleting them: {error}");
+                        continue;
+                    }
+                }
+            }
+            let ids: HashSet<_> = purgeable.iter().map(|message| message.id).collect();
+            let deleted = server_state.database.delete_messages(&ids);
+            let deleted_ids: HashSet<_> = deleted.iter().collect();
+            server_state.attachments.release(
+                purgeable
+                    .iter()
+                    .filter(|message| deleted_ids.contains(&message.id))
+                    .flat_map(|message| message.attachments.iter().map(|attachment| attachment.id)),
+            );
+            if !deleted.is_empty() {
+                log::info!("Deleted {} expired messages", deleted.len());
+                let ids: Box<[_]> = deleted.into();
+                server_state.plugins.purged(&ids);
+                server_state.reactions.forget(&ids);
+                server_state.audit_log.record(
+                    AuditActor::System,
+                    AuditAction::PurgeExpired { ids: ids.clone() },
+                );
+                server_state
+                    .broadcaster
+                    .broadcast(Event::MessagesDeleted { ids });
+            }
+        }
+    });
+}
@@ -0,0 +1,80 @@
+use std::{borrow::Cow, str::FromStr};
+
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+
+/// A step of `Sanitizer`, enabled by listing it in `ServerConfig::sanitize`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sanitization {
+    /// Strip bidi embeddings, overrides and isolates, which can make text appear reversed or
+    /// spill into the text after it. Marks (U+200E, U+200F) are kept.
+    Bidi,
+    /// Strip zero-width spaces, word joiners and byte order marks. Zero-width (non-)joiners are
+    /// kept, as emoji sequences and some scripts need them.
+    ZeroWidth,
+    /// Normalize to NFC, so that text that looks the same is the same.
+    Nfc,
+}
+
+impl Sanitization {
+    /// Used if `--sanitize` isn't given.
+    pub const DEFAULT: &[Self] = &[Self::Bidi, Self::ZeroWidth, Self::Nfc];
+}
+
+impl FromStr for Sanitization {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bidi" => Ok(Self::Bidi),
+            "zero-width" => Ok(Self::ZeroWidth),
+            "nfc" => Ok(Self::Nfc),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Cleans up user-provided text before it's stored, so it can't visually spoof other text in
+/// clients.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sanitizer {
+    strip_bidi: bool,
+    strip_zero_width: bool,
+    normalize_nfc: bool,
+}
+
+impl Sanitizer {
+    pub fn new(sanitizations: &[Sanitization]) -> Self {
+        Self {
+            strip_bidi: sanitizations.contains(&Sanitization::Bidi),
+            strip_zero_width: sanitizations.contains(&Sanitization::ZeroWidth),
+            normalize_nfc: sanitizations.contains(&Sanitization::Nfc),
+        }
+    }
+
+    /// Only allocates if `text` changes.
+    pub fn sanitize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        if text.chars().any(|c| self.is_stripped(c)) {
+            text = Cow::Owned(text.chars().filter(|&c| !self.is_stripped(c)).collect());
+        }
+        if self.normalize_nfc && is_nfc_quick(text.chars()) != IsNormalized::Yes {
+            text = Cow::Owned(text.nfc().collect());
+        }
+        text
+    }
+
+    fn is_stripped(&self, c: char) -> bool {
+        (self.strip_bidi && is_bidi_control(c)) || (self.strip_zero_width && is_zero_width(c))
+    }
+}
+
+/// Embeddings and overrides (U+202A..=U+202E), and isolates (U+2066..=U+2069).
+fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}')
+}
+
+/// Zero-width space, word joiner, byte order mark, and the Mongolian vowel separator which used
+/// to be a zero-width space.
+fn is_zero_width(c: char) -> bool {
+    matches!(c, '\u{200B}' | '\u{2060}' | '\u{FEFF}' | '\u{180E}')
+}
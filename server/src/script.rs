@@ -0,0 +1,153 @@
+use std::{
+    io::{self, Read, Write},
+    process::{Command, ExitStatus, Stdio},
+    sync::{mpsc, Condvar, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use interface::Message;
+
+use crate::plugin::ServerPlugin;
+
+/// How often a running script is checked for having exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Only this much of the output of a script is kept, for the reason of a rejection.
+const MAX_REASON_LENGTH: u64 = 200;
+
+#[derive(Debug, thiserror::Error)]
+enum ScriptError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("timed out after {0:?}")]
+    TimedOut(Duration),
+}
+
+/// Pipes every new message as JSON to the stdin of an operator-configured command, which rejects
+/// the message by exiting with a non-zero status. The first line of its stdout is the reason.
+/// Scripts that fail to run or time out are killed and the message is rejected too, so that a
+/// broken script can't let through what it was meant to stop.
+#[derive(Debug)]
+pub struct ScriptHook {
+    /// Split on whitespace and run directly, not through a shell.
+    command: Box<str>,
+    /// Counted from when a message arrives, including the wait for other scripts to exit.
+    timeout: Duration,
+    max_running: usize,
+    running: Mutex<usize>,
+    /// Notified when a script exits.
+    exited: Condvar,
+}
+
+impl ScriptHook {
+    pub fn new(command: Box<str>, timeout: Duration, max_running: usize) -> Self {
+        Self {
+            command,
+            timeout,
+            max_running: max_running.max(1),
+            running: Mutex::new(0),
+            exited: Condvar::new(),
+        }
+    }
+
+    /// Blocks until fewer than `max_running` scripts are running, then runs one.
+    fn run(&self, input: Vec<u8>) -> Result<(ExitStatus, String), ScriptError> {
+        let deadline = Instant::now() + self.timeout;
+        let mut running = self.running.lock().unwrap();
+        while *running >= self.max_running {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if timeout.is_zero() {
+                return Err(ScriptError::TimedOut(self.timeout));
+            }
+            running = self.exited.wait_timeout(running, timeout).unwrap().0;
+        }
+        *running += 1;
+        drop(running);
+        let result = self.run_unlimited(input, deadline);
+        *self.running.lock().unwrap() -= 1;
+        self.exited.notify_one();
+        result
+    }
+
+    fn run_unlimited(
+        &self,
+        input: Vec<u8>,
+        deadline: Instant,
+    ) -> Result<(ExitStatus, String), ScriptError> {
+        let mut args = self.command.split_whitespace();
+        let program = args.next().unwrap_or_default();
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        // Written and read on their own threads, so that a script writing a lot before reading
+        // its input, or not reading it at all, can't block on a full pipe until the deadline.
+        // Scripts may exit without reading all of their input, a script needing it fails itself.
+        if let Some(mut stdin) = child.stdin.take() {
+            thread::spawn(move || stdin.write_all(&input));
+        }
+        let (sender, receiver) = mpsc::channel();
+        if let Some(stdout) = child.stdout.take() {
+            thread::spawn(move || sender.send(read_reason(stdout)));
+        }
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(ScriptError::TimedOut(self.timeout));
+            }
+            thread::sleep(POLL_INTERVAL);
+        };
+        // Processes started by the script may hold on to its stdout, don't wait past the deadline
+        // for them.
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        let output = match receiver.recv_timeout(timeout) {
+            Ok(output) => output?,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                return Err(ScriptError::TimedOut(self.timeout))
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => Vec::new(),
+        };
+        Ok((status, String::from_utf8_lossy(&output).into_owned()))
+    }
+}
+
+/// The first `MAX_REASON_LENGTH` bytes of `stdout`, after reading it to the end.
+fn read_reason(mut stdout: impl Read) -> io::Result<Vec<u8>> {
+    let mut reason = Vec::new();
+    (&mut stdout)
+        .take(MAX_REASON_LENGTH)
+        .read_to_end(&mut reason)?;
+    io::copy(&mut stdout, &mut io::sink())?;
+    Ok(reason)
+}
+
+impl ServerPlugin for ScriptHook {
+    fn name(&self) -> &str {
+        "script"
+    }
+
+    fn on_message_received(&self, message: &Message) -> Result<(), Box<str>> {
+        let input = serde_json::to_vec(message).unwrap();
+        match self.run(input) {
+            Ok((status, _)) if status.success() => Ok(()),
+            Ok((_, output)) => match output.lines().next().map(str::trim) {
+                Some(reason) if !reason.is_empty() => Err(reason.into()),
+                _ => Err("rejected by moderation script".into()),
+            },
+            Err(error) => {
+                log::error!(
+                    "Moderation script {:?} failed, rejecting message: {error}",
+                    self.command
+                );
+                Err("moderation script failed".into())
+            }
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use std::time::{Duration, Instant};
+
+use interface::SearchMessagesForm;
+use regex::{Regex, RegexBuilder};
+
+use crate::database::Message;
+
+/// Searches give up after this long, each taking up a blocking thread meanwhile.
+pub const TIME_LIMIT: Duration = Duration::from_millis(500);
+
+/// Searches per minute per IP address, beyond which they're refused as rate limited.
+pub const MAX_SEARCHES_PER_MINUTE: u64 = 30;
+
+/// Size limit of compiled regexes, refusing patterns such as `\w{1000}{1000}` that would take
+/// lots of memory and time to compile. Matching itself is linear in the length of the content.
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+#[derive(Debug)]
+enum Pattern {
+    /// Lowercased if case-insensitive.
+    Text {
+        text: String,
+        case_sensitive: bool,
+    },
+    Regex(Regex),
+}
+
+/// Decides which messages match a `SearchMessagesForm`.
+#[derive(Debug)]
+pub struct Matcher {
+    pattern: Pattern,
+    /// Lowercased.
+    author: Option<String>,
+}
+
+impl Matcher {
+    pub fn new(form: &SearchMessagesForm) -> Result<Self, regex::Error> {
+        let query = form.query.trim();
+        let pattern = match (form.regex, form.case_sensitive) {
+            (true, case_sensitive) => Pattern::Regex(
+                RegexBuilder::new(query)
+                    .case_insensitive(!case_sensitive)
+                    .size_limit(REGEX_SIZE_LIMIT)
+                    .dfa_size_limit(REGEX_SIZE_LIMIT)
+                    .build()?,
+            ),
+            (false, true) => Pattern::Text {
+                text: query.to_owned(),
+                case_sensitive: true,
+            },
+            (false, false) => Pattern::Text {
+                text: query.to_lowercase(),
+                case_sensitive: false,
+            },
+        };
+        Ok(Self {
+            pattern,
+            author: form.author.as_deref().map(str::to_lowercase),
+        })
+    }
+
+    /// Whether the matcher would match every message, e.g. for a blank query.
+    pub fn is_empty(&self) -> bool {
+        self.author.is_none()
+            && match &self.pattern {
+                Pattern::Text { text, .. } => text.is_empty(),
+                Pattern::Regex(regex) => regex.as_str().is_empty(),
+            }
+    }
+
+    pub fn matches(&self, message: &Message) -> bool {
+        let is_author = self.author.as_ref().map_or(true, |author| {
+            message
+                .webhook_name
+                .as_ref()
+                .is_some_and(|webhook_name| webhook_name.to_lowercase() == *author)
+        });
+        is_author
+            && match &self.pattern {
+                Pattern::Text {
+                    text,
+                    case_sensitive: true,
+                } => message.content.contains(text.as_str()),
+                Pattern::Text {
+                    text,
+                    case_sensitive: false,
+                } => message.content.to_lowercase().contains(text.as_str()),
+                Pattern::Regex(regex) => regex.is_match(&message.content),
+            }
+    }
+}
+
+/// The `count` latest of `messages` that `matches`, oldest first, and whether `deadline` was
+/// reached before going through every message. `messages` are oldest first.
+/// Blocks for up to `TIME_LIMIT`, so it's run with `tokio::task::spawn_blocking`.
+pub fn search(
+    messages: Vec<Message>,
+    mut matches: impl FnMut(&Message) -> bool,
+    count: usize,
+    deadline: Instant,
+) -> (Vec<Message>, bool) {
+    let mut found = Vec::new();
+    let mut is_timed_out = false;
+    for message in messages.into_iter().rev() {
+        if found.len() >= count {
+            break;
+        }
+        if Instant::now() >= deadline {
+            is_timed_out = true;
+            break;
+        }
+        if matches(&message) {
+            found.push(message);
+        }
+    }
+    found.reverse();
+    (found, is_timed_out)
+}
@@ -0,0 +1,136 @@
+use std::{
+    fs,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock, RwLockReadGuard},
+};
+
+use chrono::Duration;
+use interface::CloseReason;
+use serde::Deserialize;
+
+use crate::{connections::Connections, error::ServerResult, mime};
+
+pub const DEFAULT_MAX_ATTACHMENT_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Settings that can be changed without restarting the server.
+/// Read from the JSON file at env var `MESSAGE_BOARD_CONFIG`, and read again on `SIGHUP`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// Requests per minute per IP address for `Middleware::RateLimit`.
+    /// `ServerConfig::rate_limit` is used if `None`.
+    pub rate_limit: Option<u32>,
+    /// Messages per hour per IP address sent with `/send_message`. Unlimited if `None`.
+    pub max_messages_per_hour: Option<u32>,
+    /// Messages older than this many seconds are deleted. Kept until they expire if `None`.
+    pub max_message_age: Option<u64>,
+    /// Requests from these addresses are refused.
+    pub banned_ips: Vec<IpAddr>,
+    /// Messages containing any of these, ignoring case, are refused.
+    pub blocked_words: Vec<Box<str>>,
+    /// Largest attachment in bytes. `DEFAULT_MAX_ATTACHMENT_SIZE` if `None`.
+    pub max_attachment_size: Option<u64>,
+    /// Bytes of attachments per day per IP address. Unlimited if `None`.
+    pub attachment_bytes_per_day: Option<u64>,
+    /// MIME types or `type/*` wildcards of attachments allowed, as detected from their content.
+    /// Any type is allowed if `None`.
+    pub allowed_attachment_types: Option<Vec<Box<str>>>,
+}
+
+impl Settings {
+    pub fn load(path: &Path) -> ServerResult<Self> {
+        let bytes = fs::read(path)?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    pub fn max_message_age(&self) -> Option<Duration> {
+        let seconds = i64::try_from(self.max_message_age?).unwrap_or(i64::MAX);
+        Some(Duration::try_seconds(seconds).unwrap_or(Duration::MAX))
+    }
+
+    pub fn max_attachment_size(&self) -> u64 {
+        self.max_attachment_size
+            .unwrap_or(DEFAULT_MAX_ATTACHMENT_SIZE)
+    }
+
+    pub fn is_attachment_type_allowed(&self, content_type: &str) -> bool {
+        self.allowed_attachment_types
+            .as_ref()
+            .map_or(true, |patterns| mime::is_allowed(content_type, patterns))
+    }
+
+    pub fn is_banned(&self, address: IpAddr) -> bool {
+        self.banned_ips.contains(&address)
+    }
+
+    pub fn contains_blocked_word(&self, content: &str) -> bool {
+        let content = content.to_lowercase();
+        self.blocked_words
+            .iter()
+            .any(|word| content.contains(&word.to_lowercase()))
+    }
+}
+
+/// Handle to the current settings, shared by everything that reads them.
+#[derive(Debug, Clone, Default)]
+pub struct SharedSettings(Arc<RwLock<Settings>>);
+
+impl SharedSettings {
+    pub fn new(settings: Settings) -> Self {
+        Self(Arc::new(RwLock::new(settings)))
+    }
+
+    pub fn get(&self) -> RwLockReadGuard<Settings> {
+        self.0.read().unwrap()
+    }
+
+    /// Returns the old settings.
+    fn replace(&self, settings: Settings) -> Settings {
+        std::mem::replace(&mut *self.0.write().unwrap(), settings)
+    }
+}
+
+/// Read settings from `path` again on every `SIGHUP`.
+/// If the file can't be read, the error is logged and the old settings are kept.
+/// Websockets of addresses added to `Settings::banned_ips` are closed, other connections are not
+/// affected.
+pub fn setup_reload_on_sighup(
+    settings: SharedSettings,
+    connections: Arc<Connections>,
+    path: PathBuf,
+) {
+    #[cfg(unix)]
+    tokio::spawn(async move {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut hangups = match signal(SignalKind::hangup()) {
+            Ok(hangups) => hangups,
+            Err(error) => {
+                log::error!("Can't listen for SIGHUP, settings won't be reloaded: {error}");
+                return;
+            }
+        };
+        while hangups.recv().await.is_some() {
+            match Settings::load(&path) {
+                Ok(new_settings) => {
+                    log::info!("Reloaded settings from {}", path.display());
+                    let banned_ips = new_settings.banned_ips.clone();
+                    let old_settings = settings.replace(new_settings);
+                    for address in banned_ips {
+                        if !old_settings.is_banned(address) {
+                            connections.close_address(address, CloseReason::Banned);
+                        }
+                    }
+                }
+                Err(error) => {
+                    log::error!("Can't reload settings from {}: {error}", path.display());
+                }
+            }
+        }
+    });
+    #[cfg(not(unix))]
+    {
+        let _ = (settings, connections, path);
+        log::warn!("Reloading settings on SIGHUP is only supported on Unix");
+    }
+}
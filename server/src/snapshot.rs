@@ -0,0 +1,143 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use interface::FetchSnapshotMetricsResponse;
+
+use crate::{
+    database::{DataBase, Message},
+    error::ServerResult,
+};
+
+/// Name of the snapshot in `ServerConfig::data_dir`, shared by `Persistence::Snapshot` and the
+/// WAL.
+pub const FILE_NAME: &str = "snapshot.ndjson";
+
+/// Write `messages` to `path` as NDJSON.
+/// The snapshot is written to a temporary file first and then renamed over `path`, so a crash
+/// leaves either the old or the new snapshot, never a partial one.
+pub fn write<'a>(path: &Path, messages: impl IntoIterator<Item = &'a Message>) -> io::Result<()> {
+    let temp_path = path.with_extension("tmp");
+    let mut writer = BufWriter::new(File::create(&temp_path)?);
+    for message in messages {
+        serde_json::to_writer(&mut writer, message)?;
+        writer.write_all(b"\n")?;
+    }
+    let file = writer
+        .into_inner()
+        .map_err(io::IntoInnerError::into_error)?;
+    file.sync_all()?;
+    fs::rename(&temp_path, path)?;
+    // The rename is only durable once the directory is synced, which can't be opened as a file on
+    // every platform.
+    #[cfg(unix)]
+    {
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+        File::open(dir.unwrap_or(Path::new(".")))?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Messages in the snapshot at `path`, none if there's no snapshot yet.
+pub fn load(path: &Path) -> ServerResult<Vec<Message>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error.into()),
+    };
+    let mut messages = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        messages.push(serde_json::from_str(&line)?);
+    }
+    Ok(messages)
+}
+
+/// Timings of snapshots, both of `Persistence::Snapshot` and of WAL compactions.
+#[derive(Debug, Default)]
+pub struct SnapshotMetrics {
+    inner: Mutex<MetricsInner>,
+}
+
+#[derive(Debug, Default)]
+struct MetricsInner {
+    snapshots: u64,
+    failures: u64,
+    /// Of successful snapshots.
+    total_duration: Duration,
+    last_duration: Option<Duration>,
+    max_duration: Option<Duration>,
+    last_snapshot_at: Option<DateTime<Utc>>,
+}
+
+impl SnapshotMetrics {
+    pub fn record(&self, duration: Duration, succeeded: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        if !succeeded {
+            inner.failures += 1;
+            return;
+        }
+        inner.snapshots += 1;
+        inner.total_duration += duration;
+        inner.last_duration = Some(duration);
+        inner.max_duration = inner.max_duration.max(Some(duration));
+        inner.last_snapshot_at = Some(Utc::now());
+    }
+
+    pub fn to_interface(&self) -> FetchSnapshotMetricsResponse {
+        let inner = self.inner.lock().unwrap();
+        let millis = |duration: Duration| u64::try_from(duration.as_millis()).unwrap_or(u64::MAX);
+        let average_duration = (inner.snapshots != 0)
+            .then(|| inner.total_duration / u32::try_from(inner.snapshots).unwrap_or(u32::MAX));
+        FetchSnapshotMetricsResponse {
+            snapshots: inner.snapshots,
+            failures: inner.failures,
+            last_duration_ms: inner.last_duration.map(millis),
+            max_duration_ms: inner.max_duration.map(millis),
+            average_duration_ms: average_duration.map(millis),
+            last_snapshot_at: inner.last_snapshot_at,
+        }
+    }
+}
+
+/// Write a snapshot of `database` to `path`, recorded in `metrics`. Errors are logged.
+/// Messages are copied first, so the database isn't locked while writing, which is done off the
+/// async runtime.
+pub async fn write_database(database: &DataBase, path: &Path, metrics: &SnapshotMetrics) {
+    let start = Instant::now();
+    let messages = database.all_messages();
+    let snapshot_path = path.to_owned();
+    let result = tokio::task::spawn_blocking(move || write(&snapshot_path, &messages))
+        .await
+        .unwrap_or_else(|error| Err(io::Error::other(error)));
+    metrics.record(start.elapsed(), result.is_ok());
+    if let Err(error) = result {
+        log::error!("Can't write snapshot to {}: {error}", path.display());
+    }
+}
+
+/// Write a snapshot of `database` to `path` every `interval`, see `write_database`.
+pub fn setup_snapshot_task(
+    database: Arc<DataBase>,
+    path: PathBuf,
+    interval: Duration,
+    metrics: Arc<SnapshotMetrics>,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        // The first tick completes immediately, and the snapshot was just loaded.
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            write_database(&database, &path, &metrics).await;
+        }
+    });
+}
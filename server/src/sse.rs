@@ -0,0 +1,130 @@
+use std::{collections::HashSet, convert::Infallible, net::SocketAddr};
+
+use axum::{
+    extract::{ConnectInfo, Query, State},
+    http::HeaderMap,
+    response::{
+        sse::{Event as SseEvent, KeepAlive},
+        IntoResponse, Sse,
+    },
+};
+use interface::{
+    sse_events, ApiScope, Envelope, Event, EventStreamForm, Message, MessageId, SubscriptionEvent,
+};
+use tokio_stream::{
+    wrappers::{errors::BroadcastStreamRecvError, BroadcastStream},
+    StreamExt,
+};
+
+use crate::{
+    auth::Session,
+    error::AppError,
+    middleware,
+    plugin::Transport,
+    websocket::{event_json, Broadcast},
+    ServerState,
+};
+
+pub async fn handler(
+    session: Session,
+    State(server_state): State<ServerState>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Query(form): Query<EventStreamForm>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    session.require_scope(ApiScope::Read)?;
+    session.require_access(&server_state)?;
+    let is_enveloped = form.v.is_some();
+    // Subscribe before replaying, so that nothing posted in between is missed.
+    let new_messages = BroadcastStream::new(server_state.subscriptions.subscribe_locally());
+    let events = BroadcastStream::new(server_state.broadcaster.subscribe());
+
+    let blocked = server_state.blocks.blocked_by(&session);
+    let replayed: Vec<Message> = last_event_id(&headers)
+        .and_then(|id| server_state.database.messages_after(id))
+        .unwrap_or_default()
+        .iter()
+        .filter(|message| !blocked.hides(message.poster))
+        .map(|message| message.to_interface())
+        .collect();
+    let replayed_ids: HashSet<MessageId> = replayed.iter().map(|message| message.id).collect();
+    log::info!(
+        "SSE client connected, replaying {} messages",
+        replayed.len()
+    );
+    server_state
+        .plugins
+        .client_connected(Transport::ServerSentEvents);
+
+    let state = server_state.clone();
+    let new_messages = new_messages.filter_map(move |event| match event {
+        Ok(SubscriptionEvent::NewMessage { message })
+            if !replayed_ids.contains(&message.id)
+                && !state
+                    .blocks
+                    .blocked_by(&session)
+                    .hides_message(&message, &state.database) =>
+        {
+            Some(message)
+        }
+        Ok(_) => None,
+        Err(BroadcastStreamRecvError::Lagged(count)) => {
+            log::warn!("SSE client lagged behind by {count} messages");
+            None
+        }
+    });
+    let messages = tokio_stream::iter(replayed)
+        .chain(new_messages)
+        .map(move |message| message_event(message, is_enveloped));
+    let state = server_state.clone();
+    let events = events.filter_map(move |broadcast| match broadcast {
+        // Already sent as message events.
+        Ok(Broadcast {
+            event: Event::MessagesAdded { .. },
+            ..
+        }) => None,
+        Ok(broadcast) => broadcast
+            .for_client(&session, remote_address.ip(), &state)
+            .map(|event| event_event(&event, is_enveloped)),
+        Err(BroadcastStreamRecvError::Lagged(count)) => {
+            log::warn!("SSE client lagged behind by {count} events");
+            None
+        }
+    });
+    // Members removed from a private board, and addresses banned since, stop getting events right
+    // away.
+    let stream = messages
+        .merge(events)
+        .take_while(move |_| {
+            session.require_access(&server_state).is_ok()
+                && !middleware::is_banned(&server_state, remote_address.ip())
+        })
+        .map(Ok::<_, Infallible>);
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+fn message_event(message: Message, is_enveloped: bool) -> SseEvent {
+    let id = message.id.to_string();
+    let data = if is_enveloped {
+        let event = Event::MessagesAdded {
+            messages: [message].into(),
+        };
+        serde_json::to_string(&Envelope::new(event)).unwrap()
+    } else {
+        serde_json::to_string(&message).unwrap()
+    };
+    SseEvent::default()
+        .event(sse_events::MESSAGE)
+        .id(id)
+        .data(data)
+}
+
+fn event_event(event: &Event, is_enveloped: bool) -> SseEvent {
+    SseEvent::default()
+        .event(sse_events::EVENT)
+        .data(event_json(event, is_enveloped))
+}
+
+fn last_event_id(headers: &HeaderMap) -> Option<MessageId> {
+    headers.get("last-event-id")?.to_str().ok()?.parse().ok()
+}
@@ -0,0 +1,181 @@
+use std::{
+    fs, io,
+    net::{TcpListener, UdpSocket},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    config::{Persistence, ServerConfig},
+    settings::Settings,
+};
+
+/// Clocks before this are taken as never set, as on boards without a real-time clock.
+const MIN_SANE_DATE: &str = "2024-01-01T00:00:00Z";
+
+/// Name of the file written to check that a directory is writable, deleted right after.
+const PROBE_FILE_NAME: &str = ".write_probe";
+
+#[derive(Debug, thiserror::Error)]
+pub enum CheckError {
+    #[error("{} isn't writable: {error}", .dir.display())]
+    NotWritable { dir: PathBuf, error: io::Error },
+    #[error("settings file {} is invalid: {error}", .path.display())]
+    InvalidSettings { path: PathBuf, error: String },
+    #[error("TLS file {} can't be read: {error}", .path.display())]
+    UnreadableTlsFile { path: PathBuf, error: io::Error },
+    #[error("HTTP/3 requires MESSAGE_BOARD_TLS_CERT and MESSAGE_BOARD_TLS_KEY to be set")]
+    MissingTlsFiles,
+    #[error("can't listen on {address}: {error}")]
+    NotBindable { address: String, error: io::Error },
+    #[error("system clock is at {now}, which can't be right, are date and time set?")]
+    ClockNotSet { now: DateTime<Utc> },
+    #[error("{0}")]
+    IgnoredArgument(String),
+}
+
+/// Check that the server can run with `config`, returning every problem found.
+/// Addresses are only checked if `check_addresses`, as they may be in use by a server that is
+/// still running, e.g. in deployment pipelines.
+/// Ignored arguments are only errors with `ServerConfig::check_config`, the server otherwise
+/// starts without them as it always did.
+pub fn self_check(config: &ServerConfig, check_addresses: bool) -> Vec<CheckError> {
+    let mut errors = Vec::new();
+    if config.check_config {
+        errors.extend(
+            config
+                .ignored_args
+                .iter()
+                .cloned()
+                .map(CheckError::IgnoredArgument),
+        );
+    }
+    // Attachments are kept in the data directory regardless of persistence.
+    let mut dirs = vec![config.data_dir.as_path()];
+    dirs.extend(config.archive_dir.as_deref());
+    for dir in dirs {
+        if let Err(error) = probe_writable(dir) {
+            errors.push(CheckError::NotWritable {
+                dir: dir.to_owned(),
+                error,
+            });
+        }
+    }
+    if let Some(path) = &config.settings_path {
+        if let Err(error) = Settings::load(path) {
+            errors.push(CheckError::InvalidSettings {
+                path: path.clone(),
+                error: error.to_string(),
+            });
+        }
+    }
+    if config.http3_address.is_some() {
+        match (&config.tls_cert_path, &config.tls_key_path) {
+            (Some(cert_path), Some(key_path)) => {
+                for path in [cert_path, key_path] {
+                    if let Err(error) = fs::metadata(path) {
+                        errors.push(CheckError::UnreadableTlsFile {
+                            path: path.clone(),
+                            error,
+                        });
+                    }
+                }
+            }
+            _ => errors.push(CheckError::MissingTlsFiles),
+        }
+    }
+    if check_addresses {
+        let tcp_addresses = [
+            Some(&config.bind_address),
+            config.irc_address.as_ref(),
+            config.grpc_address.as_ref(),
+        ];
+        // Sockets are dropped right away, so that the addresses are free to be bound for real.
+        let tcp_results = tcp_addresses
+            .into_iter()
+            .flatten()
+            .map(|address| (address, TcpListener::bind(address).map(drop)));
+        // HTTP/3 runs over QUIC, i.e. UDP.
+        let udp_results = config
+            .http3_address
+            .iter()
+            .map(|address| (address, UdpSocket::bind(address).map(drop)));
+        for (address, result) in tcp_results.chain(udp_results) {
+            if let Err(error) = result {
+                errors.push(CheckError::NotBindable {
+                    address: address.clone(),
+                    error,
+                });
+            }
+        }
+    }
+    let now = Utc::now();
+    if now < MIN_SANE_DATE.parse::<DateTime<Utc>>().unwrap() {
+        errors.push(CheckError::ClockNotSet { now });
+    }
+    errors
+}
+
+fn probe_writable(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(PROBE_FILE_NAME);
+    fs::write(&path, b"")?;
+    fs::remove_file(&path)
+}
+
+/// Log what the server is about to run with, one setting per line.
+pub fn log_summary(config: &ServerConfig) {
+    let http_versions = match config.http1_only {
+        true => "HTTP/1.1",
+        false => "HTTP/1.1, HTTP/2",
+    };
+    let disabled = || String::from("disabled");
+    log::info!("Message_Board server {}", env!("CARGO_PKG_VERSION"));
+    log::info!("  address:      {} ({http_versions})", config.bind_address);
+    log::info!("  persistence:  {:?}", config.persistence);
+    match config.persistence {
+        Persistence::Memory => log::info!("  data:         {}", config.data_dir.display()),
+        Persistence::Snapshot => log::info!(
+            "  data:         {} (snapshot every {}s)",
+            config.data_dir.display(),
+            config.snapshot_interval.as_secs()
+        ),
+        Persistence::Wal => log::info!("  data:         {} (WAL)", config.data_dir.display()),
+    }
+    log::info!(
+        "  archives:     {}",
+        config
+            .archive_dir
+            .as_ref()
+            .map_or_else(disabled, |dir| dir.display().to_string())
+    );
+    log::info!(
+        "  settings:     {}",
+        config
+            .settings_path
+            .as_ref()
+            .map_or_else(disabled, |path| path.display().to_string())
+    );
+    log::info!(
+        "  IRC:          {}",
+        config.irc_address.clone().unwrap_or_else(disabled)
+    );
+    log::info!(
+        "  gRPC:         {}",
+        config.grpc_address.clone().unwrap_or_else(disabled)
+    );
+    log::info!(
+        "  HTTP/3:       {}",
+        config.http3_address.clone().unwrap_or_else(disabled)
+    );
+    log::info!("  invite-only:  {}", config.invite_only);
+    log::info!("  admin token:  {}", config.admin_token.is_some());
+    log::info!("  middleware:   {:?}", config.middleware);
+    log::info!("  sanitize:     {:?}", config.sanitize);
+    log::info!("  plugins:      {:?}", config.plugins);
+    log::info!(
+        "  script:       {}",
+        config.script.as_deref().map_or_else(disabled, String::from)
+    );
+}
@@ -0,0 +1,141 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use hmac::{Hmac, Mac};
+use interface::SubscriptionEvent;
+use reqwest::Url;
+use sha2::Sha256;
+use tokio::sync::broadcast;
+
+use crate::{auth::random_secret, utils::to_hex};
+
+/// Header with the HMAC-SHA256 of the body, keyed with the subscription's secret, in hex.
+pub const SIGNATURE_HEADER: &str = "X-Message-Board-Signature";
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_ATTEMPTS: u32 = 5;
+/// Delay before the first retry, doubled for each retry after.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// Number of events buffered for each local subscriber before it starts lagging behind.
+const LOCAL_BUFFER_SIZE: usize = 256;
+
+#[derive(Debug, Clone)]
+struct Subscription {
+    id: u64,
+    url: Url,
+    secret: Box<str>,
+}
+
+/// External URLs the server POSTs events to.
+/// Bridges inside the server can also receive the events, with `subscribe_locally`.
+#[derive(Debug)]
+pub struct Subscriptions {
+    subscriptions: Mutex<Vec<Subscription>>,
+    next_id: AtomicU64,
+    client: reqwest::Client,
+    local: broadcast::Sender<SubscriptionEvent>,
+}
+
+impl Default for Subscriptions {
+    fn default() -> Self {
+        let (local, _) = broadcast::channel(LOCAL_BUFFER_SIZE);
+        Self {
+            subscriptions: Default::default(),
+            next_id: Default::default(),
+            client: Default::default(),
+            local,
+        }
+    }
+}
+
+impl Subscriptions {
+    pub fn subscribe_locally(&self) -> broadcast::Receiver<SubscriptionEvent> {
+        self.local.subscribe()
+    }
+
+    /// Returns the ID and the signing secret of the new subscription.
+    /// Returns `None` if `url` is not a HTTP(S) URL.
+    pub fn create(&self, url: &str) -> Option<(u64, Box<str>)> {
+        let url = Url::parse(url)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))?;
+        let secret = random_secret();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.subscriptions.lock().unwrap().push(Subscription {
+            id,
+            url,
+            secret: secret.clone(),
+        });
+        Some((id, secret))
+    }
+
+    /// Returns `false` if there's no such subscription.
+    pub fn delete(&self, id: u64) -> bool {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let len_before = subscriptions.len();
+        subscriptions.retain(|subscription| subscription.id != id);
+        subscriptions.len() != len_before
+    }
+
+    /// Deliver `event` to every subscription in the background, and to local subscribers.
+    pub fn notify(self: &Arc<Self>, event: SubscriptionEvent) {
+        // Only fails if there are no local subscribers.
+        let _ = self.local.send(event.clone());
+        let subscriptions = self.subscriptions.lock().unwrap().clone();
+        if subscriptions.is_empty() {
+            return;
+        }
+        let body: Arc<str> = serde_json::to_string(&event).unwrap().into();
+        for subscription in subscriptions {
+            let self_ = Arc::clone(self);
+            let body = Arc::clone(&body);
+            tokio::spawn(async move { self_.deliver(subscription, body).await });
+        }
+    }
+
+    /// POST `body` to the subscription, retrying with exponential backoff on failure.
+    async fn deliver(&self, subscription: Subscription, body: Arc<str>) {
+        let signature = sign(&subscription.secret, body.as_bytes());
+        let mut backoff = INITIAL_BACKOFF;
+        for attempt in 1..=MAX_ATTEMPTS {
+            let result = self
+                .client
+                .post(subscription.url.clone())
+                .timeout(DELIVERY_TIMEOUT)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(SIGNATURE_HEADER, &signature)
+                .body(body.to_string())
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            match result {
+                Ok(_) => return,
+                Err(error) => log::warn!(
+                    "Delivering to subscription {} failed, attempt {attempt}: {error}",
+                    subscription.id
+                ),
+            }
+            if attempt != MAX_ATTEMPTS {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+        }
+        log::error!(
+            "Giving up delivering to subscription {} ({})",
+            subscription.id,
+            subscription.url
+        );
+    }
+}
+
+/// HMAC-SHA256 of `body` in hex, prefixed with `sha256=`.
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("sha256={}", to_hex(&mac.finalize().into_bytes()))
+}
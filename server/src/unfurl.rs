@@ -0,0 +1,306 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::Semaphore;
+
+use interface::{routes, Event, FetchMessageResponse, LinkPreview, MessageId};
+use reqwest::{header, redirect, Response, StatusCode, Url};
+use url::Host;
+
+use crate::ServerState;
+
+const CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+const CACHE_CAPACITY: usize = 1024;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REDIRECTS: usize = 3;
+/// Stop reading the page after this many bytes.
+const MAX_BODY_SIZE: usize = 256 * 1024;
+/// In characters.
+const MAX_TITLE_LENGTH: usize = 200;
+/// Links posted while this many pages are being fetched get no preview, so that a flood of links
+/// can't pile up requests.
+const MAX_CONCURRENT_FETCHES: usize = 8;
+
+#[derive(Debug, thiserror::Error)]
+pub enum UnfurlError {
+    #[error("invalid URL")]
+    InvalidUrl,
+    #[error("host doesn't resolve to a public address")]
+    ForbiddenAddress,
+    #[error("too many redirects")]
+    TooManyRedirects,
+    #[error("page is not HTML")]
+    NotHtml,
+    #[error("page has no title")]
+    NoTitle,
+    #[error("board responded with {0}")]
+    Status(StatusCode),
+    #[error("response is larger than {MAX_BODY_SIZE} bytes")]
+    TooLarge,
+    #[error("board responded with another message")]
+    WrongMessage,
+    #[error("invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("request error: {0}")]
+    Request(#[from] reqwest::Error),
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    preview: Option<LinkPreview>,
+    fetched_at: Instant,
+}
+
+/// Fetches and caches link previews, and fetches messages of other boards to forward.
+#[derive(Debug)]
+pub struct Unfurler {
+    cache: Mutex<HashMap<Box<str>, CacheEntry>>,
+    fetches: Semaphore,
+}
+
+impl Default for Unfurler {
+    fn default() -> Self {
+        Self {
+            cache: Mutex::default(),
+            fetches: Semaphore::new(MAX_CONCURRENT_FETCHES),
+        }
+    }
+}
+
+impl Unfurler {
+    /// Returns `None` if no preview can be made for the URL.
+    pub async fn unfurl(&self, url: &str) -> Option<LinkPreview> {
+        if let Some(entry) = self.cache.lock().unwrap().get(url) {
+            if entry.fetched_at.elapsed() < CACHE_TTL {
+                return entry.preview.clone();
+            }
+        }
+        let Ok(_permit) = self.fetches.try_acquire() else {
+            log::info!("Not unfurling {url:?}, too many pages are being fetched");
+            return None;
+        };
+        let preview = match fetch_title(url).await {
+            Ok(title) => Some(LinkPreview {
+                url: url.into(),
+                title,
+            }),
+            Err(error) => {
+                log::info!("Can't unfurl {url:?}: {error}");
+                None
+            }
+        };
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= CACHE_CAPACITY {
+            cache.retain(|_, entry| entry.fetched_at.elapsed() < CACHE_TTL);
+        }
+        if cache.len() < CACHE_CAPACITY {
+            let entry = CacheEntry {
+                preview: preview.clone(),
+                fetched_at: Instant::now(),
+            };
+            cache.insert(url.into(), entry);
+        }
+        preview
+    }
+
+    /// Message `id` of the board at `board`, fetched as a guest, for forwarding it.
+    /// Unlike link previews, waits for other fetches to finish rather than giving up.
+    pub async fn fetch_message(
+        &self,
+        board: &str,
+        id: MessageId,
+    ) -> Result<interface::Message, UnfurlError> {
+        let _permit = self.fetches.acquire().await.unwrap();
+        let path = routes::FETCH_MESSAGE.1.replace(":id", &id.to_string());
+        let url = Url::parse(&format!("{}{path}", board.trim_end_matches('/')))
+            .map_err(|_| UnfurlError::InvalidUrl)?;
+        let mut response = fetch(url).await?;
+        if response.status() != StatusCode::OK {
+            return Err(UnfurlError::Status(response.status()));
+        }
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            body.extend_from_slice(&chunk);
+            if body.len() > MAX_BODY_SIZE {
+                return Err(UnfurlError::TooLarge);
+            }
+        }
+        let FetchMessageResponse { message } = serde_json::from_slice(&body)?;
+        if message.id != id {
+            return Err(UnfurlError::WrongMessage);
+        }
+        Ok(message)
+    }
+}
+
+/// If `content` contains a URL, fetch its preview in the background, attach it to the message
+/// and broadcast it.
+pub fn spawn_unfurl(server_state: ServerState, id: MessageId, content: &str) {
+    let Some(url) = find_url(content) else {
+        return;
+    };
+    let url = url.to_owned();
+    tokio::spawn(async move {
+        let Some(link_preview) = server_state.unfurler.unfurl(&url).await else {
+            return;
+        };
+        if server_state
+            .database
+            .set_link_preview(id, link_preview.clone())
+        {
+            server_state
+                .broadcaster
+                .broadcast(Event::LinkPreview { id, link_preview });
+        }
+    });
+}
+
+/// Find the first http(s) URL in a message.
+fn find_url(content: &str) -> Option<&str> {
+    content
+        .split_whitespace()
+        .find(|word| word.starts_with("http://") || word.starts_with("https://"))
+        .map(|url| url.trim_end_matches([')', ']', '>', '.', ',', ';', '!', '?', '"', '\'']))
+}
+
+async fn fetch_title(url: &str) -> Result<Box<str>, UnfurlError> {
+    let url = Url::parse(url).map_err(|_| UnfurlError::InvalidUrl)?;
+    let mut response = fetch(url).await?;
+    if response.status() != StatusCode::OK {
+        return Err(UnfurlError::NoTitle);
+    }
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("text/html"));
+    if !is_html {
+        return Err(UnfurlError::NotHtml);
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        body.extend_from_slice(&chunk);
+        if body.len() >= MAX_BODY_SIZE {
+            break;
+        }
+    }
+    extract_title(&String::from_utf8_lossy(&body)).ok_or(UnfurlError::NoTitle)
+}
+
+/// GET `url`, following redirects, from public addresses only.
+async fn fetch(mut url: Url) -> Result<Response, UnfurlError> {
+    for _ in 0..=MAX_REDIRECTS {
+        let address = resolve_public_address(&url).await?;
+        // Pin the resolved address, so the host can't resolve to something else between the check
+        // and the request. Redirects are followed manually for the same reason.
+        // A proxy would resolve the host itself, so proxies from the environment are ignored.
+        let mut client = reqwest::Client::builder()
+            .timeout(FETCH_TIMEOUT)
+            .redirect(redirect::Policy::none())
+            .no_proxy();
+        if let Some(domain) = url.domain() {
+            client = client.resolve(domain, address);
+        }
+        let response = client.build()?.get(url.clone()).send().await?;
+        if response.status().is_redirection() {
+            let location = response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .ok_or(UnfurlError::InvalidUrl)?;
+            url = url.join(location).map_err(|_| UnfurlError::InvalidUrl)?;
+            continue;
+        }
+        return Ok(response);
+    }
+    Err(UnfurlError::TooManyRedirects)
+}
+
+/// Resolve the host of the URL, rejecting anything that isn't a public internet address, so that
+/// messages can't be used to make the server probe its own network.
+async fn resolve_public_address(url: &Url) -> Result<SocketAddr, UnfurlError> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(UnfurlError::InvalidUrl);
+    }
+    let port = url.port_or_known_default().ok_or(UnfurlError::InvalidUrl)?;
+    let mut addresses: Vec<SocketAddr> = match url.host().ok_or(UnfurlError::InvalidUrl)? {
+        Host::Domain(domain) => tokio::net::lookup_host((domain, port)).await?.collect(),
+        Host::Ipv4(ip) => vec![SocketAddr::new(ip.into(), port)],
+        Host::Ipv6(ip) => vec![SocketAddr::new(ip.into(), port)],
+    };
+    // A host resolving to any internal address is suspicious enough.
+    if addresses.is_empty() || !addresses.iter().all(|address| is_public(address.ip())) {
+        return Err(UnfurlError::ForbiddenAddress);
+    }
+    Ok(addresses.swap_remove(0))
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            let is_this_network = a == 0; // 0.0.0.0/8
+            let is_shared = a == 100 && (b & 0b1100_0000) == 64; // 100.64.0.0/10
+            let is_protocol_assignment = a == 192 && b == 0 && c == 0; // 192.0.0.0/24
+            let is_benchmarking = a == 198 && (b & 0b1111_1110) == 18; // 198.18.0.0/15
+            let is_reserved = a >= 240; // 240.0.0.0/4, with the broadcast address
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || is_this_network
+                || is_shared
+                || is_protocol_assignment
+                || is_benchmarking
+                || is_reserved)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public(ip.into());
+            }
+            let segments = ip.segments();
+            let is_unique_local = (segments[0] & 0xfe00) == 0xfc00; // fc00::/7
+            let is_link_local = (segments[0] & 0xffc0) == 0xfe80; // fe80::/10
+
+            // These embed IPv4 addresses, which may be anything, and may be translated to them.
+            let is_ipv4_compatible = segments[..6] == [0; 6]; // ::a.b.c.d, with :: and ::1
+            let is_nat64 = segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0]; // 64:ff9b::/96
+            let is_6to4 = segments[0] == 0x2002; // 2002::/16
+            !(ip.is_multicast()
+                || is_unique_local
+                || is_link_local
+                || is_ipv4_compatible
+                || is_nat64
+                || is_6to4)
+        }
+    }
+}
+
+fn extract_title(html: &str) -> Option<Box<str>> {
+    // ASCII lowercasing keeps byte offsets the same.
+    let lowercase = html.to_ascii_lowercase();
+    let start = lowercase.find("<title")?;
+    let start = start + lowercase[start..].find('>')? + 1;
+    let end = start + lowercase[start..].find("</title")?;
+    let title = html[start..end]
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    let title: String = title
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(MAX_TITLE_LENGTH)
+        .collect();
+    (!title.is_empty()).then(|| title.into())
+}
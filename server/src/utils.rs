@@ -8,6 +8,17 @@ pub type DynResult<T> = Result<T, DynError>;
 pub trait ServerState: Send + Sync {}
 impl<T: Send + Sync> ServerState for T {}
 
+/// Lowercase hex encoding of `bytes`.
+pub fn to_hex(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
 fn infallible() -> std::convert::Infallible {
     unreachable!();
 }
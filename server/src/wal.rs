@@ -0,0 +1,213 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    iter,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc},
+    thread,
+    time::{Duration, Instant},
+};
+
+use interface::{LinkPreview, MessageId};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::{DataBase, Message},
+    error::ServerResult,
+    snapshot::{self, SnapshotMetrics},
+};
+
+const WAL_FILE_NAME: &str = "wal.ndjson";
+
+/// How often the WAL is compacted into the snapshot.
+const COMPACTION_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// A change to the messages of a `DataBase`, one per line of the WAL.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Record {
+    Add {
+        message: Message,
+    },
+    Delete {
+        ids: Box<[MessageId]>,
+    },
+    SetLinkPreview {
+        id: MessageId,
+        link_preview: LinkPreview,
+    },
+    /// Voters aren't recorded, see `PollState::voters`.
+    Vote {
+        id: MessageId,
+        option: u32,
+    },
+    /// `Message::author` and `Message::poster` were cleared.
+    Anonymize {
+        ids: Box<[MessageId]>,
+    },
+}
+
+/// Append-only log of every change to messages since the last snapshot.
+/// Messages are rebuilt by loading the snapshot and replaying the WAL on top of it.
+/// Files are written by a thread of their own, in the order of `Command`s queued by callers
+/// holding the lock on the messages, so that the lock isn't held while writing.
+#[derive(Debug)]
+pub struct Wal {
+    commands: mpsc::Sender<Command>,
+}
+
+#[derive(Debug)]
+enum Command {
+    /// A serialized `Record`, with its newline.
+    Append(Vec<u8>),
+    /// Replace the snapshot with these messages and empty the WAL.
+    Compact(Vec<Message>, mpsc::Sender<io::Result<()>>),
+    /// Sync the records queued before, and reply once they are.
+    Flush(mpsc::Sender<io::Result<()>>),
+}
+
+/// A compaction queued by `Wal::compact`.
+#[derive(Debug)]
+pub struct Compaction(mpsc::Receiver<io::Result<()>>);
+
+impl Compaction {
+    /// Blocks until the snapshot is written and the WAL emptied.
+    pub fn wait(self) -> io::Result<()> {
+        self.0
+            .recv()
+            .unwrap_or_else(|_| Err(io::Error::other("the WAL writer stopped")))
+    }
+}
+
+impl Wal {
+    /// Open the WAL in `dir`, creating the directory if needed.
+    /// Returns the WAL and the messages of the snapshot and the records to replay on top of it.
+    pub fn open(dir: &Path) -> ServerResult<(Self, Vec<Message>, Vec<Record>)> {
+        fs::create_dir_all(dir)?;
+        let messages = snapshot::load(&dir.join(snapshot::FILE_NAME))?;
+        let path = dir.join(WAL_FILE_NAME);
+        let records = read_records(&path)?;
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let (commands, receiver) = mpsc::channel();
+        let snapshot_path = dir.join(snapshot::FILE_NAME);
+        thread::spawn(move || write_commands(file, &snapshot_path, receiver));
+        Ok((Self { commands }, messages, records))
+    }
+
+    /// Callers hold the lock on the messages, so records are in the same order as changes.
+    /// Errors writing it are logged, the change is still made in memory.
+    pub fn append(&self, record: &Record) {
+        let mut line = serde_json::to_vec(record).unwrap();
+        line.push(b'\n');
+        // Only fails if the writer is gone, which it logged.
+        let _ = self.commands.send(Command::Append(line));
+    }
+
+    /// Queue replacing the snapshot with `messages` and emptying the WAL.
+    /// Callers hold the lock on the messages while queuing it, so no records are lost in between,
+    /// and wait for it after releasing the lock.
+    pub fn compact(&self, messages: Vec<Message>) -> Compaction {
+        let (sender, receiver) = mpsc::channel();
+        // If the writer is gone, `sender` is dropped and waiting fails.
+        let _ = self.commands.send(Command::Compact(messages, sender));
+        Compaction(receiver)
+    }
+
+    /// Blocks until every record appended so far is written and synced.
+    /// `append` returns before that, so this is waited for before the server exits.
+    pub fn flush(&self) -> io::Result<()> {
+        let (sender, receiver) = mpsc::channel();
+        // If the writer is gone, `sender` is dropped and receiving fails.
+        let _ = self.commands.send(Command::Flush(sender));
+        receiver
+            .recv()
+            .unwrap_or_else(|_| Err(io::Error::other("the WAL writer stopped")))
+    }
+}
+
+/// Run by the writer thread of a `Wal` until the `Wal` is dropped.
+/// Records queued together are written before syncing the file once.
+fn write_commands(mut file: File, snapshot_path: &Path, commands: mpsc::Receiver<Command>) {
+    while let Ok(first) = commands.recv() {
+        let mut needs_sync = false;
+        for command in iter::once(first).chain(commands.try_iter()) {
+            match command {
+                Command::Append(line) => {
+                    // A single write, so a crash can only leave the last line incomplete.
+                    match file.write_all(&line) {
+                        Ok(()) => needs_sync = true,
+                        Err(error) => log::error!(
+                            "Can't write to the WAL, change will be lost on restart: {error}"
+                        ),
+                    }
+                }
+                Command::Compact(messages, result) => {
+                    // The file is in append mode, so the next write goes to the start again.
+                    let compacted = snapshot::write(snapshot_path, &messages)
+                        .and_then(|()| file.set_len(0))
+                        .and_then(|()| file.sync_all());
+                    needs_sync &= compacted.is_err();
+                    let _ = result.send(compacted);
+                }
+                Command::Flush(result) => {
+                    let synced = match needs_sync {
+                        true => file.sync_data(),
+                        false => Ok(()),
+                    };
+                    needs_sync = false;
+                    let _ = result.send(synced);
+                }
+            }
+        }
+        if needs_sync {
+            if let Err(error) = file.sync_data() {
+                log::error!("Can't sync the WAL, changes may be lost on a crash: {error}");
+            }
+        }
+    }
+}
+
+/// The last line may be incomplete if the server crashed while writing it, it is skipped.
+fn read_records(path: &Path) -> ServerResult<Vec<Record>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error.into()),
+    };
+    let lines: Vec<String> = BufReader::new(file).lines().collect::<Result<_, _>>()?;
+    let mut records = Vec::with_capacity(lines.len());
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(line) {
+            Ok(record) => records.push(record),
+            Err(error) if i + 1 == lines.len() => {
+                log::warn!("Skipping incomplete last record of the WAL: {error}");
+            }
+            Err(error) => return Err(error.into()),
+        }
+    }
+    Ok(records)
+}
+
+/// Compact the WAL of `database` every `COMPACTION_INTERVAL`.
+pub fn setup_compaction_task(database: Arc<DataBase>, metrics: Arc<SnapshotMetrics>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(COMPACTION_INTERVAL);
+        // The first tick completes immediately, and the WAL was just compacted on startup.
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let start = Instant::now();
+            let database = Arc::clone(&database);
+            let result = tokio::task::spawn_blocking(move || database.compact_wal())
+                .await
+                .unwrap_or_else(|error| Err(io::Error::other(error)));
+            metrics.record(start.elapsed(), result.is_ok());
+            if let Err(error) = result {
+                log::error!("Can't compact the WAL: {error}");
+            }
+        }
+    });
+}
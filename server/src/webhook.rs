@@ -0,0 +1,86 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::auth::random_secret;
+
+/// Window of webhook rate limits.
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+struct Webhook {
+    id: u64,
+    display_name: Box<str>,
+    /// Maximum number of messages per `RATE_LIMIT_WINDOW`.
+    rate_limit: u32,
+    window_start: Instant,
+    sent_in_window: u32,
+}
+
+/// Result of posting through a webhook.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebhookPost {
+    /// Post the message with this display name.
+    Accepted {
+        display_name: Box<str>,
+    },
+    RateLimited,
+    NoSuchWebhook,
+}
+
+/// Incoming webhooks, each with a secret token that goes in the URL.
+#[derive(Debug, Default)]
+pub struct Webhooks {
+    /// Token -> webhook.
+    webhooks: Mutex<HashMap<Box<str>, Webhook>>,
+    next_id: AtomicU64,
+}
+
+impl Webhooks {
+    /// Returns the ID and the token of the new webhook.
+    pub fn create(&self, display_name: Box<str>, rate_limit: u32) -> (u64, Box<str>) {
+        let token = random_secret();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let webhook = Webhook {
+            id,
+            display_name,
+            rate_limit,
+            window_start: Instant::now(),
+            sent_in_window: 0,
+        };
+        self.webhooks.lock().unwrap().insert(token.clone(), webhook);
+        (id, token)
+    }
+
+    /// Returns `false` if there's no such webhook.
+    pub fn delete(&self, id: u64) -> bool {
+        let mut webhooks = self.webhooks.lock().unwrap();
+        let len_before = webhooks.len();
+        webhooks.retain(|_, webhook| webhook.id != id);
+        webhooks.len() != len_before
+    }
+
+    /// Count a post through the webhook with `token` against its rate limit.
+    pub fn post(&self, token: &str) -> WebhookPost {
+        let mut webhooks = self.webhooks.lock().unwrap();
+        let Some(webhook) = webhooks.get_mut(token) else {
+            return WebhookPost::NoSuchWebhook;
+        };
+        if webhook.window_start.elapsed() >= RATE_LIMIT_WINDOW {
+            webhook.window_start = Instant::now();
+            webhook.sent_in_window = 0;
+        }
+        if webhook.sent_in_window >= webhook.rate_limit {
+            return WebhookPost::RateLimited;
+        }
+        webhook.sent_in_window += 1;
+        WebhookPost::Accepted {
+            display_name: webhook.display_name.clone(),
+        }
+    }
+}
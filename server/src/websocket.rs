@@ -0,0 +1,331 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use axum::{
+    extract::{
+        ws::{CloseFrame, Message as WsMessage, WebSocket},
+        ConnectInfo, Query, State, WebSocketUpgrade,
+    },
+    response::IntoResponse,
+};
+use interface::{
+    ApiScope, CloseReason, Envelope, Event, EventStreamForm, Message, SubscriptionEvent, WsRequest,
+};
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    time::{self, Instant},
+};
+
+use crate::{
+    auth::Session, blocks::Blocked, connections::ConnectionHandle, error::AppError,
+    handlers::submit_message, plugin::Transport, ServerState,
+};
+
+/// Number of events buffered for each client before it starts lagging behind.
+const EVENT_BUFFER_SIZE: usize = 256;
+
+/// Clients are pinged this often, and closed if nothing was received from them for
+/// `IDLE_TIMEOUT`, as their connection is likely broken.
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+const IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Sends events to connected websocket and server-sent event clients, to every one of them or to
+/// one `Recipient`.
+#[derive(Debug, Clone)]
+pub struct Broadcaster {
+    sender: broadcast::Sender<Broadcast>,
+}
+
+/// An event as sent by `Broadcaster`, see `for_client` for who gets it.
+#[derive(Debug, Clone)]
+pub struct Broadcast {
+    pub event: Event,
+    /// `None` for every client.
+    recipient: Option<Recipient>,
+}
+
+/// The clients of a session, told apart by address for guests and API tokens, which have none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Recipient {
+    session: Option<u64>,
+    address: IpAddr,
+}
+
+impl Recipient {
+    pub fn new(session: &Session, address: IpAddr) -> Self {
+        Self {
+            session: session.id,
+            address,
+        }
+    }
+
+    fn matches(&self, session: &Session, address: IpAddr) -> bool {
+        match self.session {
+            Some(id) => session.id == Some(id),
+            None => session.id.is_none() && self.address == address,
+        }
+    }
+}
+
+impl Broadcast {
+    /// The event as sent to a client of `session` at `address`, `None` if it isn't for them.
+    /// Messages posted by sessions blocked by `session` are left out of `Event::MessagesAdded`.
+    pub fn for_client(
+        self,
+        session: &Session,
+        address: IpAddr,
+        server_state: &ServerState,
+    ) -> Option<Event> {
+        if self
+            .recipient
+            .is_some_and(|recipient| !recipient.matches(session, address))
+        {
+            return None;
+        }
+        let blocked = server_state.blocks.blocked_by(session);
+        without_blocked(self.event, &blocked, server_state)
+    }
+}
+
+impl Default for Broadcaster {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER_SIZE);
+        Self { sender }
+    }
+}
+
+impl Broadcaster {
+    pub fn broadcast(&self, event: Event) {
+        // Only fails if there are no clients connected.
+        let _ = self.sender.send(Broadcast {
+            event,
+            recipient: None,
+        });
+    }
+
+    /// Send `event` only to the clients of `recipient`.
+    pub fn send_to(&self, recipient: Recipient, event: Event) {
+        let _ = self.sender.send(Broadcast {
+            event,
+            recipient: Some(recipient),
+        });
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Broadcast> {
+        self.sender.subscribe()
+    }
+}
+
+/// Spawns a task broadcasting new messages as `Event::MessagesAdded`, coalescing messages posted
+/// within `window` of the first one into one event.
+pub fn setup_message_batching(server_state: ServerState, window: Duration) {
+    let mut new_messages = server_state.subscriptions.subscribe_locally();
+    tokio::spawn(async move {
+        // Wait for the first message of a batch, then collect until the window closes.
+        while let Some(message) = next_message(&mut new_messages).await {
+            let mut messages = vec![message];
+            let deadline = Instant::now() + window;
+            while let Ok(Some(message)) =
+                time::timeout_at(deadline, next_message(&mut new_messages)).await
+            {
+                messages.push(message);
+            }
+            server_state.broadcaster.broadcast(Event::MessagesAdded {
+                messages: messages.into(),
+            });
+        }
+    });
+}
+
+/// Returns `None` once there are no more messages.
+async fn next_message(
+    new_messages: &mut broadcast::Receiver<SubscriptionEvent>,
+) -> Option<Message> {
+    loop {
+        match new_messages.recv().await {
+            Ok(SubscriptionEvent::NewMessage { message }) => return Some(message),
+            Err(RecvError::Lagged(count)) => {
+                log::warn!("Message batching lagged behind by {count} messages");
+            }
+            Err(RecvError::Closed) => return None,
+        }
+    }
+}
+
+pub async fn handler(
+    session: Session,
+    State(server_state): State<ServerState>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Query(form): Query<EventStreamForm>,
+    ws: WebSocketUpgrade,
+) -> Result<impl IntoResponse, AppError> {
+    session.require_scope(ApiScope::Read)?;
+    session.require_access(&server_state)?;
+    let events = server_state.broadcaster.subscribe();
+    let is_enveloped = form.v.is_some();
+    let connection = server_state
+        .connections
+        .register(remote_address, session.role, is_enveloped);
+    Ok(ws.on_upgrade(move |socket| {
+        let address = remote_address.ip();
+        handle_socket(socket, events, server_state, session, address, connection)
+    }))
+}
+
+async fn handle_socket(
+    mut socket: WebSocket,
+    mut events: broadcast::Receiver<Broadcast>,
+    server_state: ServerState,
+    session: Session,
+    address: IpAddr,
+    connection: ConnectionHandle,
+) {
+    log::info!("Websocket client {} connected", connection.id());
+    server_state.plugins.client_connected(Transport::Websocket);
+    let is_enveloped = connection.is_enveloped();
+    let mut pings = time::interval(PING_INTERVAL);
+    let mut last_received = Instant::now();
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(broadcast) => {
+                    // Members removed from a private board stop getting events right away.
+                    if session.require_access(&server_state).is_err() {
+                        close(&mut socket, CloseReason::Disconnected).await;
+                        break;
+                    }
+                    let Some(event) = broadcast.for_client(&session, address, &server_state) else {
+                        continue;
+                    };
+                    if send_event(&mut socket, &event, is_enveloped).await.is_err() {
+                        break;
+                    }
+                    connection.set_queued_events(events.len());
+                }
+                Err(RecvError::Lagged(count)) => {
+                    log::warn!("Websocket client lagged behind by {count} events");
+                    connection.record_dropped_events(count);
+                }
+                Err(RecvError::Closed) => break,
+            },
+            reason = connection.close_requested() => {
+                close(&mut socket, reason).await;
+                break;
+            }
+            _ = pings.tick() => {
+                if last_received.elapsed() > IDLE_TIMEOUT {
+                    close(&mut socket, CloseReason::IdleTimeout).await;
+                    break;
+                }
+                if socket.send(WsMessage::Ping(Vec::new())).await.is_err() {
+                    break;
+                }
+            }
+            message = socket.recv() => {
+                last_received = Instant::now();
+                match message {
+                    Some(Ok(WsMessage::Text(text))) => {
+                        let rejected = handle_request(&server_state, session, address, &text).await;
+                        let Some(rejected) = rejected else {
+                            continue;
+                        };
+                        if send_event(&mut socket, &rejected, is_enveloped).await.is_err() {
+                            break;
+                        }
+                    }
+                    Some(Ok(WsMessage::Binary(_))) => {
+                        close(&mut socket, CloseReason::ProtocolError).await;
+                        break;
+                    }
+                    Some(Ok(WsMessage::Close(_)) | Err(_)) | None => break,
+                    Some(Ok(_)) => (),
+                }
+            }
+        }
+    }
+    log::info!("Websocket client {} disconnected", connection.id());
+}
+
+/// `event` without messages posted by sessions in `blocked`, `None` if nothing is left of it.
+fn without_blocked(event: Event, blocked: &Blocked, server_state: &ServerState) -> Option<Event> {
+    match event {
+        Event::MessagesAdded { messages } if !blocked.is_empty() => {
+            let messages: Box<[Message]> = messages
+                .into_vec()
+                .into_iter()
+                .filter(|message| !blocked.hides_message(message, &server_state.database))
+                .collect();
+            (!messages.is_empty()).then_some(Event::MessagesAdded { messages })
+        }
+        event => Some(event),
+    }
+}
+
+/// Returns the `Event::Rejected` to send back, if the request was rejected and has a client tag.
+async fn handle_request(
+    server_state: &ServerState,
+    session: Session,
+    address: IpAddr,
+    text: &str,
+) -> Option<Event> {
+    let request = match serde_json::from_str::<WsRequest>(text) {
+        Ok(request) => request,
+        Err(error) => {
+            log::info!("Unrecognized websocket request {text:?}: {error}");
+            return None;
+        }
+    };
+    match request {
+        WsRequest::SendMessage(form) => {
+            log::info!("Websocket send message request: {:?}", &form.content);
+            let client_tag = form.client_tag.clone();
+            let result = match session.require_sender(server_state) {
+                Ok(()) => submit_message(server_state.clone(), &session, address, form).await,
+                Err(error) => Err(error.into()),
+            };
+            match (result, client_tag) {
+                (Err(AppError(error)), Some(client_tag)) => Some(Event::Rejected {
+                    client_tag,
+                    error: error.to_string().into(),
+                }),
+                (Err(AppError(error)), None) => {
+                    log::info!("Rejected websocket request: {error}");
+                    None
+                }
+                (Ok(_), _) => None,
+            }
+        }
+    }
+}
+
+/// Send a close frame telling the client why, which it may not receive if the connection is
+/// broken already.
+async fn close(socket: &mut WebSocket, reason: CloseReason) {
+    log::info!("Closing websocket: {reason}");
+    let frame = CloseFrame {
+        code: reason.code(),
+        reason: reason.to_string().into(),
+    };
+    let _ = socket.send(WsMessage::Close(Some(frame))).await;
+}
+
+async fn send_event(
+    socket: &mut WebSocket,
+    event: &Event,
+    is_enveloped: bool,
+) -> Result<(), axum::Error> {
+    socket
+        .send(WsMessage::Text(event_json(event, is_enveloped)))
+        .await
+}
+
+/// `event` as JSON, in an `Envelope` if the client asked for it with `EventStreamForm::v`.
+pub fn event_json(event: &Event, is_enveloped: bool) -> String {
+    if is_enveloped {
+        serde_json::to_string(&Envelope::new(event.clone())).unwrap()
+    } else {
+        serde_json::to_string(event).unwrap()
+    }
+}